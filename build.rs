@@ -8,6 +8,11 @@ use std::path::PathBuf;
 // https://stackoverflow.com/questions/4074176/included-openssl-as-a-static-library-but-its-still-looking-for-a-dll
 // .cargo/config -> https://www.reddit.com/r/rust/comments/7mif9i/how_to_compile_binaries_without_dependencies_on/
 fn main() {
+    // vJoy only exists on Windows, other targets use their own backend.
+    if env::var("CARGO_CFG_TARGET_OS").unwrap() != "windows" {
+        return;
+    }
+
     let should_gen_bindings = false;
 
     let package_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
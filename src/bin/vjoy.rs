@@ -0,0 +1,657 @@
+//! Command-line tool to inspect and drive vJoy devices.
+//!
+//! ```text
+//! vjoy [--json] <command>
+//!
+//! info                            Driver and DLL versions, vJoy strings.
+//! list                            Devices with their status, owner and controls.
+//! set <device> <control> <value>  Sets an axis (X 16384), a button (B3 on), a discrete POV
+//!                                 (POV1 East) or a continuous POV (CPOV2 9000, CPOV2 neutral).
+//! reset <device|all>              Resets the controls of one or all devices.
+//! monitor [--interval <ms>]       Prints every change of status of the devices until killed.
+//! acquire <device> [--hold]       Acquires a device, and with --hold keeps it until Enter.
+//! tui [--interactive]             Live view of the devices, see `vjoy_extra::monitor`. Needs
+//!     [--interval <ms>]           the `tui` feature, and reads the devices back with SDL2
+//!                                 when built with the `sdl2` feature.
+//! ```
+//!
+//! Devices are written `1` to `16` or `D1` to `D16`. With `--json`, results are printed as JSON,
+//! one object per line for `monitor`. Errors are printed to the standard error, and the exit
+//! code tells what went wrong: see the `EXIT_` constants.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Options, String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_commands() {
+        let options = parse("--json set D2 b3 on").unwrap();
+        assert!(options.json);
+        assert_eq!(
+            Command::Set(
+                VJDevice::D2,
+                Control::Button(VJDButton::B3, VJDButtonState::Pressed)
+            ),
+            options.command
+        );
+
+        assert_eq!(
+            Command::Set(VJDevice::D1, Control::Axis(VJDAxis::Slider1, 0)),
+            parse("set 1 slider1 0").unwrap().command
+        );
+        assert_eq!(
+            Command::Set(
+                VJDevice::D16,
+                Control::DiscPov(VJDPovNumber::Pov4, VJDPovDisc::West)
+            ),
+            parse("set 16 POV4 west").unwrap().command
+        );
+        assert_eq!(
+            Command::Set(
+                VJDevice::D3,
+                Control::ContPov(VJDPovNumber::Pov1, VJDPovCont::NEUTRAL)
+            ),
+            parse("set 3 cpov1 neutral").unwrap().command
+        );
+        assert_eq!(Command::Reset(None), parse("reset all").unwrap().command);
+        assert_eq!(
+            Command::Monitor(Duration::from_millis(100)),
+            parse("monitor --interval 100").unwrap().command
+        );
+        assert_eq!(
+            Command::Acquire(VJDevice::D4, true),
+            parse("acquire --hold D4").unwrap().command
+        );
+        assert!(!parse("list").unwrap().json);
+        #[cfg(feature = "tui")]
+        assert_eq!(
+            Command::Tui(Duration::from_millis(50), true),
+            parse("tui --interactive").unwrap().command
+        );
+        assert_eq!(Command::Help, parse("set --help").unwrap().command);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        for line in &[
+            "",
+            "fly",
+            "set 17 X 0",
+            "set 1 X 32768",
+            "set 1 B129 on",
+            "set 1 POV5 east",
+            "set 1 CPOV1 36000",
+            "set 1 X",
+            "reset",
+            "info extra",
+            "monitor --interval 0",
+            "acquire 1 --forever",
+            "list --interval 100",
+        ] {
+            assert!(parse(line).is_err(), "{:?} was accepted", line);
+        }
+    }
+}
+
+use serde_json::json;
+use std::io::{self, BufRead};
+use std::process;
+use std::thread;
+use std::time::Duration;
+use vjoy_wrapper::vjoy_base::device::config::VJDConfig;
+use vjoy_wrapper::vjoy_base::device::feeding::{VJDOwnership, VJDSeqFeed};
+use vjoy_wrapper::vjoy_base::device::info::VJDInfo;
+use vjoy_wrapper::vjoy_base::device::pov::VJDPovCont;
+use vjoy_wrapper::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDButtonState, VJDPovDisc, VJDPovNumber, VJDStatus, VJDevice,
+};
+use vjoy_wrapper::vjoy_base::driver::VJGeneral;
+
+/// The command succeeded.
+const EXIT_OK: i32 = 0;
+
+/// vJoy refused the operation.
+const EXIT_FAILED: i32 = 1;
+
+/// The arguments are invalid.
+const EXIT_USAGE: i32 = 2;
+
+/// vJoy is not enabled, or the device does not exist.
+const EXIT_UNAVAILABLE: i32 = 3;
+
+/// The device is owned by another process.
+const EXIT_BUSY: i32 = 4;
+
+const USAGE: &str = "usage: vjoy [--json] <command>
+
+commands:
+    info                            driver and DLL versions, vJoy strings
+    list                            devices with their status, owner and controls
+    set <device> <control> <value>  set an axis (X 16384), a button (B3 on),
+                                    a discrete POV (POV1 East) or a continuous POV (CPOV2 9000)
+    reset <device|all>              reset the controls of one or all devices
+    monitor [--interval <ms>]       print every change of status of the devices
+    acquire <device> [--hold]       acquire a device, and with --hold keep it until Enter
+    tui [--interactive]             live view of the devices, built with the `tui` feature
+        [--interval <ms>]";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    json: bool,
+    command: Command,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Info,
+    List,
+    Set(VJDevice, Control),
+    /// Resets a device, or all of them.
+    Reset(Option<VJDevice>),
+    Monitor(Duration),
+    /// Acquires a device, holding it when set.
+    Acquire(VJDevice, bool),
+    /// Shows the devices in the terminal, interactively when set.
+    #[cfg(feature = "tui")]
+    Tui(Duration, bool),
+}
+
+#[derive(Debug, PartialEq)]
+enum Control {
+    Axis(VJDAxis, i32),
+    Button(VJDButton, VJDButtonState),
+    DiscPov(VJDPovNumber, VJDPovDisc),
+    ContPov(VJDPovNumber, VJDPovCont),
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    let code = match run(&options) {
+        Ok(()) => EXIT_OK,
+        Err((code, message)) => {
+            eprintln!("error: {}", message);
+            code
+        }
+    };
+
+    process::exit(code);
+}
+
+fn parse_args<I>(args: I) -> Result<Options, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut json = false;
+    let mut interval = None;
+    let mut flags = Vec::new();
+    let mut words = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                return Ok(Options {
+                    json,
+                    command: Command::Help,
+                })
+            }
+            "--interval" => interval = Some(args.next().ok_or("missing interval")?),
+            _ if arg.starts_with("--") => flags.push(arg),
+            _ => words.push(arg),
+        }
+    }
+
+    let mut words = words.iter().map(String::as_str);
+    let command = words.next().ok_or("missing command")?;
+    let mut arg = |name: &str| words.next().ok_or(format!("missing {}", name));
+    let mut flag = |name: &str| match flags.iter().position(|flag| flag == name) {
+        Some(index) => {
+            flags.remove(index);
+            true
+        }
+        None => false,
+    };
+
+    let command = match command {
+        "info" => Command::Info,
+        "list" => Command::List,
+        "set" => {
+            let device = parse_device(arg("device")?)?;
+            let control = parse_control(arg("control")?, arg("value")?)?;
+            Command::Set(device, control)
+        }
+        "reset" => match arg("device")? {
+            "all" => Command::Reset(None),
+            device => Command::Reset(Some(parse_device(device)?)),
+        },
+        "monitor" => Command::Monitor(parse_interval(interval.take(), 500)?),
+        "acquire" => {
+            let hold = flag("--hold");
+            Command::Acquire(parse_device(arg("device")?)?, hold)
+        }
+        #[cfg(feature = "tui")]
+        "tui" => {
+            let interactive = flag("--interactive");
+            Command::Tui(parse_interval(interval.take(), 50)?, interactive)
+        }
+        _ => return Err(format!("unknown command `{}`", command)),
+    };
+
+    if interval.is_some() {
+        return Err("unexpected argument `--interval`".to_string());
+    }
+    if let Some(extra) = words.next().or_else(|| flags.first().map(String::as_str)) {
+        return Err(format!("unexpected argument `{}`", extra));
+    }
+
+    Ok(Options { json, command })
+}
+
+/// Parses an interval in milliseconds, `default` if it is not given.
+fn parse_interval(interval: Option<String>, default: u64) -> Result<Duration, String> {
+    match interval {
+        Some(interval) => interval
+            .parse()
+            .ok()
+            .filter(|&millis| millis > 0)
+            .map(Duration::from_millis)
+            .ok_or(format!("invalid interval `{}`", interval)),
+        None => Ok(Duration::from_millis(default)),
+    }
+}
+
+fn parse_device(word: &str) -> Result<VJDevice, String> {
+    word.strip_prefix(|c| c == 'D' || c == 'd')
+        .unwrap_or(word)
+        .parse()
+        .ok()
+        .and_then(VJDevice::get_from)
+        .ok_or(format!("invalid device `{}`", word))
+}
+
+fn parse_control(control: &str, value: &str) -> Result<Control, String> {
+    let lower = control.to_lowercase();
+    let invalid_value = || format!("invalid value `{}` for {}", value, control);
+
+    if let Some(&axis) = VJDAxis::ALL
+        .iter()
+        .find(|axis| format!("{:?}", axis).to_lowercase() == lower)
+    {
+        return value
+            .parse()
+            .ok()
+            .filter(|value| (VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE).contains(value))
+            .map(|value| Control::Axis(axis, value))
+            .ok_or_else(invalid_value);
+    }
+
+    if let Some(number) = lower.strip_prefix("cpov") {
+        let pov = parse_pov(number).ok_or(format!("invalid control `{}`", control))?;
+        let value = if value.eq_ignore_ascii_case("neutral") {
+            Some(VJDPovCont::NEUTRAL)
+        } else {
+            value
+                .parse()
+                .ok()
+                .and_then(VJDPovCont::new)
+                .filter(|value| !value.is_neutral())
+        };
+        return value
+            .map(|value| Control::ContPov(pov, value))
+            .ok_or_else(invalid_value);
+    }
+
+    if let Some(number) = lower.strip_prefix("pov") {
+        let pov = parse_pov(number).ok_or(format!("invalid control `{}`", control))?;
+        let direction = match value.to_lowercase().as_str() {
+            "neutral" => VJDPovDisc::Neutral,
+            "north" => VJDPovDisc::North,
+            "east" => VJDPovDisc::East,
+            "south" => VJDPovDisc::South,
+            "west" => VJDPovDisc::West,
+            _ => return Err(invalid_value()),
+        };
+        return Ok(Control::DiscPov(pov, direction));
+    }
+
+    if let Some(number) = lower.strip_prefix('b') {
+        let button = number
+            .parse()
+            .ok()
+            .and_then(VJDButton::get_from)
+            .ok_or(format!("invalid control `{}`", control))?;
+        let state = match value.to_lowercase().as_str() {
+            "on" | "1" | "pressed" => VJDButtonState::Pressed,
+            "off" | "0" | "released" => VJDButtonState::Released,
+            _ => return Err(invalid_value()),
+        };
+        return Ok(Control::Button(button, state));
+    }
+
+    Err(format!("invalid control `{}`", control))
+}
+
+fn parse_pov(number: &str) -> Option<VJDPovNumber> {
+    match number {
+        "1" => Some(VJDPovNumber::Pov1),
+        "2" => Some(VJDPovNumber::Pov2),
+        "3" => Some(VJDPovNumber::Pov3),
+        "4" => Some(VJDPovNumber::Pov4),
+        _ => None,
+    }
+}
+
+/// Exit code and message of a failed command.
+type Failure = (i32, String);
+
+fn run(options: &Options) -> Result<(), Failure> {
+    let needs_vjoy = !matches!(options.command, Command::Help | Command::Info);
+    if needs_vjoy && !VJGeneral::is_enabled() {
+        return Err((EXIT_UNAVAILABLE, "vJoy is not enabled".to_string()));
+    }
+
+    match options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Info => info(options.json),
+        Command::List => list(options.json),
+        Command::Set(device, ref control) => set(options.json, device, control),
+        Command::Reset(device) => reset(options.json, device),
+        Command::Monitor(interval) => monitor(options.json, interval),
+        Command::Acquire(device, hold) => acquire(options.json, device, hold),
+        #[cfg(feature = "tui")]
+        Command::Tui(interval, interactive) => tui(interval, interactive),
+    }
+}
+
+fn devices() -> impl Iterator<Item = VJDevice> {
+    (1..=VJGeneral::MAX_DEVICES).filter_map(VJDevice::get_from)
+}
+
+fn device_number(device: VJDevice) -> u32 {
+    device as u32
+}
+
+fn version(version: Option<u16>) -> String {
+    version.map_or_else(
+        || "unknown".to_string(),
+        |version| format!("{:#x}", version),
+    )
+}
+
+fn info(json: bool) -> Result<(), Failure> {
+    let (driver, dll) = VJGeneral::get_driver_dll_version();
+
+    if json {
+        let info = json!({
+            "enabled": VJGeneral::is_enabled(),
+            "version": VJGeneral::get_version(),
+            "driver_version": driver,
+            "dll_version": dll,
+            "driver_matches_dll": VJGeneral::is_driver_match_dll(),
+            "product": VJGeneral::get_product(),
+            "manufacturer": VJGeneral::get_manufacturer(),
+            "serial_number": VJGeneral::get_serial_number(),
+        });
+        println!("{}", info);
+    } else {
+        let text = |text: Option<String>| text.unwrap_or_else(|| "unknown".to_string());
+
+        println!("vJoy enabled     | {}", VJGeneral::is_enabled());
+        println!("version          | {}", version(VJGeneral::get_version()));
+        println!("driver           | {}", version(driver));
+        println!("dll              | {}", version(dll));
+        println!("driver/dll match | {}", VJGeneral::is_driver_match_dll());
+        println!("product          | {}", text(VJGeneral::get_product()));
+        println!("manufacturer     | {}", text(VJGeneral::get_manufacturer()));
+        println!(
+            "serial number    | {}",
+            text(VJGeneral::get_serial_number())
+        );
+    }
+
+    Ok(())
+}
+
+fn list(json: bool) -> Result<(), Failure> {
+    for device in devices() {
+        let status = VJDInfo::get_status(device);
+        if status == VJDStatus::Miss {
+            continue;
+        }
+
+        let owner = VJDInfo::get_owner_pid(device).ok();
+        let config = VJDConfig::read(device);
+
+        if json {
+            let entry = json!({
+                "device": device_number(device),
+                "status": format!("{:?}", status),
+                "owner_pid": owner,
+                "controls": config,
+            });
+            println!("{}", entry);
+            continue;
+        }
+
+        let owner = owner.map_or_else(String::new, |pid| format!(" (PID {})", pid));
+        let controls = config.map_or_else(
+            || "controls unknown".to_string(),
+            |config| {
+                let axes: Vec<_> = config
+                    .axes
+                    .iter()
+                    .map(|axis| format!("{:?}", axis))
+                    .collect();
+                format!(
+                    "axes [{}], {} buttons, {} discrete POVs, {} continuous POVs",
+                    axes.join(" "),
+                    config.buttons,
+                    config.disc_povs,
+                    config.cont_povs
+                )
+            },
+        );
+        println!("{:?}: {:?}{}, {}", device, status, owner, controls);
+    }
+
+    Ok(())
+}
+
+/// Acquires a device unless this process already owns it.
+fn own(device: VJDevice) -> Result<(), Failure> {
+    match VJDInfo::get_status(device) {
+        VJDStatus::Own => Ok(()),
+        VJDStatus::Free if VJDOwnership::acquire(device) => Ok(()),
+        VJDStatus::Free => Err((EXIT_FAILED, format!("cannot acquire {:?}", device))),
+        VJDStatus::Busy => {
+            let owner = VJDInfo::get_owner_pid(device)
+                .map_or_else(|_| String::new(), |pid| format!(" by PID {}", pid));
+            Err((EXIT_BUSY, format!("{:?} is owned{}", device, owner)))
+        }
+        VJDStatus::Miss | VJDStatus::Unknown => {
+            Err((EXIT_UNAVAILABLE, format!("{:?} does not exist", device)))
+        }
+    }
+}
+
+fn set(json: bool, device: VJDevice, control: &Control) -> Result<(), Failure> {
+    own(device)?;
+
+    let sent = match *control {
+        Control::Axis(axis, value) => VJDSeqFeed::set_axis(device, axis, value),
+        Control::Button(button, state) => VJDSeqFeed::set_btn(device, button, state),
+        Control::DiscPov(pov, direction) => VJDSeqFeed::set_disc_pov(device, pov, direction),
+        Control::ContPov(pov, value) => VJDSeqFeed::set_cont_pov(device, pov, value),
+    };
+
+    if !sent {
+        return Err((
+            EXIT_FAILED,
+            format!(
+                "{:?} refused {:?}, does the control exist?",
+                device, control
+            ),
+        ));
+    }
+
+    if json {
+        println!("{}", json!({ "device": device_number(device), "ok": true }));
+    }
+    Ok(())
+}
+
+fn reset(json: bool, device: Option<VJDevice>) -> Result<(), Failure> {
+    let targets: Vec<_> = match device {
+        Some(device) => vec![device],
+        // Only the devices which can be reset, so that missing ones are not failures.
+        None => devices()
+            .filter(|&device| {
+                matches!(
+                    VJDInfo::get_status(device),
+                    VJDStatus::Own | VJDStatus::Free
+                )
+            })
+            .collect(),
+    };
+
+    let mut failed = Vec::new();
+    for &device in &targets {
+        if !VJDSeqFeed::reset(device) {
+            failed.push(device);
+        }
+    }
+
+    if json {
+        let numbers = |devices: &[VJDevice]| -> Vec<u32> {
+            devices.iter().copied().map(device_number).collect()
+        };
+        let reset: Vec<_> = targets
+            .iter()
+            .copied()
+            .filter(|device| !failed.contains(device))
+            .collect();
+        println!(
+            "{}",
+            json!({ "reset": numbers(&reset), "failed": numbers(&failed) })
+        );
+    }
+
+    match failed.as_slice() {
+        [] => Ok(()),
+        [device] => match VJDInfo::get_status(*device) {
+            VJDStatus::Busy => Err((EXIT_BUSY, format!("{:?} is owned", device))),
+            VJDStatus::Miss => Err((EXIT_UNAVAILABLE, format!("{:?} does not exist", device))),
+            _ => Err((EXIT_FAILED, format!("cannot reset {:?}", device))),
+        },
+        _ => Err((EXIT_FAILED, format!("cannot reset {:?}", failed))),
+    }
+}
+
+fn monitor(json: bool, interval: Duration) -> Result<(), Failure> {
+    let mut states: Vec<Option<(VJDStatus, Option<i32>)>> = vec![None; devices().count()];
+
+    loop {
+        if !VJGeneral::is_enabled() {
+            return Err((EXIT_UNAVAILABLE, "vJoy was disabled".to_string()));
+        }
+
+        for (device, state) in devices().zip(states.iter_mut()) {
+            let status = VJDInfo::get_status(device);
+            let owner = VJDInfo::get_owner_pid(device).ok();
+            let current = Some((status, owner));
+
+            // Missing devices are only reported when they disappear.
+            if *state == current || (state.is_none() && status == VJDStatus::Miss) {
+                *state = current;
+                continue;
+            }
+            *state = current;
+
+            if json {
+                let change = json!({
+                    "device": device_number(device),
+                    "status": format!("{:?}", status),
+                    "owner_pid": owner,
+                });
+                println!("{}", change);
+            } else {
+                let owner = owner.map_or_else(String::new, |pid| format!(" (PID {})", pid));
+                println!("{:?}: {:?}{}", device, status, owner);
+            }
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+fn acquire(json: bool, device: VJDevice, hold: bool) -> Result<(), Failure> {
+    own(device)?;
+
+    if json {
+        println!(
+            "{}",
+            json!({ "device": device_number(device), "acquired": true })
+        );
+    } else {
+        println!("{:?} acquired", device);
+    }
+
+    if hold {
+        if !json {
+            println!("press Enter to relinquish it");
+        }
+
+        // Also stops when the standard input is closed.
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line).ok();
+        VJDOwnership::relinquish(device);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn tui(interval: Duration, interactive: bool) -> Result<(), Failure> {
+    use vjoy_wrapper::vjoy_extra::monitor::MonitorApp;
+
+    let mut app = MonitorApp::existing_devices();
+    app.set_interactive(interactive);
+
+    #[cfg(feature = "sdl2")]
+    let (_sdl, mut source) = {
+        use vjoy_wrapper::vjoy_extra::monitor::SDL2Source;
+        use vjoy_wrapper::vjoy_extra::SDL2Helper;
+
+        let sdl_error = |error: String| (EXIT_FAILED, error);
+        let sdl = sdl2::init().map_err(sdl_error)?;
+        let joysticks = sdl.joystick().map_err(sdl_error)?;
+        let vjoys = SDL2Helper::get_vjoys(&joysticks)
+            .map_err(|error| (EXIT_FAILED, format!("{:?}", error)))?;
+        let event_pump = sdl.event_pump().map_err(sdl_error)?;
+
+        (sdl, SDL2Source::new(vjoys, event_pump))
+    };
+
+    // Without SDL2, only the updates made from the UI are shown.
+    #[cfg(not(feature = "sdl2"))]
+    let mut source = vjoy_wrapper::vjoy_extra::monitor::FeedSource::new();
+
+    app.run(&mut source, interval)
+        .map_err(|error| (EXIT_FAILED, error.to_string()))
+}
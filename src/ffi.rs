@@ -7,11 +7,21 @@
 pub mod force_feedback;
 
 mod rusty_structure;
+#[cfg(windows)]
 use rusty_structure::PositionV2;
 pub use rusty_structure::{VJDAxis, VJDPosition, VJDPovDisc, VJDPovNumber, VJDStatus, VJDevice};
 
 mod type_conversion;
+#[cfg(windows)]
 use type_conversion::{BOOL, DWORD, LONG, PVOID, SHORT, WORD};
+#[cfg(not(windows))]
+use type_conversion::{BOOL, PVOID};
+
+// Without vJoy, the C API is implemented by the uinput backend.
+#[cfg(target_os = "linux")]
+mod uinput;
+#[cfg(target_os = "linux")]
+pub use uinput::*;
 
 type RemovalCB = std::option::Option<unsafe extern "C" fn(arg1: BOOL, arg2: BOOL, arg3: PVOID)>;
 
@@ -63,6 +73,22 @@ pub enum VJDButton {
     B121, B122, B123, B124, B125, B126, B127, B128,
 }
 
+impl VJDButton {
+    /// Describes the maximum number of buttons of a vJoy device.
+    pub const MAX_BUTTONS: u8 = 128;
+
+    pub fn get_from(value: u8) -> Option<VJDButton> {
+        if !(1..=Self::MAX_BUTTONS).contains(&value) {
+            return None;
+        }
+
+        // Safe: the enum is represented by a u8 and its discriminants are contiguous from 1 to
+        // MAX_BUTTONS, which has just been checked.
+        Some(unsafe { std::mem::transmute::<u8, VJDButton>(value) })
+    }
+}
+
+#[cfg(windows)]
 extern "C" {
     pub fn AcquireVJD(rID: VJDevice) -> BOOL;
     pub fn DriverMatch(DllVer: *mut WORD, DrvVer: *mut WORD) -> BOOL;
@@ -4,7 +4,9 @@
 #![allow(non_upper_case_globals)]
 #![allow(clippy::upper_case_acronyms)]
 
-use super::type_conversion::{BOOL, BYTE, DWORD, LONG, PVOID, UCHAR, UINT, ULONG, WORD};
+#[cfg(windows)]
+use super::type_conversion::UINT;
+use super::type_conversion::{BOOL, BYTE, DWORD, LONG, PVOID, UCHAR, ULONG, WORD};
 
 pub const HID_USAGE_CONST: u32 = 38;
 pub const HID_USAGE_RAMP: u32 = 39;
@@ -203,6 +205,7 @@ pub type FFB_EFF_ENVLP = _FFB_EFF_ENVLP;
 
 pub type FfbGenCB = std::option::Option<unsafe extern "C" fn(arg1: PVOID, arg2: PVOID)>;
 
+#[cfg(windows)]
 extern "C" {
     pub fn Ffb_h_DevCtrl(Packet: *const FFB_DATA, Control: *mut FFB_CTRL) -> DWORD;
     pub fn Ffb_h_DevGain(Packet: *const FFB_DATA, Gain: *mut BYTE) -> DWORD;
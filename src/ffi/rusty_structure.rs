@@ -1,7 +1,11 @@
 use super::{
     type_conversion::{BYTE, DWORD, LONG, ULONG},
-    VJDButtonState,
+    VJDButton, VJDButtonState,
 };
+use crate::vjoy_base::device::axis::{VJDAxisRaw, VJDAxisValue};
+use crate::vjoy_base::device::button::{VJDButtonSet, VJDButtonSetError};
+use crate::vjoy_base::device::pov::VJDPovCont;
+use std::convert::TryFrom;
 
 /**
     Holds data that describes a position of a vJoy device.
@@ -116,6 +120,7 @@ pub struct PositionV2 {
     Holds data that describes a position of a vJoy device. This is a container of information that won't
     do anything until it is send to vJoy.
 */
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct VJDPosition {
     device: VJDevice,
     position: PositionV2,
@@ -175,6 +180,11 @@ impl VJDPosition {
         self.position
     }
 
+    /// Returns a position holding raw data received for the specified device.
+    pub(crate) fn from_raw(device: VJDevice, position: PositionV2) -> VJDPosition {
+        VJDPosition { device, position }
+    }
+
     pub fn set_button(&mut self, button: u32, state: VJDButtonState) {
         match state {
             VJDButtonState::Pressed => self.set_button_pressed(button),
@@ -183,32 +193,76 @@ impl VJDPosition {
     }
 
     pub fn set_button_pressed(&mut self, button: u32) {
-        let mask: u32 = 0b1 << ((button - 1) % 32);
-
-        match (button - 1) / 32 {
-            0 => self.position.lButtons |= mask,
-            1 => self.position.lButtonsEx1 |= mask,
-            2 => self.position.lButtonsEx2 |= mask,
-            3 => self.position.lButtonsEx3 |= mask,
-            _ => (),
+        if let Some(button) = Self::to_button(button) {
+            let mut buttons = self.get_buttons();
+            buttons.set(button);
+            self.set_buttons(&buttons);
         }
     }
 
     pub fn set_button_released(&mut self, button: u32) {
-        let mut mask: u32 = 0b1 << ((button - 1) % 32);
+        if let Some(button) = Self::to_button(button) {
+            let mut buttons = self.get_buttons();
+            buttons.clear(button);
+            self.set_buttons(&buttons);
+        }
+    }
+
+    fn to_button(button: u32) -> Option<VJDButton> {
+        u8::try_from(button).ok().and_then(VJDButton::get_from)
+    }
 
-        mask = !mask;
+    /**
+        Returns the state of all the buttons of the position.
+    */
+    pub fn get_buttons(&self) -> VJDButtonSet {
+        VJDButtonSet::from_words([
+            self.position.lButtons,
+            self.position.lButtonsEx1,
+            self.position.lButtonsEx2,
+            self.position.lButtonsEx3,
+        ])
+    }
 
-        match (button - 1) / 32 {
-            0 => self.position.lButtons &= mask,
-            1 => self.position.lButtonsEx1 &= mask,
-            2 => self.position.lButtonsEx2 &= mask,
-            3 => self.position.lButtonsEx3 &= mask,
-            _ => (),
-        }
+    /**
+        Replaces the state of all the buttons of the position: buttons in `buttons` are pressed,
+        the others are released.
+    */
+    pub fn set_buttons(&mut self, buttons: &VJDButtonSet) {
+        let [b1, b2, b3, b4] = buttons.to_words();
+
+        self.position.lButtons = b1;
+        self.position.lButtonsEx1 = b2;
+        self.position.lButtonsEx2 = b3;
+        self.position.lButtonsEx3 = b4;
+    }
+
+    /**
+        Same as [`VJDPosition::set_buttons`], but first checks every button of `buttons` exists
+        on the device of the position. The position is left unchanged on error.
+    */
+    pub fn set_buttons_checked(&mut self, buttons: &VJDButtonSet) -> Result<(), VJDButtonSetError> {
+        buttons.validate(self.device)?;
+        self.set_buttons(buttons);
+        Ok(())
+    }
+
+    /**
+        Presses the buttons in `buttons`, the other buttons keep their state.
+    */
+    pub fn press_buttons(&mut self, buttons: &VJDButtonSet) {
+        self.set_buttons(&self.get_buttons().union(buttons));
+    }
+
+    /**
+        Releases the buttons in `buttons`, the other buttons keep their state.
+    */
+    pub fn release_buttons(&mut self, buttons: &VJDButtonSet) {
+        self.set_buttons(&self.get_buttons().difference(buttons));
     }
 
-    pub fn set_disc_pov(&mut self, pov: VJDPovNumber, direction: VJDPovDisc) {
+    pub fn set_disc_pov(&mut self, pov: VJDPovNumber, direction: impl Into<VJDPovDisc>) {
+        let direction: VJDPovDisc = direction.into();
         let shift = 4 * (pov as u32 - 1);
 
         // Keep all bits except for the POV number given in argument
@@ -218,7 +272,20 @@ impl VJDPosition {
         self.position.bHats |= (direction as u32) << shift;
     }
 
-    pub fn set_cont_pov(&mut self, pov: VJDPovNumber, value: u32) {
+    /// Returns the direction of a discrete POV. Meaningless for a continuous POV.
+    pub fn get_disc_pov(&self, pov: VJDPovNumber) -> VJDPovDisc {
+        match (self.position.bHats >> (4 * (pov as u32 - 1))) & 0b1111 {
+            0 => VJDPovDisc::North,
+            1 => VJDPovDisc::East,
+            2 => VJDPovDisc::South,
+            3 => VJDPovDisc::West,
+            _ => VJDPovDisc::Neutral,
+        }
+    }
+
+    pub fn set_cont_pov(&mut self, pov: VJDPovNumber, value: impl Into<VJDPovCont>) {
+        let value = value.into().get();
+
         match pov {
             VJDPovNumber::Pov1 => self.position.bHats = value,
             VJDPovNumber::Pov2 => self.position.bHatsEx1 = value,
@@ -227,36 +294,96 @@ impl VJDPosition {
         }
     }
 
-    pub fn set_axis_x(&mut self, value: i32) {
-        self.position.wAxisX = value;
+    /**
+        Returns the raw values of the 4 POV fields, neutral being [`u32::MAX`]. Discrete POVs
+        are packed by 4 bits in the first field, continuous POVs use one field each.
+    */
+    pub fn get_povs_raw(&self) -> [u32; 4] {
+        [
+            self.position.bHats,
+            self.position.bHatsEx1,
+            self.position.bHatsEx2,
+            self.position.bHatsEx3,
+        ]
     }
 
-    pub fn set_axis_y(&mut self, value: i32) {
-        self.position.wAxisY = value;
+    /// Replaces the raw values of the 4 POV fields, see [`VJDPosition::get_povs_raw`].
+    pub fn set_povs_raw(&mut self, povs: [u32; 4]) {
+        let [p1, p2, p3, p4] = povs;
+
+        self.position.bHats = p1;
+        self.position.bHatsEx1 = p2;
+        self.position.bHatsEx2 = p3;
+        self.position.bHatsEx3 = p4;
     }
 
-    pub fn set_axis_z(&mut self, value: i32) {
-        self.position.wAxisZ = value;
+    /// Returns the value of a continuous POV. Meaningless for a discrete POV.
+    pub fn get_cont_pov(&self, pov: VJDPovNumber) -> VJDPovCont {
+        VJDPovCont::from(match pov {
+            VJDPovNumber::Pov1 => self.position.bHats,
+            VJDPovNumber::Pov2 => self.position.bHatsEx1,
+            VJDPovNumber::Pov3 => self.position.bHatsEx2,
+            VJDPovNumber::Pov4 => self.position.bHatsEx3,
+        })
     }
 
-    pub fn set_axis_xr(&mut self, value: i32) {
-        self.position.wAxisXRot = value;
+    /// Returns the value of an axis, clamped if an out-of-range `i32` was set.
+    pub fn get_axis(&self, axis: VJDAxis) -> VJDAxisRaw {
+        VJDAxisRaw::saturating(match axis {
+            VJDAxis::X => self.position.wAxisX,
+            VJDAxis::Y => self.position.wAxisY,
+            VJDAxis::Z => self.position.wAxisZ,
+            VJDAxis::Rx => self.position.wAxisXRot,
+            VJDAxis::Ry => self.position.wAxisYRot,
+            VJDAxis::Rz => self.position.wAxisZRot,
+            VJDAxis::Slider1 => self.position.wSlider,
+            VJDAxis::Slider2 => self.position.wDial,
+        })
     }
 
-    pub fn set_axis_yr(&mut self, value: i32) {
-        self.position.wAxisYRot = value;
+    pub fn set_axis(&mut self, axis: VJDAxis, value: impl VJDAxisValue) {
+        match axis {
+            VJDAxis::X => self.set_axis_x(value),
+            VJDAxis::Y => self.set_axis_y(value),
+            VJDAxis::Z => self.set_axis_z(value),
+            VJDAxis::Rx => self.set_axis_xr(value),
+            VJDAxis::Ry => self.set_axis_yr(value),
+            VJDAxis::Rz => self.set_axis_zr(value),
+            VJDAxis::Slider1 => self.set_slider1(value),
+            VJDAxis::Slider2 => self.set_slider2(value),
+        }
     }
 
-    pub fn set_axis_zr(&mut self, value: i32) {
-        self.position.wAxisZRot = value;
+    pub fn set_axis_x(&mut self, value: impl VJDAxisValue) {
+        self.position.wAxisX = value.into_vjoy();
     }
 
-    pub fn set_slider1(&mut self, value: i32) {
-        self.position.wSlider = value;
+    pub fn set_axis_y(&mut self, value: impl VJDAxisValue) {
+        self.position.wAxisY = value.into_vjoy();
     }
 
-    pub fn set_slider2(&mut self, value: i32) {
-        self.position.wDial = value;
+    pub fn set_axis_z(&mut self, value: impl VJDAxisValue) {
+        self.position.wAxisZ = value.into_vjoy();
+    }
+
+    pub fn set_axis_xr(&mut self, value: impl VJDAxisValue) {
+        self.position.wAxisXRot = value.into_vjoy();
+    }
+
+    pub fn set_axis_yr(&mut self, value: impl VJDAxisValue) {
+        self.position.wAxisYRot = value.into_vjoy();
+    }
+
+    pub fn set_axis_zr(&mut self, value: impl VJDAxisValue) {
+        self.position.wAxisZRot = value.into_vjoy();
+    }
+
+    pub fn set_slider1(&mut self, value: impl VJDAxisValue) {
+        self.position.wSlider = value.into_vjoy();
+    }
+
+    pub fn set_slider2(&mut self, value: impl VJDAxisValue) {
+        self.position.wDial = value.into_vjoy();
     }
 }
 
@@ -284,6 +411,7 @@ pub enum VJDStatus {
 /// Describes an axis of a vJoy device.
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VJDAxis {
     X = 0x30,
     Y = 0x31,
@@ -295,10 +423,25 @@ pub enum VJDAxis {
     Slider2 = 0x37,
 }
 
+impl VJDAxis {
+    /// Every axis, in the order of their usage.
+    pub const ALL: [VJDAxis; 8] = [
+        VJDAxis::X,
+        VJDAxis::Y,
+        VJDAxis::Z,
+        VJDAxis::Rx,
+        VJDAxis::Ry,
+        VJDAxis::Rz,
+        VJDAxis::Slider1,
+        VJDAxis::Slider2,
+    ];
+}
+
 // TODO: test it contains device from range [1; MAX]; may need custom macro
 /// Describes a vJoy device number ("id").
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VJDevice {
     // index is 1-based
     /// Device #1.
@@ -394,6 +537,7 @@ pub enum VJDPovDisc {
 */
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VJDPovNumber {
     /// POV #1.
     Pov1 = 1,
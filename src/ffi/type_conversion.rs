@@ -1,11 +1,12 @@
 #![allow(clippy::upper_case_acronyms)]
+// Windows types are LLP64: (U)LONG and DWORD are 32 bits wide whatever the target.
 pub type BOOL = bool;
 pub type BYTE = std::os::raw::c_uchar;
 pub type UCHAR = std::os::raw::c_uchar;
 pub type SHORT = std::os::raw::c_short;
 pub type WORD = std::os::raw::c_ushort;
-pub type DWORD = std::os::raw::c_ulong;
+pub type DWORD = u32;
 pub type UINT = std::os::raw::c_uint;
-pub type ULONG = std::os::raw::c_ulong;
-pub type LONG = std::os::raw::c_long;
+pub type ULONG = u32;
+pub type LONG = i32;
 pub type PVOID = *mut std::os::raw::c_void;
@@ -0,0 +1,156 @@
+//! Implements the vJoy C API on top of the uinput backend of
+//! [`vjoy_base::uinput`](crate::vjoy_base::uinput), with the same signatures as the Windows
+//! bindings so that the rest of the crate is unaware of the platform.
+//!
+//! The backend reports its own version and strings, not those of the vJoy driver.
+
+use super::rusty_structure::PositionV2;
+use super::type_conversion::{BOOL, DWORD, LONG, PVOID, SHORT, WORD};
+use super::{RemovalCB, VJDButton, VJDButtonState};
+use super::{VJDAxis, VJDPosition, VJDPovDisc, VJDPovNumber, VJDStatus, VJDevice};
+use crate::vjoy_base::device::button::VJDButtonSet;
+use crate::vjoy_base::driver::VJGeneral;
+use crate::vjoy_base::uinput::{self, MANUFACTURER, PRODUCT, SERIAL_NUMBER, VERSION};
+use std::sync::OnceLock;
+
+static PRODUCT_STRING: OnceLock<Vec<u16>> = OnceLock::new();
+static MANUFACTURER_STRING: OnceLock<Vec<u16>> = OnceLock::new();
+static SERIAL_NUMBER_STRING: OnceLock<Vec<u16>> = OnceLock::new();
+
+/// Returns a pointer to a null-terminated wide string which lives as long as the program.
+fn wide_string(cell: &'static OnceLock<Vec<u16>>, value: &str) -> PVOID {
+    cell.get_or_init(|| value.encode_utf16().chain(Some(0)).collect())
+        .as_ptr() as PVOID
+}
+
+/// Like vJoy, a device which does not exist has no control.
+fn count(count: Option<u8>) -> std::os::raw::c_int {
+    count.map_or(0, std::os::raw::c_int::from)
+}
+
+pub unsafe fn AcquireVJD(rID: VJDevice) -> BOOL {
+    uinput::acquire(rID)
+}
+
+pub unsafe fn DriverMatch(DllVer: *mut WORD, DrvVer: *mut WORD) -> BOOL {
+    for version in &[DllVer, DrvVer] {
+        if !version.is_null() {
+            **version = VERSION as WORD;
+        }
+    }
+
+    true
+}
+
+pub unsafe fn GetOwnerPid(rID: VJDevice) -> std::os::raw::c_int {
+    match uinput::status(rID) {
+        VJDStatus::Own => std::process::id() as std::os::raw::c_int,
+        VJDStatus::Free => -13,
+        _ => -12,
+    }
+}
+
+pub unsafe fn GetVJDAxisExist(rID: VJDevice, Axis: VJDAxis) -> BOOL {
+    uinput::config(rID).is_some_and(|config| config.has_axis(Axis))
+}
+
+pub unsafe fn GetVJDButtonNumber(rID: VJDevice) -> std::os::raw::c_int {
+    count(uinput::config(rID).map(|config| config.buttons))
+}
+
+pub unsafe fn GetVJDContPovNumber(rID: VJDevice) -> std::os::raw::c_int {
+    count(uinput::config(rID).map(|config| config.cont_povs))
+}
+
+pub unsafe fn GetVJDDiscPovNumber(rID: VJDevice) -> std::os::raw::c_int {
+    count(uinput::config(rID).map(|config| config.disc_povs))
+}
+
+pub unsafe fn GetVJDStatus(rID: VJDevice) -> VJDStatus {
+    uinput::status(rID)
+}
+
+pub unsafe fn GetvJoyManufacturerString() -> PVOID {
+    wide_string(&MANUFACTURER_STRING, MANUFACTURER)
+}
+
+pub unsafe fn GetvJoyProductString() -> PVOID {
+    wide_string(&PRODUCT_STRING, PRODUCT)
+}
+
+pub unsafe fn GetvJoySerialNumberString() -> PVOID {
+    wide_string(&SERIAL_NUMBER_STRING, SERIAL_NUMBER)
+}
+
+pub unsafe fn GetvJoyVersion() -> SHORT {
+    VERSION
+}
+
+pub unsafe fn isVJDExists(rID: VJDevice) -> BOOL {
+    uinput::status(rID) != VJDStatus::Miss
+}
+
+/// Devices are only removed by this process: the callback is never called.
+pub unsafe fn RegisterRemovalCB(_cb: RemovalCB, _data: PVOID) {}
+
+pub unsafe fn RelinquishVJD(rID: VJDevice) {
+    uinput::relinquish(rID)
+}
+
+pub unsafe fn ResetAll() {
+    for n in 1..=VJGeneral::MAX_DEVICES {
+        ResetVJD(VJDevice::get_from(n).unwrap());
+    }
+}
+
+pub unsafe fn ResetButtons(rID: VJDevice) -> BOOL {
+    uinput::update(rID, |position| position.set_buttons(&VJDButtonSet::new()))
+}
+
+pub unsafe fn ResetPovs(rID: VJDevice) -> BOOL {
+    // The neutral value of continuous POVs also sets every discrete POV to neutral.
+    uinput::update(rID, |position| {
+        for &pov in &[
+            VJDPovNumber::Pov1,
+            VJDPovNumber::Pov2,
+            VJDPovNumber::Pov3,
+            VJDPovNumber::Pov4,
+        ] {
+            position.set_cont_pov(pov, u32::MAX);
+        }
+    })
+}
+
+pub unsafe fn ResetVJD(rID: VJDevice) -> BOOL {
+    uinput::update(rID, |position| *position = VJDPosition::new(rID))
+}
+
+pub unsafe fn SetAxis(Value: LONG, rID: VJDevice, Axis: VJDAxis) -> BOOL {
+    GetVJDAxisExist(rID, Axis) && uinput::update(rID, |position| position.set_axis(Axis, Value))
+}
+
+pub unsafe fn SetBtn(Value: VJDButtonState, rID: VJDevice, nBtn: VJDButton) -> BOOL {
+    GetVJDButtonNumber(rID) >= nBtn as std::os::raw::c_int
+        && uinput::update(rID, |position| position.set_button(nBtn as u32, Value))
+}
+
+pub unsafe fn SetContPov(Value: DWORD, rID: VJDevice, nPov: VJDPovNumber) -> BOOL {
+    GetVJDContPovNumber(rID) >= nPov as std::os::raw::c_int
+        && uinput::update(rID, |position| position.set_cont_pov(nPov, Value))
+}
+
+pub unsafe fn SetDiscPov(Value: VJDPovDisc, rID: VJDevice, nPov: VJDPovNumber) -> BOOL {
+    GetVJDDiscPovNumber(rID) >= nPov as std::os::raw::c_int
+        && uinput::update(rID, |position| position.set_disc_pov(nPov, Value))
+}
+
+pub unsafe fn UpdateVJD(rID: VJDevice, pData: *mut PositionV2) -> BOOL {
+    !pData.is_null()
+        && uinput::update(rID, |position| {
+            *position = VJDPosition::from_raw(rID, *pData)
+        })
+}
+
+pub unsafe fn vJoyEnabled() -> BOOL {
+    true
+}
@@ -28,15 +28,52 @@ pub mod test_env {
     //      - Device id: 10 (editable)
     //      - Activated axes: X, Y, Z, Rx, Ry, Rz, Slider 1, Slider 2
     //      - Number of buttons: 1
-    //      - # of Disc POVs: 1
+    //      - # of Disc POVs: 4
     //      - # of Cont POVs: 0
     //      - Activated force feedback: none, effects disabled
     pub const TEST_DEVICE_1: VJDevice = VJDevice::D9; // Device of test #1
     pub const TEST_DEVICE_2: VJDevice = VJDevice::D10; // Device of test #2
     pub const TEST_DEVICE_INACTIVE: VJDevice = VJDevice::D16; // Device not activated
 
+    #[cfg(not(target_os = "linux"))]
     pub const TEST_VERSION: u16 = 219;
+    #[cfg(not(target_os = "linux"))]
     pub const TEST_PRODUCT: &str = "vJoy - Virtual Joystick";
+    #[cfg(not(target_os = "linux"))]
     pub const TEST_MANUFACTURER: &str = "Shaul Eizikovich";
+    #[cfg(not(target_os = "linux"))]
     pub const TEST_SERIAL_NUMBER: &str = "2.1.9";
+
+    // On Linux the uinput backend reports its own identity.
+    #[cfg(target_os = "linux")]
+    pub const TEST_VERSION: u16 = 100;
+    #[cfg(target_os = "linux")]
+    pub const TEST_PRODUCT: &str = super::vjoy_base::uinput::PRODUCT;
+    #[cfg(target_os = "linux")]
+    pub const TEST_MANUFACTURER: &str = super::vjoy_base::uinput::MANUFACTURER;
+    #[cfg(target_os = "linux")]
+    pub const TEST_SERIAL_NUMBER: &str = super::vjoy_base::uinput::SERIAL_NUMBER;
+
+    // On Linux the devices of tests are uinput devices configured as above, feeding fake writers
+    // so that tests do not need access to /dev/uinput.
+    #[cfg(all(test, target_os = "linux"))]
+    #[ctor::ctor]
+    fn configure_uinput_devices() {
+        use super::vjoy_base::device::config::VJDConfig;
+        use super::vjoy_base::device::VJDAxis;
+        use super::vjoy_base::uinput::{FakeUinputLog, FakeUinputWriter, VJDUinput};
+
+        let device_1 = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Ry, VJDAxis::Slider1])
+            .buttons(5)
+            .cont_povs(2);
+        let device_2 = VJDConfig::new().axes(&VJDAxis::ALL).buttons(1).disc_povs(4);
+
+        VJDUinput::configure(TEST_DEVICE_1, device_1).unwrap();
+        VJDUinput::configure(TEST_DEVICE_2, device_2).unwrap();
+        // Kept for the whole test run.
+        std::mem::forget(VJDUinput::set_writer_factory(|_| {
+            Box::new(FakeUinputWriter::new(&FakeUinputLog::new()))
+        }));
+    }
 }
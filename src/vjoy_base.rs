@@ -6,3 +6,5 @@
 pub mod device;
 pub mod driver;
 pub mod force_feedback;
+#[cfg(target_os = "linux")]
+pub mod uinput;
@@ -4,5 +4,9 @@ pub use crate::ffi::{
     VJDAxis, VJDButton, VJDButtonState, VJDPosition, VJDPovDisc, VJDPovNumber, VJDStatus, VJDevice,
 };
 
+pub mod axis;
+pub mod button;
+pub mod config;
 pub mod feeding;
 pub mod info;
+pub mod pov;
@@ -0,0 +1,460 @@
+//! Contains value types of vJoy axes and the conversions between them.
+//!
+//! vJoy axes hold 15 bits values from [`VJGeneral::MIN_AXIS_VALUE`] to
+//! [`VJGeneral::MAX_AXIS_VALUE`], represented by [`VJDAxisRaw`]. Every axis setter accepts any
+//! [`VJDAxisValue`]:
+//! - [`VJDAxisRaw`]: a value checked to be in the vJoy range.
+//! - [`VJDAxisUnit`]: a `f32` in the range 0.0 to 1.0.
+//! - [`VJDAxisSigned`]: a `f32` in the range -1.0 to 1.0, 0.0 being the neutral point.
+//! - [`SDL2AxisValue`]: a 16 bits value as read from an SDL2 joystick.
+//! - [`i32`]: passed to vJoy unchanged.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_saturating_clamps() {
+        assert_eq!(VJDAxisRaw::MIN, VJDAxisRaw::saturating(-5));
+        assert_eq!(VJDAxisRaw::MAX, VJDAxisRaw::saturating(40000));
+        assert_eq!(1234, VJDAxisRaw::saturating(1234).get());
+    }
+
+    #[test]
+    fn raw_try_from_i32_checks_range() {
+        assert_eq!(Err(VJDAxisRangeError(-5)), VJDAxisRaw::try_from(-5));
+        assert_eq!(Err(VJDAxisRangeError(40000)), VJDAxisRaw::try_from(40000));
+        assert_eq!(Ok(1234), VJDAxisRaw::try_from(1234).map(VJDAxisRaw::get));
+    }
+
+    #[test]
+    fn i32_values_are_unchanged() {
+        assert_eq!(-5, (-5).into_vjoy());
+        assert_eq!(40000, 40000.into_vjoy());
+        assert_eq!(VJGeneral::MAX_AXIS_VALUE, VJDAxisUnit::MAX.into_vjoy());
+    }
+
+    #[test]
+    fn raw_new_checks_range() {
+        assert!(VJDAxisRaw::new(VJGeneral::MIN_AXIS_VALUE - 1).is_none());
+        assert!(VJDAxisRaw::new(VJGeneral::MAX_AXIS_VALUE + 1).is_none());
+        assert_eq!(Some(VJDAxisRaw::NEUTRAL), VJDAxisRaw::new(16384));
+    }
+
+    #[test]
+    fn float_new_checks_range() {
+        assert!(VJDAxisUnit::new(-0.01).is_none());
+        assert!(VJDAxisUnit::new(1.01).is_none());
+        assert!(VJDAxisUnit::new(f32::NAN).is_none());
+        assert!(VJDAxisUnit::new(0.5).is_some());
+
+        assert!(VJDAxisSigned::new(-1.01).is_none());
+        assert!(VJDAxisSigned::new(1.01).is_none());
+        assert!(VJDAxisSigned::new(f32::NAN).is_none());
+        assert!(VJDAxisSigned::new(-1.0).is_some());
+    }
+
+    #[test]
+    fn float_saturating() {
+        assert_eq!(1.0, VJDAxisUnit::saturating(3.0).get());
+        assert_eq!(0.0, VJDAxisUnit::saturating(-3.0).get());
+        assert_eq!(0.5, VJDAxisUnit::saturating(f32::NAN).get());
+
+        assert_eq!(1.0, VJDAxisSigned::saturating(3.0).get());
+        assert_eq!(-1.0, VJDAxisSigned::saturating(-3.0).get());
+        assert_eq!(0.0, VJDAxisSigned::saturating(f32::NAN).get());
+    }
+
+    #[test]
+    fn unit_limits() {
+        assert_eq!(VJDAxisRaw::MIN, VJDAxisRaw::from(VJDAxisUnit::MIN));
+        assert_eq!(VJDAxisRaw::MAX, VJDAxisRaw::from(VJDAxisUnit::MAX));
+    }
+
+    #[test]
+    fn signed_limits_and_neutral() {
+        assert_eq!(VJDAxisRaw::MIN, VJDAxisRaw::from(VJDAxisSigned::MIN));
+        assert_eq!(
+            VJDAxisRaw::NEUTRAL,
+            VJDAxisRaw::from(VJDAxisSigned::NEUTRAL)
+        );
+        assert_eq!(VJDAxisRaw::MAX, VJDAxisRaw::from(VJDAxisSigned::MAX));
+        assert_eq!(
+            VJDAxisSigned::NEUTRAL,
+            VJDAxisSigned::from(VJDAxisRaw::NEUTRAL)
+        );
+    }
+
+    #[test]
+    fn sdl2_limits_and_neutral() {
+        assert_eq!(VJDAxisRaw::MIN, VJDAxisRaw::from(SDL2AxisValue::MIN));
+        assert_eq!(
+            VJDAxisRaw::NEUTRAL,
+            VJDAxisRaw::from(SDL2AxisValue::NEUTRAL)
+        );
+        assert_eq!(VJDAxisRaw::MAX, VJDAxisRaw::from(SDL2AxisValue::MAX));
+    }
+
+    #[test]
+    fn unit_round_trip_full_range() {
+        for value in VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE {
+            let raw = VJDAxisRaw::new(value).unwrap();
+            let unit = VJDAxisUnit::from(raw);
+
+            assert!((0.0..=1.0).contains(&unit.get()));
+            assert_eq!(raw, VJDAxisRaw::from(unit));
+        }
+    }
+
+    #[test]
+    fn signed_round_trip_full_range() {
+        for value in VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE {
+            let raw = VJDAxisRaw::new(value).unwrap();
+            let signed = VJDAxisSigned::from(raw);
+
+            assert!((-1.0..=1.0).contains(&signed.get()));
+            assert_eq!(raw, VJDAxisRaw::from(signed));
+        }
+    }
+
+    #[test]
+    fn sdl2_round_trip_full_range() {
+        for value in VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE {
+            let raw = VJDAxisRaw::new(value).unwrap();
+            assert_eq!(raw, VJDAxisRaw::from(SDL2AxisValue::from(raw)));
+        }
+    }
+
+    #[test]
+    fn sdl2_pairs_share_raw_value() {
+        // SDL2 values have one more bit than vJoy values: every pair of SDL2 values starting at
+        // an even offset from the minimum gives the same vJoy value.
+        for value in i16::MIN..=i16::MAX {
+            let offset = value as i32 - i16::MIN as i32;
+
+            assert_eq!(
+                offset / 2,
+                VJDAxisRaw::from(SDL2AxisValue::new(value)).get()
+            );
+        }
+    }
+
+    #[test]
+    fn conversions_are_monotonic() {
+        let mut previous_unit = -1.0;
+        let mut previous_signed = -2.0;
+        let mut previous_sdl2 = i32::MIN;
+
+        for value in VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE {
+            let raw = VJDAxisRaw::new(value).unwrap();
+
+            let unit = VJDAxisUnit::from(raw).get();
+            let signed = VJDAxisSigned::from(raw).get();
+            let sdl2 = SDL2AxisValue::from(raw).get() as i32;
+
+            assert!(unit > previous_unit);
+            assert!(signed > previous_signed);
+            assert!(sdl2 > previous_sdl2);
+
+            previous_unit = unit;
+            previous_signed = signed;
+            previous_sdl2 = sdl2;
+        }
+    }
+}
+
+use crate::vjoy_base::driver::VJGeneral;
+use std::{convert::TryFrom, fmt};
+
+/**
+    Describes a value accepted by the axis setters.
+
+    A plain [`i32`] is given to vJoy unchanged, out-of-range values included. The other types
+    are converted into [`VJDAxisRaw`] first.
+*/
+pub trait VJDAxisValue {
+    /// Returns the value given to vJoy.
+    fn into_vjoy(self) -> i32;
+}
+
+impl VJDAxisValue for i32 {
+    fn into_vjoy(self) -> i32 {
+        self
+    }
+}
+
+impl VJDAxisValue for VJDAxisRaw {
+    fn into_vjoy(self) -> i32 {
+        self.0
+    }
+}
+
+impl VJDAxisValue for VJDAxisUnit {
+    fn into_vjoy(self) -> i32 {
+        VJDAxisRaw::from(self).0
+    }
+}
+
+impl VJDAxisValue for VJDAxisSigned {
+    fn into_vjoy(self) -> i32 {
+        VJDAxisRaw::from(self).0
+    }
+}
+
+impl VJDAxisValue for SDL2AxisValue {
+    fn into_vjoy(self) -> i32 {
+        VJDAxisRaw::from(self).0
+    }
+}
+
+/**
+    Error returned when converting an [`i32`] out of the vJoy axis range into a [`VJDAxisRaw`].
+    Holds the rejected value.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VJDAxisRangeError(pub i32);
+
+impl fmt::Display for VJDAxisRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "axis value {} is out of the range {} to {}",
+            self.0,
+            VJGeneral::MIN_AXIS_VALUE,
+            VJGeneral::MAX_AXIS_VALUE
+        )
+    }
+}
+
+impl std::error::Error for VJDAxisRangeError {}
+
+/**
+    Describes a vJoy axis value in its native unit, in the range [`VJGeneral::MIN_AXIS_VALUE`]
+    to [`VJGeneral::MAX_AXIS_VALUE`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VJDAxisRaw(i32);
+
+impl VJDAxisRaw {
+    /// Minimum value of a vJoy axis.
+    pub const MIN: VJDAxisRaw = VJDAxisRaw(VJGeneral::MIN_AXIS_VALUE);
+
+    /// Neutral value of a vJoy axis.
+    pub const NEUTRAL: VJDAxisRaw = VJDAxisRaw(VJGeneral::NEUTRAL_AXIS_VALUE);
+
+    /// Maximum value of a vJoy axis.
+    pub const MAX: VJDAxisRaw = VJDAxisRaw(VJGeneral::MAX_AXIS_VALUE);
+
+    /**
+        Returns the value, or [`None`] if it is out of the vJoy axis range.
+    */
+    pub fn new(value: i32) -> Option<VJDAxisRaw> {
+        if (VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE).contains(&value) {
+            Some(VJDAxisRaw(value))
+        } else {
+            None
+        }
+    }
+
+    /**
+        Returns the value clamped to the vJoy axis range.
+    */
+    pub fn saturating(value: i32) -> VJDAxisRaw {
+        VJDAxisRaw(value.clamp(VJGeneral::MIN_AXIS_VALUE, VJGeneral::MAX_AXIS_VALUE))
+    }
+
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl TryFrom<i32> for VJDAxisRaw {
+    type Error = VJDAxisRangeError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        VJDAxisRaw::new(value).ok_or(VJDAxisRangeError(value))
+    }
+}
+
+impl From<VJDAxisRaw> for i32 {
+    fn from(value: VJDAxisRaw) -> Self {
+        value.0
+    }
+}
+
+/**
+    Describes an axis value in the range 0.0 to 1.0.
+
+    Converting into [`VJDAxisRaw`] rounds to the nearest vJoy value. A [`VJDAxisRaw`] converted
+    back and forth is unchanged.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct VJDAxisUnit(f32);
+
+impl VJDAxisUnit {
+    pub const MIN: VJDAxisUnit = VJDAxisUnit(0.0);
+    pub const MAX: VJDAxisUnit = VJDAxisUnit(1.0);
+
+    /**
+        Returns the value, or [`None`] if it is out of range or not a number.
+    */
+    pub fn new(value: f32) -> Option<VJDAxisUnit> {
+        if (0.0..=1.0).contains(&value) {
+            Some(VJDAxisUnit(value))
+        } else {
+            None
+        }
+    }
+
+    /**
+        Returns the value clamped to the range. Not a number is mapped to 0.5.
+    */
+    pub fn saturating(value: f32) -> VJDAxisUnit {
+        if value.is_nan() {
+            VJDAxisUnit(0.5)
+        } else {
+            VJDAxisUnit(value.clamp(0.0, 1.0))
+        }
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl From<VJDAxisRaw> for VJDAxisUnit {
+    fn from(value: VJDAxisRaw) -> Self {
+        VJDAxisUnit(
+            (value.0 - VJGeneral::MIN_AXIS_VALUE) as f32
+                / (VJGeneral::MAX_AXIS_VALUE - VJGeneral::MIN_AXIS_VALUE) as f32,
+        )
+    }
+}
+
+impl From<VJDAxisUnit> for VJDAxisRaw {
+    fn from(value: VJDAxisUnit) -> Self {
+        let span = (VJGeneral::MAX_AXIS_VALUE - VJGeneral::MIN_AXIS_VALUE) as f32;
+        VJDAxisRaw::saturating((value.0 * span).round() as i32 + VJGeneral::MIN_AXIS_VALUE)
+    }
+}
+
+/**
+    Describes an axis value in the range -1.0 to 1.0, 0.0 being the neutral point.
+
+    The vJoy neutral point [`VJGeneral::NEUTRAL_AXIS_VALUE`] is not the exact middle of the
+    vJoy range: there are 16384 values below it and 16383 above it. To keep 0.0 on the neutral
+    point, each half is scaled separately, so a step in the negative half is slightly smaller than
+    a step in the positive half.
+
+    Converting into [`VJDAxisRaw`] rounds to the nearest vJoy value. A [`VJDAxisRaw`] converted
+    back and forth is unchanged.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct VJDAxisSigned(f32);
+
+impl VJDAxisSigned {
+    pub const MIN: VJDAxisSigned = VJDAxisSigned(-1.0);
+    pub const NEUTRAL: VJDAxisSigned = VJDAxisSigned(0.0);
+    pub const MAX: VJDAxisSigned = VJDAxisSigned(1.0);
+
+    /**
+        Returns the value, or [`None`] if it is out of range or not a number.
+    */
+    pub fn new(value: f32) -> Option<VJDAxisSigned> {
+        if (-1.0..=1.0).contains(&value) {
+            Some(VJDAxisSigned(value))
+        } else {
+            None
+        }
+    }
+
+    /**
+        Returns the value clamped to the range. Not a number is mapped to 0.0.
+    */
+    pub fn saturating(value: f32) -> VJDAxisSigned {
+        if value.is_nan() {
+            VJDAxisSigned::NEUTRAL
+        } else {
+            VJDAxisSigned(value.clamp(-1.0, 1.0))
+        }
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+
+    fn lower_span() -> f32 {
+        (VJGeneral::NEUTRAL_AXIS_VALUE - VJGeneral::MIN_AXIS_VALUE) as f32
+    }
+
+    fn upper_span() -> f32 {
+        (VJGeneral::MAX_AXIS_VALUE - VJGeneral::NEUTRAL_AXIS_VALUE) as f32
+    }
+}
+
+impl From<VJDAxisRaw> for VJDAxisSigned {
+    fn from(value: VJDAxisRaw) -> Self {
+        let offset = (value.0 - VJGeneral::NEUTRAL_AXIS_VALUE) as f32;
+
+        if offset < 0.0 {
+            VJDAxisSigned(offset / VJDAxisSigned::lower_span())
+        } else {
+            VJDAxisSigned(offset / VJDAxisSigned::upper_span())
+        }
+    }
+}
+
+impl From<VJDAxisSigned> for VJDAxisRaw {
+    fn from(value: VJDAxisSigned) -> Self {
+        let span = if value.0 < 0.0 {
+            VJDAxisSigned::lower_span()
+        } else {
+            VJDAxisSigned::upper_span()
+        };
+
+        VJDAxisRaw::saturating((value.0 * span).round() as i32 + VJGeneral::NEUTRAL_AXIS_VALUE)
+    }
+}
+
+/**
+    Describes an axis value as read from an SDL2 joystick, in the range -32768 to 32767.
+
+    SDL2 values have 16 bits of precision while vJoy values have 15 bits. Converting into
+    [`VJDAxisRaw`] drops the least significant bit: two consecutive SDL2 values give the same
+    vJoy value. Converting from [`VJDAxisRaw`] is exact and gives the lower value of the pair, so a
+    [`VJDAxisRaw`] converted back and forth is unchanged.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SDL2AxisValue(i16);
+
+impl SDL2AxisValue {
+    pub const MIN: SDL2AxisValue = SDL2AxisValue(i16::MIN);
+    pub const NEUTRAL: SDL2AxisValue = SDL2AxisValue(0);
+    pub const MAX: SDL2AxisValue = SDL2AxisValue(i16::MAX);
+
+    pub fn new(value: i16) -> SDL2AxisValue {
+        SDL2AxisValue(value)
+    }
+
+    pub fn get(self) -> i16 {
+        self.0
+    }
+}
+
+impl From<i16> for SDL2AxisValue {
+    fn from(value: i16) -> Self {
+        SDL2AxisValue(value)
+    }
+}
+
+impl From<SDL2AxisValue> for VJDAxisRaw {
+    fn from(value: SDL2AxisValue) -> Self {
+        VJDAxisRaw(((value.0 as i32 - i16::MIN as i32) >> 1) + VJGeneral::MIN_AXIS_VALUE)
+    }
+}
+
+impl From<VJDAxisRaw> for SDL2AxisValue {
+    fn from(value: VJDAxisRaw) -> Self {
+        SDL2AxisValue((((value.0 - VJGeneral::MIN_AXIS_VALUE) << 1) + i16::MIN as i32) as i16)
+    }
+}
@@ -0,0 +1,381 @@
+//! Contains a set of vJoy buttons to handle the state of all the buttons of a device at once.
+
+#[cfg(test)]
+mod tests {
+    use super::super::VJDPosition;
+    use super::*;
+
+    fn set_of(buttons: &[VJDButton]) -> VJDButtonSet {
+        buttons.iter().copied().collect()
+    }
+
+    #[test]
+    fn button_get_from() {
+        assert_eq!(None, VJDButton::get_from(0));
+        assert_eq!(Some(VJDButton::B1), VJDButton::get_from(1));
+        assert_eq!(Some(VJDButton::B77), VJDButton::get_from(77));
+        assert_eq!(Some(VJDButton::B128), VJDButton::get_from(128));
+        assert_eq!(None, VJDButton::get_from(129));
+
+        for value in 1..=VJDButton::MAX_BUTTONS {
+            assert_eq!(value, VJDButton::get_from(value).unwrap() as u8);
+        }
+    }
+
+    #[test]
+    fn set_clear_toggle() {
+        let mut set = VJDButtonSet::new();
+        assert!(set.is_empty());
+
+        set.set(VJDButton::B3);
+        set.set(VJDButton::B100);
+        assert!(set.contains(VJDButton::B3));
+        assert!(set.contains(VJDButton::B100));
+        assert!(!set.contains(VJDButton::B4));
+        assert_eq!(2, set.len());
+
+        set.clear(VJDButton::B3);
+        assert!(!set.contains(VJDButton::B3));
+
+        set.toggle(VJDButton::B3);
+        set.toggle(VJDButton::B100);
+        assert!(set.contains(VJDButton::B3));
+        assert!(!set.contains(VJDButton::B100));
+
+        set.set_state(VJDButton::B5, VJDButtonState::Pressed);
+        assert_eq!(VJDButtonState::Pressed, set.get_state(VJDButton::B5));
+        set.set_state(VJDButton::B5, VJDButtonState::Released);
+        assert_eq!(VJDButtonState::Released, set.get_state(VJDButton::B5));
+    }
+
+    #[test]
+    fn words_layout() {
+        let set = set_of(&[
+            VJDButton::B1,
+            VJDButton::B32,
+            VJDButton::B33,
+            VJDButton::B96,
+            VJDButton::B128,
+        ]);
+
+        assert_eq!(
+            [0x8000_0001, 0x0000_0001, 0x8000_0000, 0x8000_0000],
+            set.to_words()
+        );
+        assert_eq!(set, VJDButtonSet::from_words(set.to_words()));
+    }
+
+    #[test]
+    fn iteration_is_ascending() {
+        let set = set_of(&[VJDButton::B128, VJDButton::B2, VJDButton::B64]);
+        let buttons: Vec<VJDButton> = set.iter().collect();
+
+        assert_eq!(
+            vec![VJDButton::B2, VJDButton::B64, VJDButton::B128],
+            buttons
+        );
+        assert_eq!(Some(VJDButton::B128), set.highest());
+        assert_eq!(None, VJDButtonSet::new().highest());
+    }
+
+    #[test]
+    fn full_set() {
+        let all = VJDButtonSet::all();
+
+        assert_eq!(VJDButton::MAX_BUTTONS as usize, all.len());
+        assert_eq!(
+            (1..=VJDButton::MAX_BUTTONS).collect::<Vec<u8>>(),
+            all.iter().map(|b| b as u8).collect::<Vec<u8>>()
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = set_of(&[VJDButton::B1, VJDButton::B2, VJDButton::B40]);
+        let b = set_of(&[VJDButton::B2, VJDButton::B90]);
+
+        assert_eq!(
+            set_of(&[VJDButton::B1, VJDButton::B2, VJDButton::B40, VJDButton::B90]),
+            a.union(&b)
+        );
+        assert_eq!(set_of(&[VJDButton::B1, VJDButton::B40]), a.difference(&b));
+        assert_eq!(set_of(&[VJDButton::B2]), a.intersection(&b));
+        assert_eq!(a.union(&b), a | b);
+        assert_eq!(a.difference(&b), a - b);
+        assert_eq!(a.intersection(&b), a & b);
+    }
+
+    #[test]
+    fn validate_against_total() {
+        let set = set_of(&[VJDButton::B1, VJDButton::B5]);
+
+        assert_eq!(Ok(()), set.validate_total(5));
+        assert_eq!(
+            Err(VJDButtonSetError::OutOfRange {
+                button: VJDButton::B5,
+                total: 4
+            }),
+            set.validate_total(4)
+        );
+        assert_eq!(Ok(()), VJDButtonSet::new().validate_total(0));
+    }
+
+    #[test]
+    fn position_apply_set() {
+        let mut position = VJDPosition::new(VJDevice::D1);
+
+        position.set_buttons(&set_of(&[VJDButton::B1, VJDButton::B70]));
+        position.press_buttons(&set_of(&[VJDButton::B2]));
+        position.release_buttons(&set_of(&[VJDButton::B1]));
+
+        assert_eq!(
+            set_of(&[VJDButton::B2, VJDButton::B70]),
+            position.get_buttons()
+        );
+
+        let mut expected = VJDPosition::new(VJDevice::D1);
+        expected.set_button_pressed(2);
+        expected.set_button_pressed(70);
+        assert_eq!(expected.get_position(), position.get_position());
+
+        expected.set_button_released(70);
+        expected.set_button(33, VJDButtonState::Pressed);
+        assert_eq!(
+            set_of(&[VJDButton::B2, VJDButton::B33]),
+            expected.get_buttons()
+        );
+    }
+
+    #[test]
+    fn position_ignores_invalid_button_number() {
+        let mut position = VJDPosition::new(VJDevice::D1);
+
+        position.set_button_pressed(0);
+        position.set_button_pressed(129);
+        position.set_button_pressed(1000);
+
+        assert!(position.get_buttons().is_empty());
+    }
+}
+
+use super::info::{TotalBtnsFailed, VJDInfo};
+use super::{VJDButton, VJDButtonState, VJDevice};
+use std::iter::FromIterator;
+use std::ops::{BitAnd, BitOr, Sub};
+
+/**
+    Describes an error state of [`VJDButtonSet::validate`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VJDButtonSetError {
+    /// The set holds a button which does not exist on the device. The number of buttons of the
+    /// device is provided.
+    OutOfRange { button: VJDButton, total: u8 },
+
+    /// The number of buttons of the device could not be retrieved.
+    TotalBtns(TotalBtnsFailed),
+}
+
+/**
+    Holds the state of the 128 buttons of a vJoy device, one bit per button.
+
+    The set is stored the same way as in a [`VJDPosition`](super::VJDPosition): four words of
+    32 buttons, the lowest bit of the first word being button 1.
+*/
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VJDButtonSet([u32; 4]);
+
+impl VJDButtonSet {
+    /// Returns an empty set: every button is released.
+    pub fn new() -> VJDButtonSet {
+        VJDButtonSet::default()
+    }
+
+    /// Returns a set holding the 128 buttons.
+    pub fn all() -> VJDButtonSet {
+        VJDButtonSet([u32::MAX; 4])
+    }
+
+    /**
+        Returns a set from the four button words of a position: buttons 1-32, 33-64, 65-96
+        and 97-128.
+    */
+    pub fn from_words(words: [u32; 4]) -> VJDButtonSet {
+        VJDButtonSet(words)
+    }
+
+    /**
+        Returns the four button words of a position: buttons 1-32, 33-64, 65-96 and 97-128.
+    */
+    pub fn to_words(&self) -> [u32; 4] {
+        self.0
+    }
+
+    fn locate(button: VJDButton) -> (usize, u32) {
+        let index = button as usize - 1;
+        (index / 32, 0b1 << (index % 32))
+    }
+
+    /// Marks the button as pressed.
+    pub fn set(&mut self, button: VJDButton) {
+        let (word, mask) = Self::locate(button);
+        self.0[word] |= mask;
+    }
+
+    /// Marks the button as released.
+    pub fn clear(&mut self, button: VJDButton) {
+        let (word, mask) = Self::locate(button);
+        self.0[word] &= !mask;
+    }
+
+    /// Inverts the state of the button.
+    pub fn toggle(&mut self, button: VJDButton) {
+        let (word, mask) = Self::locate(button);
+        self.0[word] ^= mask;
+    }
+
+    /// Returns `true` if the button is pressed, `false` otherwise.
+    pub fn contains(&self, button: VJDButton) -> bool {
+        let (word, mask) = Self::locate(button);
+        self.0[word] & mask != 0
+    }
+
+    pub fn set_state(&mut self, button: VJDButton, state: VJDButtonState) {
+        match state {
+            VJDButtonState::Pressed => self.set(button),
+            VJDButtonState::Released => self.clear(button),
+        }
+    }
+
+    pub fn get_state(&self, button: VJDButton) -> VJDButtonState {
+        if self.contains(button) {
+            VJDButtonState::Pressed
+        } else {
+            VJDButtonState::Released
+        }
+    }
+
+    /// Returns the number of pressed buttons.
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Returns `true` if every button is released.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    /// Iterates over the pressed buttons in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = VJDButton> + '_ {
+        (1..=VJDButton::MAX_BUTTONS)
+            .filter_map(VJDButton::get_from)
+            .filter(move |&button| self.contains(button))
+    }
+
+    /// Returns the pressed button with the highest number, or [`None`] if the set is empty.
+    pub fn highest(&self) -> Option<VJDButton> {
+        self.0
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, &word)| word != 0)
+            .and_then(|(index, word)| {
+                VJDButton::get_from((index * 32 + 32 - word.leading_zeros() as usize) as u8)
+            })
+    }
+
+    /// Returns the buttons pressed in `self`, `other` or both.
+    pub fn union(&self, other: &VJDButtonSet) -> VJDButtonSet {
+        *self | *other
+    }
+
+    /// Returns the buttons pressed in `self` but not in `other`.
+    pub fn difference(&self, other: &VJDButtonSet) -> VJDButtonSet {
+        *self - *other
+    }
+
+    /// Returns the buttons pressed in both `self` and `other`.
+    pub fn intersection(&self, other: &VJDButtonSet) -> VJDButtonSet {
+        *self & *other
+    }
+
+    /**
+        Returns [`VJDButtonSetError::OutOfRange`] if the set holds a button greater than
+        `total`, the number of buttons of a device.
+    */
+    pub fn validate_total(&self, total: u8) -> Result<(), VJDButtonSetError> {
+        match self.highest() {
+            Some(button) if button as u8 > total => {
+                Err(VJDButtonSetError::OutOfRange { button, total })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /**
+        Checks every button of the set exists on the specified device, as given by
+        [`VJDInfo::get_total_btns`].
+    */
+    pub fn validate(&self, device: VJDevice) -> Result<(), VJDButtonSetError> {
+        let total = VJDInfo::get_total_btns(device).map_err(VJDButtonSetError::TotalBtns)?;
+        self.validate_total(total)
+    }
+}
+
+impl BitOr for VJDButtonSet {
+    type Output = VJDButtonSet;
+
+    fn bitor(self, other: VJDButtonSet) -> VJDButtonSet {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0.iter()) {
+            *word |= other;
+        }
+        VJDButtonSet(words)
+    }
+}
+
+impl BitAnd for VJDButtonSet {
+    type Output = VJDButtonSet;
+
+    fn bitand(self, other: VJDButtonSet) -> VJDButtonSet {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0.iter()) {
+            *word &= other;
+        }
+        VJDButtonSet(words)
+    }
+}
+
+impl Sub for VJDButtonSet {
+    type Output = VJDButtonSet;
+
+    fn sub(self, other: VJDButtonSet) -> VJDButtonSet {
+        let mut words = self.0;
+        for (word, other) in words.iter_mut().zip(other.0.iter()) {
+            *word &= !other;
+        }
+        VJDButtonSet(words)
+    }
+}
+
+impl FromIterator<VJDButton> for VJDButtonSet {
+    fn from_iter<I: IntoIterator<Item = VJDButton>>(iter: I) -> Self {
+        let mut set = VJDButtonSet::new();
+        for button in iter {
+            set.set(button);
+        }
+        set
+    }
+}
+
+impl From<[u32; 4]> for VJDButtonSet {
+    fn from(words: [u32; 4]) -> Self {
+        VJDButtonSet::from_words(words)
+    }
+}
+
+impl From<VJDButtonSet> for [u32; 4] {
+    fn from(set: VJDButtonSet) -> Self {
+        set.to_words()
+    }
+}
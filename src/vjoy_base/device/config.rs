@@ -0,0 +1,240 @@
+//! Contains the description of the controls of a vJoy device.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_config_is_valid() {
+        let config = VJDConfig::full();
+
+        assert_eq!(Ok(()), config.validate());
+        assert_eq!(VJDAxis::ALL.len(), config.axes.len());
+        assert_eq!(VJDButton::MAX_BUTTONS, config.buttons);
+        assert!(config.has_axis(VJDAxis::Slider2));
+    }
+
+    #[test]
+    fn invalid_configs() {
+        assert_eq!(
+            Err(VJDConfigError::TooManyButtons(129)),
+            VJDConfig::new().buttons(129).validate()
+        );
+        assert_eq!(
+            Err(VJDConfigError::TooManyPovs(5)),
+            VJDConfig::new().disc_povs(5).validate()
+        );
+        assert_eq!(
+            Err(VJDConfigError::TooManyPovs(5)),
+            VJDConfig::new().cont_povs(5).validate()
+        );
+        assert_eq!(
+            Err(VJDConfigError::MixedPovs),
+            VJDConfig::new().disc_povs(1).cont_povs(1).validate()
+        );
+        assert_eq!(
+            Err(VJDConfigError::DuplicateAxis(VJDAxis::Y)),
+            VJDConfig::new()
+                .axes(&[VJDAxis::X, VJDAxis::Y, VJDAxis::Y])
+                .validate()
+        );
+        assert_eq!(
+            Err(VJDConfigError::DuplicateFfbEffect(VJDFfbEffect::Ramp)),
+            VJDConfig::new()
+                .ffb_effects(&[VJDFfbEffect::Ramp, VJDFfbEffect::Ramp])
+                .validate()
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Ry])
+            .buttons(5)
+            .cont_povs(2)
+            .ffb_effects(&[VJDFfbEffect::Spring]);
+
+        assert_eq!(
+            VJDConfig {
+                axes: vec![VJDAxis::X, VJDAxis::Ry],
+                buttons: 5,
+                disc_povs: 0,
+                cont_povs: 2,
+                ffb_effects: vec![VJDFfbEffect::Spring],
+            },
+            config
+        );
+        assert!(!config.has_axis(VJDAxis::Y));
+        assert!(config.has_ffb_effect(VJDFfbEffect::Spring));
+        assert!(!config.has_ffb_effect(VJDFfbEffect::Sine));
+        assert_eq!(Ok(()), config.validate());
+    }
+}
+
+use super::info::VJDInfo;
+use super::{VJDAxis, VJDButton, VJDevice};
+use crate::vjoy_base::force_feedback::VJDFfbEffect;
+
+/**
+    Describes an error state of [`VJDConfig::validate`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VJDConfigError {
+    /// More than [`VJDButton::MAX_BUTTONS`] buttons. The requested number is provided.
+    TooManyButtons(u8),
+
+    /// More than [`VJDConfig::MAX_POVS`] POVs of one type. The requested number is provided.
+    TooManyPovs(u8),
+
+    /// Discrete and continuous POVs cannot reside in the same device.
+    MixedPovs,
+
+    /// An axis is listed more than once.
+    DuplicateAxis(VJDAxis),
+
+    /// A force feedback effect is listed more than once.
+    DuplicateFfbEffect(VJDFfbEffect),
+}
+
+/**
+    Describes the controls of a vJoy device: which axes exist, how many buttons, how many POVs
+    of each type and which force feedback effects are supported.
+
+    On Windows the controls are set with the vJoy configuration tool and can be read back with
+    [`VJDConfig::read`]. Other backends use a [`VJDConfig`] to create their devices.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct VJDConfig {
+    /// Existing axes, in the order given at creation.
+    pub axes: Vec<VJDAxis>,
+
+    /// Number of buttons, from 0 to 128.
+    pub buttons: u8,
+
+    /// Number of discrete POVs, from 0 to 4.
+    pub disc_povs: u8,
+
+    /// Number of continuous POVs, from 0 to 4.
+    pub cont_povs: u8,
+
+    /// Supported force feedback effects. Force feedback is disabled when empty.
+    pub ffb_effects: Vec<VJDFfbEffect>,
+}
+
+impl VJDConfig {
+    /// Describes the maximum number of POVs of one type.
+    pub const MAX_POVS: u8 = 4;
+
+    /// Returns a configuration without any control.
+    pub fn new() -> VJDConfig {
+        VJDConfig::default()
+    }
+
+    /// Returns the configuration of vJoy default device: every axis, 128 buttons and 4
+    /// continuous POVs.
+    pub fn full() -> VJDConfig {
+        VJDConfig::new()
+            .axes(&VJDAxis::ALL)
+            .buttons(VJDButton::MAX_BUTTONS)
+            .cont_povs(Self::MAX_POVS)
+    }
+
+    pub fn axes(mut self, axes: &[VJDAxis]) -> VJDConfig {
+        self.axes = axes.to_vec();
+        self
+    }
+
+    pub fn buttons(mut self, buttons: u8) -> VJDConfig {
+        self.buttons = buttons;
+        self
+    }
+
+    pub fn disc_povs(mut self, povs: u8) -> VJDConfig {
+        self.disc_povs = povs;
+        self
+    }
+
+    pub fn cont_povs(mut self, povs: u8) -> VJDConfig {
+        self.cont_povs = povs;
+        self
+    }
+
+    pub fn ffb_effects(mut self, effects: &[VJDFfbEffect]) -> VJDConfig {
+        self.ffb_effects = effects.to_vec();
+        self
+    }
+
+    pub fn has_axis(&self, axis: VJDAxis) -> bool {
+        self.axes.contains(&axis)
+    }
+
+    pub fn has_ffb_effect(&self, effect: VJDFfbEffect) -> bool {
+        self.ffb_effects.contains(&effect)
+    }
+
+    /**
+        Checks the configuration can be created by vJoy, or returns the first inconsistency as
+        [`VJDConfigError`].
+    */
+    pub fn validate(&self) -> Result<(), VJDConfigError> {
+        if self.buttons > VJDButton::MAX_BUTTONS {
+            return Err(VJDConfigError::TooManyButtons(self.buttons));
+        }
+
+        for &povs in &[self.disc_povs, self.cont_povs] {
+            if povs > Self::MAX_POVS {
+                return Err(VJDConfigError::TooManyPovs(povs));
+            }
+        }
+
+        if self.disc_povs > 0 && self.cont_povs > 0 {
+            return Err(VJDConfigError::MixedPovs);
+        }
+
+        for (i, axis) in self.axes.iter().enumerate() {
+            if self.axes[..i].contains(axis) {
+                return Err(VJDConfigError::DuplicateAxis(*axis));
+            }
+        }
+
+        for (i, effect) in self.ffb_effects.iter().enumerate() {
+            if self.ffb_effects[..i].contains(effect) {
+                return Err(VJDConfigError::DuplicateFfbEffect(*effect));
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+        Returns the configuration of an existing device as reported by [`VJDInfo`], or [`None`]
+        if the device does not exist or cannot be queried.
+
+        The driver does not report force feedback effects: they are left empty. Use
+        [`read_device_config`](crate::vjoy_extra::registry::read_device_config) to get them.
+    */
+    pub fn read(device: VJDevice) -> Option<VJDConfig> {
+        if !VJDInfo::is_exist_device(device) {
+            return None;
+        }
+
+        let axes: Vec<VJDAxis> = VJDAxis::ALL
+            .iter()
+            .copied()
+            .filter(|&axis| VJDInfo::is_exist_axis(device, axis))
+            .collect();
+
+        Some(VJDConfig {
+            axes,
+            buttons: VJDInfo::get_total_btns(device).ok()?,
+            disc_povs: VJDInfo::get_total_disc_povs(device).ok()?,
+            cont_povs: VJDInfo::get_total_cont_povs(device).ok()?,
+            ffb_effects: Vec::new(),
+        })
+    }
+}
@@ -37,10 +37,44 @@ mod tests {
         VJDOwnership::relinquish(TEST_DEVICE_1);
         VJDOwnership::relinquish(TEST_DEVICE_2);
     }
+
+    #[test]
+    #[serial]
+    fn observers_are_removed_independently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let event = VJDFeedEvent::Position(VJDPosition::new(TEST_DEVICE_2));
+        let counts: Vec<Arc<AtomicUsize>> = (0..2).map(|_| Arc::default()).collect();
+        let ids: Vec<_> = counts
+            .iter()
+            .map(|count| {
+                let count = count.clone();
+                VJDFeedObserver::add(move |observed| {
+                    if *observed == event {
+                        count.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+
+        assert!(notify(true, event));
+        assert!(VJDFeedObserver::remove(ids[0]));
+        assert!(!VJDFeedObserver::remove(ids[0]));
+        assert!(!notify(false, event));
+        assert!(notify(true, event));
+        assert!(VJDFeedObserver::remove(ids[1]));
+
+        assert_eq!(1, counts[0].load(Ordering::SeqCst));
+        assert_eq!(2, counts[1].load(Ordering::SeqCst));
+    }
 }
 
+use super::axis::{VJDAxisRaw, VJDAxisValue};
 use super::info::VJDInfo;
+use super::pov::VJDPovCont;
 use crate::{ffi::*, vjoy_base::driver::VJGeneral};
+use std::sync::{Mutex, MutexGuard};
 
 /**
     Holder of utility methods to manage devices acquisition and relinquishment.
@@ -91,7 +125,8 @@ impl VJDPosFeed {
         Returns `true` if the operation succeeds, `false` otherwise.
     */
     pub fn send_position(position: &VJDPosition) -> bool {
-        unsafe { UpdateVJD(position.get_device(), &mut position.get_position()) }
+        let sent = unsafe { UpdateVJD(position.get_device(), &mut position.get_position()) };
+        notify(sent, VJDFeedEvent::Position(*position))
     }
 }
 
@@ -163,6 +198,7 @@ impl VJDSeqFeed {
         // false when providing a wrong device number, which is impossible
         // because we provide it by a controlled enum
         unsafe { ResetButtons(device) };
+        notify(true, VJDFeedEvent::ResetButtons(device));
     }
 
     /**
@@ -173,6 +209,7 @@ impl VJDSeqFeed {
         // false when providing a wrong device number, which is impossible
         // because we provide it by a controlled enum
         unsafe { ResetPovs(device) };
+        notify(true, VJDFeedEvent::ResetPovs(device));
     }
 
     // TODO: check range 0x1-0x8000 for setaxis and update doc
@@ -182,13 +219,19 @@ impl VJDSeqFeed {
 
         Returns `true` if the operation succeeds, `false` otherwise.
 
-        Value can be in the range of 0 to 32767. Middle point is at 16384.
+        Value can be in the range of 0 to 32767. Middle point is at 16384. Any [`VJDAxisValue`] is
+        accepted, see [`crate::vjoy_base::device::axis`].
     */
     // Value range is annonced 1 to 32768 in the vJoy doc, but the reality
     // when tested is 0 to 32767. See this thread for more details:
     // https://vjoy.freeforums.net/thread/15/axis-value-range
-    pub fn set_axis(device: VJDevice, axis: VJDAxis, value: i32) -> bool {
-        unsafe { SetAxis(value, device, axis) }
+    pub fn set_axis(device: VJDevice, axis: VJDAxis, value: impl VJDAxisValue) -> bool {
+        let value = value.into_vjoy();
+        let sent = unsafe { SetAxis(value, device, axis) };
+        notify(
+            sent,
+            VJDFeedEvent::Axis(device, axis, VJDAxisRaw::saturating(value)),
+        )
     }
 
     /**
@@ -199,20 +242,26 @@ impl VJDSeqFeed {
         Button number can be in the range 1 to 128.
     */
     pub fn set_btn(device: VJDevice, button_number: VJDButton, state: VJDButtonState) -> bool {
-        unsafe { SetBtn(state, device, button_number) }
+        let sent = unsafe { SetBtn(state, device, button_number) };
+        notify(sent, VJDFeedEvent::Button(device, button_number, state))
     }
 
     /**
         Write a discrete direction to a given discrete POV of the specified device.
 
         Returns `true` if the operation succeeds, `false` otherwise.
+
+        A [`VJDPovDirection`](super::pov::VJDPovDirection) is also accepted, diagonals are then degraded to their vertical
+        component.
     */
     pub fn set_disc_pov(
         device: VJDevice,
         pov_number: VJDPovNumber,
-        disc_direction: VJDPovDisc,
+        disc_direction: impl Into<VJDPovDisc>,
     ) -> bool {
-        unsafe { SetDiscPov(disc_direction, device, pov_number) }
+        let direction = disc_direction.into();
+        let sent = unsafe { SetDiscPov(direction, device, pov_number) };
+        notify(sent, VJDFeedEvent::DiscPov(device, pov_number, direction))
     }
 
     /**
@@ -220,10 +269,173 @@ impl VJDSeqFeed {
 
         Returns `true` if the operation succeeds, `false` otherwise.
 
-        Value can be in the range 0 to 35999, neutral is [`u32::MAX`].\
-        A value is measured in units of one-hundredth a degree.
+        Value can be in the range 0 to 35999, neutral is [`u32::MAX`]. Any other value is neutral.\
+        A value is measured in units of one-hundredth a degree. See [`VJDPovCont`] to build a
+        value from an angle, a vector or a
+        [`VJDPovDirection`](super::pov::VJDPovDirection).
+    */
+    pub fn set_cont_pov(
+        device: VJDevice,
+        pov_number: VJDPovNumber,
+        value: impl Into<VJDPovCont>,
+    ) -> bool {
+        let value = value.into();
+        let sent = unsafe { SetContPov(value.get(), device, pov_number) };
+        notify(sent, VJDFeedEvent::ContPov(device, pov_number, value))
+    }
+}
+
+/**
+    Describes an update of a vJoy device made through [`VJDPosFeed`] or [`VJDSeqFeed`], as
+    reported to the observers added with [`VJDFeedObserver::add`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VJDFeedEvent {
+    /// A whole position was sent.
+    Position(VJDPosition),
+
+    Axis(VJDevice, VJDAxis, VJDAxisRaw),
+
+    Button(VJDevice, VJDButton, VJDButtonState),
+
+    DiscPov(VJDevice, VJDPovNumber, VJDPovDisc),
+
+    ContPov(VJDevice, VJDPovNumber, VJDPovCont),
+
+    /// Every button of the device was released.
+    ResetButtons(VJDevice),
+
+    /// Every POV of the device was set to neutral.
+    ResetPovs(VJDevice),
+}
+
+impl VJDFeedEvent {
+    pub fn get_device(&self) -> VJDevice {
+        match *self {
+            VJDFeedEvent::Position(position) => position.get_device(),
+            VJDFeedEvent::Axis(device, _, _)
+            | VJDFeedEvent::Button(device, _, _)
+            | VJDFeedEvent::DiscPov(device, _, _)
+            | VJDFeedEvent::ContPov(device, _, _)
+            | VJDFeedEvent::ResetButtons(device)
+            | VJDFeedEvent::ResetPovs(device) => device,
+        }
+    }
+
+    /**
+        Applies the update to a position of the same device. Since vJoy positions cannot be
+        read back, this is how sequential updates are turned into whole positions.
     */
-    pub fn set_cont_pov(device: VJDevice, pov_number: VJDPovNumber, value: u32) -> bool {
-        unsafe { SetContPov(value, device, pov_number) }
+    pub fn apply(&self, position: &mut VJDPosition) {
+        match *self {
+            VJDFeedEvent::Position(sent) => *position = sent,
+            VJDFeedEvent::Axis(_, axis, value) => position.set_axis(axis, value),
+            VJDFeedEvent::Button(_, button, state) => position.set_button(button as u32, state),
+            VJDFeedEvent::DiscPov(_, pov, direction) => position.set_disc_pov(pov, direction),
+            VJDFeedEvent::ContPov(_, pov, value) => position.set_cont_pov(pov, value),
+            VJDFeedEvent::ResetButtons(_) => position.set_buttons(&Default::default()),
+            VJDFeedEvent::ResetPovs(_) => {
+                position.set_povs_raw(VJDPosition::new(position.get_device()).get_povs_raw())
+            }
+        }
+    }
+
+    /**
+        Sends the update through [`VJDPosFeed`] or [`VJDSeqFeed`].
+
+        Returns `true` if the operation succeeds, `false` otherwise.
+    */
+    pub fn send(&self) -> bool {
+        match *self {
+            VJDFeedEvent::Position(position) => VJDPosFeed::send_position(&position),
+            VJDFeedEvent::Axis(device, axis, value) => VJDSeqFeed::set_axis(device, axis, value),
+            VJDFeedEvent::Button(device, button, state) => {
+                VJDSeqFeed::set_btn(device, button, state)
+            }
+            VJDFeedEvent::DiscPov(device, pov, direction) => {
+                VJDSeqFeed::set_disc_pov(device, pov, direction)
+            }
+            VJDFeedEvent::ContPov(device, pov, value) => {
+                VJDSeqFeed::set_cont_pov(device, pov, value)
+            }
+            VJDFeedEvent::ResetButtons(device) => {
+                VJDSeqFeed::reset_btns(device);
+                true
+            }
+            VJDFeedEvent::ResetPovs(device) => {
+                VJDSeqFeed::reset_povs(device);
+                true
+            }
+        }
+    }
+}
+
+type Observer = Box<dyn FnMut(&VJDFeedEvent) + Send>;
+
+/// Observers of the updates, in the order they were added.
+struct Observers {
+    next_id: u64,
+    list: Vec<(VJDFeedObserverId, Observer)>,
+}
+
+static OBSERVERS: Mutex<Observers> = Mutex::new(Observers {
+    next_id: 0,
+    list: Vec::new(),
+});
+
+fn observers() -> MutexGuard<'static, Observers> {
+    OBSERVERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Reports a successful update to the observers, and returns whether it was successful.
+fn notify(sent: bool, event: VJDFeedEvent) -> bool {
+    if sent {
+        for (_, observer) in &mut observers().list {
+            observer(&event);
+        }
+    }
+
+    sent
+}
+
+/**
+    Identifies an observer added with [`VJDFeedObserver::add`], to remove it.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VJDFeedObserverId(u64);
+
+/**
+    Holder of utility methods to observe every successful update made through [`VJDPosFeed`]
+    and [`VJDSeqFeed`], for instance to record them.
+*/
+pub struct VJDFeedObserver(());
+
+impl VJDFeedObserver {
+    /**
+        Calls `observer` after each successful update, from the thread making the update, until
+        it is removed with the returned identifier. Observers are called in the order they were
+        added and must not feed devices nor add or remove observers themselves.
+    */
+    pub fn add<F>(observer: F) -> VJDFeedObserverId
+    where
+        F: FnMut(&VJDFeedEvent) + Send + 'static,
+    {
+        let mut observers = observers();
+        let id = VJDFeedObserverId(observers.next_id);
+
+        observers.next_id += 1;
+        observers.list.push((id, Box::new(observer)));
+        id
+    }
+
+    /// Stops calling an observer. Returns `false` if it was already removed.
+    pub fn remove(id: VJDFeedObserverId) -> bool {
+        let mut observers = observers();
+        let count = observers.list.len();
+
+        observers.list.retain(|(observer, _)| *observer != id);
+        observers.list.len() != count
     }
 }
@@ -0,0 +1,359 @@
+//! Contains value types of vJoy POV hats and the conversions between them.
+//!
+//! A continuous POV holds an angle in hundredths of a degree, represented by [`VJDPovCont`].
+//! Angles are measured clockwise from North (0) to 35999, [`u32::MAX`] being the neutral state.
+//!
+//! A discrete POV only knows the four directions of [`VJDPovDisc`]. [`VJDPovDirection`] adds the
+//! diagonals and can be sent to both kinds of POV.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_checks_range() {
+        assert_eq!(Some(0), VJDPovCont::new(0).map(VJDPovCont::get));
+        assert_eq!(Some(35999), VJDPovCont::new(35999).map(VJDPovCont::get));
+        assert_eq!(Some(VJDPovCont::NEUTRAL), VJDPovCont::new(u32::MAX));
+        assert_eq!(None, VJDPovCont::new(36000));
+    }
+
+    #[test]
+    fn from_u32_out_of_range_is_neutral() {
+        assert_eq!(35999, VJDPovCont::from(35999).get());
+        assert_eq!(VJDPovCont::NEUTRAL, VJDPovCont::from(36000));
+        assert_eq!(VJDPovCont::NEUTRAL, VJDPovCont::from(72500));
+        assert_eq!(VJDPovCont::NEUTRAL, VJDPovCont::from(u32::MAX));
+    }
+
+    #[test]
+    fn degrees_wrap() {
+        assert_eq!(9000, VJDPovCont::from_degrees(90.0).unwrap().get());
+        assert_eq!(27000, VJDPovCont::from_degrees(-90.0).unwrap().get());
+        assert_eq!(0, VJDPovCont::from_degrees(360.0).unwrap().get());
+        assert_eq!(4500, VJDPovCont::from_degrees(765.0).unwrap().get());
+        assert_eq!(0, VJDPovCont::from_degrees(359.999).unwrap().get());
+        assert_eq!(1234, VJDPovCont::from_degrees(12.34).unwrap().get());
+        assert_eq!(None, VJDPovCont::from_degrees(f32::NAN));
+        assert_eq!(None, VJDPovCont::from_degrees(f32::INFINITY));
+    }
+
+    #[test]
+    fn radians_wrap() {
+        use std::f32::consts::PI;
+
+        assert_eq!(18000, VJDPovCont::from_radians(PI).unwrap().get());
+        assert_eq!(27000, VJDPovCont::from_radians(-PI / 2.0).unwrap().get());
+        assert_eq!(None, VJDPovCont::from_radians(f32::NAN));
+    }
+
+    #[test]
+    fn vector_angles() {
+        assert_eq!(0, VJDPovCont::from_vector(0.0, 1.0).unwrap().get());
+        assert_eq!(9000, VJDPovCont::from_vector(1.0, 0.0).unwrap().get());
+        assert_eq!(18000, VJDPovCont::from_vector(0.0, -0.5).unwrap().get());
+        assert_eq!(27000, VJDPovCont::from_vector(-2.0, 0.0).unwrap().get());
+        assert_eq!(31500, VJDPovCont::from_vector(-1.0, 1.0).unwrap().get());
+        assert_eq!(Some(VJDPovCont::NEUTRAL), VJDPovCont::from_vector(0.0, 0.0));
+        assert_eq!(None, VJDPovCont::from_vector(f32::NAN, 1.0));
+    }
+
+    #[test]
+    fn degrees_read_back() {
+        assert_eq!(Some(123.45), VJDPovCont::new(12345).unwrap().to_degrees());
+        assert_eq!(None, VJDPovCont::NEUTRAL.to_degrees());
+    }
+
+    #[test]
+    fn direction_to_cont() {
+        assert_eq!(VJDPovCont::NEUTRAL, VJDPovDirection::Neutral.into());
+        assert_eq!(0, VJDPovCont::from(VJDPovDirection::North).get());
+        assert_eq!(13500, VJDPovCont::from(VJDPovDirection::SouthEast).get());
+        assert_eq!(31500, VJDPovCont::from(VJDPovDirection::NorthWest).get());
+    }
+
+    #[test]
+    fn cont_to_nearest_direction() {
+        let nearest = |value| VJDPovDirection::from(VJDPovCont::new(value).unwrap());
+
+        assert_eq!(VJDPovDirection::North, nearest(0));
+        assert_eq!(VJDPovDirection::North, nearest(2249));
+        assert_eq!(VJDPovDirection::NorthEast, nearest(2250));
+        assert_eq!(VJDPovDirection::West, nearest(27000));
+        assert_eq!(VJDPovDirection::North, nearest(35999));
+        assert_eq!(VJDPovDirection::Neutral, nearest(u32::MAX));
+    }
+
+    #[test]
+    fn direction_cont_round_trip() {
+        for direction in VJDPovDirection::ALL.iter().copied() {
+            assert_eq!(direction, VJDPovCont::from(direction).into());
+        }
+    }
+
+    #[test]
+    fn disc_round_trip() {
+        for disc in [
+            VJDPovDisc::Neutral,
+            VJDPovDisc::North,
+            VJDPovDisc::East,
+            VJDPovDisc::South,
+            VJDPovDisc::West,
+        ]
+        .iter()
+        .copied()
+        {
+            assert_eq!(disc, VJDPovDisc::from(VJDPovDirection::from(disc)));
+        }
+    }
+
+    #[test]
+    fn diagonal_degrades_to_vertical() {
+        assert_eq!(VJDPovDisc::North, VJDPovDirection::NorthEast.into());
+        assert_eq!(VJDPovDisc::South, VJDPovDirection::SouthEast.into());
+        assert_eq!(VJDPovDisc::South, VJDPovDirection::SouthWest.into());
+        assert_eq!(VJDPovDisc::North, VJDPovDirection::NorthWest.into());
+    }
+
+    #[test]
+    fn diagonal_keeps_previous_component() {
+        use VJDPovDirection::*;
+
+        assert_eq!(VJDPovDisc::East, NorthEast.to_disc_from(VJDPovDisc::East));
+        assert_eq!(VJDPovDisc::North, NorthEast.to_disc_from(VJDPovDisc::North));
+        assert_eq!(VJDPovDisc::South, SouthWest.to_disc_from(VJDPovDisc::East));
+        assert_eq!(VJDPovDisc::West, SouthWest.to_disc_from(VJDPovDisc::West));
+        assert_eq!(VJDPovDisc::East, East.to_disc_from(VJDPovDisc::North));
+        assert_eq!(VJDPovDisc::Neutral, Neutral.to_disc_from(VJDPovDisc::North));
+    }
+}
+
+use super::VJDPovDisc;
+
+/**
+    Describes the value of a continuous POV, in hundredths of a degree.
+
+    Valid values are 0 to 35999, measured clockwise from North, or [`VJDPovCont::NEUTRAL`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VJDPovCont(u32);
+
+impl VJDPovCont {
+    /// Neutral state of a continuous POV.
+    pub const NEUTRAL: VJDPovCont = VJDPovCont(u32::MAX);
+
+    /// Maximum value of a continuous POV.
+    pub const MAX_VALUE: u32 = 35999;
+
+    /// Number of values in a full turn.
+    const FULL_TURN: u32 = Self::MAX_VALUE + 1;
+
+    /**
+        Returns the value, or [`None`] if it is neither in the range 0 to 35999 nor
+        [`u32::MAX`] (neutral).
+    */
+    pub fn new(value: u32) -> Option<VJDPovCont> {
+        if value <= Self::MAX_VALUE || value == u32::MAX {
+            Some(VJDPovCont(value))
+        } else {
+            None
+        }
+    }
+
+    /**
+        Returns the angle in degrees, wrapped into a single turn and rounded to the nearest
+        hundredth of a degree. Negative angles are measured counterclockwise.
+
+        Returns [`None`] if the angle is not a finite number.
+    */
+    pub fn from_degrees(degrees: f32) -> Option<VJDPovCont> {
+        if !degrees.is_finite() {
+            return None;
+        }
+
+        let hundredths = (degrees as f64 * 100.0).round() as i64;
+        Some(VJDPovCont(
+            hundredths.rem_euclid(Self::FULL_TURN as i64) as u32
+        ))
+    }
+
+    /**
+        Same as [`VJDPovCont::from_degrees`] with an angle in radians.
+    */
+    pub fn from_radians(radians: f32) -> Option<VJDPovCont> {
+        Self::from_degrees(radians.to_degrees())
+    }
+
+    /**
+        Returns the angle pointed by the vector (`x`, `y`): `x` grows to the East and `y` grows
+        to the North. A null vector gives [`VJDPovCont::NEUTRAL`].
+
+        Returns [`None`] if a coordinate is not a finite number.
+    */
+    pub fn from_vector(x: f32, y: f32) -> Option<VJDPovCont> {
+        if !x.is_finite() || !y.is_finite() {
+            return None;
+        }
+
+        if x == 0.0 && y == 0.0 {
+            return Some(Self::NEUTRAL);
+        }
+
+        // Clockwise from North: the usual atan2(y, x) with swapped arguments.
+        Self::from_radians(x.atan2(y))
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    pub fn is_neutral(self) -> bool {
+        self == Self::NEUTRAL
+    }
+
+    /**
+        Returns the angle in degrees, or [`None`] if the POV is neutral.
+    */
+    pub fn to_degrees(self) -> Option<f32> {
+        if self.is_neutral() {
+            None
+        } else {
+            Some(self.0 as f32 / 100.0)
+        }
+    }
+}
+
+/// Any value out of the range 0 to 35999 is neutral, as vJoy treats it.
+impl From<u32> for VJDPovCont {
+    fn from(value: u32) -> Self {
+        Self::new(value).unwrap_or(Self::NEUTRAL)
+    }
+}
+
+impl From<VJDPovCont> for u32 {
+    fn from(value: VJDPovCont) -> Self {
+        value.0
+    }
+}
+
+/**
+    Describes one of the 8 directions of a POV hat, or its neutral state.
+
+    Diagonals do not exist on a discrete POV. When sent to one, a diagonal is degraded to one of
+    its two components, see [`VJDPovDirection::to_disc_from`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VJDPovDirection {
+    Neutral,
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl VJDPovDirection {
+    /// Every direction, neutral included.
+    pub const ALL: [VJDPovDirection; 9] = [
+        VJDPovDirection::Neutral,
+        VJDPovDirection::North,
+        VJDPovDirection::NorthEast,
+        VJDPovDirection::East,
+        VJDPovDirection::SouthEast,
+        VJDPovDirection::South,
+        VJDPovDirection::SouthWest,
+        VJDPovDirection::West,
+        VJDPovDirection::NorthWest,
+    ];
+
+    /// Directions ordered clockwise from North, one every 45 degrees.
+    const CLOCKWISE: [VJDPovDirection; 8] = [
+        VJDPovDirection::North,
+        VJDPovDirection::NorthEast,
+        VJDPovDirection::East,
+        VJDPovDirection::SouthEast,
+        VJDPovDirection::South,
+        VJDPovDirection::SouthWest,
+        VJDPovDirection::West,
+        VJDPovDirection::NorthWest,
+    ];
+
+    /**
+        Converts the direction to a discrete POV direction.
+
+        A diagonal keeps `previous` if it is one of its two components, so a hat rolling from a
+        cardinal direction to a neighbouring diagonal doesn't change the discrete output.
+        Otherwise, the vertical component (North or South) is chosen.
+    */
+    pub fn to_disc_from(self, previous: VJDPovDisc) -> VJDPovDisc {
+        let (vertical, horizontal) = match self {
+            VJDPovDirection::Neutral => return VJDPovDisc::Neutral,
+            VJDPovDirection::North => return VJDPovDisc::North,
+            VJDPovDirection::East => return VJDPovDisc::East,
+            VJDPovDirection::South => return VJDPovDisc::South,
+            VJDPovDirection::West => return VJDPovDisc::West,
+            VJDPovDirection::NorthEast => (VJDPovDisc::North, VJDPovDisc::East),
+            VJDPovDirection::SouthEast => (VJDPovDisc::South, VJDPovDisc::East),
+            VJDPovDirection::SouthWest => (VJDPovDisc::South, VJDPovDisc::West),
+            VJDPovDirection::NorthWest => (VJDPovDisc::North, VJDPovDisc::West),
+        };
+
+        if previous == horizontal {
+            horizontal
+        } else {
+            vertical
+        }
+    }
+}
+
+impl From<VJDPovDirection> for VJDPovCont {
+    fn from(direction: VJDPovDirection) -> Self {
+        match VJDPovDirection::CLOCKWISE
+            .iter()
+            .position(|&d| d == direction)
+        {
+            Some(index) => VJDPovCont(index as u32 * 4500),
+            None => VJDPovCont::NEUTRAL,
+        }
+    }
+}
+
+/// Snaps the angle to the nearest of the 8 directions.
+impl From<VJDPovCont> for VJDPovDirection {
+    fn from(value: VJDPovCont) -> Self {
+        if value.is_neutral() {
+            return VJDPovDirection::Neutral;
+        }
+
+        let index = ((value.0 % VJDPovCont::FULL_TURN + 2250) / 4500) % 8;
+        VJDPovDirection::CLOCKWISE[index as usize]
+    }
+}
+
+/// Diagonals are degraded to their vertical component, see [`VJDPovDirection::to_disc_from`].
+impl From<VJDPovDirection> for VJDPovDisc {
+    fn from(direction: VJDPovDirection) -> Self {
+        direction.to_disc_from(VJDPovDisc::Neutral)
+    }
+}
+
+impl From<VJDPovDisc> for VJDPovDirection {
+    fn from(disc: VJDPovDisc) -> Self {
+        match disc {
+            VJDPovDisc::Neutral => VJDPovDirection::Neutral,
+            VJDPovDisc::North => VJDPovDirection::North,
+            VJDPovDisc::East => VJDPovDirection::East,
+            VJDPovDisc::South => VJDPovDirection::South,
+            VJDPovDisc::West => VJDPovDirection::West,
+        }
+    }
+}
+
+impl From<VJDPovDisc> for VJDPovCont {
+    fn from(disc: VJDPovDisc) -> Self {
+        VJDPovDirection::from(disc).into()
+    }
+}
@@ -1 +1,83 @@
 //! Contains logics to operate force feedback.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_conversion_consistency() {
+        for &effect in &VJDFfbEffect::ALL {
+            assert_eq!(Some(effect), VJDFfbEffect::from_usage(effect.usage()));
+        }
+
+        assert_eq!(None, VJDFfbEffect::from_usage(0x25));
+        assert_eq!(None, VJDFfbEffect::from_usage(0x35));
+    }
+}
+
+use crate::ffi::force_feedback::{
+    HID_USAGE_CONST, HID_USAGE_DMPR, HID_USAGE_FRIC, HID_USAGE_INRT, HID_USAGE_RAMP,
+    HID_USAGE_SINE, HID_USAGE_SPRNG, HID_USAGE_SQUR, HID_USAGE_STDN, HID_USAGE_STUP,
+    HID_USAGE_TRNG,
+};
+
+/**
+    Describes a force feedback effect a vJoy device can support.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VJDFfbEffect {
+    Constant,
+    Ramp,
+    Square,
+    Sine,
+    Triangle,
+    SawtoothUp,
+    SawtoothDown,
+    Spring,
+    Damper,
+    Inertia,
+    Friction,
+}
+
+impl VJDFfbEffect {
+    /// Every effect, in the order of the vJoy configuration tool.
+    pub const ALL: [VJDFfbEffect; 11] = [
+        VJDFfbEffect::Constant,
+        VJDFfbEffect::Ramp,
+        VJDFfbEffect::Square,
+        VJDFfbEffect::Sine,
+        VJDFfbEffect::Triangle,
+        VJDFfbEffect::SawtoothUp,
+        VJDFfbEffect::SawtoothDown,
+        VJDFfbEffect::Spring,
+        VJDFfbEffect::Damper,
+        VJDFfbEffect::Inertia,
+        VJDFfbEffect::Friction,
+    ];
+
+    /// Returns the usage of the effect in the Physical Interface Device (PID) usage page.
+    pub fn usage(self) -> u32 {
+        match self {
+            VJDFfbEffect::Constant => HID_USAGE_CONST,
+            VJDFfbEffect::Ramp => HID_USAGE_RAMP,
+            VJDFfbEffect::Square => HID_USAGE_SQUR,
+            VJDFfbEffect::Sine => HID_USAGE_SINE,
+            VJDFfbEffect::Triangle => HID_USAGE_TRNG,
+            VJDFfbEffect::SawtoothUp => HID_USAGE_STUP,
+            VJDFfbEffect::SawtoothDown => HID_USAGE_STDN,
+            VJDFfbEffect::Spring => HID_USAGE_SPRNG,
+            VJDFfbEffect::Damper => HID_USAGE_DMPR,
+            VJDFfbEffect::Inertia => HID_USAGE_INRT,
+            VJDFfbEffect::Friction => HID_USAGE_FRIC,
+        }
+    }
+
+    /// Returns the effect of a usage of the PID usage page, or [`None`] if it is not an effect.
+    pub fn from_usage(usage: u32) -> Option<VJDFfbEffect> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|effect| effect.usage() == usage)
+    }
+}
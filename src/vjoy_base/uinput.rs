@@ -0,0 +1,988 @@
+//! Provides a Linux backend which emulates vJoy devices with uinput virtual devices.
+//!
+//! There is no vJoy driver on Linux. Instead, each [`VJDevice`] is described by a [`VJDConfig`]
+//! with [`VJDUinput::configure`]. Acquiring the device creates a uinput device with the same
+//! controls, and every update of the device, through [`VJDPosFeed`](super::device::feeding::VJDPosFeed)
+//! or [`VJDSeqFeed`](super::device::feeding::VJDSeqFeed), is translated into `EV_ABS`/`EV_KEY`
+//! events. Relinquishing the device destroys the uinput device.
+//!
+//! Axes keep the vJoy range (0 to 32767). POVs become hats, continuous POVs being snapped to the
+//! nearest of the 8 directions. Button 1 to 128 are mapped to the key codes given by
+//! [`button_code`].
+//!
+//! Events go through a [`UinputWriter`]: [`DevUinput`] writes to `/dev/uinput`, which requires
+//! write access to it, while [`FakeUinputWriter`] captures them for tests.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::feeding::{VJDOwnership, VJDPosFeed, VJDSeqFeed};
+    use crate::vjoy_base::device::info::VJDInfo;
+    use crate::vjoy_base::device::{VJDButtonState, VJDPovDisc, VJDStatus};
+    use serial_test::serial;
+
+    const DEVICE: VJDevice = VJDevice::D3;
+    const KEY_MAX: u16 = 0x2ff;
+
+    fn abs(code: u16, value: i32) -> UinputEvent {
+        UinputEvent::new(EV_ABS, code, value)
+    }
+
+    fn key(code: u16, pressed: bool) -> UinputEvent {
+        UinputEvent::new(EV_KEY, code, pressed as i32)
+    }
+
+    fn syn() -> UinputEvent {
+        UinputEvent::new(EV_SYN, SYN_REPORT, 0)
+    }
+
+    fn with_fake_device<F: FnOnce(&FakeUinputLog)>(config: VJDConfig, f: F) {
+        let log = FakeUinputLog::new();
+        let factory_log = log.clone();
+
+        // Other devices keep being faked: tests of other modules may run meanwhile.
+        let _factory = VJDUinput::set_writer_factory(move |device| {
+            if device == DEVICE {
+                Box::new(FakeUinputWriter::new(&factory_log))
+            } else {
+                Box::new(FakeUinputWriter::new(&FakeUinputLog::new()))
+            }
+        });
+        VJDUinput::configure(DEVICE, config).unwrap();
+
+        f(&log);
+
+        VJDOwnership::relinquish(DEVICE);
+        VJDUinput::remove(DEVICE).unwrap();
+    }
+
+    #[test]
+    fn button_codes_are_unique_and_defined() {
+        let codes: Vec<u16> = (1..=VJDButton::MAX_BUTTONS)
+            .map(|n| button_code(VJDButton::get_from(n).unwrap()))
+            .collect();
+
+        for (i, code) in codes.iter().enumerate() {
+            assert!(!codes[i + 1..].contains(code), "duplicate code {:#x}", code);
+            assert!(*code >= 0x100 && *code <= KEY_MAX);
+        }
+
+        // Codes missing from input-event-codes.h.
+        for code in (0x10a..=0x10f).chain(0x12c..=0x12e).chain(Some(0x13f)) {
+            assert!(!codes.contains(&code), "undefined code {:#x}", code);
+        }
+        assert!(codes.iter().all(|&code| !(0x2e8..=0x2ff).contains(&code)));
+
+        assert_eq!(0x120, button_code(VJDButton::B1));
+        assert_eq!(0x12f, button_code(VJDButton::B13));
+        assert_eq!(0x2c0, button_code(VJDButton::B14));
+        assert_eq!(0x2ad, button_code(VJDButton::B128));
+    }
+
+    #[test]
+    fn setup_follows_config() {
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Slider2])
+            .buttons(3)
+            .disc_povs(2);
+
+        let setup = UinputSetup::from_config(&config);
+
+        assert_eq!(
+            vec![
+                button_code(VJDButton::B1),
+                button_code(VJDButton::B2),
+                button_code(VJDButton::B3)
+            ],
+            setup.keys
+        );
+        assert_eq!(
+            vec![
+                UinputAbs::new(ABS_X, 0, 32767),
+                UinputAbs::new(ABS_RUDDER, 0, 32767),
+                UinputAbs::new(ABS_HAT0X, -1, 1),
+                UinputAbs::new(ABS_HAT0Y, -1, 1),
+                UinputAbs::new(ABS_HAT0X + 2, -1, 1),
+                UinputAbs::new(ABS_HAT0Y + 2, -1, 1),
+            ],
+            setup.abs
+        );
+    }
+
+    #[test]
+    fn only_changes_are_emitted() {
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Y])
+            .buttons(40)
+            .cont_povs(1);
+        let mut position = VJDPosition::new(DEVICE);
+        let initial = state_events(&config, &position);
+
+        assert!(diff_events(&initial, &initial).is_empty());
+
+        position.set_axis_y(100);
+        position.set_button_pressed(33);
+        position.set_cont_pov(VJDPovNumber::Pov1, 13500);
+
+        let current = state_events(&config, &position);
+
+        assert_eq!(
+            vec![
+                abs(ABS_Y, 100),
+                key(button_code(VJDButton::B33), true),
+                abs(ABS_HAT0X, 1),
+                abs(ABS_HAT0Y, 1),
+                syn(),
+            ],
+            diff_events(&initial, &current)
+        );
+    }
+
+    #[test]
+    fn controls_outside_config_are_ignored() {
+        let config = VJDConfig::new().axes(&[VJDAxis::X]).buttons(2);
+        let mut position = VJDPosition::new(DEVICE);
+        let initial = state_events(&config, &position);
+
+        position.set_axis_z(0);
+        position.set_button_pressed(3);
+        position.set_disc_pov(VJDPovNumber::Pov1, VJDPovDisc::East);
+
+        assert!(diff_events(&initial, &state_events(&config, &position)).is_empty());
+    }
+
+    #[test]
+    fn hat_directions() {
+        assert_eq!((0, 0), hat_values(VJDPovDirection::Neutral));
+        assert_eq!((0, -1), hat_values(VJDPovDirection::North));
+        assert_eq!((1, -1), hat_values(VJDPovDirection::NorthEast));
+        assert_eq!((-1, 1), hat_values(VJDPovDirection::SouthWest));
+        assert_eq!((-1, 0), hat_values(VJDPovDirection::West));
+    }
+
+    #[test]
+    #[serial]
+    fn feeding_drives_uinput_device() {
+        let config = VJDConfig::new().axes(&[VJDAxis::X]).buttons(2).disc_povs(1);
+
+        with_fake_device(config.clone(), |log| {
+            assert_eq!(VJDStatus::Free, VJDInfo::get_status(DEVICE));
+            assert_eq!(Some(config.clone()), VJDConfig::read(DEVICE));
+            assert!(log.setup().is_none());
+
+            assert!(VJDOwnership::acquire(DEVICE));
+            assert_eq!(VJDStatus::Own, VJDInfo::get_status(DEVICE));
+            assert_eq!(Some(UinputSetup::from_config(&config)), log.setup());
+
+            // The initial state of the device is written at creation.
+            assert_eq!(
+                vec![
+                    abs(ABS_X, 16384),
+                    key(button_code(VJDButton::B1), false),
+                    key(button_code(VJDButton::B2), false),
+                    abs(ABS_HAT0X, 0),
+                    abs(ABS_HAT0Y, 0),
+                    syn(),
+                ],
+                log.take_events()
+            );
+
+            assert!(VJDSeqFeed::set_axis(DEVICE, VJDAxis::X, 5));
+            assert!(VJDSeqFeed::set_btn(
+                DEVICE,
+                VJDButton::B2,
+                VJDButtonState::Pressed
+            ));
+            assert_eq!(
+                vec![
+                    abs(ABS_X, 5),
+                    syn(),
+                    key(button_code(VJDButton::B2), true),
+                    syn()
+                ],
+                log.take_events()
+            );
+
+            let mut position = VJDPosition::new(DEVICE);
+            position.set_disc_pov(VJDPovNumber::Pov1, VJDPovDisc::South);
+            assert!(VJDPosFeed::send_position(&position));
+            assert_eq!(
+                vec![
+                    abs(ABS_X, 16384),
+                    key(button_code(VJDButton::B2), false),
+                    abs(ABS_HAT0Y, 1),
+                    syn(),
+                ],
+                log.take_events()
+            );
+
+            assert!(VJDOwnership::relinquish(DEVICE));
+            assert!(log.is_destroyed());
+            assert!(!VJDSeqFeed::set_axis(DEVICE, VJDAxis::X, 5));
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn configuration_is_locked_while_acquired() {
+        with_fake_device(VJDConfig::new().buttons(1), |_| {
+            assert!(VJDOwnership::acquire(DEVICE));
+            assert!(!VJDOwnership::acquire(DEVICE));
+
+            assert_eq!(
+                Err(UinputError::Acquired(DEVICE)),
+                VJDUinput::configure(DEVICE, VJDConfig::full())
+            );
+            assert_eq!(
+                Err(UinputError::Acquired(DEVICE)),
+                VJDUinput::remove(DEVICE)
+            );
+        });
+
+        assert_eq!(
+            Err(UinputError::Config(VJDConfigError::MixedPovs)),
+            VJDUinput::configure(DEVICE, VJDConfig::new().disc_povs(1).cont_povs(1))
+        );
+        assert_eq!(VJDStatus::Miss, VJDInfo::get_status(DEVICE));
+    }
+}
+
+use super::device::config::{VJDConfig, VJDConfigError};
+use super::device::pov::VJDPovDirection;
+use super::device::{VJDAxis, VJDButton, VJDPosition, VJDPovNumber, VJDStatus, VJDevice};
+use super::driver::VJGeneral;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+pub const SYN_REPORT: u16 = 0x00;
+
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_Z: u16 = 0x02;
+pub const ABS_RX: u16 = 0x03;
+pub const ABS_RY: u16 = 0x04;
+pub const ABS_RZ: u16 = 0x05;
+pub const ABS_THROTTLE: u16 = 0x06;
+pub const ABS_RUDDER: u16 = 0x07;
+pub const ABS_HAT0X: u16 = 0x10;
+pub const ABS_HAT0Y: u16 = 0x11;
+
+const ABS_CNT: usize = 0x40;
+const BUS_VIRTUAL: u16 = 0x06;
+
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const UI_SET_EVBIT: u64 = 0x4004_5564;
+const UI_SET_KEYBIT: u64 = 0x4004_5565;
+const UI_SET_ABSBIT: u64 = 0x4004_5567;
+
+/// Ranges of defined key codes given to buttons, in order. They hold exactly 128 codes.
+const BUTTON_RANGES: [(u16, u16); 8] = [
+    // BTN_TRIGGER to BTN_BASE6
+    (0x120, 0x12b),
+    // BTN_DEAD
+    (0x12f, 0x12f),
+    // BTN_TRIGGER_HAPPY1 to BTN_TRIGGER_HAPPY40
+    (0x2c0, 0x2e7),
+    // BTN_SOUTH to BTN_THUMBR
+    (0x130, 0x13e),
+    // BTN_0 to BTN_9
+    (0x100, 0x109),
+    // BTN_DPAD_UP to BTN_DPAD_RIGHT
+    (0x220, 0x223),
+    // KEY_NUMERIC_0 to KEY_NUMERIC_D
+    (0x200, 0x20f),
+    // KEY_MACRO1 to KEY_MACRO30
+    (0x290, 0x2ad),
+];
+
+/**
+    Returns the key code of a button.
+
+    Linux has no contiguous range of 128 joystick buttons. Buttons 1-13 use the joystick range
+    (`BTN_TRIGGER`... and `BTN_DEAD`), 14-53 `BTN_TRIGGER_HAPPY1` to `BTN_TRIGGER_HAPPY40`,
+    54-68 the gamepad range (`BTN_SOUTH`...), 69-78 the misc range (`BTN_0`...), 79-82 the
+    directional pad (`BTN_DPAD_UP`...), 83-98 the numeric keys (`KEY_NUMERIC_0`...) and 99-128
+    the macro keys (`KEY_MACRO1`...). Only defined codes are used.
+*/
+pub fn button_code(button: VJDButton) -> u16 {
+    let mut index = button as u16 - 1;
+
+    for &(first, last) in &BUTTON_RANGES {
+        let len = last - first + 1;
+
+        if index < len {
+            return first + index;
+        }
+
+        index -= len;
+    }
+
+    unreachable!("the button ranges hold 128 codes")
+}
+
+/// Returns the absolute axis code of a vJoy axis. Sliders are mapped to throttle and rudder.
+pub fn axis_code(axis: VJDAxis) -> u16 {
+    match axis {
+        VJDAxis::X => ABS_X,
+        VJDAxis::Y => ABS_Y,
+        VJDAxis::Z => ABS_Z,
+        VJDAxis::Rx => ABS_RX,
+        VJDAxis::Ry => ABS_RY,
+        VJDAxis::Rz => ABS_RZ,
+        VJDAxis::Slider1 => ABS_THROTTLE,
+        VJDAxis::Slider2 => ABS_RUDDER,
+    }
+}
+
+/// Returns the absolute axis codes of the hat of a POV: (horizontal, vertical).
+pub fn hat_codes(pov: VJDPovNumber) -> (u16, u16) {
+    let offset = 2 * (pov as u16 - 1);
+    (ABS_HAT0X + offset, ABS_HAT0Y + offset)
+}
+
+/// Returns the hat values of a direction: (horizontal, vertical), North being -1.
+fn hat_values(direction: VJDPovDirection) -> (i32, i32) {
+    match direction {
+        VJDPovDirection::Neutral => (0, 0),
+        VJDPovDirection::North => (0, -1),
+        VJDPovDirection::NorthEast => (1, -1),
+        VJDPovDirection::East => (1, 0),
+        VJDPovDirection::SouthEast => (1, 1),
+        VJDPovDirection::South => (0, 1),
+        VJDPovDirection::SouthWest => (-1, 1),
+        VJDPovDirection::West => (-1, 0),
+        VJDPovDirection::NorthWest => (-1, -1),
+    }
+}
+
+fn povs(count: u8) -> impl Iterator<Item = VJDPovNumber> {
+    [
+        VJDPovNumber::Pov1,
+        VJDPovNumber::Pov2,
+        VJDPovNumber::Pov3,
+        VJDPovNumber::Pov4,
+    ]
+    .iter()
+    .copied()
+    .take(count as usize)
+}
+
+fn buttons(count: u8) -> impl Iterator<Item = VJDButton> {
+    (1..=count).filter_map(VJDButton::get_from)
+}
+
+/**
+    Describes an input event, as written to uinput.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct UinputEvent {
+    /// Event type, such as [`EV_ABS`].
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl UinputEvent {
+    pub fn new(kind: u16, code: u16, value: i32) -> UinputEvent {
+        UinputEvent { kind, code, value }
+    }
+}
+
+/**
+    Describes an absolute axis of a uinput device.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct UinputAbs {
+    pub code: u16,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl UinputAbs {
+    pub fn new(code: u16, min: i32, max: i32) -> UinputAbs {
+        UinputAbs { code, min, max }
+    }
+}
+
+/**
+    Describes a uinput device to create.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UinputSetup {
+    pub name: String,
+    pub vendor: u16,
+    pub product: u16,
+    pub version: u16,
+    pub keys: Vec<u16>,
+    pub abs: Vec<UinputAbs>,
+}
+
+impl UinputSetup {
+    /// Name given to every device, the same as vJoy devices on Windows.
+    pub const NAME: &'static str = "vJoy Device";
+
+    /// Vendor id of vJoy devices.
+    pub const VENDOR: u16 = 0x1234;
+
+    /// Product id of vJoy devices.
+    pub const PRODUCT: u16 = 0xBEAD;
+
+    /// Returns the description of a uinput device with the controls of a configuration.
+    pub fn from_config(config: &VJDConfig) -> UinputSetup {
+        let mut abs: Vec<UinputAbs> = config
+            .axes
+            .iter()
+            .map(|&axis| {
+                UinputAbs::new(
+                    axis_code(axis),
+                    VJGeneral::MIN_AXIS_VALUE,
+                    VJGeneral::MAX_AXIS_VALUE,
+                )
+            })
+            .collect();
+
+        for pov in povs(config.disc_povs.max(config.cont_povs)) {
+            let (x, y) = hat_codes(pov);
+            abs.push(UinputAbs::new(x, -1, 1));
+            abs.push(UinputAbs::new(y, -1, 1));
+        }
+
+        UinputSetup {
+            name: Self::NAME.to_string(),
+            vendor: Self::VENDOR,
+            product: Self::PRODUCT,
+            version: VERSION as u16,
+            keys: buttons(config.buttons).map(button_code).collect(),
+            abs,
+        }
+    }
+}
+
+/**
+    Returns the state of every control of the configuration as events, always in the same order
+    for a given configuration, without the final synchronization event.
+*/
+fn state_events(config: &VJDConfig, position: &VJDPosition) -> Vec<UinputEvent> {
+    let mut events = Vec::new();
+
+    for &axis in &config.axes {
+        events.push(UinputEvent::new(
+            EV_ABS,
+            axis_code(axis),
+            position.get_axis(axis).get(),
+        ));
+    }
+
+    let pressed = position.get_buttons();
+    for button in buttons(config.buttons) {
+        events.push(UinputEvent::new(
+            EV_KEY,
+            button_code(button),
+            pressed.contains(button) as i32,
+        ));
+    }
+
+    for pov in povs(config.disc_povs) {
+        push_hat(&mut events, pov, position.get_disc_pov(pov).into());
+    }
+
+    for pov in povs(config.cont_povs) {
+        push_hat(&mut events, pov, position.get_cont_pov(pov).into());
+    }
+
+    events
+}
+
+fn push_hat(events: &mut Vec<UinputEvent>, pov: VJDPovNumber, direction: VJDPovDirection) {
+    let (x_code, y_code) = hat_codes(pov);
+    let (x, y) = hat_values(direction);
+
+    events.push(UinputEvent::new(EV_ABS, x_code, x));
+    events.push(UinputEvent::new(EV_ABS, y_code, y));
+}
+
+/**
+    Returns the events of `current` which differ from `previous`, followed by a synchronization
+    event, or nothing if no control changed. Both states must come from the same configuration.
+*/
+fn diff_events(previous: &[UinputEvent], current: &[UinputEvent]) -> Vec<UinputEvent> {
+    let mut events: Vec<UinputEvent> = current
+        .iter()
+        .zip(previous)
+        .filter(|(current, previous)| current != previous)
+        .map(|(current, _)| *current)
+        .collect();
+
+    if !events.is_empty() {
+        events.push(UinputEvent::new(EV_SYN, SYN_REPORT, 0));
+    }
+
+    events
+}
+
+/**
+    Describes a destination of uinput devices and events.
+
+    The device is destroyed when the writer is dropped.
+*/
+pub trait UinputWriter: Send {
+    /// Creates the device. Called once, before any event is written.
+    fn create(&mut self, setup: &UinputSetup) -> std::io::Result<()>;
+
+    /// Writes events to the device.
+    fn write(&mut self, events: &[UinputEvent]) -> std::io::Result<()>;
+}
+
+/**
+    Writes uinput devices and events to `/dev/uinput`.
+*/
+#[derive(Default)]
+pub struct DevUinput {
+    file: Option<std::fs::File>,
+}
+
+impl DevUinput {
+    pub const PATH: &'static str = "/dev/uinput";
+
+    pub fn new() -> DevUinput {
+        DevUinput::default()
+    }
+
+    fn ioctl(file: &std::fs::File, request: u64, value: libc::c_int) -> std::io::Result<()> {
+        // Safe: the requests used only read an integer argument passed by value.
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), request as _, value) };
+
+        if result < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the legacy `uinput_user_dev` structure describing the device.
+    fn user_dev(setup: &UinputSetup) -> Vec<u8> {
+        let mut absmax = [0i32; ABS_CNT];
+        let mut absmin = [0i32; ABS_CNT];
+
+        for abs in &setup.abs {
+            absmax[abs.code as usize] = abs.max;
+            absmin[abs.code as usize] = abs.min;
+        }
+
+        let mut name = [0u8; 80];
+        for (dst, src) in name.iter_mut().zip(setup.name.bytes().take(79)) {
+            *dst = src;
+        }
+
+        let mut bytes = name.to_vec();
+        for id in &[BUS_VIRTUAL, setup.vendor, setup.product, setup.version] {
+            bytes.extend_from_slice(&id.to_ne_bytes());
+        }
+
+        // No force feedback effects.
+        bytes.extend_from_slice(&0u32.to_ne_bytes());
+
+        // absmax, absmin, absfuzz, absflat
+        for values in &[absmax, absmin, [0; ABS_CNT], [0; ABS_CNT]] {
+            for value in values.iter() {
+                bytes.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+
+        bytes
+    }
+}
+
+impl UinputWriter for DevUinput {
+    fn create(&mut self, setup: &UinputSetup) -> std::io::Result<()> {
+        let mut file = std::fs::OpenOptions::new().write(true).open(Self::PATH)?;
+
+        Self::ioctl(&file, UI_SET_EVBIT, EV_SYN as libc::c_int)?;
+        Self::ioctl(&file, UI_SET_EVBIT, EV_KEY as libc::c_int)?;
+        Self::ioctl(&file, UI_SET_EVBIT, EV_ABS as libc::c_int)?;
+
+        for &key in &setup.keys {
+            Self::ioctl(&file, UI_SET_KEYBIT, key as libc::c_int)?;
+        }
+
+        for abs in &setup.abs {
+            Self::ioctl(&file, UI_SET_ABSBIT, abs.code as libc::c_int)?;
+        }
+
+        file.write_all(&Self::user_dev(setup))?;
+        Self::ioctl(&file, UI_DEV_CREATE, 0)?;
+
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn write(&mut self, events: &[UinputEvent]) -> std::io::Result<()> {
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return Err(std::io::ErrorKind::NotConnected.into()),
+        };
+
+        let size = std::mem::size_of::<libc::input_event>();
+        let mut bytes = Vec::with_capacity(events.len() * size);
+
+        for event in events {
+            // The kernel sets the time of the event.
+            let raw = libc::input_event {
+                time: libc::timeval {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                },
+                type_: event.kind,
+                code: event.code,
+                value: event.value,
+            };
+
+            // Safe: input_event is a plain C structure, read for its size only.
+            bytes.extend_from_slice(unsafe {
+                std::slice::from_raw_parts(&raw as *const libc::input_event as *const u8, size)
+            });
+        }
+
+        file.write_all(&bytes)
+    }
+}
+
+impl Drop for DevUinput {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.as_ref() {
+            // Closing the file destroys the device anyway.
+            let _ = Self::ioctl(file, UI_DEV_DESTROY, 0);
+        }
+    }
+}
+
+/**
+    Holds what a [`FakeUinputWriter`] received. Clones share the same data.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct FakeUinputLog(Arc<Mutex<FakeUinputData>>);
+
+#[derive(Debug, Default)]
+struct FakeUinputData {
+    setup: Option<UinputSetup>,
+    events: Vec<UinputEvent>,
+    destroyed: bool,
+}
+
+impl FakeUinputLog {
+    pub fn new() -> FakeUinputLog {
+        FakeUinputLog::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeUinputData> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the description of the created device, or [`None`] if not created yet.
+    pub fn setup(&self) -> Option<UinputSetup> {
+        self.lock().setup.clone()
+    }
+
+    /// Returns every event written so far.
+    pub fn events(&self) -> Vec<UinputEvent> {
+        self.lock().events.clone()
+    }
+
+    /// Returns every event written so far and forgets them.
+    pub fn take_events(&self) -> Vec<UinputEvent> {
+        std::mem::take(&mut self.lock().events)
+    }
+
+    /// Returns `true` if the writer has been dropped, destroying the device.
+    pub fn is_destroyed(&self) -> bool {
+        self.lock().destroyed
+    }
+}
+
+/**
+    Captures uinput devices and events into a [`FakeUinputLog`] instead of creating a device.
+*/
+pub struct FakeUinputWriter {
+    log: FakeUinputLog,
+}
+
+impl FakeUinputWriter {
+    pub fn new(log: &FakeUinputLog) -> FakeUinputWriter {
+        FakeUinputWriter { log: log.clone() }
+    }
+}
+
+impl UinputWriter for FakeUinputWriter {
+    fn create(&mut self, setup: &UinputSetup) -> std::io::Result<()> {
+        let mut data = self.log.lock();
+        data.setup = Some(setup.clone());
+        data.destroyed = false;
+        Ok(())
+    }
+
+    fn write(&mut self, events: &[UinputEvent]) -> std::io::Result<()> {
+        self.log.lock().events.extend_from_slice(events);
+        Ok(())
+    }
+}
+
+impl Drop for FakeUinputWriter {
+    fn drop(&mut self) {
+        self.log.lock().destroyed = true;
+    }
+}
+
+/**
+    Describes an error state of [`VJDUinput`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UinputError {
+    /// The configuration is invalid.
+    Config(VJDConfigError),
+
+    /// The device is acquired: its configuration cannot change until it is relinquished.
+    Acquired(VJDevice),
+}
+
+/// Version of the backend, in vJoy hexadecimal form: 0x0100 is version 1.0.0.
+pub(crate) const VERSION: i16 = 0x0100;
+
+/// Product string reported by the backend in place of the vJoy driver one.
+pub(crate) const PRODUCT: &str = "vjoy-wrapper uinput backend";
+
+/// Manufacturer string reported by the backend.
+pub(crate) const MANUFACTURER: &str = "vjoy-wrapper";
+
+/// Serial number string reported by the backend, its version.
+pub(crate) const SERIAL_NUMBER: &str = "1.0.0";
+
+type WriterFactory = Box<dyn Fn(VJDevice) -> Box<dyn UinputWriter> + Send>;
+
+struct Slot {
+    device: VJDevice,
+    config: VJDConfig,
+    writer: Option<Box<dyn UinputWriter>>,
+    position: VJDPosition,
+    state: Vec<UinputEvent>,
+}
+
+struct Backend {
+    slots: Vec<Slot>,
+    factory: Option<WriterFactory>,
+    last_error: Option<std::io::Error>,
+}
+
+static BACKEND: Mutex<Backend> = Mutex::new(Backend {
+    slots: Vec::new(),
+    factory: None,
+    last_error: None,
+});
+
+fn backend() -> MutexGuard<'static, Backend> {
+    BACKEND
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Backend {
+    fn slot(&mut self, device: VJDevice) -> Option<&mut Slot> {
+        self.slots.iter_mut().find(|slot| slot.device == device)
+    }
+}
+
+/// Returns the configuration of a configured device.
+pub(crate) fn config(device: VJDevice) -> Option<VJDConfig> {
+    backend().slot(device).map(|slot| slot.config.clone())
+}
+
+pub(crate) fn status(device: VJDevice) -> VJDStatus {
+    match backend().slot(device) {
+        Some(slot) if slot.writer.is_some() => VJDStatus::Own,
+        Some(_) => VJDStatus::Free,
+        None => VJDStatus::Miss,
+    }
+}
+
+/// Creates the uinput device of a free configured device and writes its initial state.
+pub(crate) fn acquire(device: VJDevice) -> bool {
+    let mut backend = backend();
+    let mut writer = match backend.factory.as_ref() {
+        Some(factory) => factory(device),
+        None => Box::new(DevUinput::new()),
+    };
+
+    let slot = match backend.slot(device) {
+        Some(slot) if slot.writer.is_none() => slot,
+        _ => return false,
+    };
+
+    let position = VJDPosition::new(device);
+    let mut state = state_events(&slot.config, &position);
+
+    let mut created = writer.create(&UinputSetup::from_config(&slot.config));
+    if created.is_ok() {
+        state.push(UinputEvent::new(EV_SYN, SYN_REPORT, 0));
+        created = writer.write(&state);
+        state.pop();
+    }
+
+    match created {
+        Ok(()) => {
+            slot.writer = Some(writer);
+            slot.position = position;
+            slot.state = state;
+            true
+        }
+        Err(error) => {
+            backend.last_error = Some(error);
+            false
+        }
+    }
+}
+
+/// Destroys the uinput device of an acquired device.
+pub(crate) fn relinquish(device: VJDevice) {
+    if let Some(slot) = backend().slot(device) {
+        slot.writer = None;
+    }
+}
+
+/**
+    Updates the position of an acquired device and writes the controls which changed.
+    Returns `false` if the device is not acquired or if writing fails.
+*/
+pub(crate) fn update<F: FnOnce(&mut VJDPosition)>(device: VJDevice, f: F) -> bool {
+    let mut backend = backend();
+
+    let slot = match backend.slot(device) {
+        Some(slot) if slot.writer.is_some() => slot,
+        _ => return false,
+    };
+
+    f(&mut slot.position);
+
+    let state = state_events(&slot.config, &slot.position);
+    let events = diff_events(&slot.state, &state);
+
+    if events.is_empty() {
+        return true;
+    }
+
+    // The writer has just been checked.
+    match slot.writer.as_mut().unwrap().write(&events) {
+        Ok(()) => {
+            slot.state = state;
+            true
+        }
+        Err(error) => {
+            backend.last_error = Some(error);
+            false
+        }
+    }
+}
+
+/**
+    Holder of utility methods to configure the devices of the uinput backend.
+*/
+pub struct VJDUinput(());
+
+impl VJDUinput {
+    /**
+        Declares a device with the controls of `config`. The device then exists and is
+        [`VJDStatus::Free`]. A device which is not acquired can be configured again.
+    */
+    pub fn configure(device: VJDevice, config: VJDConfig) -> Result<(), UinputError> {
+        config.validate().map_err(UinputError::Config)?;
+
+        let mut backend = backend();
+
+        match backend.slot(device) {
+            Some(slot) if slot.writer.is_some() => Err(UinputError::Acquired(device)),
+            Some(slot) => {
+                slot.config = config;
+                Ok(())
+            }
+            None => {
+                backend.slots.push(Slot {
+                    device,
+                    config,
+                    writer: None,
+                    position: VJDPosition::new(device),
+                    state: Vec::new(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    /**
+        Removes a device which is not acquired. The device is then [`VJDStatus::Miss`].
+    */
+    pub fn remove(device: VJDevice) -> Result<(), UinputError> {
+        let mut backend = backend();
+
+        if let Some(slot) = backend.slot(device) {
+            if slot.writer.is_some() {
+                return Err(UinputError::Acquired(device));
+            }
+        }
+
+        backend.slots.retain(|slot| slot.device != device);
+        Ok(())
+    }
+
+    /**
+        Replaces the writer created when a device is acquired, [`DevUinput`] by default. Tests
+        typically provide a [`FakeUinputWriter`].
+
+        The previous factory is restored when the returned guard is dropped. Guards must be
+        dropped in the reverse order of their creation.
+    */
+    pub fn set_writer_factory<F>(factory: F) -> WriterFactoryGuard
+    where
+        F: Fn(VJDevice) -> Box<dyn UinputWriter> + Send + 'static,
+    {
+        let previous = backend().factory.replace(Box::new(factory));
+        WriterFactoryGuard { previous }
+    }
+
+    /// Goes back to creating devices with [`DevUinput`].
+    pub fn reset_writer_factory() {
+        backend().factory = None;
+    }
+
+    /**
+        Returns the last I/O error met when creating a device or writing events, and forgets
+        it. Feeding methods only report failures with `false`.
+    */
+    pub fn take_last_error() -> Option<std::io::Error> {
+        backend().last_error.take()
+    }
+}
+
+/**
+    Restores the writer factory replaced by [`VJDUinput::set_writer_factory`] when dropped.
+*/
+#[must_use = "the previous writer factory is restored as soon as the guard is dropped"]
+pub struct WriterFactoryGuard {
+    previous: Option<WriterFactory>,
+}
+
+impl Drop for WriterFactoryGuard {
+    fn drop(&mut self) {
+        backend().factory = self.previous.take();
+    }
+}
+
+impl From<VJDConfigError> for UinputError {
+    fn from(error: VJDConfigError) -> Self {
+        UinputError::Config(error)
+    }
+}
@@ -1,6 +1,32 @@
 //! Provides additional functionalities.
 
-#[cfg(test)]
+pub mod behaviour;
+pub mod combine;
+#[cfg(feature = "serde")]
+pub mod config_file;
+pub mod descriptor;
+pub mod filter;
+pub mod layer;
+#[cfg(feature = "tui")]
+pub mod monitor;
+pub mod osc;
+#[cfg(feature = "sdl2")]
+pub mod probe;
+#[cfg(feature = "serde")]
+pub mod profile;
+pub mod record;
+#[cfg(feature = "sdl2")]
+pub mod remap;
+pub mod registry;
+pub mod replay;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod sequencer;
+pub mod udp;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+#[cfg(all(test, feature = "sdl2"))]
 mod tests {
     use super::*;
     use crate::vjoy_base::driver::VJGeneral;
@@ -69,16 +95,22 @@ mod tests {
 }
 
 use crate::vjoy_base::device::VJDevice;
+#[cfg(feature = "sdl2")]
 use crate::vjoy_base::driver::VJGeneral;
-use std::cmp::Ordering;
-use winreg::enums::*;
-use winreg::RegKey;
-
-/// Describes an error state of [`get_vjoy_devices_reg`].
+#[cfg(feature = "sdl2")]
+use crate::vjoy_base::device::axis::{SDL2AxisValue, VJDAxisRaw};
+use crate::vjoy_base::device::config::VJDConfigError;
+use descriptor::VJDDescriptorError;
+#[cfg(feature = "registry")]
+use registry::WindowsRegistry;
+
+/// Describes an error state of the [`registry`] functions and of `reg_vjoy_devices`.
 #[derive(Debug)]
 pub enum VJDRegistryError {
     /// A device number was not in the range [1, [`VJGeneral::MAX_DEVICES`]]. This
     /// is a sign of malformed entries.
+    ///
+    /// [`VJGeneral::MAX_DEVICES`]: crate::vjoy_base::driver::VJGeneral::MAX_DEVICES
     InvalidDevice,
 
     /// A device number found in registry appeared in an unordered fashion. This is
@@ -92,65 +124,40 @@ pub enum VJDRegistryError {
     /// The access to the registry path where devices are registered has failed.
     /// [`std::io::Error`] is provided for further investigation.
     PathError(std::io::Error),
+
+    /// The key of the provided device has no report descriptor.
+    MissingDescriptor(VJDevice),
+
+    /// The report descriptor of the provided device cannot be decoded.
+    InvalidDescriptor(VJDevice, VJDDescriptorError),
+
+    /// The configuration to write for the provided device cannot be created by vJoy.
+    InvalidConfig(VJDevice, VJDConfigError),
 }
 
 /**
     Returns a list of device numbers registered in the windows registry, or [`VJDRegistryError`] if it fails. The list is ordered.
 */
+#[cfg(feature = "registry")]
 pub fn reg_vjoy_devices() -> Result<Vec<VJDevice>, VJDRegistryError> {
-    let mut captured_device_numbers: Vec<VJDevice> = Vec::new();
-
-    let devices_path =
-        match RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(VJGeneral::REG_DEVICES_PATH) {
-            Ok(reg_key) => reg_key,
-            Err(error) => return Err(VJDRegistryError::PathError(error)),
-        };
-
-    let reg_device_prefix = VJGeneral::REG_DEVICE_PREFIX;
-
-    for i in devices_path.enum_keys().map(|x| x.unwrap()) {
-        // First check reg key starts with 'Device' with a potential following number.
-        if i.starts_with(reg_device_prefix) && i.len() > reg_device_prefix.len() {
-            // Then check the potential number is really a valid number.
-            if let Ok(val) = i[reg_device_prefix.len()..].parse::<u8>() {
-                // A device number should be in the correct range in registry
-                if !(1..=VJGeneral::MAX_DEVICES).contains(&val) {
-                    return Err(VJDRegistryError::InvalidDevice);
-                } else {
-                    for i in &captured_device_numbers {
-                        match (*i as u8).cmp(&val) {
-                            // A device number should not appear twice in registry
-                            Ordering::Equal => return Err(VJDRegistryError::DuplicateEntry),
-
-                            // A device number should not appear in unordered fashion in registry
-                            Ordering::Greater => return Err(VJDRegistryError::InvalidOrder),
-                            _ => {}
-                        }
-                    }
-                }
+    let registry = WindowsRegistry::open().map_err(VJDRegistryError::PathError)?;
 
-                let device = match VJDevice::get_from(val) {
-                    Some(vjd) => vjd,
-                    None => return Err(VJDRegistryError::InvalidDevice),
-                };
-
-                captured_device_numbers.push(device);
-            }
-        };
-    }
-
-    Ok(captured_device_numbers)
+    registry::registered_devices(&registry)
 }
 
+#[cfg(feature = "sdl2")]
 use sdl2::{joystick::Joystick, IntegerOrSdlError, JoystickSubsystem};
+#[cfg(feature = "sdl2")]
 use std::collections::HashMap;
 
 /// Maps a vJoy device ID to a SDL2 joystick.
+#[cfg(feature = "sdl2")]
 pub type SDL2Vjoys = HashMap<VJDevice, Joystick>;
 
 /**
     Describes error from [`sdl2_get_vjoys`].
 */
+#[cfg(feature = "sdl2")]
 #[derive(Debug)]
 pub enum SDL2VjoyError {
     /// The number of vjoy joysticks found by SDL2 doesn't match the number of vjoy devices found in
@@ -167,11 +174,25 @@ pub enum SDL2VjoyError {
     SdlError(String),
 }
 
+#[cfg(feature = "sdl2")]
+impl From<IntegerOrSdlError> for SDL2VjoyError {
+    fn from(error: IntegerOrSdlError) -> Self {
+        match error {
+            IntegerOrSdlError::IntegerOverflows(msg, int) => {
+                SDL2VjoyError::IntegerOverflows(msg, int)
+            }
+            IntegerOrSdlError::SdlError(msg) => SDL2VjoyError::SdlError(msg),
+        }
+    }
+}
+
 /**
     Provides utilities to handle and recognize vJoy devices inside SDL2.
 */
+#[cfg(feature = "sdl2")]
 pub struct SDL2Helper(());
 
+#[cfg(feature = "sdl2")]
 impl SDL2Helper {
     #[allow(dead_code)]
     /// Describes the maximum value of a SDL2 axis.
@@ -187,6 +208,9 @@ impl SDL2Helper {
 
     /**
         Returns a list of SDL2 devices which are recognized as vJoy devices, or [`SDL2VjoyError`] if it fails. The list is mapped as [`SDL2Vjoys`].
+
+        Devices are matched by order only, which fails as soon as a device is disabled or SDL2
+        enumerates them differently. [`SDL2Probe`](probe::SDL2Probe) verifies the mapping instead.
     */
     pub fn get_vjoys(joy_subsystem: &JoystickSubsystem) -> Result<SDL2Vjoys, SDL2VjoyError> {
         // Total number of joysticks found by SDL2
@@ -259,19 +283,6 @@ impl SDL2Helper {
         Ok(vjoys)
     }
 
-    fn general_scaling(
-        value: i16,
-        source_rmin: i32,
-        source_rmax: i32,
-        target_rmin: i32,
-        target_rmax: i32,
-    ) -> i32 {
-        (((value as i32 - source_rmin) * (target_rmax - target_rmin)) as f32
-            / (source_rmax - source_rmin) as f32)
-            .round() as i32
-            + target_rmin
-    }
-
     #[allow(dead_code)]
     // debug purpose atm; f32 precise enough
     fn precise_scaling(
@@ -290,13 +301,7 @@ impl SDL2Helper {
         Scales a SDL2 axis value to fit into a vJoy axis. The scaled value is rounded.
     */
     pub fn axis_scale_sdl2_to_vjoy_unchecked(value: i16) -> i32 {
-        Self::general_scaling(
-            value,
-            Self::MIN_AXIS_VALUE,
-            Self::MAX_AXIS_VALUE,
-            VJGeneral::MIN_AXIS_VALUE,
-            VJGeneral::MAX_AXIS_VALUE,
-        )
+        VJDAxisRaw::from(SDL2AxisValue::new(value)).get()
     }
 
     /**
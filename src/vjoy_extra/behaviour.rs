@@ -0,0 +1,530 @@
+//! Contains stateful behaviours turning the state of a physical button into the states of vJoy
+//! buttons a game expects: toggle, pulse, long press, double tap and turbo.
+//!
+//! Like [`filter`](crate::vjoy_extra::filter), the timestamp of each update is given by the
+//! caller instead of being read from a clock, so a behaviour can be driven by a virtual clock.
+//! Timed behaviours change their output while the input is held or after it is released: they
+//! must be updated on every tick, not only when the input changes.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock advanced by the tests, feeding a behaviour at each step.
+    struct VirtualClock(Duration);
+
+    impl VirtualClock {
+        fn new() -> VirtualClock {
+            VirtualClock(Duration::ZERO)
+        }
+
+        fn advance(&mut self, millis: u64) -> Duration {
+            self.0 += Duration::from_millis(millis);
+            self.0
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Feeds `(pressed, elapsed milliseconds)` steps and returns whether the output was pressed.
+    fn run(behaviour: &mut dyn ButtonBehaviour, steps: &[(bool, u64)]) -> Vec<bool> {
+        let mut clock = VirtualClock::new();
+
+        steps
+            .iter()
+            .map(|&(pressed, millis)| {
+                behaviour.update(pressed, clock.advance(millis)) == VJDButtonState::Pressed
+            })
+            .collect()
+    }
+
+    #[test]
+    fn toggle_flips_on_each_press() {
+        let mut toggle = Toggle::new();
+
+        assert_eq!(
+            vec![true, true, true, false, false, true],
+            run(
+                &mut toggle,
+                &[
+                    (true, 0),
+                    (true, 10),
+                    (false, 10),
+                    (true, 10),
+                    (false, 10),
+                    (true, 10)
+                ]
+            )
+        );
+
+        toggle.reset();
+        assert_eq!(VJDButtonState::Released, toggle.update(false, ms(100)));
+    }
+
+    #[test]
+    fn pulse_has_a_fixed_length() {
+        let mut on_press = Pulse::new(ms(50), PulseEdge::Press).unwrap();
+        assert_eq!(
+            vec![true, true, false, false, true],
+            run(
+                &mut on_press,
+                &[(true, 0), (true, 40), (true, 10), (false, 10), (true, 10)]
+            )
+        );
+
+        let mut on_release = Pulse::new(ms(50), PulseEdge::Release).unwrap();
+        assert_eq!(
+            vec![false, true, true, false],
+            run(
+                &mut on_release,
+                &[(true, 0), (false, 100), (false, 20), (false, 30)]
+            )
+        );
+    }
+
+    #[test]
+    fn long_press_splits_targets() {
+        let mut long_press = LongPress::new(VJDButton::B1, VJDButton::B2, ms(300), ms(50)).unwrap();
+        let mut clock = VirtualClock::new();
+        let mut step = |pressed, millis| {
+            let states = long_press.update(pressed, clock.advance(millis));
+            assert_eq!(VJDButton::B1, states[0].0);
+            assert_eq!(VJDButton::B2, states[1].0);
+
+            (
+                states[0].1 == VJDButtonState::Pressed,
+                states[1].1 == VJDButtonState::Pressed,
+            )
+        };
+
+        // Short press: the short target pulses once released.
+        assert_eq!((false, false), step(true, 0));
+        assert_eq!((false, false), step(true, 200));
+        assert_eq!((true, false), step(false, 50));
+        assert_eq!((true, false), step(false, 40));
+        assert_eq!((false, false), step(false, 10));
+
+        // Long press: the long target is held from the threshold until the release.
+        assert_eq!((false, false), step(true, 100));
+        assert_eq!((false, false), step(true, 299));
+        assert_eq!((false, true), step(true, 1));
+        assert_eq!((false, true), step(true, 500));
+        assert_eq!((false, false), step(false, 10));
+    }
+
+    #[test]
+    fn double_tap_within_window() {
+        let mut double_tap = DoubleTap::new(ms(250)).unwrap();
+
+        // The second press follows the first by 200ms: held until released.
+        assert_eq!(
+            vec![false, false, true, true, false],
+            run(
+                &mut double_tap,
+                &[
+                    (true, 0),
+                    (false, 100),
+                    (true, 100),
+                    (true, 500),
+                    (false, 10)
+                ]
+            )
+        );
+
+        // Presses 300ms apart are two single taps, and a third press does not chain.
+        let mut double_tap = DoubleTap::new(ms(250)).unwrap();
+        assert_eq!(
+            vec![false, false, false, false, true, false, false],
+            run(
+                &mut double_tap,
+                &[
+                    (true, 1000),
+                    (false, 100),
+                    (true, 200),
+                    (false, 100),
+                    (true, 100),
+                    (false, 50),
+                    (true, 50)
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn turbo_repeats_while_held() {
+        // 10 presses per second: pressed for 50ms, released for 50ms.
+        let mut turbo = Turbo::new(10.0).unwrap();
+
+        assert_eq!(
+            vec![true, true, false, false, true, false, true],
+            run(
+                &mut turbo,
+                &[
+                    (true, 0),
+                    (true, 49),
+                    (true, 1),
+                    (true, 49),
+                    (true, 1),
+                    (false, 10),
+                    (true, 10)
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(
+            Err(BehaviourError::InvalidParameter("length")),
+            Pulse::new(ms(0), PulseEdge::Press).map(|_| ())
+        );
+        assert_eq!(
+            Err(BehaviourError::InvalidParameter("threshold")),
+            LongPress::new(VJDButton::B1, VJDButton::B2, ms(0), ms(50)).map(|_| ())
+        );
+        assert_eq!(
+            Err(BehaviourError::SameTargets),
+            LongPress::new(VJDButton::B1, VJDButton::B1, ms(300), ms(50)).map(|_| ())
+        );
+        assert_eq!(
+            Err(BehaviourError::InvalidParameter("window")),
+            DoubleTap::new(ms(0)).map(|_| ())
+        );
+        for rate in &[
+            0.0,
+            -1.0,
+            f64::NAN,
+            f64::INFINITY,
+            1e12,
+            1e-300,
+            f64::MIN_POSITIVE,
+        ] {
+            assert_eq!(
+                Err(BehaviourError::InvalidParameter("rate")),
+                Turbo::new(*rate).map(|_| ())
+            );
+        }
+    }
+}
+
+use crate::vjoy_base::device::{VJDButton, VJDButtonState};
+use std::time::Duration;
+
+/**
+    Describes an error state when creating a button behaviour.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BehaviourError {
+    /// A parameter was out of its valid range or not a finite number. The name of the
+    /// parameter is provided.
+    InvalidParameter(&'static str),
+
+    /// A [`LongPress`] must split into two different buttons.
+    SameTargets,
+}
+
+/**
+    Describes a stateful behaviour of a button.
+
+    The `timestamp` of an update is the time elapsed since an arbitrary origin chosen by the
+    caller, which must stay the same for the whole life of the behaviour. A timestamp older than
+    the previous one is considered equal to it.
+*/
+pub trait ButtonBehaviour: Send {
+    /// Feeds the state of the input at `timestamp` and returns the state of the target button.
+    fn update(&mut self, pressed: bool, timestamp: Duration) -> VJDButtonState;
+
+    /// Forgets every previous update, as if the input had always been released.
+    fn reset(&mut self);
+}
+
+/// Returns the elapsed time between two timestamps, never negative.
+fn elapsed(from: Duration, to: Duration) -> Duration {
+    to.checked_sub(from).unwrap_or_default()
+}
+
+/// Checks a duration is not zero.
+fn check_duration(value: Duration, name: &'static str) -> Result<Duration, BehaviourError> {
+    if value > Duration::ZERO {
+        Ok(value)
+    } else {
+        Err(BehaviourError::InvalidParameter(name))
+    }
+}
+
+fn state_of(pressed: bool) -> VJDButtonState {
+    if pressed {
+        VJDButtonState::Pressed
+    } else {
+        VJDButtonState::Released
+    }
+}
+
+/**
+    Latches the target: each press of the input flips it between pressed and released.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Toggle {
+    pressed: bool,
+    latched: bool,
+}
+
+impl Toggle {
+    pub fn new() -> Toggle {
+        Toggle::default()
+    }
+}
+
+impl ButtonBehaviour for Toggle {
+    fn update(&mut self, pressed: bool, _timestamp: Duration) -> VJDButtonState {
+        if pressed && !self.pressed {
+            self.latched = !self.latched;
+        }
+        self.pressed = pressed;
+
+        state_of(self.latched)
+    }
+
+    fn reset(&mut self) {
+        *self = Toggle::default();
+    }
+}
+
+/**
+    Describes the change of the input starting a [`Pulse`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PulseEdge {
+    Press,
+    Release,
+}
+
+/**
+    Presses the target for a fixed length when the input is pressed or released, however long
+    the input is held. A new edge during a pulse starts it again.
+*/
+#[derive(Debug, Clone)]
+pub struct Pulse {
+    length: Duration,
+    edge: PulseEdge,
+    pressed: bool,
+    start: Option<Duration>,
+}
+
+impl Pulse {
+    /**
+        Returns [`BehaviourError::InvalidParameter`] if `length` is zero.
+    */
+    pub fn new(length: Duration, edge: PulseEdge) -> Result<Pulse, BehaviourError> {
+        Ok(Pulse {
+            length: check_duration(length, "length")?,
+            edge,
+            pressed: false,
+            start: None,
+        })
+    }
+}
+
+impl ButtonBehaviour for Pulse {
+    fn update(&mut self, pressed: bool, timestamp: Duration) -> VJDButtonState {
+        let edge = match self.edge {
+            PulseEdge::Press => pressed && !self.pressed,
+            PulseEdge::Release => !pressed && self.pressed,
+        };
+        self.pressed = pressed;
+
+        if edge {
+            self.start = Some(timestamp);
+        }
+
+        let length = self.length;
+        self.start = self
+            .start
+            .filter(|&start| elapsed(start, timestamp) < length);
+
+        state_of(self.start.is_some())
+    }
+
+    fn reset(&mut self) {
+        self.pressed = false;
+        self.start = None;
+    }
+}
+
+/**
+    Splits the input into two buttons depending on how long it is held.
+
+    Once the input is held for the threshold, the long button is pressed until the input is
+    released. When the input is released before the threshold, the short button is pressed for
+    a fixed length: a short press is only known when it ends.
+*/
+#[derive(Debug, Clone)]
+pub struct LongPress {
+    short: VJDButton,
+    long: VJDButton,
+    threshold: Duration,
+    pulse: Duration,
+    held_since: Option<Duration>,
+    short_start: Option<Duration>,
+}
+
+impl LongPress {
+    /**
+        Returns [`BehaviourError::InvalidParameter`] if `threshold` or `pulse` is zero, or
+        [`BehaviourError::SameTargets`] if both buttons are the same.
+    */
+    pub fn new(
+        short: VJDButton,
+        long: VJDButton,
+        threshold: Duration,
+        pulse: Duration,
+    ) -> Result<LongPress, BehaviourError> {
+        if short == long {
+            return Err(BehaviourError::SameTargets);
+        }
+
+        Ok(LongPress {
+            short,
+            long,
+            threshold: check_duration(threshold, "threshold")?,
+            pulse: check_duration(pulse, "pulse")?,
+            held_since: None,
+            short_start: None,
+        })
+    }
+
+    /**
+        Feeds the state of the input at `timestamp` and returns the state of the short button,
+        then the state of the long button.
+    */
+    pub fn update(
+        &mut self,
+        pressed: bool,
+        timestamp: Duration,
+    ) -> [(VJDButton, VJDButtonState); 2] {
+        let mut long = false;
+
+        if pressed {
+            let since = *self.held_since.get_or_insert(timestamp);
+            long = elapsed(since, timestamp) >= self.threshold;
+        } else if let Some(since) = self.held_since.take() {
+            if elapsed(since, timestamp) < self.threshold {
+                self.short_start = Some(timestamp);
+            }
+        }
+
+        let pulse = self.pulse;
+        self.short_start = self
+            .short_start
+            .filter(|&start| elapsed(start, timestamp) < pulse);
+
+        [
+            (self.short, state_of(self.short_start.is_some())),
+            (self.long, state_of(long)),
+        ]
+    }
+
+    /// Forgets every previous update, as if the input had always been released.
+    pub fn reset(&mut self) {
+        self.held_since = None;
+        self.short_start = None;
+    }
+}
+
+/**
+    Presses the target while the second of two quick presses is held. The second press must
+    start within the window after the start of the first one. Single presses are ignored.
+*/
+#[derive(Debug, Clone)]
+pub struct DoubleTap {
+    window: Duration,
+    pressed: bool,
+    first_press: Option<Duration>,
+    active: bool,
+}
+
+impl DoubleTap {
+    /**
+        Returns [`BehaviourError::InvalidParameter`] if `window` is zero.
+    */
+    pub fn new(window: Duration) -> Result<DoubleTap, BehaviourError> {
+        Ok(DoubleTap {
+            window: check_duration(window, "window")?,
+            pressed: false,
+            first_press: None,
+            active: false,
+        })
+    }
+}
+
+impl ButtonBehaviour for DoubleTap {
+    fn update(&mut self, pressed: bool, timestamp: Duration) -> VJDButtonState {
+        if pressed && !self.pressed {
+            match self.first_press.take() {
+                Some(first) if elapsed(first, timestamp) <= self.window => self.active = true,
+                _ => self.first_press = Some(timestamp),
+            }
+        } else if !pressed {
+            self.active = false;
+        }
+        self.pressed = pressed;
+
+        state_of(self.active)
+    }
+
+    fn reset(&mut self) {
+        self.pressed = false;
+        self.first_press = None;
+        self.active = false;
+    }
+}
+
+/**
+    Repeats presses of the target while the input is held, starting with a press. Each period is
+    split evenly between pressed and released.
+*/
+#[derive(Debug, Clone)]
+pub struct Turbo {
+    period: Duration,
+    held_since: Option<Duration>,
+}
+
+impl Turbo {
+    /**
+        Creates a turbo repeating `rate` presses per second, or returns
+        [`BehaviourError::InvalidParameter`] if the rate is not a positive number or its period
+        cannot be represented (more than one press per microsecond, or too slow for a
+        [`Duration`]).
+    */
+    pub fn new(rate: f64) -> Result<Turbo, BehaviourError> {
+        if !(rate.is_finite() && rate > 0.0 && rate <= 1e6) {
+            return Err(BehaviourError::InvalidParameter("rate"));
+        }
+
+        Ok(Turbo {
+            period: Duration::try_from_secs_f64(1.0 / rate)
+                .map_err(|_| BehaviourError::InvalidParameter("rate"))?,
+            held_since: None,
+        })
+    }
+}
+
+impl ButtonBehaviour for Turbo {
+    fn update(&mut self, pressed: bool, timestamp: Duration) -> VJDButtonState {
+        if !pressed {
+            self.held_since = None;
+            return VJDButtonState::Released;
+        }
+
+        let since = *self.held_since.get_or_insert(timestamp);
+        let phase = elapsed(since, timestamp).as_nanos() % self.period.as_nanos();
+
+        state_of(phase < self.period.as_nanos() / 2)
+    }
+
+    fn reset(&mut self) {
+        self.held_since = None;
+    }
+}
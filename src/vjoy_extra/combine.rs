@@ -0,0 +1,499 @@
+//! Contains operators merging and splitting axes, and converting between axes and buttons.
+//!
+//! Like [`filter`](crate::vjoy_extra::filter), operators work in vJoy units and their outputs
+//! always lie in the vJoy axis range, so they compose with each other and with filters: inputs
+//! can be filtered before being combined, and the combined value filtered again.
+//!
+//! The operators also implement [`AxisFilter`] to be stages of a
+//! [`FilterChain`](crate::vjoy_extra::filter::FilterChain): [`CombineFilter`] combines the value
+//! with constant operands, [`AxisHalf`] keeps one half of [`split_axis`], [`AxisThreshold`]
+//! outputs the minimum or the maximum of the axis, and [`StepAxis`] is stepped by a centered
+//! axis used as a rocker.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_extra::filter::FilterChain;
+
+    const MAX: i32 = VJGeneral::MAX_AXIS_VALUE;
+    const NEUTRAL: i32 = VJGeneral::NEUTRAL_AXIS_VALUE;
+
+    #[test]
+    fn toe_brakes_make_a_rudder() {
+        let rudder = |left, right| AxisCombine::Difference.apply(&[right, left]).unwrap();
+
+        assert_eq!(NEUTRAL, rudder(0, 0));
+        assert_eq!(NEUTRAL, rudder(MAX, MAX));
+        assert_eq!(MAX, rudder(0, MAX));
+        assert_eq!(0, rudder(MAX, 0));
+        assert_eq!(NEUTRAL + 5000, rudder(0, 10000));
+    }
+
+    #[test]
+    fn combinations_stay_in_range() {
+        let values = [1000, 30000, 5000];
+
+        assert_eq!(Some(MAX), AxisCombine::Sum.apply(&values));
+        assert_eq!(Some(6000), AxisCombine::Sum.apply(&[1000, 5000]));
+        assert_eq!(Some(12000), AxisCombine::Average.apply(&values));
+        assert_eq!(Some(1000), AxisCombine::Min.apply(&values));
+        assert_eq!(Some(30000), AxisCombine::Max.apply(&values));
+        assert_eq!(Some(0), AxisCombine::Difference.apply(&values));
+        assert_eq!(Some(MAX), AxisCombine::Max.apply(&[-5, 40000]));
+        assert_eq!(None, AxisCombine::Sum.apply(&[]));
+    }
+
+    #[test]
+    fn split_into_half_ranges() {
+        assert_eq!((0, 0), split_axis(NEUTRAL));
+        assert_eq!((MAX, 0), split_axis(0));
+        assert_eq!((0, MAX), split_axis(MAX));
+        assert_eq!((16383, 0), split_axis(NEUTRAL / 2));
+        assert_eq!((0, 16384), split_axis(NEUTRAL + 8192));
+
+        // Merging the halves back gives the original value.
+        for &value in &[0, 100, NEUTRAL / 2, NEUTRAL, NEUTRAL + 1, 30000, MAX] {
+            let (lower, upper) = split_axis(value);
+            assert_eq!(value, merge_halves(lower, upper));
+        }
+    }
+
+    #[test]
+    fn threshold_has_hysteresis() {
+        let mut above = AxisThreshold::new(20000, 1000, ThresholdDirection::Above).unwrap();
+        let pressed: Vec<_> = [0, 19999, 20000, 19500, 18999, 19999, 32767]
+            .iter()
+            .map(|&value| above.update(value) == VJDButtonState::Pressed)
+            .collect();
+        assert_eq!(vec![false, false, true, true, false, false, true], pressed);
+
+        let mut below = AxisThreshold::new(1000, 0, ThresholdDirection::Below).unwrap();
+        assert_eq!(VJDButtonState::Pressed, below.update(1000));
+        assert_eq!(VJDButtonState::Released, below.update(1001));
+    }
+
+    #[test]
+    fn operators_are_filters() {
+        let ms = Duration::from_millis;
+        let mut chain = FilterChain::new()
+            .with(AxisHalf::Upper)
+            .with(AxisCombine::Min.with(vec![20000]));
+        assert_eq!(0, chain.filter(NEUTRAL, ms(0)));
+        assert_eq!(20000, chain.filter(MAX, ms(10)));
+        assert_eq!(0, AxisHalf::Lower.filter(MAX, ms(0)));
+
+        let mut threshold = AxisThreshold::new(20000, 1000, ThresholdDirection::Above).unwrap();
+        assert_eq!(0, threshold.filter(19999, ms(0)));
+        assert_eq!(MAX, threshold.filter(20000, ms(10)));
+        AxisFilter::reset(&mut threshold);
+        assert_eq!(0, threshold.filter(19500, ms(20)));
+
+        let mut trim = StepAxis::new(NEUTRAL, 1000).unwrap();
+        let values: Vec<_> = [NEUTRAL, MAX, MAX, NEUTRAL, 0, NEUTRAL + 1000]
+            .iter()
+            .map(|&value| trim.filter(value, ms(0)))
+            .collect();
+        assert_eq!(
+            vec![
+                NEUTRAL,
+                NEUTRAL + 1000,
+                NEUTRAL + 1000,
+                NEUTRAL + 1000,
+                NEUTRAL,
+                NEUTRAL
+            ],
+            values
+        );
+    }
+
+    #[test]
+    fn buttons_step_an_axis() {
+        let mut axis = StepAxis::new(NEUTRAL, 10000).unwrap();
+
+        assert_eq!(NEUTRAL + 10000, axis.update(true, false));
+        assert_eq!(NEUTRAL + 10000, axis.update(true, false));
+        assert_eq!(NEUTRAL + 10000, axis.update(false, false));
+        assert_eq!(MAX, axis.update(true, false));
+        assert_eq!(MAX - 10000, axis.update(false, true));
+        assert_eq!(MAX - 10000, axis.update(false, true));
+        assert_eq!(MAX, axis.update(true, true));
+
+        axis.reset();
+        assert_eq!(NEUTRAL, axis.value());
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(
+            Err(FilterError::InvalidParameter("threshold")),
+            AxisThreshold::new(-1, 0, ThresholdDirection::Above).map(|_| ())
+        );
+        assert_eq!(
+            Err(FilterError::InvalidParameter("hysteresis")),
+            AxisThreshold::new(100, -1, ThresholdDirection::Above).map(|_| ())
+        );
+        assert_eq!(
+            Err(FilterError::InvalidParameter("hysteresis")),
+            AxisThreshold::new(100, MAX + 1, ThresholdDirection::Below).map(|_| ())
+        );
+        assert!(AxisThreshold::new(MAX, MAX, ThresholdDirection::Below).is_ok());
+        assert_eq!(
+            Err(FilterError::InvalidParameter("step")),
+            StepAxis::new(0, 0).map(|_| ())
+        );
+        assert_eq!(
+            Err(FilterError::InvalidParameter("initial")),
+            StepAxis::new(MAX + 1, 10).map(|_| ())
+        );
+    }
+}
+
+use crate::vjoy_base::device::VJDButtonState;
+use crate::vjoy_base::driver::VJGeneral;
+use crate::vjoy_extra::filter::{AxisFilter, FilterError};
+use std::time::Duration;
+
+/// Clamps a value to the vJoy axis range.
+fn clamp_axis(value: i64) -> i32 {
+    value.clamp(
+        VJGeneral::MIN_AXIS_VALUE as i64,
+        VJGeneral::MAX_AXIS_VALUE as i64,
+    ) as i32
+}
+
+fn in_range(value: i32) -> bool {
+    (VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE).contains(&value)
+}
+
+/**
+    Describes how several axis values are combined into one.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum AxisCombine {
+    /// Sum of the values.
+    Sum,
+
+    /**
+        Half of the first value minus the others, centered on the neutral value. Two toe
+        brakes combined as `[right, left]` make a rudder axis.
+    */
+    Difference,
+
+    /// Average of the values, rounded to the nearest unit.
+    Average,
+
+    /// Lowest of the values.
+    Min,
+
+    /// Highest of the values.
+    Max,
+}
+
+impl AxisCombine {
+    /**
+        Combines the values, each clamped to the vJoy axis range first, and clamps the result.
+        Returns [`None`] if there is no value.
+    */
+    pub fn apply(self, values: &[i32]) -> Option<i32> {
+        let mut values = values.iter().map(|&value| clamp_axis(value as i64) as i64);
+        let first = values.next()?;
+
+        let combined = match self {
+            AxisCombine::Sum => values.fold(first, |sum, value| sum + value),
+            AxisCombine::Difference => {
+                let difference = values.fold(first, |difference, value| difference - value);
+                VJGeneral::NEUTRAL_AXIS_VALUE as i64 + difference.div_euclid(2)
+            }
+            AxisCombine::Average => {
+                let (sum, count) =
+                    values.fold((first, 1), |(sum, count), value| (sum + value, count + 1));
+                (sum + count / 2) / count
+            }
+            AxisCombine::Min => values.fold(first, i64::min),
+            AxisCombine::Max => values.fold(first, i64::max),
+        };
+
+        Some(clamp_axis(combined))
+    }
+
+    /// Returns a filter combining its value, first, with `operands`.
+    pub fn with(self, operands: Vec<i32>) -> CombineFilter {
+        let mut values = operands;
+        values.insert(0, VJGeneral::NEUTRAL_AXIS_VALUE);
+
+        CombineFilter {
+            combine: self,
+            values,
+        }
+    }
+}
+
+/**
+    Filter combining its value with constant operands, such as a floor with [`AxisCombine::Max`]
+    or an offset with [`AxisCombine::Sum`]. Made by [`AxisCombine::with`].
+*/
+#[derive(Debug, Clone)]
+pub struct CombineFilter {
+    combine: AxisCombine,
+
+    /// The filtered value followed by the operands.
+    values: Vec<i32>,
+}
+
+impl AxisFilter for CombineFilter {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        self.values[0] = value;
+        self.combine.apply(&self.values).unwrap_or(value)
+    }
+
+    fn reset(&mut self) {}
+}
+
+/**
+    Splits an axis at its neutral value into two axes covering the whole vJoy range: the first
+    grows as the value goes below neutral, the second as it goes above. The axis not moved stays
+    at the minimum.
+*/
+pub fn split_axis(value: i32) -> (i32, i32) {
+    let value = clamp_axis(value as i64) as i64;
+    let neutral = VJGeneral::NEUTRAL_AXIS_VALUE as i64;
+    let max = VJGeneral::MAX_AXIS_VALUE as i64;
+
+    let lower = (neutral - value).max(0) * max / neutral;
+    let upper = (value - neutral).max(0) * max / (max - neutral);
+
+    (lower as i32, upper as i32)
+}
+
+/**
+    Merges two half-range axes back into one axis: the opposite of [`split_axis`]. When both are
+    moved, the result is their difference.
+*/
+pub fn merge_halves(lower: i32, upper: i32) -> i32 {
+    let neutral = VJGeneral::NEUTRAL_AXIS_VALUE as i64;
+    let max = VJGeneral::MAX_AXIS_VALUE as i64;
+    let round_up = |value: i64, range: i64| (value * range + max - 1) / max;
+
+    let lower = round_up(clamp_axis(lower as i64) as i64, neutral);
+    let upper = round_up(clamp_axis(upper as i64) as i64, max - neutral);
+
+    clamp_axis(neutral - lower + upper)
+}
+
+/**
+    Selects one of the axes given by [`split_axis`]. As a filter, it keeps that half of the value.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum AxisHalf {
+    /// The axis growing as the value goes below neutral.
+    Lower,
+
+    /// The axis growing as the value goes above neutral.
+    Upper,
+}
+
+impl AxisFilter for AxisHalf {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        let (lower, upper) = split_axis(value);
+
+        match self {
+            AxisHalf::Lower => lower,
+            AxisHalf::Upper => upper,
+        }
+    }
+
+    fn reset(&mut self) {}
+}
+
+/**
+    Describes on which side of an [`AxisThreshold`] the button is pressed.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ThresholdDirection {
+    /// Pressed when the value reaches the threshold from below.
+    Above,
+
+    /// Pressed when the value reaches the threshold from above.
+    Below,
+}
+
+/**
+    Presses a button when an axis crosses a threshold.
+
+    Once pressed, the button is only released when the value moves back past the threshold by
+    more than the hysteresis, so a value hovering around the threshold does not make the button
+    chatter. Several thresholds on the same axis make several buttons. As a filter, the button
+    is pressed at the maximum of the axis and released at its minimum.
+*/
+#[derive(Debug, Clone)]
+pub struct AxisThreshold {
+    threshold: i32,
+    hysteresis: i32,
+    direction: ThresholdDirection,
+    pressed: bool,
+}
+
+impl AxisThreshold {
+    /**
+        Returns [`FilterError::InvalidParameter`] if `threshold` is out of the vJoy axis range or
+        if `hysteresis` is negative or wider than the range.
+    */
+    pub fn new(
+        threshold: i32,
+        hysteresis: i32,
+        direction: ThresholdDirection,
+    ) -> Result<AxisThreshold, FilterError> {
+        if !in_range(threshold) {
+            return Err(FilterError::InvalidParameter("threshold"));
+        }
+        if !(0..=VJGeneral::MAX_AXIS_VALUE - VJGeneral::MIN_AXIS_VALUE).contains(&hysteresis) {
+            return Err(FilterError::InvalidParameter("hysteresis"));
+        }
+
+        Ok(AxisThreshold {
+            threshold,
+            hysteresis,
+            direction,
+            pressed: false,
+        })
+    }
+
+    /// Feeds a new value and returns the state of the button.
+    pub fn update(&mut self, value: i32) -> VJDButtonState {
+        let (reached, left) = match self.direction {
+            ThresholdDirection::Above => (
+                value >= self.threshold,
+                value < self.threshold - self.hysteresis,
+            ),
+            ThresholdDirection::Below => (
+                value <= self.threshold,
+                value > self.threshold + self.hysteresis,
+            ),
+        };
+
+        if reached {
+            self.pressed = true;
+        } else if left {
+            self.pressed = false;
+        }
+
+        if self.pressed {
+            VJDButtonState::Pressed
+        } else {
+            VJDButtonState::Released
+        }
+    }
+
+    /// Releases the button.
+    pub fn reset(&mut self) {
+        self.pressed = false;
+    }
+}
+
+impl AxisFilter for AxisThreshold {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        match self.update(value) {
+            VJDButtonState::Pressed => VJGeneral::MAX_AXIS_VALUE,
+            VJDButtonState::Released => VJGeneral::MIN_AXIS_VALUE,
+        }
+    }
+
+    fn reset(&mut self) {
+        AxisThreshold::reset(self);
+    }
+}
+
+/**
+    Moves an axis by a fixed step on each press of an increment or a decrement button, like a
+    trim wheel. The value stays in the vJoy axis range.
+
+    As a filter, the input is a centered rocker: moving it more than halfway above neutral
+    presses the increment button, and more than halfway below presses the decrement button.
+*/
+#[derive(Debug, Clone)]
+pub struct StepAxis {
+    initial: i32,
+    step: i32,
+    value: i32,
+    increment: bool,
+    decrement: bool,
+}
+
+impl StepAxis {
+    /**
+        Returns [`FilterError::InvalidParameter`] if `initial` is out of the vJoy axis range or
+        if `step` is not positive.
+    */
+    pub fn new(initial: i32, step: i32) -> Result<StepAxis, FilterError> {
+        if !in_range(initial) {
+            return Err(FilterError::InvalidParameter("initial"));
+        }
+        if step <= 0 {
+            return Err(FilterError::InvalidParameter("step"));
+        }
+
+        Ok(StepAxis {
+            initial,
+            step,
+            value: initial,
+            increment: false,
+            decrement: false,
+        })
+    }
+
+    /// Feeds the states of the buttons and returns the value of the axis.
+    pub fn update(&mut self, increment: bool, decrement: bool) -> i32 {
+        let mut value = self.value as i64;
+
+        if increment && !self.increment {
+            value += self.step as i64;
+        }
+        if decrement && !self.decrement {
+            value -= self.step as i64;
+        }
+
+        self.increment = increment;
+        self.decrement = decrement;
+        self.value = clamp_axis(value);
+        self.value
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    /// Moves the axis back to its initial value and forgets the state of the buttons.
+    pub fn reset(&mut self) {
+        self.value = self.initial;
+        self.increment = false;
+        self.decrement = false;
+    }
+}
+
+impl AxisFilter for StepAxis {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        let (lower, upper) = split_axis(value);
+        let half = VJGeneral::MAX_AXIS_VALUE / 2;
+
+        self.update(upper > half, lower > half)
+    }
+
+    fn reset(&mut self) {
+        StepAxis::reset(self);
+    }
+}
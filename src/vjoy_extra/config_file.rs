@@ -0,0 +1,709 @@
+//! Exports the vJoy devices of a registry to a TOML or JSON file, and imports them back.
+//!
+//! A [`VJDDevicesFile`] lists the configuration of each device. Importing it first computes a
+//! [`VJDImportPlan`] against the current registry, which can be displayed as a dry run, then
+//! writes the changes through a [`VJDRegistryWriter`].
+//!
+//! The TOML form reads:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[devices]]
+//! device = "D9"
+//! axes = ["X", "Ry", "Slider1"]
+//! buttons = 5
+//! cont_povs = 2
+//! ffb_effects = ["Constant", "Spring"]
+//! ```
+//!
+//! Omitted controls are absent from the device.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::VJDAxis;
+    use crate::vjoy_base::force_feedback::VJDFfbEffect;
+    use crate::vjoy_extra::registry::{registered_devices, MemoryRegistry};
+
+    fn sample() -> VJDDevicesFile {
+        VJDDevicesFile::new(vec![
+            (
+                VJDevice::D1,
+                VJDConfig::new()
+                    .axes(&[VJDAxis::X, VJDAxis::Y])
+                    .buttons(8)
+                    .disc_povs(1),
+            ),
+            (
+                VJDevice::D3,
+                VJDConfig::new()
+                    .axes(&[VJDAxis::Slider1])
+                    .cont_povs(2)
+                    .ffb_effects(&[VJDFfbEffect::Constant, VJDFfbEffect::Spring]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let file = sample();
+
+        for &format in &[VJDFileFormat::Toml, VJDFileFormat::Json] {
+            let text = file.to_string(format).unwrap();
+            assert_eq!(file, VJDDevicesFile::from_str(&text, format).unwrap());
+        }
+
+        assert_eq!(
+            Some(VJDFileFormat::Json),
+            VJDFileFormat::from_path(Path::new("devices.JSON"))
+        );
+        assert_eq!(None, VJDFileFormat::from_path(Path::new("devices")));
+    }
+
+    #[test]
+    fn omitted_controls_are_absent() {
+        let text = "version = 1\n[[devices]]\ndevice = \"D2\"\nbuttons = 4\n";
+
+        assert_eq!(
+            VJDDevicesFile::new(vec![(VJDevice::D2, VJDConfig::new().buttons(4))]),
+            VJDDevicesFile::from_str(text, VJDFileFormat::Toml).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_files() {
+        let mut file = sample();
+        file.version = 2;
+        assert!(matches!(
+            file.validate(),
+            Err(VJDConfigFileError::UnsupportedVersion(2))
+        ));
+
+        let mut file = sample();
+        file.devices[1].device = VJDevice::D1;
+        assert!(matches!(
+            file.validate(),
+            Err(VJDConfigFileError::DuplicateDevice(VJDevice::D1))
+        ));
+
+        let mut file = sample();
+        file.devices[0].config.buttons = 200;
+        assert!(matches!(
+            file.validate(),
+            Err(VJDConfigFileError::InvalidConfig(
+                VJDevice::D1,
+                VJDConfigError::TooManyButtons(200)
+            ))
+        ));
+
+        assert!(matches!(
+            VJDDevicesFile::from_str("{\"version\": 1}", VJDFileFormat::Json),
+            Err(VJDConfigFileError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn import_then_export() {
+        let mut registry = MemoryRegistry::new();
+        let file = sample();
+
+        // A dry run leaves the registry untouched.
+        let options = VJDImportOptions {
+            dry_run: true,
+            ..VJDImportOptions::default()
+        };
+        let plan = import_devices(&mut registry, &file, options).unwrap();
+        assert_eq!(2, plan.pending_changes());
+        assert_eq!(MemoryRegistry::new(), registry);
+
+        import_devices(&mut registry, &file, VJDImportOptions::default()).unwrap();
+        assert_eq!(file, VJDDevicesFile::export(&registry).unwrap());
+
+        // Importing again changes nothing.
+        let plan = import_devices(&mut registry, &file, VJDImportOptions::default()).unwrap();
+        assert_eq!(0, plan.pending_changes());
+    }
+
+    #[test]
+    fn documented_file_imports() {
+        let text = r#"
+            version = 1
+
+            [[devices]]
+            device = "D9"
+            axes = ["X", "Ry", "Slider1"]
+            buttons = 5
+            cont_povs = 2
+            ffb_effects = ["Constant", "Spring"]
+        "#;
+        let file = VJDDevicesFile::from_str(text, VJDFileFormat::Toml).unwrap();
+        let mut registry = MemoryRegistry::new();
+
+        import_devices(&mut registry, &file, VJDImportOptions::default()).unwrap();
+        assert_eq!(file, VJDDevicesFile::export(&registry).unwrap());
+    }
+
+    #[test]
+    fn import_plan() {
+        let mut registry = MemoryRegistry::new();
+        let mut file = sample();
+        import_devices(&mut registry, &file, VJDImportOptions::default()).unwrap();
+
+        // Order of axes and effects does not matter.
+        file.devices[1].config.ffb_effects.reverse();
+        file.devices[0].config.buttons = 16;
+        file.devices.remove(0);
+        file.devices.push(VJDDeviceEntry {
+            device: VJDevice::D4,
+            config: VJDConfig::new().buttons(1),
+        });
+        file.devices.insert(
+            0,
+            VJDDeviceEntry {
+                device: VJDevice::D1,
+                config: VJDConfig::new()
+                    .axes(&[VJDAxis::Y, VJDAxis::X])
+                    .buttons(16)
+                    .disc_povs(1),
+            },
+        );
+
+        let plan = VJDImportPlan::new(&registry, &file, true).unwrap();
+        assert_eq!(
+            vec![
+                VJDImportChange::Update {
+                    device: VJDevice::D1,
+                    from: Some(sample().devices[0].config.clone()),
+                    to: file.devices[0].config.clone(),
+                },
+                VJDImportChange::Unchanged {
+                    device: VJDevice::D3,
+                },
+                VJDImportChange::Create {
+                    device: VJDevice::D4,
+                    config: VJDConfig::new().buttons(1),
+                },
+            ],
+            plan.changes
+        );
+        assert_eq!(
+            "~ Device01: buttons 8 -> 16\n= Device03\n+ Device04: 1 buttons\n",
+            plan.to_string()
+        );
+
+        // Without the device 3, it is only removed when pruning.
+        file.devices.remove(1);
+        let plan = VJDImportPlan::new(&registry, &file, false).unwrap();
+        assert_eq!(2, plan.pending_changes());
+
+        let plan = VJDImportPlan::new(&registry, &file, true).unwrap();
+        assert!(plan.changes.contains(&VJDImportChange::Remove {
+            device: VJDevice::D3,
+            config: Some(sample().devices[1].config.clone()),
+        }));
+
+        plan.apply(&mut registry).unwrap();
+        assert_eq!(
+            vec![VJDevice::D1, VJDevice::D4],
+            registered_devices(&registry).unwrap()
+        );
+        assert_eq!(
+            0,
+            VJDImportPlan::new(&registry, &file, true)
+                .unwrap()
+                .pending_changes()
+        );
+    }
+}
+
+use super::descriptor::build_descriptor;
+use super::registry::{self, VJDRegistrySource, VJDRegistryWriter};
+use super::VJDRegistryError;
+use crate::vjoy_base::device::config::{VJDConfig, VJDConfigError};
+use crate::vjoy_base::device::VJDevice;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/**
+    Describes an error state of the [`config_file`](self) functions.
+*/
+#[derive(Debug)]
+pub enum VJDConfigFileError {
+    /// The file cannot be read or written. [`std::io::Error`] is provided for further
+    /// investigation.
+    Io(io::Error),
+
+    /// The format of the provided path cannot be deduced from its extension.
+    UnknownFormat(PathBuf),
+
+    /// The TOML text is malformed.
+    TomlRead(toml::de::Error),
+
+    /// The devices cannot be written as TOML.
+    TomlWrite(toml::ser::Error),
+
+    /// The JSON text is malformed, or the devices cannot be written as JSON.
+    Json(serde_json::Error),
+
+    /// The file was written by an unknown version of the format. The version is provided.
+    UnsupportedVersion(u32),
+
+    /// The provided device is listed more than once.
+    DuplicateDevice(VJDevice),
+
+    /// The configuration of the provided device cannot be created by vJoy.
+    InvalidConfig(VJDevice, VJDConfigError),
+
+    /// Reading or writing the registry failed.
+    Registry(VJDRegistryError),
+}
+
+impl From<VJDRegistryError> for VJDConfigFileError {
+    fn from(error: VJDRegistryError) -> Self {
+        VJDConfigFileError::Registry(error)
+    }
+}
+
+/**
+    Describes the format of a devices file.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VJDFileFormat {
+    Toml,
+    Json,
+}
+
+impl VJDFileFormat {
+    /// Returns the format matching the extension of a path, `.toml` or `.json`.
+    pub fn from_path(path: &Path) -> Option<VJDFileFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "toml" => Some(VJDFileFormat::Toml),
+            "json" => Some(VJDFileFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/**
+    Describes the configuration of one device of a [`VJDDevicesFile`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VJDDeviceEntry {
+    pub device: VJDevice,
+
+    #[serde(flatten)]
+    pub config: VJDConfig,
+}
+
+/**
+    Describes the configurations of a set of vJoy devices, as saved in a file.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VJDDevicesFile {
+    /// Version of the format, [`VJDDevicesFile::VERSION`] when written by this crate.
+    pub version: u32,
+
+    /// Configured devices, ordered by device when exported.
+    pub devices: Vec<VJDDeviceEntry>,
+}
+
+impl VJDDevicesFile {
+    /// Describes the version of the format written by this crate.
+    pub const VERSION: u32 = 1;
+
+    pub fn new(devices: Vec<(VJDevice, VJDConfig)>) -> VJDDevicesFile {
+        VJDDevicesFile {
+            version: Self::VERSION,
+            devices: devices
+                .into_iter()
+                .map(|(device, config)| VJDDeviceEntry { device, config })
+                .collect(),
+        }
+    }
+
+    /// Returns the devices registered in a source, or [`VJDRegistryError`] if one cannot be read.
+    pub fn export<S: VJDRegistrySource + ?Sized>(
+        source: &S,
+    ) -> Result<VJDDevicesFile, VJDRegistryError> {
+        Ok(VJDDevicesFile::new(registry::read_device_configs(source)?))
+    }
+
+    /// Returns the entry of a device, or [`None`] if the device is not listed.
+    pub fn get(&self, device: VJDevice) -> Option<&VJDConfig> {
+        self.devices
+            .iter()
+            .find(|entry| entry.device == device)
+            .map(|entry| &entry.config)
+    }
+
+    /**
+        Checks the file can be imported, or returns the first inconsistency as
+        [`VJDConfigFileError`]. The descriptor of every device is built, so an import of a valid
+        file only fails on registry errors.
+    */
+    pub fn validate(&self) -> Result<(), VJDConfigFileError> {
+        if self.version != Self::VERSION {
+            return Err(VJDConfigFileError::UnsupportedVersion(self.version));
+        }
+
+        for (i, entry) in self.devices.iter().enumerate() {
+            if self.devices[..i]
+                .iter()
+                .any(|other| other.device == entry.device)
+            {
+                return Err(VJDConfigFileError::DuplicateDevice(entry.device));
+            }
+
+            // Building the descriptor catches what the import would fail on, before any write.
+            build_descriptor(entry.device, &entry.config)
+                .map_err(|error| VJDConfigFileError::InvalidConfig(entry.device, error))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the file written in a format.
+    pub fn to_string(&self, format: VJDFileFormat) -> Result<String, VJDConfigFileError> {
+        match format {
+            VJDFileFormat::Toml => toml::to_string(self).map_err(VJDConfigFileError::TomlWrite),
+            VJDFileFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(VJDConfigFileError::Json)
+            }
+        }
+    }
+
+    /// Returns the file read from a text in a format. The file is validated.
+    pub fn from_str(
+        text: &str,
+        format: VJDFileFormat,
+    ) -> Result<VJDDevicesFile, VJDConfigFileError> {
+        let file: VJDDevicesFile = match format {
+            VJDFileFormat::Toml => toml::from_str(text).map_err(VJDConfigFileError::TomlRead)?,
+            VJDFileFormat::Json => serde_json::from_str(text).map_err(VJDConfigFileError::Json)?,
+        };
+
+        file.validate()?;
+
+        Ok(file)
+    }
+
+    /// Returns the file read from a path, whose format is deduced from its extension.
+    pub fn load(path: &Path) -> Result<VJDDevicesFile, VJDConfigFileError> {
+        let format = format_of(path)?;
+        let text = fs::read_to_string(path).map_err(VJDConfigFileError::Io)?;
+
+        VJDDevicesFile::from_str(&text, format)
+    }
+
+    /// Writes the file to a path, in the format deduced from its extension.
+    pub fn save(&self, path: &Path) -> Result<(), VJDConfigFileError> {
+        let text = self.to_string(format_of(path)?)?;
+
+        fs::write(path, text).map_err(VJDConfigFileError::Io)
+    }
+}
+
+fn format_of(path: &Path) -> Result<VJDFileFormat, VJDConfigFileError> {
+    VJDFileFormat::from_path(path).ok_or_else(|| VJDConfigFileError::UnknownFormat(path.into()))
+}
+
+/// Returns the configuration with axes and effects in the order of their usage, the order a
+/// configuration is read from the registry.
+fn normalized(config: &VJDConfig) -> VJDConfig {
+    let mut config = config.clone();
+
+    config.axes.sort_by_key(|&axis| axis as u32);
+    config.ffb_effects.sort();
+    config
+}
+
+/**
+    Describes the change an import makes to one device.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VJDImportChange {
+    /// The device is not registered and will be created.
+    Create { device: VJDevice, config: VJDConfig },
+
+    /// The device is registered with another configuration, or with a descriptor which cannot be
+    /// decoded (no previous configuration), and will be rewritten.
+    Update {
+        device: VJDevice,
+        from: Option<VJDConfig>,
+        to: VJDConfig,
+    },
+
+    /// The device is registered but not in the file, and will be removed.
+    Remove {
+        device: VJDevice,
+        config: Option<VJDConfig>,
+    },
+
+    /// The device is registered with the same configuration.
+    Unchanged { device: VJDevice },
+}
+
+impl VJDImportChange {
+    pub fn device(&self) -> VJDevice {
+        match *self {
+            VJDImportChange::Create { device, .. }
+            | VJDImportChange::Update { device, .. }
+            | VJDImportChange::Remove { device, .. }
+            | VJDImportChange::Unchanged { device } => device,
+        }
+    }
+}
+
+/**
+    Configures [`import_devices`].
+*/
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VJDImportOptions {
+    /// Removes the registered devices which are not in the file.
+    pub prune: bool,
+
+    /// Only computes the plan, without writing the registry.
+    pub dry_run: bool,
+}
+
+/**
+    Holds the changes importing a [`VJDDevicesFile`] makes to a registry, one per device, ordered
+    by device. Displaying the plan gives one line per device:
+    - `+ Device04: 1 buttons` for a created device;
+    - `~ Device01: buttons 8 -> 16` for an updated device, with each changed control;
+    - `- Device03` for a removed device;
+    - `= Device02` for an unchanged device.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VJDImportPlan {
+    pub changes: Vec<VJDImportChange>,
+}
+
+impl VJDImportPlan {
+    /**
+        Returns the plan to import a file into a source, or [`VJDConfigFileError`] if the file is
+        invalid or the registry cannot be read. Registered devices missing from the file are
+        removed when `prune` is set.
+    */
+    pub fn new<S: VJDRegistrySource + ?Sized>(
+        source: &S,
+        file: &VJDDevicesFile,
+        prune: bool,
+    ) -> Result<VJDImportPlan, VJDConfigFileError> {
+        file.validate()?;
+
+        let registered = registry::registered_devices(source)?;
+        let mut changes = Vec::new();
+
+        for &device in &registered {
+            // A broken descriptor is replaced or removed like any other.
+            let current = match registry::read_device_config(source, device) {
+                Ok(config) => Some(config),
+                Err(VJDRegistryError::MissingDescriptor(_))
+                | Err(VJDRegistryError::InvalidDescriptor(..)) => None,
+                Err(error) => return Err(error.into()),
+            };
+
+            match file.get(device) {
+                Some(config) if current.as_ref() == Some(&normalized(config)) => {
+                    changes.push(VJDImportChange::Unchanged { device })
+                }
+                Some(config) => changes.push(VJDImportChange::Update {
+                    device,
+                    from: current,
+                    to: config.clone(),
+                }),
+                None if prune => changes.push(VJDImportChange::Remove {
+                    device,
+                    config: current,
+                }),
+                None => {}
+            }
+        }
+
+        for entry in &file.devices {
+            if !registered.contains(&entry.device) {
+                changes.push(VJDImportChange::Create {
+                    device: entry.device,
+                    config: entry.config.clone(),
+                });
+            }
+        }
+
+        changes.sort_by_key(|change| change.device() as u32);
+
+        Ok(VJDImportPlan { changes })
+    }
+
+    /// Returns the number of devices the plan creates, updates or removes.
+    pub fn pending_changes(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| !matches!(change, VJDImportChange::Unchanged { .. }))
+            .count()
+    }
+
+    /// Writes the changes of the plan, or returns the first [`VJDRegistryError`] met.
+    pub fn apply<W: VJDRegistryWriter + ?Sized>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), VJDRegistryError> {
+        for change in &self.changes {
+            match change {
+                VJDImportChange::Create { device, config }
+                | VJDImportChange::Update {
+                    device, to: config, ..
+                } => registry::write_device_config(writer, *device, config)?,
+                VJDImportChange::Remove { device, .. } => {
+                    registry::remove_device_config(writer, *device)?
+                }
+                VJDImportChange::Unchanged { .. } => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a list of names, such as `X, Ry`, or `none`.
+fn write_list<T: fmt::Debug>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    if items.is_empty() {
+        return write!(f, "none");
+    }
+
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+
+        write!(f, "{:?}", item)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the controls of a configuration, such as `axes X, Y; 8 buttons; 1 discrete POVs`.
+fn write_config(f: &mut fmt::Formatter, config: &VJDConfig) -> fmt::Result {
+    let mut separator = "";
+
+    if *config == VJDConfig::new() {
+        return write!(f, "no control");
+    }
+
+    if !config.axes.is_empty() {
+        write!(f, "axes ")?;
+        write_list(f, &config.axes)?;
+        separator = "; ";
+    }
+
+    for &(name, count) in &[
+        ("buttons", config.buttons),
+        ("discrete POVs", config.disc_povs),
+        ("continuous POVs", config.cont_povs),
+    ] {
+        if count > 0 {
+            write!(f, "{}{} {}", separator, count, name)?;
+            separator = "; ";
+        }
+    }
+
+    if !config.ffb_effects.is_empty() {
+        write!(f, "{}effects ", separator)?;
+        write_list(f, &config.ffb_effects)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the controls which differ between two configurations, such as `buttons 8 -> 16`.
+fn write_diff(f: &mut fmt::Formatter, from: &VJDConfig, to: &VJDConfig) -> fmt::Result {
+    let from = normalized(from);
+    let to = normalized(to);
+    let mut separator = "";
+
+    if from.axes != to.axes {
+        write!(f, "{}axes ", separator)?;
+        write_list(f, &from.axes)?;
+        write!(f, " -> ")?;
+        write_list(f, &to.axes)?;
+        separator = "; ";
+    }
+
+    for &(name, from, to) in &[
+        ("buttons", from.buttons, to.buttons),
+        ("discrete POVs", from.disc_povs, to.disc_povs),
+        ("continuous POVs", from.cont_povs, to.cont_povs),
+    ] {
+        if from != to {
+            write!(f, "{}{} {} -> {}", separator, name, from, to)?;
+            separator = "; ";
+        }
+    }
+
+    if from.ffb_effects != to.ffb_effects {
+        write!(f, "{}effects ", separator)?;
+        write_list(f, &from.ffb_effects)?;
+        write!(f, " -> ")?;
+        write_list(f, &to.ffb_effects)?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for VJDImportPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            let key = registry::device_key(change.device());
+
+            match change {
+                VJDImportChange::Create { config, .. } => {
+                    write!(f, "+ {}: ", key)?;
+                    write_config(f, config)?;
+                }
+                VJDImportChange::Update {
+                    from: Some(from),
+                    to,
+                    ..
+                } => {
+                    write!(f, "~ {}: ", key)?;
+                    write_diff(f, from, to)?;
+                }
+                VJDImportChange::Update { from: None, to, .. } => {
+                    write!(f, "~ {}: unreadable descriptor -> ", key)?;
+                    write_config(f, to)?;
+                }
+                VJDImportChange::Remove { .. } => write!(f, "- {}", key)?,
+                VJDImportChange::Unchanged { .. } => write!(f, "= {}", key)?,
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/**
+    Imports a file into a registry: computes the [`VJDImportPlan`] and, unless `dry_run` is set,
+    writes it. The plan is returned for display. The vJoy driver must be restarted for the changes
+    to apply.
+*/
+pub fn import_devices<W: VJDRegistryWriter + ?Sized>(
+    writer: &mut W,
+    file: &VJDDevicesFile,
+    options: VJDImportOptions,
+) -> Result<VJDImportPlan, VJDConfigFileError> {
+    let plan = VJDImportPlan::new(writer, file, options.prune)?;
+
+    if !options.dry_run {
+        plan.apply(writer)?;
+    }
+
+    Ok(plan)
+}
@@ -0,0 +1,813 @@
+//! Decodes the HID report descriptor of a vJoy device into a [`VJDConfig`], and builds the
+//! descriptor of a [`VJDConfig`].
+//!
+//! The vJoy configuration tool stores one report descriptor per device in the registry. It
+//! declares a joystick application collection holding the axes, POVs and buttons, followed by
+//! the Physical Interface Device (PID) collections when force feedback is enabled.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hand-written report descriptor laid out the way the vJoy configuration tool does: axes
+    /// X, Ry and Slider 1, 2 continuous POVs and 5 buttons, followed by a shortened PID section
+    /// declaring the constant, ramp and spring effects.
+    #[rustfmt::skip]
+    const FIXTURE: &[u8] = &[
+        0x05, 0x01, 0x15, 0x00, 0x09, 0x04, 0xA1, 0x01,
+        0x85, 0x09, 0xA1, 0x00,
+        // Axes: 8 fields of 32 bits, unused axes are constant
+        0x05, 0x01, 0x15, 0x00, 0x26, 0xFF, 0x7F, 0x75, 0x20, 0x95, 0x01,
+        0x09, 0x30, 0x81, 0x02,
+        0x81, 0x01,
+        0x81, 0x01,
+        0x81, 0x01,
+        0x09, 0x34, 0x81, 0x02,
+        0x81, 0x01,
+        0x09, 0x36, 0x81, 0x02,
+        0x81, 0x01,
+        // Continuous POVs
+        0x09, 0x39, 0x15, 0x00, 0x27, 0x3C, 0x8C, 0x00, 0x00, 0x35, 0x00,
+        0x47, 0x3C, 0x8C, 0x00, 0x00, 0x65, 0x14, 0x75, 0x20, 0x95, 0x01, 0x81, 0x02,
+        0x09, 0x39, 0x81, 0x02,
+        0x95, 0x02, 0x81, 0x01,
+        // Buttons
+        0x05, 0x09, 0x15, 0x00, 0x25, 0x01, 0x55, 0x00, 0x65, 0x00,
+        0x19, 0x01, 0x29, 0x05, 0x75, 0x01, 0x95, 0x05, 0x81, 0x02,
+        0x75, 0x7B, 0x95, 0x01, 0x81, 0x01,
+        0xC0,
+        // Set effect report
+        0x05, 0x0F, 0x09, 0x21, 0xA1, 0x02,
+        0x09, 0x22, 0x15, 0x01, 0x25, 0x28, 0x75, 0x08, 0x95, 0x01, 0x91, 0x02,
+        0x09, 0x25, 0xA1, 0x02,
+        0x09, 0x26, 0x09, 0x27, 0x09, 0x40,
+        0x25, 0x03, 0x15, 0x01, 0x75, 0x08, 0x95, 0x01, 0x91, 0x00,
+        0xC0,
+        0xC0,
+        // Create new effect report, listing the effects again
+        0x09, 0xAB, 0xA1, 0x02,
+        0x09, 0x25, 0xA1, 0x02,
+        0x09, 0x26, 0x09, 0x27, 0x09, 0x40,
+        0x25, 0x03, 0x15, 0x01, 0x75, 0x08, 0x95, 0x01, 0xB1, 0x00,
+        0xC0,
+        0xC0,
+        0xC0,
+    ];
+
+    #[test]
+    fn fixture_is_decoded() {
+        let descriptor = parse_descriptor(FIXTURE).unwrap();
+
+        assert_eq!(Some(9), descriptor.report_id);
+        assert_eq!(
+            VJDConfig::new()
+                .axes(&[VJDAxis::X, VJDAxis::Ry, VJDAxis::Slider1])
+                .buttons(5)
+                .cont_povs(2)
+                .ffb_effects(&[
+                    VJDFfbEffect::Constant,
+                    VJDFfbEffect::Ramp,
+                    VJDFfbEffect::Spring
+                ]),
+            descriptor.config
+        );
+    }
+
+    #[test]
+    fn discrete_povs_and_button_range() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x85, 0x02,
+            0x09, 0x39, 0x15, 0x00, 0x25, 0x03, 0x75, 0x04, 0x95, 0x03, 0x81, 0x02,
+            0x95, 0x01, 0x81, 0x01,
+            0x05, 0x09, 0x19, 0x01, 0x29, 0x80, 0x25, 0x01, 0x75, 0x01, 0x96, 0x80, 0x00, 0x81, 0x02,
+            0xC0,
+        ];
+
+        let descriptor = parse_descriptor(&bytes).unwrap();
+
+        assert_eq!(Some(2), descriptor.report_id);
+        assert_eq!(
+            VJDConfig::new().buttons(128).disc_povs(3),
+            descriptor.config
+        );
+    }
+
+    #[test]
+    fn built_descriptor_layout() {
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Y])
+            .buttons(3)
+            .disc_povs(1);
+
+        #[rustfmt::skip]
+        let expected = vec![
+            0x05, 0x01, 0x15, 0x00, 0x09, 0x04, 0xA1, 0x01,
+            0x85, 0x01, 0xA1, 0x00,
+            0x05, 0x01, 0x15, 0x00, 0x26, 0xFF, 0x7F, 0x35, 0x00, 0x46, 0xFF, 0x7F,
+            0x65, 0x00, 0x75, 0x20, 0x95, 0x01,
+            0x09, 0x30, 0x81, 0x02,
+            0x09, 0x31, 0x81, 0x02,
+            0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01,
+            0x15, 0x00, 0x25, 0x03, 0x35, 0x00, 0x46, 0x0E, 0x01, 0x65, 0x14,
+            0x75, 0x04, 0x95, 0x01,
+            0x09, 0x39, 0x81, 0x02,
+            0x75, 0x04, 0x95, 0x03, 0x81, 0x01,
+            0x75, 0x08, 0x95, 0x0E, 0x81, 0x01,
+            0x05, 0x09, 0x15, 0x00, 0x25, 0x01, 0x35, 0x00, 0x45, 0x01,
+            0x75, 0x01, 0x95, 0x03, 0x55, 0x00, 0x65, 0x00, 0x19, 0x01, 0x29, 0x03, 0x81, 0x02,
+            0x75, 0x01, 0x95, 0x7D, 0x81, 0x01,
+            0xC0,
+            0xC0,
+        ];
+
+        assert_eq!(Ok(expected), build_descriptor(VJDevice::D1, &config));
+    }
+
+    #[test]
+    fn built_descriptor_layout_with_ffb() {
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X])
+            .buttons(1)
+            .cont_povs(1)
+            .ffb_effects(&[VJDFfbEffect::Spring, VJDFfbEffect::Constant]);
+
+        #[rustfmt::skip]
+        let expected = vec![
+            0x05, 0x01, 0x15, 0x00, 0x09, 0x04, 0xA1, 0x01,
+            0x85, 0x02, 0xA1, 0x00,
+            0x05, 0x01, 0x15, 0x00, 0x26, 0xFF, 0x7F, 0x35, 0x00, 0x46, 0xFF, 0x7F,
+            0x65, 0x00, 0x75, 0x20, 0x95, 0x01,
+            0x09, 0x30, 0x81, 0x02,
+            0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01, 0x81, 0x01,
+            0x15, 0x00, 0x27, 0x3C, 0x8C, 0x00, 0x00, 0x35, 0x00, 0x47, 0x3C, 0x8C, 0x00, 0x00,
+            0x65, 0x14, 0x75, 0x20, 0x95, 0x01,
+            0x09, 0x39, 0x81, 0x02,
+            0x75, 0x20, 0x95, 0x03, 0x81, 0x01,
+            0x05, 0x09, 0x15, 0x00, 0x25, 0x01, 0x35, 0x00, 0x45, 0x01,
+            0x75, 0x01, 0x95, 0x01, 0x55, 0x00, 0x65, 0x00, 0x19, 0x01, 0x29, 0x01, 0x81, 0x02,
+            0x75, 0x01, 0x95, 0x7F, 0x81, 0x01,
+            0xC0,
+            // Set effect report, id 0x10 * 2 + 1
+            0x05, 0x0F, 0x09, 0x21, 0xA1, 0x02, 0x85, 0x21,
+            0x09, 0x22, 0x15, 0x01, 0x25, 0x28, 0x35, 0x01, 0x45, 0x28,
+            0x75, 0x08, 0x95, 0x01, 0x91, 0x02,
+            0x09, 0x25, 0xA1, 0x02,
+            0x09, 0x26, 0x09, 0x40,
+            0x25, 0x02, 0x15, 0x01, 0x35, 0x01, 0x45, 0x02, 0x75, 0x08, 0x95, 0x01, 0x91, 0x00,
+            0xC0,
+            0xC0,
+            // Create new effect report, id 0x10 * 2 + 1
+            0x09, 0xAB, 0xA1, 0x02, 0x85, 0x21,
+            0x09, 0x25, 0xA1, 0x02,
+            0x09, 0x26, 0x09, 0x40,
+            0x25, 0x02, 0x15, 0x01, 0x35, 0x01, 0x45, 0x02, 0x75, 0x08, 0x95, 0x01, 0xB1, 0x00,
+            0xC0,
+            0xC0,
+            0xC0,
+        ];
+
+        assert_eq!(Ok(expected), build_descriptor(VJDevice::D2, &config));
+    }
+
+    #[test]
+    fn built_descriptors_round_trip() {
+        let configs = [
+            (VJDevice::D1, VJDConfig::new()),
+            (VJDevice::D2, VJDConfig::full()),
+            (VJDevice::D3, VJDConfig::full().cont_povs(0).disc_povs(4)),
+            (
+                VJDevice::D9,
+                VJDConfig::new()
+                    .axes(&[VJDAxis::X, VJDAxis::Ry, VJDAxis::Slider1])
+                    .buttons(5)
+                    .cont_povs(2)
+                    .ffb_effects(&VJDFfbEffect::ALL),
+            ),
+            (
+                VJDevice::D16,
+                VJDConfig::new()
+                    .axes(&[VJDAxis::Slider2])
+                    .buttons(1)
+                    .disc_povs(1)
+                    .ffb_effects(&[VJDFfbEffect::Sine, VJDFfbEffect::Friction]),
+            ),
+        ];
+
+        for (device, config) in &configs {
+            let bytes = build_descriptor(*device, config).unwrap();
+
+            assert_eq!(
+                Ok(VJDDescriptor {
+                    report_id: Some(*device as u8),
+                    config: config.clone(),
+                }),
+                parse_descriptor(&bytes)
+            );
+        }
+
+        assert_eq!(
+            Err(VJDConfigError::TooManyButtons(200)),
+            build_descriptor(VJDevice::D1, &VJDConfig::new().buttons(200))
+        );
+    }
+
+    #[test]
+    fn huge_counts_and_ranges_are_bounded() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x05, 0x01, 0x09, 0x04, 0xA1, 0x01,
+            // u32::MAX X axes
+            0x09, 0x30, 0x97, 0xFF, 0xFF, 0xFF, 0xFF, 0x81, 0x02,
+            0xC0,
+            // An effect type collection with a usage range covering every page
+            0x05, 0x0F, 0x09, 0x25, 0xA1, 0x02,
+            0x1B, 0x00, 0x00, 0x00, 0x00, 0x2B, 0xFF, 0xFF, 0xFF, 0xFF, 0x91, 0x00,
+            0xC0,
+        ];
+
+        assert_eq!(
+            Err(VJDDescriptorError::Config(VJDConfigError::DuplicateAxis(
+                VJDAxis::X
+            ))),
+            parse_descriptor(&bytes)
+        );
+    }
+
+    #[test]
+    fn malformed_descriptors() {
+        assert_eq!(Err(VJDDescriptorError::NotJoystick), parse_descriptor(&[]));
+        assert_eq!(
+            Err(VJDDescriptorError::Truncated(6)),
+            parse_descriptor(&[0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x26, 0xFF])
+        );
+        assert_eq!(
+            Err(VJDDescriptorError::UnbalancedCollection(7)),
+            parse_descriptor(&[0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0xC0, 0xC0])
+        );
+        assert_eq!(
+            Err(VJDDescriptorError::UnbalancedCollection(4)),
+            parse_descriptor(&[0x05, 0x01, 0x09, 0x04, 0xA1, 0x01])
+        );
+        assert_eq!(
+            Err(VJDDescriptorError::Config(VJDConfigError::MixedPovs)),
+            parse_descriptor(&[
+                0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x09, 0x39, 0x25, 0x03, 0x75, 0x04, 0x95, 0x01,
+                0x81, 0x02, 0x09, 0x39, 0x27, 0x3C, 0x8C, 0x00, 0x00, 0x75, 0x20, 0x81, 0x02, 0xC0,
+            ])
+        );
+    }
+}
+
+use crate::ffi::force_feedback::{HID_ID_EFFREP, HID_ID_NEWEFREP};
+use crate::vjoy_base::device::config::{VJDConfig, VJDConfigError};
+use crate::vjoy_base::device::{VJDAxis, VJDButton, VJDevice};
+use crate::vjoy_base::force_feedback::VJDFfbEffect;
+
+pub(crate) const PAGE_GENERIC_DESKTOP: u32 = 0x01;
+pub(crate) const PAGE_BUTTON: u32 = 0x09;
+pub(crate) const PAGE_PID: u32 = 0x0F;
+
+pub(crate) const USAGE_JOYSTICK: u32 = 0x04;
+pub(crate) const USAGE_HAT_SWITCH: u32 = 0x39;
+pub(crate) const USAGE_SET_EFFECT_REPORT: u32 = 0x21;
+pub(crate) const USAGE_EFFECT_BLOCK_INDEX: u32 = 0x22;
+pub(crate) const USAGE_EFFECT_TYPE: u32 = 0x25;
+pub(crate) const USAGE_CREATE_NEW_EFFECT: u32 = 0xAB;
+
+/// Largest logical maximum of a discrete POV: vJoy declares 3, 8-way hats declare 7.
+const DISC_POV_MAX: i32 = 7;
+
+/// Maximum number of effect blocks declared by vJoy.
+const MAX_EFFECT_BLOCKS: u8 = 40;
+
+/// Number of fields of an item taken into account, more than a vJoy input report holds.
+const MAX_FIELDS: u32 = 256;
+
+/**
+    Describes an error state of [`parse_descriptor`]. Offsets are in bytes from the start of
+    the descriptor.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VJDDescriptorError {
+    /// The item at the provided offset is cut by the end of the descriptor.
+    Truncated(usize),
+
+    /// The collection closed or left open at the provided offset has no counterpart.
+    UnbalancedCollection(usize),
+
+    /// No joystick application collection has been found.
+    NotJoystick,
+
+    /// The decoded controls cannot form a vJoy device.
+    Config(VJDConfigError),
+}
+
+/**
+    Holds the content of the report descriptor of a vJoy device.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VJDDescriptor {
+    /// Report id of the joystick collection, which vJoy sets to the device number.
+    pub report_id: Option<u8>,
+
+    pub config: VJDConfig,
+}
+
+/// Describes a short item of a report descriptor.
+#[derive(Debug, Copy, Clone)]
+struct Item {
+    offset: usize,
+    kind: u8,
+    tag: u8,
+    size: usize,
+    data: u32,
+}
+
+impl Item {
+    const MAIN: u8 = 0;
+    const GLOBAL: u8 = 1;
+    const LOCAL: u8 = 2;
+
+    fn signed(&self) -> i32 {
+        match self.size {
+            1 => self.data as u8 as i8 as i32,
+            2 => self.data as u16 as i16 as i32,
+            _ => self.data as i32,
+        }
+    }
+}
+
+/// Splits a report descriptor into its short items. Long items carry no control and are skipped.
+fn items(bytes: &[u8]) -> Result<Vec<Item>, VJDDescriptorError> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let prefix = bytes[offset];
+
+        if prefix == 0xFE {
+            let size = *bytes
+                .get(offset + 1)
+                .ok_or(VJDDescriptorError::Truncated(offset))? as usize;
+
+            offset += 3 + size;
+            if offset > bytes.len() {
+                return Err(VJDDescriptorError::Truncated(offset - 3 - size));
+            }
+            continue;
+        }
+
+        let size = match prefix & 0b11 {
+            3 => 4,
+            size => size as usize,
+        };
+
+        let data = bytes
+            .get(offset + 1..offset + 1 + size)
+            .ok_or(VJDDescriptorError::Truncated(offset))?
+            .iter()
+            .rev()
+            .fold(0u32, |data, &byte| (data << 8) | byte as u32);
+
+        items.push(Item {
+            offset,
+            kind: (prefix >> 2) & 0b11,
+            tag: prefix >> 4,
+            size,
+            data,
+        });
+
+        offset += 1 + size;
+    }
+
+    Ok(items)
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct Globals {
+    usage_page: u32,
+    logical_max: i32,
+    report_size: u32,
+    report_count: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Locals {
+    usages: Vec<u32>,
+    usage_min: Option<u32>,
+    usage_max: Option<u32>,
+}
+
+impl Locals {
+    /// Returns the extended usage (page in the high 16 bits) of the field `index` of a main item.
+    fn usage(&self, index: u32) -> Option<u32> {
+        if let (Some(min), Some(max)) = (self.usage_min, self.usage_max) {
+            let usage = min + index;
+            return if usage <= max { Some(usage) } else { None };
+        }
+
+        // The last usage applies to the remaining fields.
+        let index = (index as usize).min(self.usages.len().checked_sub(1)?);
+        Some(self.usages[index])
+    }
+}
+
+/// Returns the extended usage of a local item, using the current page unless one is given.
+fn extended_usage(item: &Item, page: u32) -> u32 {
+    if item.size == 4 {
+        item.data
+    } else {
+        (page << 16) | item.data
+    }
+}
+
+/**
+    Decodes a vJoy report descriptor, or returns [`VJDDescriptorError`] if it is malformed.
+
+    Axes, POVs and buttons are the data input fields of the joystick collection. A hat switch is
+    a discrete POV when its logical maximum is at most 7, continuous otherwise. Force feedback
+    effects are the usages listed in the effect type collections.
+*/
+pub fn parse_descriptor(bytes: &[u8]) -> Result<VJDDescriptor, VJDDescriptorError> {
+    let mut config = VJDConfig::new();
+    let mut report_id = None;
+    let mut is_joystick = false;
+
+    let mut globals = Globals::default();
+    let mut stack: Vec<Globals> = Vec::new();
+    let mut locals = Locals::default();
+
+    // Extended usage and offset of each open collection, and the depth of the joystick
+    // collection if open.
+    let mut collections: Vec<(u32, usize)> = Vec::new();
+    let mut joystick_depth: Option<usize> = None;
+
+    for item in items(bytes)? {
+        match (item.kind, item.tag) {
+            (Item::GLOBAL, 0x0) => globals.usage_page = item.data,
+            (Item::GLOBAL, 0x2) => globals.logical_max = item.signed(),
+            (Item::GLOBAL, 0x7) => globals.report_size = item.data,
+            (Item::GLOBAL, 0x8) if joystick_depth.is_some() && report_id.is_none() => {
+                report_id = Some(item.data as u8)
+            }
+            (Item::GLOBAL, 0x9) => globals.report_count = item.data,
+            (Item::GLOBAL, 0xA) => stack.push(globals),
+            (Item::GLOBAL, 0xB) => globals = stack.pop().unwrap_or_default(),
+            (Item::LOCAL, 0x0) => locals
+                .usages
+                .push(extended_usage(&item, globals.usage_page)),
+            (Item::LOCAL, 0x1) => {
+                locals.usage_min = Some(extended_usage(&item, globals.usage_page))
+            }
+            (Item::LOCAL, 0x2) => {
+                locals.usage_max = Some(extended_usage(&item, globals.usage_page))
+            }
+            (Item::MAIN, 0xA) => {
+                let usage = locals.usages.first().copied().unwrap_or(0);
+
+                if usage == ((PAGE_GENERIC_DESKTOP << 16) | USAGE_JOYSTICK)
+                    && joystick_depth.is_none()
+                {
+                    joystick_depth = Some(collections.len());
+                    is_joystick = true;
+                }
+
+                collections.push((usage, item.offset));
+            }
+            (Item::MAIN, 0xC) => {
+                if collections.pop().is_none() {
+                    return Err(VJDDescriptorError::UnbalancedCollection(item.offset));
+                }
+
+                if joystick_depth == Some(collections.len()) {
+                    joystick_depth = None;
+                }
+            }
+            // Input, Output and Feature
+            (Item::MAIN, 0x8) | (Item::MAIN, 0x9) | (Item::MAIN, 0xB) => {
+                let is_constant = item.data & 1 == 1;
+                let is_input = item.tag == 0x8;
+
+                let effect_type = (PAGE_PID << 16) | USAGE_EFFECT_TYPE;
+
+                if collections.iter().any(|&(usage, _)| usage == effect_type) {
+                    add_ffb_effects(&mut config, &locals);
+                } else if is_input && !is_constant && joystick_depth.is_some() {
+                    add_inputs(&mut config, &locals, &globals);
+                }
+            }
+            _ => {}
+        }
+
+        if item.kind == Item::MAIN {
+            locals = Locals::default();
+        }
+    }
+
+    if let Some(&(_, offset)) = collections.last() {
+        return Err(VJDDescriptorError::UnbalancedCollection(offset));
+    }
+
+    if !is_joystick {
+        return Err(VJDDescriptorError::NotJoystick);
+    }
+
+    config.ffb_effects.sort();
+    config.validate().map_err(VJDDescriptorError::Config)?;
+
+    Ok(VJDDescriptor { report_id, config })
+}
+
+/// Adds the controls of the fields of an input item of the joystick collection.
+fn add_inputs(config: &mut VJDConfig, locals: &Locals, globals: &Globals) {
+    for index in 0..globals.report_count.min(MAX_FIELDS) {
+        let usage = match locals.usage(index) {
+            Some(usage) => usage,
+            None => break,
+        };
+
+        let (page, id) = (usage >> 16, usage & 0xFFFF);
+
+        match page {
+            PAGE_BUTTON => config.buttons = config.buttons.saturating_add(1),
+            PAGE_GENERIC_DESKTOP if id == USAGE_HAT_SWITCH => {
+                if globals.logical_max <= DISC_POV_MAX {
+                    config.disc_povs = config.disc_povs.saturating_add(1);
+                } else {
+                    config.cont_povs = config.cont_povs.saturating_add(1);
+                }
+            }
+            PAGE_GENERIC_DESKTOP => {
+                let axis = VJDAxis::ALL.iter().copied().find(|&axis| axis as u32 == id);
+
+                // Duplicates are kept to be reported by the validation.
+                if let Some(axis) = axis {
+                    config.axes.push(axis);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Adds the effects listed by the usages of an item of an effect type collection.
+fn add_ffb_effects(config: &mut VJDConfig, locals: &Locals) {
+    // Only the usages of known effects are looked up in the range, which may be huge.
+    let in_range = |usage: u32| match (locals.usage_min, locals.usage_max) {
+        (Some(min), Some(max)) => (min..=max).contains(&usage),
+        _ => false,
+    };
+
+    for &effect in &VJDFfbEffect::ALL {
+        let usage = (PAGE_PID << 16) | effect.usage();
+
+        if (locals.usages.contains(&usage) || in_range(usage))
+            && !config.ffb_effects.contains(&effect)
+        {
+            config.ffb_effects.push(effect);
+        }
+    }
+}
+
+/// Appends the HID items of the report descriptor of a vJoy device.
+#[derive(Debug, Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    const USAGE_PAGE: u8 = 0x05;
+    const LOGICAL_MIN: u8 = 0x15;
+    const LOGICAL_MAX: u8 = 0x25;
+    const PHYSICAL_MIN: u8 = 0x35;
+    const PHYSICAL_MAX: u8 = 0x45;
+    const UNIT_EXPONENT: u8 = 0x55;
+    const UNIT: u8 = 0x65;
+    const REPORT_SIZE: u8 = 0x75;
+    const REPORT_ID: u8 = 0x85;
+    const REPORT_COUNT: u8 = 0x95;
+    const USAGE: u8 = 0x09;
+    const USAGE_MIN: u8 = 0x19;
+    const USAGE_MAX: u8 = 0x29;
+    const INPUT: u8 = 0x81;
+    const OUTPUT: u8 = 0x91;
+    const FEATURE: u8 = 0xB1;
+    const COLLECTION: u8 = 0xA1;
+    const END_COLLECTION: u8 = 0xC0;
+
+    const DATA_VARIABLE: u8 = 0x02;
+    const CONSTANT: u8 = 0x01;
+    const DATA_ARRAY: u8 = 0x00;
+
+    const COLLECTION_PHYSICAL: u8 = 0x00;
+    const COLLECTION_APPLICATION: u8 = 0x01;
+    const COLLECTION_LOGICAL: u8 = 0x02;
+
+    /// Unit of POVs: English rotation, degrees.
+    const UNIT_DEGREES: u8 = 0x14;
+
+    /// Appends an item with one byte of data.
+    fn byte(&mut self, prefix: u8, data: u8) -> &mut Writer {
+        self.0.extend_from_slice(&[prefix, data]);
+        self
+    }
+
+    /// Appends an item with two bytes of data.
+    fn short(&mut self, prefix: u8, data: u16) -> &mut Writer {
+        self.0.push(prefix + 1);
+        self.0.extend_from_slice(&data.to_le_bytes());
+        self
+    }
+
+    /// Appends an item with four bytes of data.
+    fn long(&mut self, prefix: u8, data: u32) -> &mut Writer {
+        self.0.push(prefix + 2);
+        self.0.extend_from_slice(&data.to_le_bytes());
+        self
+    }
+
+    fn end_collection(&mut self) -> &mut Writer {
+        self.0.push(Self::END_COLLECTION);
+        self
+    }
+
+    /// Appends constant fields, unless there is none.
+    fn padding(&mut self, size: u8, count: u8) -> &mut Writer {
+        if count > 0 {
+            self.byte(Self::REPORT_SIZE, size)
+                .byte(Self::REPORT_COUNT, count)
+                .byte(Self::INPUT, Self::CONSTANT);
+        }
+        self
+    }
+}
+
+/**
+    Returns the report descriptor of a device, as stored in its `DeviceNN` registry key, or the
+    [`VJDConfigError`] of an invalid configuration.
+
+    The descriptor follows the layout of the vJoy configuration tool, whose input report matches
+    the position of a device: 8 axes of 32 bits, unused axes being constant, 4 POVs (4 bits per
+    discrete POV or 32 bits per continuous POV), then 128 buttons. The report id is the device
+    number.
+
+    When force feedback effects are set, the PID reports listing the effect types are declared
+    after the input report. Their report ids are computed like the tool does:
+    `0x10 * device + report`.
+
+    Axes are laid out in the order of [`VJDAxis::ALL`] and effects in the order of
+    [`VJDFfbEffect::ALL`]: [`parse_descriptor`] returns them in these orders.
+*/
+pub fn build_descriptor(device: VJDevice, config: &VJDConfig) -> Result<Vec<u8>, VJDConfigError> {
+    config.validate()?;
+
+    let report_id = device as u8;
+    let mut w = Writer::default();
+
+    w.byte(Writer::USAGE_PAGE, PAGE_GENERIC_DESKTOP as u8)
+        .byte(Writer::LOGICAL_MIN, 0)
+        .byte(Writer::USAGE, USAGE_JOYSTICK as u8)
+        .byte(Writer::COLLECTION, Writer::COLLECTION_APPLICATION)
+        .byte(Writer::REPORT_ID, report_id)
+        .byte(Writer::COLLECTION, Writer::COLLECTION_PHYSICAL);
+
+    write_axes(&mut w, config);
+    write_povs(&mut w, config);
+    write_buttons(&mut w, config);
+    w.end_collection();
+
+    if !config.ffb_effects.is_empty() {
+        write_ffb_effects(&mut w, report_id, config);
+    }
+
+    w.end_collection();
+
+    Ok(w.0)
+}
+
+fn write_axes(w: &mut Writer, config: &VJDConfig) {
+    w.byte(Writer::USAGE_PAGE, PAGE_GENERIC_DESKTOP as u8)
+        .byte(Writer::LOGICAL_MIN, 0)
+        .short(Writer::LOGICAL_MAX, 0x7FFF)
+        .byte(Writer::PHYSICAL_MIN, 0)
+        .short(Writer::PHYSICAL_MAX, 0x7FFF)
+        .byte(Writer::UNIT, 0)
+        .byte(Writer::REPORT_SIZE, 32)
+        .byte(Writer::REPORT_COUNT, 1);
+
+    for &axis in &VJDAxis::ALL {
+        if config.has_axis(axis) {
+            w.byte(Writer::USAGE, axis as u8)
+                .byte(Writer::INPUT, Writer::DATA_VARIABLE);
+        } else {
+            w.byte(Writer::INPUT, Writer::CONSTANT);
+        }
+    }
+}
+
+fn write_povs(w: &mut Writer, config: &VJDConfig) {
+    if config.disc_povs > 0 {
+        w.byte(Writer::LOGICAL_MIN, 0)
+            .byte(Writer::LOGICAL_MAX, 3)
+            .byte(Writer::PHYSICAL_MIN, 0)
+            .short(Writer::PHYSICAL_MAX, 270)
+            .byte(Writer::UNIT, Writer::UNIT_DEGREES)
+            .byte(Writer::REPORT_SIZE, 4)
+            .byte(Writer::REPORT_COUNT, 1);
+
+        for _ in 0..config.disc_povs {
+            w.byte(Writer::USAGE, USAGE_HAT_SWITCH as u8)
+                .byte(Writer::INPUT, Writer::DATA_VARIABLE);
+        }
+
+        // The remaining discrete POVs, then the 3 other words of POVs.
+        w.padding(4, VJDConfig::MAX_POVS - config.disc_povs)
+            .padding(8, 14);
+    } else if config.cont_povs > 0 {
+        w.byte(Writer::LOGICAL_MIN, 0)
+            .long(Writer::LOGICAL_MAX, 35900)
+            .byte(Writer::PHYSICAL_MIN, 0)
+            .long(Writer::PHYSICAL_MAX, 35900)
+            .byte(Writer::UNIT, Writer::UNIT_DEGREES)
+            .byte(Writer::REPORT_SIZE, 32)
+            .byte(Writer::REPORT_COUNT, 1);
+
+        for _ in 0..config.cont_povs {
+            w.byte(Writer::USAGE, USAGE_HAT_SWITCH as u8)
+                .byte(Writer::INPUT, Writer::DATA_VARIABLE);
+        }
+
+        w.padding(32, VJDConfig::MAX_POVS - config.cont_povs);
+    } else {
+        w.padding(32, VJDConfig::MAX_POVS);
+    }
+}
+
+fn write_buttons(w: &mut Writer, config: &VJDConfig) {
+    if config.buttons > 0 {
+        w.byte(Writer::USAGE_PAGE, PAGE_BUTTON as u8)
+            .byte(Writer::LOGICAL_MIN, 0)
+            .byte(Writer::LOGICAL_MAX, 1)
+            .byte(Writer::PHYSICAL_MIN, 0)
+            .byte(Writer::PHYSICAL_MAX, 1)
+            .byte(Writer::REPORT_SIZE, 1)
+            .byte(Writer::REPORT_COUNT, config.buttons)
+            .byte(Writer::UNIT_EXPONENT, 0)
+            .byte(Writer::UNIT, 0)
+            .byte(Writer::USAGE_MIN, 1)
+            .byte(Writer::USAGE_MAX, config.buttons)
+            .byte(Writer::INPUT, Writer::DATA_VARIABLE);
+    }
+
+    w.padding(1, VJDButton::MAX_BUTTONS - config.buttons);
+}
+
+/// Writes an effect type collection listing the effects, closed by an item of type `main`.
+fn write_effect_types(w: &mut Writer, config: &VJDConfig, main: u8) {
+    let count = config.ffb_effects.len() as u8;
+    let mut effects = config.ffb_effects.clone();
+    effects.sort();
+
+    w.byte(Writer::USAGE, USAGE_EFFECT_TYPE as u8)
+        .byte(Writer::COLLECTION, Writer::COLLECTION_LOGICAL);
+
+    for effect in effects {
+        w.byte(Writer::USAGE, effect.usage() as u8);
+    }
+
+    w.byte(Writer::LOGICAL_MAX, count)
+        .byte(Writer::LOGICAL_MIN, 1)
+        .byte(Writer::PHYSICAL_MIN, 1)
+        .byte(Writer::PHYSICAL_MAX, count)
+        .byte(Writer::REPORT_SIZE, 8)
+        .byte(Writer::REPORT_COUNT, 1)
+        .byte(main, Writer::DATA_ARRAY)
+        .end_collection();
+}
+
+fn write_ffb_effects(w: &mut Writer, report_id: u8, config: &VJDConfig) {
+    // Report ids are bytes in the tool as well: they wrap for device 16.
+    let ffb_report_id = |report: u32| 0x10u8.wrapping_mul(report_id).wrapping_add(report as u8);
+
+    // Set effect report
+    w.byte(Writer::USAGE_PAGE, PAGE_PID as u8)
+        .byte(Writer::USAGE, USAGE_SET_EFFECT_REPORT as u8)
+        .byte(Writer::COLLECTION, Writer::COLLECTION_LOGICAL)
+        .byte(Writer::REPORT_ID, ffb_report_id(HID_ID_EFFREP))
+        .byte(Writer::USAGE, USAGE_EFFECT_BLOCK_INDEX as u8)
+        .byte(Writer::LOGICAL_MIN, 1)
+        .byte(Writer::LOGICAL_MAX, MAX_EFFECT_BLOCKS)
+        .byte(Writer::PHYSICAL_MIN, 1)
+        .byte(Writer::PHYSICAL_MAX, MAX_EFFECT_BLOCKS)
+        .byte(Writer::REPORT_SIZE, 8)
+        .byte(Writer::REPORT_COUNT, 1)
+        .byte(Writer::OUTPUT, Writer::DATA_VARIABLE);
+    write_effect_types(w, config, Writer::OUTPUT);
+    w.end_collection();
+
+    // Create new effect report
+    w.byte(Writer::USAGE, USAGE_CREATE_NEW_EFFECT as u8)
+        .byte(Writer::COLLECTION, Writer::COLLECTION_LOGICAL)
+        .byte(Writer::REPORT_ID, ffb_report_id(HID_ID_NEWEFREP));
+    write_effect_types(w, config, Writer::FEATURE);
+    w.end_collection();
+}
@@ -0,0 +1,691 @@
+//! Contains stateful filters to clean up noisy axis values before they are sent to vJoy.
+//!
+//! Filters work in vJoy units (see [`VJGeneral::MIN_AXIS_VALUE`] and
+//! [`VJGeneral::MAX_AXIS_VALUE`]). The timestamp of each sample is given by the caller
+//! instead of being read from a clock, so the output of a filter only depends on its inputs.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::VJDevice;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn ema_first_sample_passes_through() {
+        let mut ema = EmaFilter::new(ms(100));
+        assert_eq!(20000, ema.filter(20000, ms(0)));
+    }
+
+    #[test]
+    fn ema_reaches_63_percent_after_time_constant() {
+        let mut ema = EmaFilter::new(ms(100));
+        ema.filter(0, ms(0));
+
+        // A step of 10000 filtered by a time constant of 100ms must be at ~63.2% after 100ms.
+        assert_eq!(6321, ema.filter(10000, ms(100)));
+    }
+
+    #[test]
+    fn ema_zero_time_constant_is_pass_through() {
+        let mut ema = EmaFilter::new(ms(0));
+        ema.filter(0, ms(0));
+        assert_eq!(12345, ema.filter(12345, ms(1)));
+    }
+
+    #[test]
+    fn ema_same_timestamp_keeps_output() {
+        let mut ema = EmaFilter::new(ms(100));
+        ema.filter(1000, ms(10));
+        assert_eq!(1000, ema.filter(30000, ms(10)));
+    }
+
+    #[test]
+    fn one_euro_invalid_parameters() {
+        assert_eq!(
+            Err(FilterError::InvalidParameter("min_cutoff")),
+            OneEuroFilter::new(0.0, 0.0, 1.0).map(|_| ())
+        );
+        assert_eq!(
+            Err(FilterError::InvalidParameter("beta")),
+            OneEuroFilter::new(1.0, -1.0, 1.0).map(|_| ())
+        );
+        assert_eq!(
+            Err(FilterError::InvalidParameter("derivative_cutoff")),
+            OneEuroFilter::new(1.0, 0.0, f64::NAN).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn one_euro_smooths_jitter_at_rest() {
+        let mut filter = OneEuroFilter::new(1.0, 0.0, 1.0).unwrap();
+        filter.filter(16384, ms(0));
+
+        for i in 1..=100 {
+            let jitter = if i % 2 == 0 { 40 } else { -40 };
+            let value = filter.filter(16384 + jitter, ms(i * 10));
+            assert!((value - 16384).abs() <= 3, "value {} is too noisy", value);
+        }
+    }
+
+    #[test]
+    fn one_euro_beta_reduces_lag() {
+        let mut slow = OneEuroFilter::new(1.0, 0.0, 1.0).unwrap();
+        let mut fast = OneEuroFilter::new(1.0, 0.1, 1.0).unwrap();
+
+        slow.filter(0, ms(0));
+        fast.filter(0, ms(0));
+
+        let mut slow_value = 0;
+        let mut fast_value = 0;
+
+        // Quick sweep of the axis: the speed coefficient must make the filter follow closely.
+        for i in 1..=10 {
+            slow_value = slow.filter(i * 3000, ms(i as u64 * 10));
+            fast_value = fast.filter(i * 3000, ms(i as u64 * 10));
+        }
+
+        assert!(fast_value > slow_value);
+        assert!(30000 - fast_value < 3000);
+    }
+
+    #[test]
+    fn median_rejects_spike() {
+        let mut median = MedianFilter::new(3).unwrap();
+
+        assert_eq!(100, median.filter(100, ms(0)));
+        assert_eq!(100, median.filter(100, ms(1)));
+        assert_eq!(100, median.filter(32767, ms(2)));
+        assert_eq!(110, median.filter(110, ms(3)));
+        assert_eq!(120, median.filter(120, ms(4)));
+    }
+
+    #[test]
+    fn median_even_window_averages() {
+        let mut median = MedianFilter::new(2).unwrap();
+
+        median.filter(100, ms(0));
+        assert_eq!(150, median.filter(200, ms(1)));
+
+        // Filters may be fed values out of the vJoy range, before a chain clamps them.
+        median.filter(i32::MAX, ms(2));
+        assert_eq!(i32::MAX, median.filter(i32::MAX, ms(3)));
+    }
+
+    #[test]
+    fn median_empty_window_is_error() {
+        assert_eq!(
+            Err(FilterError::EmptyWindow),
+            MedianFilter::new(0).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn slew_rate_limits_change() {
+        // 1000 vJoy units per second.
+        let mut slew = SlewRateLimiter::new(1000.0).unwrap();
+
+        assert_eq!(0, slew.filter(0, ms(0)));
+        assert_eq!(100, slew.filter(32767, ms(100)));
+        assert_eq!(600, slew.filter(32767, ms(600)));
+        assert_eq!(500, slew.filter(0, ms(700)));
+        assert_eq!(510, slew.filter(510, ms(1000)));
+    }
+
+    #[test]
+    fn slew_rate_invalid_parameter() {
+        assert_eq!(
+            Err(FilterError::InvalidParameter("max_rate")),
+            SlewRateLimiter::new(-1.0).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn hysteresis_holds_inside_band() {
+        let mut hysteresis = HysteresisFilter::new(50).unwrap();
+
+        assert_eq!(1000, hysteresis.filter(1000, ms(0)));
+        assert_eq!(1000, hysteresis.filter(1050, ms(1)));
+        assert_eq!(1000, hysteresis.filter(950, ms(2)));
+        assert_eq!(1051, hysteresis.filter(1051, ms(3)));
+        assert_eq!(1051, hysteresis.filter(1010, ms(4)));
+
+        assert_eq!(i32::MIN, hysteresis.filter(i32::MIN, ms(5)));
+        assert_eq!(i32::MAX, hysteresis.filter(i32::MAX, ms(6)));
+    }
+
+    #[test]
+    fn hysteresis_reaches_axis_limits() {
+        let mut hysteresis = HysteresisFilter::new(50).unwrap();
+
+        hysteresis.filter(VJGeneral::MAX_AXIS_VALUE - 10, ms(0));
+        assert_eq!(
+            VJGeneral::MAX_AXIS_VALUE,
+            hysteresis.filter(VJGeneral::MAX_AXIS_VALUE, ms(1))
+        );
+
+        hysteresis.filter(VJGeneral::MIN_AXIS_VALUE + 10, ms(2));
+        assert_eq!(
+            VJGeneral::MIN_AXIS_VALUE,
+            hysteresis.filter(VJGeneral::MIN_AXIS_VALUE, ms(3))
+        );
+    }
+
+    #[test]
+    fn reset_forgets_state() {
+        let mut slew = SlewRateLimiter::new(1000.0).unwrap();
+
+        slew.filter(0, ms(0));
+        slew.reset();
+        assert_eq!(30000, slew.filter(30000, ms(1)));
+    }
+
+    #[test]
+    fn chain_applies_filters_in_order_and_clamps() {
+        let mut chain = FilterChain::new()
+            .with(MedianFilter::new(3).unwrap())
+            .with(HysteresisFilter::new(10).unwrap());
+
+        assert_eq!(VJGeneral::MAX_AXIS_VALUE, chain.filter(40000, ms(0)));
+
+        chain.reset();
+        assert_eq!(1000, chain.filter(1000, ms(0)));
+        assert_eq!(1000, chain.filter(1005, ms(1)));
+        assert_eq!(1000, chain.filter(32767, ms(2)));
+        assert_eq!(1020, chain.filter(1020, ms(3)));
+    }
+
+    #[test]
+    fn chain_is_deterministic() {
+        let build = || {
+            FilterChain::new()
+                .with(EmaFilter::new(ms(30)))
+                .with(OneEuroFilter::new(1.5, 0.01, 1.0).unwrap())
+                .with(SlewRateLimiter::new(50000.0).unwrap())
+        };
+
+        let mut first = build();
+        let mut second = build();
+
+        for i in 0..200 {
+            let value = ((i * 7919) % 32768) as i32;
+            let timestamp = ms(i * 4);
+            assert_eq!(
+                first.filter(value, timestamp),
+                second.filter(value, timestamp)
+            );
+        }
+    }
+
+    #[test]
+    fn axis_filters_update_position() {
+        let mut filters = AxisFilters::new();
+        filters.set_chain(
+            VJDAxis::Ry,
+            FilterChain::new().with(HysteresisFilter::new(100).unwrap()),
+        );
+
+        let mut position = VJDPosition::new(VJDevice::D1);
+        let mut expected = VJDPosition::new(VJDevice::D1);
+
+        assert_eq!(
+            5000,
+            filters.set_axis(&mut position, VJDAxis::Ry, 5000, ms(0))
+        );
+        assert_eq!(
+            5000,
+            filters.set_axis(&mut position, VJDAxis::Ry, 5050, ms(1))
+        );
+        expected.set_axis_yr(5000);
+        assert_eq!(expected.get_position(), position.get_position());
+
+        // Axes without a chain are left untouched.
+        assert_eq!(
+            5050,
+            filters.set_axis(&mut position, VJDAxis::X, 5050, ms(2))
+        );
+        expected.set_axis_x(5050);
+        assert_eq!(expected.get_position(), position.get_position());
+    }
+}
+
+use crate::vjoy_base::device::{VJDAxis, VJDPosition};
+use crate::vjoy_base::driver::VJGeneral;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/**
+    Describes an error state when creating a filter.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FilterError {
+    /// The window of a [`MedianFilter`] must hold at least one sample.
+    EmptyWindow,
+
+    /// A parameter was out of its valid range or not a finite number. The name of the
+    /// parameter is provided.
+    InvalidParameter(&'static str),
+}
+
+/**
+    Describes a stateful filter of axis values.
+
+    Values are expressed in vJoy units. The `timestamp` of a sample is the time elapsed since an
+    arbitrary origin chosen by the caller, which must stay the same for the whole life of the
+    filter. A timestamp older than the previous one is considered equal to it.
+*/
+pub trait AxisFilter: Send {
+    /// Feeds a new value sampled at `timestamp` and returns the filtered value.
+    fn filter(&mut self, value: i32, timestamp: Duration) -> i32;
+
+    /// Forgets every previous sample. The next value will pass through unchanged.
+    fn reset(&mut self);
+}
+
+/// Returns the elapsed time between two timestamps in seconds, never negative.
+fn elapsed_secs(from: Duration, to: Duration) -> f64 {
+    to.checked_sub(from).unwrap_or_default().as_secs_f64()
+}
+
+/// Checks a parameter is a finite number not lower than `min`.
+fn check_parameter(value: f64, min: f64, name: &'static str) -> Result<f64, FilterError> {
+    if value.is_finite() && value >= min {
+        Ok(value)
+    } else {
+        Err(FilterError::InvalidParameter(name))
+    }
+}
+
+/**
+    Exponential moving average parameterized by a time constant.
+
+    After a step change of the input, the output covers ~63.2% of the step after one time
+    constant. Samples do not need to be evenly spaced in time. A time constant of zero disables
+    the smoothing.
+*/
+#[derive(Debug, Clone)]
+pub struct EmaFilter {
+    time_constant: f64,
+    state: Option<(f64, Duration)>,
+}
+
+impl EmaFilter {
+    pub fn new(time_constant: Duration) -> EmaFilter {
+        EmaFilter {
+            time_constant: time_constant.as_secs_f64(),
+            state: None,
+        }
+    }
+}
+
+impl AxisFilter for EmaFilter {
+    fn filter(&mut self, value: i32, timestamp: Duration) -> i32 {
+        let output = match self.state {
+            None => value as f64,
+            Some((previous, last_timestamp)) => {
+                let dt = elapsed_secs(last_timestamp, timestamp);
+
+                let alpha = if self.time_constant == 0.0 {
+                    1.0
+                } else {
+                    1.0 - (-dt / self.time_constant).exp()
+                };
+
+                previous + alpha * (value as f64 - previous)
+            }
+        };
+
+        let last_timestamp = self.state.map(|(_, t)| t).unwrap_or_default();
+        self.state = Some((output, timestamp.max(last_timestamp)));
+
+        output.round() as i32
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OneEuroState {
+    value: f64,
+    derivative: f64,
+    timestamp: Duration,
+}
+
+/**
+    One euro filter: an adaptive low-pass filter which smooths strongly when the axis is at rest
+    and lowers its lag when the axis moves fast.
+
+    - `min_cutoff`: cutoff frequency in Hz used when the axis is at rest. Lower it to remove more
+      jitter.
+    - `beta`: increase of the cutoff frequency in Hz per vJoy unit per second of speed. Raise it
+      to reduce lag during fast moves.
+    - `derivative_cutoff`: cutoff frequency in Hz used to smooth the speed estimation. 1 Hz is a
+      good default.
+
+    See <https://gery.casiez.net/1euro/> for a description of the algorithm.
+*/
+#[derive(Debug, Clone)]
+pub struct OneEuroFilter {
+    min_cutoff: f64,
+    beta: f64,
+    derivative_cutoff: f64,
+    state: Option<OneEuroState>,
+}
+
+impl OneEuroFilter {
+    /**
+        Returns a new filter, or [`FilterError::InvalidParameter`] if a cutoff frequency is not
+        strictly positive or `beta` is negative.
+    */
+    pub fn new(
+        min_cutoff: f64,
+        beta: f64,
+        derivative_cutoff: f64,
+    ) -> Result<OneEuroFilter, FilterError> {
+        let min_cutoff = check_parameter(min_cutoff, f64::MIN_POSITIVE, "min_cutoff")?;
+        let beta = check_parameter(beta, 0.0, "beta")?;
+        let derivative_cutoff =
+            check_parameter(derivative_cutoff, f64::MIN_POSITIVE, "derivative_cutoff")?;
+
+        Ok(OneEuroFilter {
+            min_cutoff,
+            beta,
+            derivative_cutoff,
+            state: None,
+        })
+    }
+
+    fn alpha(cutoff: f64, dt: f64) -> f64 {
+        let tau = 1.0 / (2.0 * std::f64::consts::PI * cutoff);
+        1.0 / (1.0 + tau / dt)
+    }
+}
+
+impl AxisFilter for OneEuroFilter {
+    fn filter(&mut self, value: i32, timestamp: Duration) -> i32 {
+        let value = value as f64;
+
+        let state = match self.state {
+            None => OneEuroState {
+                value,
+                derivative: 0.0,
+                timestamp,
+            },
+            Some(previous) => {
+                let dt = elapsed_secs(previous.timestamp, timestamp);
+
+                // No time elapsed, no way to estimate a speed: keep the previous output.
+                if dt == 0.0 {
+                    return previous.value.round() as i32;
+                }
+
+                let raw_derivative = (value - previous.value) / dt;
+                let derivative = previous.derivative
+                    + Self::alpha(self.derivative_cutoff, dt)
+                        * (raw_derivative - previous.derivative);
+
+                let cutoff = self.min_cutoff + self.beta * derivative.abs();
+
+                OneEuroState {
+                    value: previous.value + Self::alpha(cutoff, dt) * (value - previous.value),
+                    derivative,
+                    timestamp,
+                }
+            }
+        };
+
+        self.state = Some(state);
+        state.value.round() as i32
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/**
+    Returns the median of the last `size` samples. Well suited to remove short spikes.
+
+    With an even `size`, the median is the mean of the two middle samples rounded down.
+*/
+#[derive(Debug, Clone)]
+pub struct MedianFilter {
+    size: usize,
+    window: VecDeque<i32>,
+}
+
+impl MedianFilter {
+    /**
+        Returns a new filter, or [`FilterError::EmptyWindow`] if `size` is zero.
+    */
+    pub fn new(size: usize) -> Result<MedianFilter, FilterError> {
+        if size == 0 {
+            return Err(FilterError::EmptyWindow);
+        }
+
+        Ok(MedianFilter {
+            size,
+            window: VecDeque::with_capacity(size),
+        })
+    }
+}
+
+impl AxisFilter for MedianFilter {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        if self.window.len() == self.size {
+            self.window.pop_front();
+        }
+
+        self.window.push_back(value);
+
+        let mut sorted: Vec<i32> = self.window.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let middle = sorted.len() / 2;
+
+        if sorted.len() % 2 == 1 {
+            sorted[middle]
+        } else {
+            (sorted[middle - 1] as i64 + sorted[middle] as i64).div_euclid(2) as i32
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/**
+    Limits how fast the output can change, expressed in vJoy units per second.
+*/
+#[derive(Debug, Clone)]
+pub struct SlewRateLimiter {
+    max_rate: f64,
+    state: Option<(f64, Duration)>,
+}
+
+impl SlewRateLimiter {
+    /**
+        Returns a new limiter, or [`FilterError::InvalidParameter`] if `max_rate` is negative.
+    */
+    pub fn new(max_rate: f64) -> Result<SlewRateLimiter, FilterError> {
+        Ok(SlewRateLimiter {
+            max_rate: check_parameter(max_rate, 0.0, "max_rate")?,
+            state: None,
+        })
+    }
+}
+
+impl AxisFilter for SlewRateLimiter {
+    fn filter(&mut self, value: i32, timestamp: Duration) -> i32 {
+        let output = match self.state {
+            None => value as f64,
+            Some((previous, last_timestamp)) => {
+                let max_step = self.max_rate * elapsed_secs(last_timestamp, timestamp);
+                previous + (value as f64 - previous).clamp(-max_step, max_step)
+            }
+        };
+
+        let last_timestamp = self.state.map(|(_, t)| t).unwrap_or_default();
+        self.state = Some((output, timestamp.max(last_timestamp)));
+
+        output.round() as i32
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/**
+    Keeps the last output until the input moves more than `threshold` vJoy units away from it.
+
+    The limits of the vJoy axis range are always let through, so the axis can reach its ends
+    even when they lie inside the band.
+*/
+#[derive(Debug, Clone)]
+pub struct HysteresisFilter {
+    threshold: i32,
+    last: Option<i32>,
+}
+
+impl HysteresisFilter {
+    /**
+        Returns a new filter, or [`FilterError::InvalidParameter`] if `threshold` is negative.
+    */
+    pub fn new(threshold: i32) -> Result<HysteresisFilter, FilterError> {
+        if threshold < 0 {
+            return Err(FilterError::InvalidParameter("threshold"));
+        }
+
+        Ok(HysteresisFilter {
+            threshold,
+            last: None,
+        })
+    }
+}
+
+impl AxisFilter for HysteresisFilter {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        let output = match self.last {
+            Some(last)
+                if value.abs_diff(last) <= self.threshold as u32
+                    && value != VJGeneral::MIN_AXIS_VALUE
+                    && value != VJGeneral::MAX_AXIS_VALUE =>
+            {
+                last
+            }
+            _ => value,
+        };
+
+        self.last = Some(output);
+        output
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/**
+    Applies several filters one after the other. The final value is clamped to the vJoy axis
+    range.
+*/
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn AxisFilter>>,
+}
+
+impl FilterChain {
+    pub fn new() -> FilterChain {
+        FilterChain::default()
+    }
+
+    /// Appends a filter at the end of the chain.
+    pub fn with<F: AxisFilter + 'static>(mut self, filter: F) -> FilterChain {
+        self.push(filter);
+        self
+    }
+
+    /// Appends a filter at the end of the chain.
+    pub fn push<F: AxisFilter + 'static>(&mut self, filter: F) {
+        self.filters.push(Box::new(filter));
+    }
+
+    /// Appends a boxed filter at the end of the chain.
+    pub fn push_boxed(&mut self, filter: Box<dyn AxisFilter>) {
+        self.filters.push(filter);
+    }
+}
+
+impl AxisFilter for FilterChain {
+    fn filter(&mut self, value: i32, timestamp: Duration) -> i32 {
+        self.filters
+            .iter_mut()
+            .fold(value, |value, filter| filter.filter(value, timestamp))
+            .clamp(VJGeneral::MIN_AXIS_VALUE, VJGeneral::MAX_AXIS_VALUE)
+    }
+
+    fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+    }
+}
+
+/**
+    Holds one [`FilterChain`] per axis and applies it before the value is written into a
+    [`VJDPosition`].
+*/
+#[derive(Default)]
+pub struct AxisFilters {
+    chains: HashMap<VJDAxis, FilterChain>,
+}
+
+impl AxisFilters {
+    pub fn new() -> AxisFilters {
+        AxisFilters::default()
+    }
+
+    /// Sets the chain of the given axis, replacing the previous one.
+    pub fn set_chain(&mut self, axis: VJDAxis, chain: FilterChain) {
+        self.chains.insert(axis, chain);
+    }
+
+    /// Removes the chain of the given axis. Values of this axis will pass through unchanged.
+    pub fn remove_chain(&mut self, axis: VJDAxis) -> Option<FilterChain> {
+        self.chains.remove(&axis)
+    }
+
+    /**
+        Filters `value` with the chain of `axis` and writes the result into `position`.
+
+        Returns the value written.
+    */
+    pub fn set_axis(
+        &mut self,
+        position: &mut VJDPosition,
+        axis: VJDAxis,
+        value: i32,
+        timestamp: Duration,
+    ) -> i32 {
+        let value = match self.chains.get_mut(&axis) {
+            Some(chain) => chain.filter(value, timestamp),
+            None => value,
+        };
+
+        position.set_axis(axis, value);
+        value
+    }
+
+    /// Resets the chains of every axis.
+    pub fn reset(&mut self) {
+        for chain in self.chains.values_mut() {
+            chain.reset();
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Multiplexes inputs through shift layers, so a few physical controls can reach many vJoy
+//! buttons and axes.
+//!
+//! A [`LayerMap`] routes named button and axis inputs to targets depending on the active layer.
+//! Modifier inputs switch layers, either while they are held or each time they are pressed.
+//! An input not mapped in the active layer falls back to its mapping in the base layer.
+//!
+//! A button keeps the target it was pressed on until it is released, even if the layer changes
+//! meanwhile: its release always clears the bit it set, and no button is left stuck. An axis
+//! moves to the target of the active layer on its next update, the previous target keeping its
+//! last value.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(map: &LayerMap, device: VJDevice, button: VJDButton) -> bool {
+        map.position(device).unwrap().get_buttons().contains(button)
+    }
+
+    fn map() -> (LayerMap, LayerId) {
+        let mut map = LayerMap::new();
+        let shift = map.add_layer("shift");
+
+        map.add_modifier("shift", shift, LayerMode::Hold).unwrap();
+        map.map_button(LayerId::BASE, "fire", VJDevice::D1, VJDButton::B1)
+            .unwrap();
+        map.map_button(LayerId::BASE, "gear", VJDevice::D1, VJDButton::B2)
+            .unwrap();
+        map.map_button(shift, "fire", VJDevice::D2, VJDButton::B65)
+            .unwrap();
+        map.map_axis(LayerId::BASE, "wheel", VJDevice::D1, VJDAxis::Z)
+            .unwrap();
+        map.map_axis(shift, "wheel", VJDevice::D1, VJDAxis::Slider1)
+            .unwrap();
+
+        (map, shift)
+    }
+
+    #[test]
+    fn hold_modifier_switches_targets() {
+        let (mut map, shift) = map();
+        assert_eq!(vec![VJDevice::D1, VJDevice::D2], map.devices());
+
+        map.set_button("fire", true);
+        map.set_button("fire", false);
+        map.set_axis("wheel", 1000);
+        assert_eq!(LayerId::BASE, map.active_layer());
+        assert_eq!(
+            1000,
+            map.position(VJDevice::D1)
+                .unwrap()
+                .get_axis(VJDAxis::Z)
+                .get()
+        );
+
+        assert!(map.set_button("shift", true));
+        assert_eq!(shift, map.active_layer());
+        assert_eq!("shift", map.layer_name(shift));
+
+        map.set_button("fire", true);
+        map.set_axis("wheel", 2000);
+        assert!(pressed(&map, VJDevice::D2, VJDButton::B65));
+        assert!(!pressed(&map, VJDevice::D1, VJDButton::B1));
+
+        let d1 = map.position(VJDevice::D1).unwrap();
+        assert_eq!(1000, d1.get_axis(VJDAxis::Z).get());
+        assert_eq!(2000, d1.get_axis(VJDAxis::Slider1).get());
+
+        // Not mapped in the shift layer: falls back to the base layer.
+        map.set_button("gear", true);
+        assert!(pressed(&map, VJDevice::D1, VJDButton::B2));
+
+        map.set_button("shift", false);
+        assert_eq!(LayerId::BASE, map.active_layer());
+    }
+
+    #[test]
+    fn layer_change_while_held_releases_original_target() {
+        let (mut map, _) = map();
+
+        map.set_button("fire", true);
+        map.set_button("shift", true);
+        map.set_button("fire", false);
+
+        assert!(!pressed(&map, VJDevice::D1, VJDButton::B1));
+        assert!(map.position(VJDevice::D2).unwrap().get_buttons().is_empty());
+
+        // Pressed on the shift layer, released on the base layer.
+        map.set_button("fire", true);
+        map.set_button("shift", false);
+        assert!(pressed(&map, VJDevice::D2, VJDButton::B65));
+        map.set_button("fire", false);
+        assert!(!pressed(&map, VJDevice::D2, VJDButton::B65));
+    }
+
+    #[test]
+    fn toggle_modifiers_and_stacking() {
+        let (mut map, shift) = map();
+        let mode = map.add_layer("mode");
+        map.add_modifier("mode", mode, LayerMode::Toggle).unwrap();
+
+        map.set_button("mode", true);
+        map.set_button("mode", false);
+        assert_eq!(mode, map.active_layer());
+
+        // A held layer takes precedence over the toggled one.
+        map.set_button("shift", true);
+        assert_eq!(shift, map.active_layer());
+        map.set_button("shift", false);
+        assert_eq!(mode, map.active_layer());
+
+        map.set_button("mode", true);
+        assert_eq!(LayerId::BASE, map.active_layer());
+
+        map.reset();
+        assert_eq!(LayerId::BASE, map.active_layer());
+    }
+
+    #[test]
+    fn shared_target_stays_pressed_until_last_release() {
+        let (mut map, _) = map();
+        map.map_button(LayerId::BASE, "trigger", VJDevice::D1, VJDButton::B1)
+            .unwrap();
+
+        map.set_button("fire", true);
+        map.set_button("trigger", true);
+        map.set_button("fire", false);
+        assert!(pressed(&map, VJDevice::D1, VJDButton::B1));
+        map.set_button("trigger", false);
+        assert!(!pressed(&map, VJDevice::D1, VJDButton::B1));
+    }
+
+    #[test]
+    fn invalid_mappings() {
+        let (mut map, shift) = map();
+
+        assert!(!map.set_button("brake", true));
+        assert!(!map.set_axis("brake", 0));
+        assert!(!map.set_axis("wheel", 40000));
+        assert_eq!(
+            Err(LayerError::UnknownLayer(LayerId(7))),
+            map.map_button(LayerId(7), "x", VJDevice::D1, VJDButton::B1)
+        );
+        assert_eq!(
+            Err(LayerError::ModifierInput("shift".to_string())),
+            map.map_button(shift, "shift", VJDevice::D1, VJDButton::B1)
+        );
+        assert_eq!(
+            Err(LayerError::ModifierInput("fire".to_string())),
+            map.add_modifier("fire", shift, LayerMode::Toggle)
+        );
+        assert_eq!(
+            Err(LayerError::BaseModifier),
+            map.add_modifier("base", LayerId::BASE, LayerMode::Hold)
+        );
+    }
+}
+
+use crate::vjoy_base::device::button::VJDButtonSet;
+use crate::vjoy_base::device::{VJDAxis, VJDButton, VJDPosition, VJDevice};
+use crate::vjoy_base::driver::VJGeneral;
+use std::collections::HashMap;
+
+/**
+    Describes an error state when configuring a [`LayerMap`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LayerError {
+    /// The layer was not added to the map.
+    UnknownLayer(LayerId),
+
+    /// An input cannot be both a modifier and mapped to a target. The input is provided.
+    ModifierInput(String),
+
+    /// The base layer is active when no modifier is, it cannot have a modifier.
+    BaseModifier,
+}
+
+/**
+    Identifies a layer of a [`LayerMap`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LayerId(usize);
+
+impl LayerId {
+    /// The layer active when no modifier is.
+    pub const BASE: LayerId = LayerId(0);
+
+    /// Returns the index of the layer, in the order they were added.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/**
+    Describes how a modifier input activates its layer.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LayerMode {
+    /// The layer is active while the modifier is held.
+    Hold,
+
+    /// Each press of the modifier activates or deactivates the layer.
+    Toggle,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Layer {
+    name: String,
+    buttons: HashMap<String, (VJDevice, VJDButton)>,
+    axes: HashMap<String, (VJDevice, VJDAxis)>,
+}
+
+#[derive(Debug, Clone)]
+struct Modifier {
+    layer: LayerId,
+    mode: LayerMode,
+    pressed: bool,
+}
+
+/**
+    Routes button and axis inputs to the targets of the active layer, and holds the resulting
+    position of each targeted device.
+
+    The active layer is the one of the last held [`LayerMode::Hold`] modifier, otherwise the one
+    toggled by a [`LayerMode::Toggle`] modifier, otherwise the base layer.
+*/
+#[derive(Debug, Clone)]
+pub struct LayerMap {
+    layers: Vec<Layer>,
+    modifiers: HashMap<String, Modifier>,
+    held_layers: Vec<LayerId>,
+    toggled: Option<LayerId>,
+    held_buttons: HashMap<String, (VJDevice, VJDButton)>,
+    positions: Vec<VJDPosition>,
+}
+
+impl Default for LayerMap {
+    fn default() -> Self {
+        LayerMap::new()
+    }
+}
+
+impl LayerMap {
+    /// Returns a map with only the base layer, named "base".
+    pub fn new() -> LayerMap {
+        LayerMap {
+            layers: vec![Layer {
+                name: "base".to_string(),
+                ..Layer::default()
+            }],
+            modifiers: HashMap::new(),
+            held_layers: Vec::new(),
+            toggled: None,
+            held_buttons: HashMap::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn add_layer(&mut self, name: &str) -> LayerId {
+        self.layers.push(Layer {
+            name: name.to_string(),
+            ..Layer::default()
+        });
+
+        LayerId(self.layers.len() - 1)
+    }
+
+    /// Returns the name of a layer. Panics if the layer was not added to the map.
+    pub fn layer_name(&self, layer: LayerId) -> &str {
+        &self.layers[layer.0].name
+    }
+
+    fn layer_mut(&mut self, layer: LayerId) -> Result<&mut Layer, LayerError> {
+        self.layers
+            .get_mut(layer.0)
+            .ok_or(LayerError::UnknownLayer(layer))
+    }
+
+    /**
+        Makes a button input switch to a layer, or returns [`LayerError`] if the layer is
+        unknown or is the base layer, or if the input is mapped to a target.
+    */
+    pub fn add_modifier(
+        &mut self,
+        input: &str,
+        layer: LayerId,
+        mode: LayerMode,
+    ) -> Result<(), LayerError> {
+        self.layer_mut(layer)?;
+
+        if layer == LayerId::BASE {
+            return Err(LayerError::BaseModifier);
+        }
+        if self
+            .layers
+            .iter()
+            .any(|other| other.buttons.contains_key(input))
+        {
+            return Err(LayerError::ModifierInput(input.to_string()));
+        }
+
+        let modifier = Modifier {
+            layer,
+            mode,
+            pressed: false,
+        };
+        self.modifiers.insert(input.to_string(), modifier);
+
+        Ok(())
+    }
+
+    /**
+        Routes a button input to a button of a device when a layer is active, replacing the
+        previous target of the input in this layer. Returns [`LayerError`] if the layer is
+        unknown or if the input is a modifier.
+    */
+    pub fn map_button(
+        &mut self,
+        layer: LayerId,
+        input: &str,
+        device: VJDevice,
+        button: VJDButton,
+    ) -> Result<(), LayerError> {
+        if self.modifiers.contains_key(input) {
+            return Err(LayerError::ModifierInput(input.to_string()));
+        }
+
+        self.layer_mut(layer)?
+            .buttons
+            .insert(input.to_string(), (device, button));
+        self.position_mut(device);
+
+        Ok(())
+    }
+
+    /**
+        Routes an axis input to an axis of a device when a layer is active, replacing the
+        previous target of the input in this layer. Returns [`LayerError::UnknownLayer`] if the
+        layer is unknown.
+    */
+    pub fn map_axis(
+        &mut self,
+        layer: LayerId,
+        input: &str,
+        device: VJDevice,
+        axis: VJDAxis,
+    ) -> Result<(), LayerError> {
+        self.layer_mut(layer)?
+            .axes
+            .insert(input.to_string(), (device, axis));
+        self.position_mut(device);
+
+        Ok(())
+    }
+
+    /// Returns the layer inputs are currently routed through.
+    pub fn active_layer(&self) -> LayerId {
+        self.held_layers
+            .last()
+            .copied()
+            .or(self.toggled)
+            .unwrap_or(LayerId::BASE)
+    }
+
+    /**
+        Sets the state of a button input, either a modifier or an input mapped in any layer.
+        Returns `false` if the input is neither.
+    */
+    pub fn set_button(&mut self, input: &str, pressed: bool) -> bool {
+        if let Some(modifier) = self.modifiers.get_mut(input) {
+            let was_pressed = std::mem::replace(&mut modifier.pressed, pressed);
+            let (layer, mode) = (modifier.layer, modifier.mode);
+
+            match mode {
+                LayerMode::Hold if pressed && !was_pressed => self.held_layers.push(layer),
+                LayerMode::Hold if !pressed => {
+                    if let Some(index) = self.held_layers.iter().rposition(|&held| held == layer) {
+                        self.held_layers.remove(index);
+                    }
+                }
+                LayerMode::Toggle if pressed && !was_pressed => {
+                    self.toggled = if self.toggled == Some(layer) {
+                        None
+                    } else {
+                        Some(layer)
+                    };
+                }
+                _ => {}
+            }
+
+            return true;
+        }
+
+        if pressed {
+            if self.held_buttons.contains_key(input) {
+                return true;
+            }
+
+            let (device, button) = match self.route(input, |layer| &layer.buttons) {
+                Some(&target) => target,
+                None => {
+                    return self
+                        .layers
+                        .iter()
+                        .any(|layer| layer.buttons.contains_key(input))
+                }
+            };
+
+            self.held_buttons
+                .insert(input.to_string(), (device, button));
+            self.position_mut(device).set_button_pressed(button as u32);
+        } else {
+            let target = match self.held_buttons.remove(input) {
+                Some(target) => target,
+                None => {
+                    return self
+                        .layers
+                        .iter()
+                        .any(|layer| layer.buttons.contains_key(input))
+                }
+            };
+
+            // Another held input may still press the same button.
+            if !self.held_buttons.values().any(|&other| other == target) {
+                self.position_mut(target.0)
+                    .set_button_released(target.1 as u32);
+            }
+        }
+
+        true
+    }
+
+    /**
+        Sets the value of an axis input on its target in the active layer. Returns `false` if
+        the input has no target in the active nor the base layer, or if the value is out of the
+        vJoy range.
+    */
+    pub fn set_axis(&mut self, input: &str, value: i32) -> bool {
+        if !(VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE).contains(&value) {
+            return false;
+        }
+
+        match self.route(input, |layer| &layer.axes).copied() {
+            Some((device, axis)) => {
+                self.position_mut(device).set_axis(axis, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the target of an input in the active layer, or in the base layer.
+    fn route<T>(&self, input: &str, targets: impl Fn(&Layer) -> &HashMap<String, T>) -> Option<&T> {
+        targets(&self.layers[self.active_layer().0])
+            .get(input)
+            .or_else(|| targets(&self.layers[LayerId::BASE.0]).get(input))
+    }
+
+    fn position_mut(&mut self, device: VJDevice) -> &mut VJDPosition {
+        let index = match self
+            .positions
+            .binary_search_by_key(&(device as u32), |position| position.get_device() as u32)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.positions.insert(index, VJDPosition::new(device));
+                index
+            }
+        };
+
+        &mut self.positions[index]
+    }
+
+    /// Returns every device targeted by a layer, ordered by device.
+    pub fn devices(&self) -> Vec<VJDevice> {
+        self.positions.iter().map(VJDPosition::get_device).collect()
+    }
+
+    /// Returns the position of every device targeted by a layer, ordered by device.
+    pub fn positions(&self) -> &[VJDPosition] {
+        &self.positions
+    }
+
+    /// Returns the position of a device, or [`None`] if the device is not targeted by a layer.
+    pub fn position(&self, device: VJDevice) -> Option<&VJDPosition> {
+        self.positions
+            .iter()
+            .find(|position| position.get_device() == device)
+    }
+
+    /// Releases every button and modifier and goes back to the base layer. Axes keep their value.
+    pub fn reset(&mut self) {
+        for position in &mut self.positions {
+            position.set_buttons(&VJDButtonSet::new());
+        }
+        for modifier in self.modifiers.values_mut() {
+            modifier.pressed = false;
+        }
+
+        self.held_layers.clear();
+        self.toggled = None;
+        self.held_buttons.clear();
+    }
+}
@@ -0,0 +1,884 @@
+//! Terminal UI showing the live state of vJoy devices: a gauge for each axis, a grid of the
+//! buttons and a compass for each POV.
+//!
+//! vJoy cannot read positions back, so they come from a [`PositionSource`]:
+//! - [`FeedSource`] shows what this process feeds, through [`VJDFeedObserver`].
+//! - `SDL2Source`, with the `sdl2` feature, reads the devices back as SDL2 joysticks, whoever
+//!   feeds them.
+//!
+//! In interactive mode, the selected control can be changed with the keyboard: the updates are
+//! sent to the device, which is acquired first if it is free.
+//!
+//! | Key            | Action                                              |
+//! |----------------|-----------------------------------------------------|
+//! | Tab, Shift+Tab | Next or previous device.                            |
+//! | Up, Down       | Select a control (interactive).                     |
+//! | Left, Right    | Move an axis by the step, turn a POV (interactive). |
+//! | Space, Enter   | Toggle a button (interactive).                      |
+//! | `[`, `]`       | Divide or multiply the axis step by 4.              |
+//! | `c`            | Center the selected control (interactive).          |
+//! | `q`, Esc       | Quit.                                               |
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn app(interactive: bool) -> MonitorApp {
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Rz])
+            .buttons(20)
+            .disc_povs(1)
+            .cont_povs(1);
+        let mut app = MonitorApp::new(vec![(VJDevice::D2, config)]);
+        app.set_interactive(interactive);
+        app
+    }
+
+    fn screen(app: &MonitorApp) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(90, 24)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let width = buffer.area.width as usize;
+        let symbols: Vec<_> = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        symbols
+            .chunks(width)
+            .map(|line| line.concat())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn compass_points_to_the_direction() {
+        assert_eq!(["· · ·", "· ● ·", "· · ·"], compass(None));
+        assert_eq!(["· ● ·", "· + ·", "· · ·"], compass(Some(0)));
+        assert_eq!(["· · ·", "· + ●", "· · ·"], compass(Some(2)));
+        assert_eq!(["● · ·", "· + ·", "· · ·"], compass(Some(7)));
+
+        assert_eq!(None, cont_direction(VJDPovCont::NEUTRAL));
+        assert_eq!(Some(0), cont_direction(VJDPovCont::new(35000).unwrap()));
+        assert_eq!(Some(1), cont_direction(VJDPovCont::new(4500).unwrap()));
+        assert_eq!(Some(4), cont_direction(VJDPovCont::new(18000).unwrap()));
+    }
+
+    #[test]
+    fn keys_poke_the_selected_control() {
+        let mut app = app(true);
+
+        // X is selected first, the default step is a sixteenth of the range.
+        assert_eq!(
+            vec![VJDFeedEvent::Axis(
+                VJDevice::D2,
+                VJDAxis::X,
+                VJDAxisRaw::saturating(18432)
+            )],
+            app.handle_key(KeyCode::Right)
+        );
+        app.handle_key(KeyCode::Char('['));
+        assert_eq!(
+            vec![VJDFeedEvent::Axis(
+                VJDevice::D2,
+                VJDAxis::X,
+                VJDAxisRaw::saturating(17920)
+            )],
+            app.handle_key(KeyCode::Left)
+        );
+
+        // Rz, then the first button.
+        app.handle_key(KeyCode::Down);
+        app.handle_key(KeyCode::Down);
+        assert_eq!(Some(Control::Button(VJDButton::B1)), app.selected_control());
+        let press = app.handle_key(KeyCode::Char(' '));
+        assert_eq!(
+            vec![VJDFeedEvent::Button(
+                VJDevice::D2,
+                VJDButton::B1,
+                VJDButtonState::Pressed
+            )],
+            press
+        );
+        assert_eq!(
+            VJDButtonState::Released,
+            match app.handle_key(KeyCode::Enter)[0] {
+                VJDFeedEvent::Button(_, _, state) => state,
+                _ => unreachable!(),
+            }
+        );
+
+        // Up wraps around to the continuous POV, turned clockwise by an eighth.
+        app.handle_key(KeyCode::Up);
+        app.handle_key(KeyCode::Up);
+        app.handle_key(KeyCode::Up);
+        assert_eq!(
+            Some(Control::ContPov(VJDPovNumber::Pov1)),
+            app.selected_control()
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::ContPov(
+                VJDevice::D2,
+                VJDPovNumber::Pov1,
+                VJDPovCont::new(0).unwrap()
+            )],
+            app.handle_key(KeyCode::Right)
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::ContPov(
+                VJDevice::D2,
+                VJDPovNumber::Pov1,
+                VJDPovCont::new(31500).unwrap()
+            )],
+            app.handle_key(KeyCode::Left)
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::ContPov(
+                VJDevice::D2,
+                VJDPovNumber::Pov1,
+                VJDPovCont::NEUTRAL
+            )],
+            app.handle_key(KeyCode::Char('c'))
+        );
+
+        assert!(!app.should_quit());
+        app.handle_key(KeyCode::Char('q'));
+        assert!(app.should_quit());
+    }
+
+    #[test]
+    fn monitoring_only_ignores_pokes() {
+        let mut app = app(false);
+
+        assert!(app.handle_key(KeyCode::Right).is_empty());
+        assert!(app.handle_key(KeyCode::Char(' ')).is_empty());
+        assert_eq!(None, app.selected_control());
+    }
+
+    #[test]
+    fn draws_the_device() {
+        // Discrete and continuous POVs share their storage: devices only have one type.
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Rz])
+            .buttons(20)
+            .disc_povs(2);
+        let mut app = MonitorApp::new(vec![(VJDevice::D2, config)]);
+        let mut position = VJDPosition::new(VJDevice::D2);
+        position.set_axis(VJDAxis::Rz, 32767);
+        position.set_button(12, VJDButtonState::Pressed);
+        position.set_disc_pov(VJDPovNumber::Pov1, VJDPovDisc::East);
+        app.update(position);
+
+        let screen = screen(&app);
+        assert!(screen.contains("D2"), "{}", screen);
+        assert!(screen.contains("X 16384"), "{}", screen);
+        assert!(screen.contains("Rz 32767"), "{}", screen);
+        assert!(screen.contains("POV1 East"), "{}", screen);
+        assert!(screen.contains("POV2 Neutral"), "{}", screen);
+        assert!(screen.contains("  20"), "{}", screen);
+        assert!(!screen.contains("  21"), "{}", screen);
+    }
+
+    #[test]
+    fn extra_povs_are_ignored() {
+        // Configurations are not validated: only the 4 POVs of a device are shown.
+        let config = VJDConfig::new().buttons(1).disc_povs(6);
+        let app = MonitorApp::new(vec![(VJDevice::D2, config)]);
+
+        let screen = screen(&app);
+        assert!(screen.contains("POV4 Neutral"), "{}", screen);
+        assert!(!screen.contains("POV5"), "{}", screen);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn feed_sources_keep_other_observers() {
+        use crate::test_env::TEST_DEVICE_1;
+        use crate::vjoy_base::device::feeding::VJDSeqFeed;
+        use crate::vjoy_extra::record::{Recorder, RecordingHeader};
+
+        VJDOwnership::acquire(TEST_DEVICE_1);
+
+        let recorder = Recorder::new(RecordingHeader::default());
+        recorder.capture_feeds();
+        let mut source = FeedSource::new();
+
+        assert!(VJDSeqFeed::set_axis(TEST_DEVICE_1, VJDAxis::X, 200));
+        let config = VJDConfig::new().axes(&[VJDAxis::X]);
+        let position = source.read(TEST_DEVICE_1, &config).unwrap();
+        assert_eq!(200, position.get_axis(VJDAxis::X).get());
+
+        drop(source);
+        assert!(VJDSeqFeed::set_axis(TEST_DEVICE_1, VJDAxis::X, 300));
+
+        let recording = recorder.finish();
+        VJDOwnership::relinquish(TEST_DEVICE_1);
+        assert_eq!(2, recording.frames.len());
+    }
+}
+
+use crate::vjoy_base::device::axis::VJDAxisRaw;
+use crate::vjoy_base::device::config::VJDConfig;
+use crate::vjoy_base::device::feeding::{
+    VJDFeedEvent, VJDFeedObserver, VJDFeedObserverId, VJDOwnership,
+};
+use crate::vjoy_base::device::info::VJDInfo;
+use crate::vjoy_base::device::pov::VJDPovCont;
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDButtonState, VJDPosition, VJDPovDisc, VJDPovNumber, VJDStatus, VJDevice,
+};
+use crate::vjoy_base::driver::VJGeneral;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, Paragraph, Tabs};
+use ratatui::Frame;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/**
+    Provides the current positions of vJoy devices.
+*/
+pub trait PositionSource {
+    /// Returns the position of a device with the given controls, or [`None`] if it is unknown.
+    fn read(&mut self, device: VJDevice, config: &VJDConfig) -> Option<VJDPosition>;
+}
+
+/**
+    Tracks the positions fed by this process, through [`VJDFeedObserver`]. Creating a source
+    adds an observer and dropping it removes that observer only, so other observers such as a
+    [`Recorder`](crate::vjoy_extra::record::Recorder) keep working.
+*/
+pub struct FeedSource {
+    positions: Arc<Mutex<HashMap<VJDevice, VJDPosition>>>,
+    observer: VJDFeedObserverId,
+}
+
+impl FeedSource {
+    pub fn new() -> FeedSource {
+        let positions: Arc<Mutex<HashMap<VJDevice, VJDPosition>>> = Arc::default();
+        let observed = positions.clone();
+
+        let observer = VJDFeedObserver::add(move |event: &VJDFeedEvent| {
+            let device = event.get_device();
+            let mut positions = observed
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let position = positions
+                .entry(device)
+                .or_insert_with(|| VJDPosition::new(device));
+            event.apply(position);
+        });
+
+        FeedSource {
+            positions,
+            observer,
+        }
+    }
+}
+
+impl Default for FeedSource {
+    fn default() -> Self {
+        FeedSource::new()
+    }
+}
+
+impl Drop for FeedSource {
+    fn drop(&mut self) {
+        VJDFeedObserver::remove(self.observer);
+    }
+}
+
+impl PositionSource for FeedSource {
+    fn read(&mut self, device: VJDevice, _config: &VJDConfig) -> Option<VJDPosition> {
+        self.positions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&device)
+            .copied()
+    }
+}
+
+/**
+    Reads vJoy devices back as SDL2 joysticks, as matched by
+    [`SDL2Helper::get_vjoys`](crate::vjoy_extra::SDL2Helper::get_vjoys) or
+    [`SDL2Probe`](crate::vjoy_extra::probe::SDL2Probe).
+
+    SDL2 reports the existing axes in the order of their usage, and POVs as hats with eight
+    directions: continuous POVs are read to the nearest eighth of a turn.
+*/
+#[cfg(feature = "sdl2")]
+pub struct SDL2Source {
+    vjoys: crate::vjoy_extra::SDL2Vjoys,
+    event_pump: sdl2::EventPump,
+}
+
+#[cfg(feature = "sdl2")]
+impl SDL2Source {
+    pub fn new(vjoys: crate::vjoy_extra::SDL2Vjoys, event_pump: sdl2::EventPump) -> SDL2Source {
+        SDL2Source { vjoys, event_pump }
+    }
+}
+
+#[cfg(feature = "sdl2")]
+impl PositionSource for SDL2Source {
+    fn read(&mut self, device: VJDevice, config: &VJDConfig) -> Option<VJDPosition> {
+        use crate::vjoy_base::device::axis::SDL2AxisValue;
+        use sdl2::joystick::HatState;
+
+        self.event_pump.pump_events();
+        let joystick = self.vjoys.get(&device)?;
+        let mut position = VJDPosition::new(device);
+
+        let mut axes: Vec<VJDAxis> = config.axes.clone();
+        axes.sort_by_key(|&axis| axis as u32);
+        for (index, &axis) in axes.iter().enumerate() {
+            let value = joystick.axis(index as u32).ok()?;
+            position.set_axis(axis, VJDAxisRaw::from(SDL2AxisValue::new(value)));
+        }
+
+        for index in 0..config.buttons as u32 {
+            if joystick.button(index).ok()? {
+                position.set_button(index + 1, VJDButtonState::Pressed);
+            }
+        }
+
+        let povs = config.disc_povs.max(config.cont_povs) as u32;
+        for (index, pov) in POVS.iter().copied().enumerate().take(povs as usize) {
+            let direction = match joystick.hat(index as u32).ok()? {
+                HatState::Centered => None,
+                HatState::Up => Some(0),
+                HatState::RightUp => Some(1),
+                HatState::Right => Some(2),
+                HatState::RightDown => Some(3),
+                HatState::Down => Some(4),
+                HatState::LeftDown => Some(5),
+                HatState::Left => Some(6),
+                HatState::LeftUp => Some(7),
+            };
+
+            if config.disc_povs > 0 {
+                let direction = match direction {
+                    None => VJDPovDisc::Neutral,
+                    Some(0) | Some(1) => VJDPovDisc::North,
+                    Some(2) | Some(3) => VJDPovDisc::East,
+                    Some(4) | Some(5) => VJDPovDisc::South,
+                    Some(_) => VJDPovDisc::West,
+                };
+                position.set_disc_pov(pov, direction);
+            } else {
+                let value = direction.map_or(VJDPovCont::NEUTRAL, |direction| {
+                    VJDPovCont::from(direction as u32 * 4500)
+                });
+                position.set_cont_pov(pov, value);
+            }
+        }
+
+        Some(position)
+    }
+}
+
+const POVS: [VJDPovNumber; 4] = [
+    VJDPovNumber::Pov1,
+    VJDPovNumber::Pov2,
+    VJDPovNumber::Pov3,
+    VJDPovNumber::Pov4,
+];
+
+/// Number of buttons in a row of the grid.
+const BUTTONS_PER_ROW: u8 = 16;
+
+/**
+    Describes a control of a device which can be selected in interactive mode.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Control {
+    Axis(VJDAxis),
+    Button(VJDButton),
+    DiscPov(VJDPovNumber),
+    ContPov(VJDPovNumber),
+}
+
+/**
+    Holds what a [`MonitorApp`] displays of a device.
+*/
+struct DeviceView {
+    device: VJDevice,
+    config: VJDConfig,
+    position: VJDPosition,
+    status: VJDStatus,
+}
+
+impl DeviceView {
+    fn controls(&self) -> Vec<Control> {
+        let config = &self.config;
+        let buttons = (1..=config.buttons).filter_map(VJDButton::get_from);
+
+        config
+            .axes
+            .iter()
+            .map(|&axis| Control::Axis(axis))
+            .chain(buttons.map(Control::Button))
+            .chain(
+                POVS.iter()
+                    .take(config.disc_povs as usize)
+                    .map(|&pov| Control::DiscPov(pov)),
+            )
+            .chain(
+                POVS.iter()
+                    .take(config.cont_povs as usize)
+                    .map(|&pov| Control::ContPov(pov)),
+            )
+            .collect()
+    }
+}
+
+/**
+    Holds the state of the terminal UI. [`MonitorApp::run`] drives it in a terminal; the state
+    can also be updated and drawn by the caller.
+*/
+pub struct MonitorApp {
+    devices: Vec<DeviceView>,
+    selected_device: usize,
+    selected_control: usize,
+    interactive: bool,
+    step: i32,
+    message: String,
+    quit: bool,
+}
+
+impl MonitorApp {
+    /// Default amount an axis is moved by in interactive mode, a sixteenth of the range.
+    pub const DEFAULT_STEP: i32 = 2048;
+
+    /// Shows the devices with the given controls, at their initial position.
+    pub fn new(devices: Vec<(VJDevice, VJDConfig)>) -> MonitorApp {
+        let devices = devices
+            .into_iter()
+            .map(|(device, config)| DeviceView {
+                device,
+                config,
+                position: VJDPosition::new(device),
+                status: VJDStatus::Unknown,
+            })
+            .collect();
+
+        MonitorApp {
+            devices,
+            selected_device: 0,
+            selected_control: 0,
+            interactive: false,
+            step: MonitorApp::DEFAULT_STEP,
+            message: String::new(),
+            quit: false,
+        }
+    }
+
+    /// Shows every existing device, with the controls read with [`VJDConfig::read`].
+    pub fn existing_devices() -> MonitorApp {
+        let devices = (1..=VJGeneral::MAX_DEVICES)
+            .filter_map(VJDevice::get_from)
+            .filter_map(|device| VJDConfig::read(device).map(|config| (device, config)))
+            .collect();
+
+        MonitorApp::new(devices)
+    }
+
+    /// Allows changing the selected control with the keyboard.
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Returns the selected control, or [`None`] if not in interactive mode.
+    pub fn selected_control(&self) -> Option<Control> {
+        if !self.interactive {
+            return None;
+        }
+
+        self.devices
+            .get(self.selected_device)?
+            .controls()
+            .get(self.selected_control)
+            .copied()
+    }
+
+    /// Shows a new position of a device.
+    pub fn update(&mut self, position: VJDPosition) {
+        if let Some(view) = self
+            .devices
+            .iter_mut()
+            .find(|view| view.device == position.get_device())
+        {
+            view.position = position;
+        }
+    }
+
+    /// Reads the positions and statuses of the devices. Unknown positions are left as they are.
+    pub fn refresh<S: PositionSource + ?Sized>(&mut self, source: &mut S) {
+        for view in &mut self.devices {
+            view.status = VJDInfo::get_status(view.device);
+            if let Some(position) = source.read(view.device, &view.config) {
+                view.position = position;
+            }
+        }
+    }
+
+    /**
+        Handles a key and returns the updates to send to the selected device. The updates are
+        already shown.
+    */
+    pub fn handle_key(&mut self, key: KeyCode) -> Vec<VJDFeedEvent> {
+        let total_devices = self.devices.len().max(1);
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab => {
+                self.selected_device = (self.selected_device + 1) % total_devices;
+                self.selected_control = 0;
+            }
+            KeyCode::BackTab => {
+                self.selected_device = (self.selected_device + total_devices - 1) % total_devices;
+                self.selected_control = 0;
+            }
+            KeyCode::Char('[') => self.step = (self.step / 4).max(1),
+            KeyCode::Char(']') => self.step = (self.step * 4).min(VJGeneral::MAX_AXIS_VALUE),
+            _ => return self.poke(key),
+        }
+
+        Vec::new()
+    }
+
+    /// Handles the keys changing the selected control.
+    fn poke(&mut self, key: KeyCode) -> Vec<VJDFeedEvent> {
+        let control = match self.selected_control() {
+            Some(control) => control,
+            None => return Vec::new(),
+        };
+        let view = &mut self.devices[self.selected_device];
+        let total_controls = view.controls().len();
+        let device = view.device;
+        let position = &view.position;
+
+        let event = match (key, control) {
+            (KeyCode::Down, _) => {
+                self.selected_control = (self.selected_control + 1) % total_controls;
+                None
+            }
+            (KeyCode::Up, _) => {
+                self.selected_control =
+                    (self.selected_control + total_controls - 1) % total_controls;
+                None
+            }
+            (KeyCode::Left, Control::Axis(axis)) | (KeyCode::Right, Control::Axis(axis)) => {
+                let step = if key == KeyCode::Left {
+                    -self.step
+                } else {
+                    self.step
+                };
+                let value = position.get_axis(axis).get() + step;
+                Some(VJDFeedEvent::Axis(
+                    device,
+                    axis,
+                    VJDAxisRaw::saturating(value),
+                ))
+            }
+            (KeyCode::Char('c'), Control::Axis(axis)) => {
+                Some(VJDFeedEvent::Axis(device, axis, VJDAxisRaw::NEUTRAL))
+            }
+            (KeyCode::Char(' '), Control::Button(button))
+            | (KeyCode::Enter, Control::Button(button)) => {
+                let state = match position.get_buttons().get_state(button) {
+                    VJDButtonState::Pressed => VJDButtonState::Released,
+                    VJDButtonState::Released => VJDButtonState::Pressed,
+                };
+                Some(VJDFeedEvent::Button(device, button, state))
+            }
+            (KeyCode::Char('c'), Control::Button(button)) => Some(VJDFeedEvent::Button(
+                device,
+                button,
+                VJDButtonState::Released,
+            )),
+            (KeyCode::Left, Control::DiscPov(pov)) | (KeyCode::Right, Control::DiscPov(pov)) => {
+                let directions = [
+                    VJDPovDisc::North,
+                    VJDPovDisc::East,
+                    VJDPovDisc::South,
+                    VJDPovDisc::West,
+                ];
+                let direction = match position.get_disc_pov(pov) {
+                    VJDPovDisc::Neutral if key == KeyCode::Left => VJDPovDisc::West,
+                    VJDPovDisc::Neutral => VJDPovDisc::North,
+                    current => {
+                        let index = directions.iter().position(|&d| d == current).unwrap_or(0);
+                        let turn = if key == KeyCode::Left { 3 } else { 1 };
+                        directions[(index + turn) % 4]
+                    }
+                };
+                Some(VJDFeedEvent::DiscPov(device, pov, direction))
+            }
+            (KeyCode::Char('c'), Control::DiscPov(pov)) => {
+                Some(VJDFeedEvent::DiscPov(device, pov, VJDPovDisc::Neutral))
+            }
+            (KeyCode::Left, Control::ContPov(pov)) | (KeyCode::Right, Control::ContPov(pov)) => {
+                let eighth = match cont_direction(position.get_cont_pov(pov)) {
+                    None if key == KeyCode::Left => 7,
+                    None => 0,
+                    Some(eighth) if key == KeyCode::Left => (eighth + 7) % 8,
+                    Some(eighth) => (eighth + 1) % 8,
+                };
+                let value = VJDPovCont::from(eighth as u32 * 4500);
+                Some(VJDFeedEvent::ContPov(device, pov, value))
+            }
+            (KeyCode::Char('c'), Control::ContPov(pov)) => {
+                Some(VJDFeedEvent::ContPov(device, pov, VJDPovCont::NEUTRAL))
+            }
+            _ => None,
+        };
+
+        match event {
+            Some(event) => {
+                event.apply(&mut view.position);
+                vec![event]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Draws the selected device.
+    pub fn draw(&self, frame: &mut Frame) {
+        let [tabs_area, device_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let titles: Vec<_> = self
+            .devices
+            .iter()
+            .map(|view| format!("{:?} {:?}", view.device, view.status))
+            .collect();
+        let tabs = Tabs::new(titles)
+            .select(self.selected_device)
+            .block(Block::bordered().title("vJoy devices"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_widget(tabs, tabs_area);
+
+        match self.devices.get(self.selected_device) {
+            Some(view) => self.draw_device(frame, view, device_area),
+            None => frame.render_widget(Paragraph::new("no vJoy device"), device_area),
+        }
+
+        let help = if self.interactive {
+            format!(
+                "Tab device  ↑↓ control  ←→ move (step {})  [ ] step  space toggle  c center  q quit  {}",
+                self.step, self.message
+            )
+        } else {
+            format!("Tab device  q quit  {}", self.message)
+        };
+        frame.render_widget(Paragraph::new(help), help_area);
+    }
+
+    fn draw_device(&self, frame: &mut Frame, view: &DeviceView, area: Rect) {
+        let block = Block::bordered().title(format!("{:?}", view.device));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let config = &view.config;
+        let button_rows = config.buttons.div_ceil(BUTTONS_PER_ROW);
+        let has_povs = config.disc_povs + config.cont_povs > 0;
+
+        let mut constraints = vec![Constraint::Length(1); config.axes.len()];
+        constraints.push(Constraint::Length(button_rows as u16 + 1));
+        constraints.push(Constraint::Length(if has_povs { 4 } else { 0 }));
+        constraints.push(Constraint::Min(0));
+        let areas = Layout::vertical(constraints).split(inner);
+
+        let selected = self.selected_control();
+        let highlight = |control: Control| selected == Some(control);
+
+        for (&axis, &axis_area) in config.axes.iter().zip(areas.iter()) {
+            let value = view.position.get_axis(axis).get();
+            let color = if highlight(Control::Axis(axis)) {
+                Color::Yellow
+            } else {
+                Color::Cyan
+            };
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(value as f64 / VJGeneral::MAX_AXIS_VALUE as f64)
+                .label(format!("{:?} {}", axis, value));
+            frame.render_widget(gauge, axis_area);
+        }
+
+        let buttons = view.position.get_buttons();
+        let rows: Vec<Line> = (0..button_rows)
+            .map(|row| {
+                let first = row * BUTTONS_PER_ROW + 1;
+                let last = (first + BUTTONS_PER_ROW - 1).min(config.buttons);
+                let spans: Vec<Span> = (first..=last)
+                    .filter_map(VJDButton::get_from)
+                    .map(|button| {
+                        let mut style = Style::default();
+                        if buttons.contains(button) {
+                            style = style.add_modifier(Modifier::REVERSED);
+                        }
+                        if highlight(Control::Button(button)) {
+                            style = style.fg(Color::Yellow);
+                        }
+                        Span::styled(format!("{:>4}", button as u8), style)
+                    })
+                    .collect();
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(rows), areas[config.axes.len()]);
+
+        let povs = POVS
+            .iter()
+            .take(config.disc_povs as usize)
+            .map(|&pov| {
+                let direction = view.position.get_disc_pov(pov);
+                let eighth = match direction {
+                    VJDPovDisc::Neutral => None,
+                    direction => Some(direction as usize * 2),
+                };
+                (
+                    Control::DiscPov(pov),
+                    format!("POV{} {:?}", pov as u8, direction),
+                    eighth,
+                )
+            })
+            .chain(POVS.iter().take(config.cont_povs as usize).map(|&pov| {
+                let value = view.position.get_cont_pov(pov);
+                let label = match value.to_degrees() {
+                    Some(degrees) => format!("CPOV{} {:.2}°", pov as u8, degrees),
+                    None => format!("CPOV{} Neutral", pov as u8),
+                };
+                (Control::ContPov(pov), label, cont_direction(value))
+            }));
+
+        let povs: Vec<_> = povs.collect();
+        let pov_areas = Layout::horizontal(vec![Constraint::Length(16); povs.len()])
+            .split(areas[config.axes.len() + 1]);
+
+        for ((control, label, eighth), &pov_area) in povs.into_iter().zip(pov_areas.iter()) {
+            let style = if highlight(control) {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let mut lines: Vec<Line> = compass(eighth).iter().cloned().map(Line::from).collect();
+            lines.push(Line::styled(label, style));
+            frame.render_widget(Paragraph::new(lines), pov_area);
+        }
+    }
+
+    /**
+        Runs the UI in the terminal until `q` or Esc is pressed, refreshing the devices from
+        `source` every `interval`. In interactive mode, the updates are sent to the devices,
+        which are acquired first if they are free.
+    */
+    pub fn run<S: PositionSource + ?Sized>(
+        &mut self,
+        source: &mut S,
+        interval: Duration,
+    ) -> io::Result<()> {
+        let mut terminal = ratatui::try_init()?;
+        let result = self.run_in(&mut terminal, source, interval);
+        ratatui::restore();
+        result
+    }
+
+    fn run_in<S: PositionSource + ?Sized>(
+        &mut self,
+        terminal: &mut ratatui::DefaultTerminal,
+        source: &mut S,
+        interval: Duration,
+    ) -> io::Result<()> {
+        while !self.quit {
+            self.refresh(source);
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(interval)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                for update in self.handle_key(key.code) {
+                    self.message = send(&update);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends an update, acquiring its device if it is free, and returns what went wrong if any.
+fn send(update: &VJDFeedEvent) -> String {
+    let device = update.get_device();
+
+    if VJDInfo::get_status(device) == VJDStatus::Free && !VJDOwnership::acquire(device) {
+        return format!("cannot acquire {:?}", device);
+    }
+    if !update.send() {
+        return format!("{:?} refused the update", device);
+    }
+
+    String::new()
+}
+
+/// Returns the eighth of a turn nearest to a continuous POV, clockwise from North.
+fn cont_direction(value: VJDPovCont) -> Option<usize> {
+    if value.is_neutral() {
+        return None;
+    }
+
+    Some(((value.get() + 2250) / 4500 % 8) as usize)
+}
+
+/**
+    Returns the rows of a compass pointing to an eighth of a turn clockwise from North, or
+    showing its center when neutral.
+*/
+fn compass(eighth: Option<usize>) -> [String; 3] {
+    // Row and column of each eighth, from North.
+    const CELLS: [(usize, usize); 8] = [
+        (0, 1),
+        (0, 2),
+        (1, 2),
+        (2, 2),
+        (2, 1),
+        (2, 0),
+        (1, 0),
+        (0, 0),
+    ];
+    let active = match eighth {
+        Some(eighth) => CELLS[eighth % 8],
+        None => (1, 1),
+    };
+
+    let row = |row: usize| -> String {
+        (0..3)
+            .map(|column| match (row, column) {
+                cell if cell == active => "●",
+                (1, 1) => "+",
+                _ => "·",
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    [row(0), row(1), row(2)]
+}
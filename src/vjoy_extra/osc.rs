@@ -0,0 +1,1021 @@
+//! Feeds vJoy devices with Open Sound Control (OSC) messages received over UDP, as sent by
+//! TouchOSC and similar control surfaces.
+//!
+//! An [`OscRouter`] turns each message into updates of device controls, which an
+//! [`OscAdapter`] sends with [`VJDFeedEvent::send`].
+//!
+//! # Addresses
+//!
+//! Without configuration, the addresses name the controls directly:
+//!
+//! | Address                     | Control              | Default range |
+//! |-----------------------------|----------------------|---------------|
+//! | `/vjoy/<device>/axis/<axis>` | Axis, by name (`X`, `Rx`, `Slider1`...) | 0 to 1 |
+//! | `/vjoy/<device>/button/<n>` | Button, 1 to 128      | 0 to 1        |
+//! | `/vjoy/<device>/pov/<n>`    | Discrete POV, 1 to 4  | 0 to 4        |
+//! | `/vjoy/<device>/cpov/<n>`   | Continuous POV, 1 to 4 | 0 to 360     |
+//!
+//! [`OscMapping`]s route other addresses, for instance `/1/fader1`, to controls with their own
+//! range. When the address of a message matches a mapping, the default addresses are not
+//! looked at. The address of a message may be an OSC pattern (`*`, `?`, `[a-z]`, `{a,b}`)
+//! matching several mappings.
+//!
+//! # Values
+//!
+//! The first argument of a message, an integer, a float or a boolean, is scaled from the range
+//! of the control to:
+//! - the whole range of an axis, clamped;
+//! - a button, pressed from the middle of the range;
+//! - a discrete POV, whose range is split in North, East, South and West, a value below the
+//!   range being neutral;
+//! - a continuous POV, whose range is a full turn, a value below the range being neutral.
+//!
+//! `true` is the top of the range and `false` its bottom. Messages without such an argument
+//! are ignored, and bundles are applied at once, whatever their time tag.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    fn message(address: &str, args: Vec<OscArg>) -> OscMessage {
+        OscMessage {
+            address: address.to_string(),
+            args,
+        }
+    }
+
+    fn route(router: &OscRouter, address: &str, arg: OscArg) -> Vec<VJDFeedEvent> {
+        router.route(&message(address, vec![arg]))
+    }
+
+    #[test]
+    fn parse_messages_and_bundles() {
+        let fader = message(
+            "/1/fader1",
+            vec![
+                OscArg::Float(0.25),
+                OscArg::Int(-3),
+                OscArg::String("abc".to_string()),
+                OscArg::Blob(vec![1, 2, 3, 4, 5]),
+                OscArg::Long(1 << 40),
+                OscArg::Double(0.5),
+                OscArg::True,
+                OscArg::False,
+                OscArg::Nil,
+                OscArg::Impulse,
+            ],
+        );
+        let bytes = fader.encode();
+        assert_eq!(0, bytes.len() % 4);
+        assert_eq!(b"/1/fader1\0\0\0,fisbhdTFNI\0", &bytes[..24]);
+        assert_eq!(Ok(vec![fader.clone()]), parse_packet(&bytes));
+
+        // A bundle holding the message and a bundle holding another message.
+        let toggle = message("/1/toggle1", vec![OscArg::Int(1)]);
+        let bundle = |elements: &[Vec<u8>]| {
+            let mut bytes = b"#bundle\0".to_vec();
+            bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+            for element in elements {
+                bytes.extend_from_slice(&(element.len() as i32).to_be_bytes());
+                bytes.extend_from_slice(element);
+            }
+            bytes
+        };
+        let packet = bundle(&[fader.encode(), bundle(&[toggle.encode()])]);
+        assert_eq!(Ok(vec![fader, toggle.clone()]), parse_packet(&packet));
+
+        let valid = toggle.encode();
+        assert_eq!(
+            Err(OscError::Truncated),
+            parse_packet(&valid[..valid.len() - 2])
+        );
+        assert_eq!(
+            Err(OscError::InvalidAddress),
+            parse_packet(b"x\0\0\0,\0\0\0")
+        );
+        assert_eq!(
+            Err(OscError::MissingTypeTags),
+            parse_packet(b"/a\0\0i\0\0\0")
+        );
+        assert_eq!(Err(OscError::InvalidString), parse_packet(b"/abc"));
+        assert_eq!(
+            Err(OscError::UnsupportedType('c')),
+            parse_packet(b"/a\0\0,c\0\0\0\0\0\x41")
+        );
+        let mut trailing = valid.clone();
+        trailing.extend_from_slice(&[0; 4]);
+        assert_eq!(Err(OscError::TrailingBytes), parse_packet(&trailing));
+        let mut oversized = bundle(&[valid]);
+        oversized[19] += 4;
+        assert_eq!(Err(OscError::InvalidBundle), parse_packet(&oversized));
+    }
+
+    #[test]
+    fn match_patterns() {
+        assert!(pattern_matches("/vjoy/1/button/3", "/vjoy/1/button/3"));
+        assert!(pattern_matches("/1/fader*", "/1/fader12"));
+        assert!(pattern_matches("/*/fader?", "/2/fader1"));
+        assert!(pattern_matches("/1/[a-f]ader[!2]", "/1/fader1"));
+        assert!(pattern_matches("/1/{push,toggle}1", "/1/toggle1"));
+        assert!(pattern_matches("/1/*er*1", "/1/fader1"));
+
+        assert!(!pattern_matches("/1/fader*", "/1/fader1/z"));
+        assert!(!pattern_matches("/*", "/1/fader1"));
+        assert!(!pattern_matches("/1/[!f]ader1", "/1/fader1"));
+        assert!(!pattern_matches("/1/{push,toggle}1", "/1/rotary1"));
+        assert!(!pattern_matches("/1/[a-f", "/1/a"));
+        assert!(!pattern_matches(
+            &format!("/{}b", "*a".repeat(40)),
+            &format!("/{}", "a".repeat(80))
+        ));
+    }
+
+    #[test]
+    fn default_addresses() {
+        let router = OscRouter::new();
+
+        assert_eq!(
+            vec![VJDFeedEvent::Axis(
+                VJDevice::D9,
+                VJDAxis::X,
+                VJDAxisRaw::MAX
+            )],
+            route(&router, "/vjoy/9/axis/X", OscArg::Float(1.0))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::Axis(
+                VJDevice::D9,
+                VJDAxis::Slider1,
+                VJDAxisRaw::MIN
+            )],
+            route(&router, "/vjoy/9/axis/slider1", OscArg::Int(-5))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::Button(
+                VJDevice::D9,
+                VJDButton::B3,
+                VJDButtonState::Pressed
+            )],
+            route(&router, "/vjoy/9/button/3", OscArg::True)
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::Button(
+                VJDevice::D1,
+                VJDButton::B3,
+                VJDButtonState::Released
+            )],
+            route(&router, "/vjoy/1/button/3", OscArg::Float(0.4))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::DiscPov(
+                VJDevice::D1,
+                VJDPovNumber::Pov2,
+                VJDPovDisc::South
+            )],
+            route(&router, "/vjoy/1/pov/2", OscArg::Int(2))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::DiscPov(
+                VJDevice::D1,
+                VJDPovNumber::Pov2,
+                VJDPovDisc::Neutral
+            )],
+            route(&router, "/vjoy/1/pov/2", OscArg::Int(-1))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::ContPov(
+                VJDevice::D1,
+                VJDPovNumber::Pov1,
+                VJDPovCont::new(9000).unwrap()
+            )],
+            route(&router, "/vjoy/1/cpov/1", OscArg::Double(90.0))
+        );
+
+        for address in &[
+            "/vjoy/17/axis/X",
+            "/vjoy/1/axis/W",
+            "/vjoy/1/button/129",
+            "/vjoy/1/pov/5",
+            "/vjoy/1/axis/X/Y",
+            "/vjoy/*/axis/X",
+        ] {
+            assert!(route(&router, address, OscArg::Float(1.0)).is_empty());
+        }
+        assert!(route(&router, "/vjoy/1/axis/X", OscArg::Nil).is_empty());
+        assert!(router.route(&message("/vjoy/1/axis/X", vec![])).is_empty());
+    }
+
+    #[test]
+    fn user_mappings() {
+        let router = OscRouter::new()
+            .default_addresses(false)
+            .map(
+                OscMapping::new("/1/fader1", OscTarget::Axis(VJDevice::D2, VJDAxis::Y))
+                    .range(-1.0, 1.0),
+            )
+            .map(OscMapping::new(
+                "/1/fader2",
+                OscTarget::Axis(VJDevice::D2, VJDAxis::Z),
+            ))
+            .map(
+                OscMapping::new(
+                    "/1/rotary1",
+                    OscTarget::ContPov(VJDevice::D2, VJDPovNumber::Pov1),
+                )
+                .range(0.0, 1.0),
+            )
+            .map(
+                OscMapping::new("/1/push1", OscTarget::Button(VJDevice::D2, VJDButton::B1))
+                    .range(0.0, 127.0),
+            );
+
+        assert_eq!(
+            vec![VJDFeedEvent::Axis(
+                VJDevice::D2,
+                VJDAxis::Y,
+                VJDAxisRaw::new(24575).unwrap()
+            )],
+            route(&router, "/1/fader1", OscArg::Float(0.5))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::ContPov(
+                VJDevice::D2,
+                VJDPovNumber::Pov1,
+                VJDPovCont::new(27000).unwrap()
+            )],
+            route(&router, "/1/rotary1", OscArg::Float(0.75))
+        );
+        assert_eq!(
+            vec![VJDFeedEvent::Button(
+                VJDevice::D2,
+                VJDButton::B1,
+                VJDButtonState::Pressed
+            )],
+            route(&router, "/1/push1", OscArg::Int(127))
+        );
+
+        // A pattern reaches every matching mapping.
+        assert_eq!(
+            vec![
+                VJDFeedEvent::Axis(VJDevice::D2, VJDAxis::Y, VJDAxisRaw::MIN),
+                VJDFeedEvent::Axis(VJDevice::D2, VJDAxis::Z, VJDAxisRaw::MIN),
+            ],
+            route(&router, "/1/fader*", OscArg::False)
+        );
+
+        assert!(route(&router, "/vjoy/1/button/1", OscArg::True).is_empty());
+    }
+
+    #[test]
+    fn loopback_adapter() {
+        let mut adapter = OscAdapter::bind("127.0.0.1:0", OscRouter::new()).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(adapter.local_addr().unwrap()).unwrap();
+
+        let press = message("/vjoy/4/button/10", vec![OscArg::Int(1)]);
+        socket.send(&press.encode()).unwrap();
+        socket.send(b"not osc").unwrap();
+        socket
+            .send(&message("/unknown", vec![OscArg::Int(1)]).encode())
+            .unwrap();
+
+        let mut events = Vec::new();
+        while adapter.invalid_packets() == 0 {
+            events.extend(adapter.receive(Duration::from_secs(2)).unwrap());
+        }
+        assert_eq!(
+            vec![VJDFeedEvent::Button(
+                VJDevice::D4,
+                VJDButton::B10,
+                VJDButtonState::Pressed
+            )],
+            events
+        );
+    }
+}
+
+use crate::vjoy_base::device::axis::{VJDAxisRaw, VJDAxisUnit};
+use crate::vjoy_base::device::feeding::VJDFeedEvent;
+use crate::vjoy_base::device::pov::VJDPovCont;
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDButtonState, VJDPovDisc, VJDPovNumber, VJDevice,
+};
+use std::convert::{TryFrom, TryInto};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Deepest nesting of bundles accepted.
+const MAX_BUNDLE_DEPTH: usize = 8;
+
+/**
+    Describes why a datagram is not a valid OSC packet.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OscError {
+    /// The packet ends in the middle of a field.
+    Truncated,
+
+    /// A string is not terminated, not padded with zeros or not UTF-8.
+    InvalidString,
+
+    /// The address of a message does not start with `/`.
+    InvalidAddress,
+
+    /// A message has no type tag string.
+    MissingTypeTags,
+
+    /// An argument has a type which is not supported. Its type tag is provided.
+    UnsupportedType(char),
+
+    /// An element of a bundle has an invalid size, or bundles are nested too deep.
+    InvalidBundle,
+
+    /// Bytes follow the last argument of a message.
+    TrailingBytes,
+}
+
+/**
+    Describes an argument of an OSC message.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    /// `i`: 32-bit integer.
+    Int(i32),
+
+    /// `f`: 32-bit float.
+    Float(f32),
+
+    /// `s`: string.
+    String(String),
+
+    /// `b`: blob of bytes.
+    Blob(Vec<u8>),
+
+    /// `h`: 64-bit integer.
+    Long(i64),
+
+    /// `d`: 64-bit float.
+    Double(f64),
+
+    /// `T`.
+    True,
+
+    /// `F`.
+    False,
+
+    /// `N`.
+    Nil,
+
+    /// `I`: impulse, or bang.
+    Impulse,
+}
+
+/**
+    Describes an OSC message: an address, which may be a pattern, and its arguments.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+impl OscMessage {
+    /// Encodes the message as an OSC packet.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, &self.address);
+
+        let tags: String = std::iter::once(',')
+            .chain(self.args.iter().map(|arg| match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+                OscArg::Blob(_) => 'b',
+                OscArg::Long(_) => 'h',
+                OscArg::Double(_) => 'd',
+                OscArg::True => 'T',
+                OscArg::False => 'F',
+                OscArg::Nil => 'N',
+                OscArg::Impulse => 'I',
+            }))
+            .collect();
+        write_string(&mut bytes, &tags);
+
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                OscArg::Float(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                OscArg::String(value) => write_string(&mut bytes, value),
+                OscArg::Blob(blob) => {
+                    bytes.extend_from_slice(&(blob.len() as i32).to_be_bytes());
+                    bytes.extend_from_slice(blob);
+                    pad(&mut bytes);
+                }
+                OscArg::Long(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                OscArg::Double(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+                OscArg::True | OscArg::False | OscArg::Nil | OscArg::Impulse => (),
+            }
+        }
+
+        bytes
+    }
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    bytes.resize(bytes.len().div_ceil(4) * 4, 0);
+}
+
+/// Writes a string terminated by at least one zero, padded to a multiple of four bytes.
+fn write_string(bytes: &mut Vec<u8>, text: &str) {
+    bytes.extend_from_slice(text.as_bytes());
+    bytes.push(0);
+    pad(bytes);
+}
+
+/// Reads the fields of a packet.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], OscError> {
+        if self.0.len() < length {
+            return Err(OscError::Truncated);
+        }
+
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], OscError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn string(&mut self) -> Result<&'a str, OscError> {
+        let end = self
+            .0
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(OscError::InvalidString)?;
+        // The terminating zero and the padding.
+        let field = self
+            .take((end + 4) / 4 * 4)
+            .map_err(|_| OscError::InvalidString)?;
+
+        if field[end..].iter().any(|&byte| byte != 0) {
+            return Err(OscError::InvalidString);
+        }
+        std::str::from_utf8(&field[..end]).map_err(|_| OscError::InvalidString)
+    }
+
+    fn blob(&mut self) -> Result<&'a [u8], OscError> {
+        let length = i32::from_be_bytes(self.array()?);
+        let length = usize::try_from(length).map_err(|_| OscError::Truncated)?;
+        let blob = self.take(length)?;
+        self.take((4 - length % 4) % 4)?;
+        Ok(blob)
+    }
+}
+
+/**
+    Decodes an OSC packet, a message or a bundle, and returns its messages. The messages of
+    bundles are returned in order, ignoring their time tags.
+*/
+pub fn parse_packet(bytes: &[u8]) -> Result<Vec<OscMessage>, OscError> {
+    let mut messages = Vec::new();
+    parse_element(bytes, 0, &mut messages)?;
+    Ok(messages)
+}
+
+fn parse_element(
+    bytes: &[u8],
+    depth: usize,
+    messages: &mut Vec<OscMessage>,
+) -> Result<(), OscError> {
+    if !bytes.starts_with(b"#bundle\0") {
+        messages.push(parse_message(bytes)?);
+        return Ok(());
+    }
+
+    if depth == MAX_BUNDLE_DEPTH {
+        return Err(OscError::InvalidBundle);
+    }
+
+    let mut reader = Reader(bytes);
+    // The header and the time tag.
+    reader.take(16)?;
+
+    while !reader.0.is_empty() {
+        let size = i32::from_be_bytes(reader.array()?);
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|size| size % 4 == 0 && *size <= reader.0.len())
+            .ok_or(OscError::InvalidBundle)?;
+        parse_element(reader.take(size)?, depth + 1, messages)?;
+    }
+
+    Ok(())
+}
+
+fn parse_message(bytes: &[u8]) -> Result<OscMessage, OscError> {
+    let mut reader = Reader(bytes);
+
+    let address = reader.string()?;
+    if !address.starts_with('/') {
+        return Err(OscError::InvalidAddress);
+    }
+    if reader.0.first() != Some(&b',') {
+        return Err(OscError::MissingTypeTags);
+    }
+    let tags = reader.string()?;
+
+    let mut args = Vec::new();
+    for tag in tags.chars().skip(1) {
+        let arg = match tag {
+            'i' => OscArg::Int(i32::from_be_bytes(reader.array()?)),
+            'f' => OscArg::Float(f32::from_be_bytes(reader.array()?)),
+            's' => OscArg::String(reader.string()?.to_string()),
+            'b' => OscArg::Blob(reader.blob()?.to_vec()),
+            'h' => OscArg::Long(i64::from_be_bytes(reader.array()?)),
+            'd' => OscArg::Double(f64::from_be_bytes(reader.array()?)),
+            'T' => OscArg::True,
+            'F' => OscArg::False,
+            'N' => OscArg::Nil,
+            'I' => OscArg::Impulse,
+            tag => return Err(OscError::UnsupportedType(tag)),
+        };
+        args.push(arg);
+    }
+
+    if !reader.0.is_empty() {
+        return Err(OscError::TrailingBytes);
+    }
+
+    Ok(OscMessage {
+        address: address.to_string(),
+        args,
+    })
+}
+
+/// Describes an element of an OSC address pattern.
+enum Token<'a> {
+    Byte(u8),
+    AnyByte,
+    AnyBytes,
+    Class { negated: bool, set: &'a [u8] },
+    Choice(Vec<&'a [u8]>),
+}
+
+/// Splits a part of a pattern into tokens, or returns [`None`] if a bracket is not closed.
+fn tokenize(pattern: &[u8]) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < pattern.len() {
+        let closing = |close: u8| {
+            pattern[index..]
+                .iter()
+                .position(|&byte| byte == close)
+                .map(|offset| index + offset)
+        };
+
+        let token = match pattern[index] {
+            b'?' => Token::AnyByte,
+            b'*' => Token::AnyBytes,
+            b'[' => {
+                let end = closing(b']')?;
+                let set = &pattern[index + 1..end];
+                index = end;
+                match set.split_first() {
+                    Some((b'!', set)) => Token::Class { negated: true, set },
+                    _ => Token::Class {
+                        negated: false,
+                        set,
+                    },
+                }
+            }
+            b'{' => {
+                let end = closing(b'}')?;
+                let choices = pattern[index + 1..end]
+                    .split(|&byte| byte == b',')
+                    .collect();
+                index = end;
+                Token::Choice(choices)
+            }
+            byte => Token::Byte(byte),
+        };
+
+        tokens.push(token);
+        index += 1;
+    }
+
+    Some(tokens)
+}
+
+/// Whether a class like `a-z0` of a pattern holds a byte.
+fn class_contains(set: &[u8], byte: u8) -> bool {
+    let mut index = 0;
+
+    while index < set.len() {
+        if index + 2 < set.len() && set[index + 1] == b'-' {
+            if (set[index]..=set[index + 2]).contains(&byte) {
+                return true;
+            }
+            index += 3;
+        } else {
+            if set[index] == byte {
+                return true;
+            }
+            index += 1;
+        }
+    }
+
+    false
+}
+
+/**
+    Whether a part of an address, between two slashes, matches a part of a pattern. The
+    possible positions in the text are followed token after token, so that the time is bounded
+    whatever the pattern.
+*/
+fn part_matches(pattern: &str, text: &str) -> bool {
+    let tokens = match tokenize(pattern.as_bytes()) {
+        Some(tokens) => tokens,
+        None => return false,
+    };
+    let text = text.as_bytes();
+
+    // Positions in the text reached after the tokens matched so far.
+    let mut reached = vec![false; text.len() + 1];
+    reached[0] = true;
+
+    for token in tokens {
+        let mut next = vec![false; text.len() + 1];
+
+        for start in (0..=text.len()).filter(|&start| reached[start]) {
+            let byte = text.get(start).copied();
+            match &token {
+                Token::Byte(expected) if byte == Some(*expected) => next[start + 1] = true,
+                Token::AnyByte if byte.is_some() => next[start + 1] = true,
+                Token::AnyBytes => {
+                    next[start..]
+                        .iter_mut()
+                        .for_each(|position| *position = true);
+                    break;
+                }
+                Token::Class { negated, set } => {
+                    if let Some(byte) = byte {
+                        if class_contains(set, byte) != *negated {
+                            next[start + 1] = true;
+                        }
+                    }
+                }
+                Token::Choice(choices) => {
+                    for choice in choices {
+                        if text[start..].starts_with(choice) {
+                            next[start + choice.len()] = true;
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        reached = next;
+    }
+
+    reached[text.len()]
+}
+
+/// Whether an address matches an OSC address pattern.
+fn pattern_matches(pattern: &str, address: &str) -> bool {
+    let patterns = pattern.split('/');
+    let parts = address.split('/');
+
+    patterns.clone().count() == parts.clone().count()
+        && patterns
+            .zip(parts)
+            .all(|(pattern, part)| part_matches(pattern, part))
+}
+
+/**
+    Describes a control of a device fed by OSC messages.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OscTarget {
+    Axis(VJDevice, VJDAxis),
+    Button(VJDevice, VJDButton),
+    DiscPov(VJDevice, VJDPovNumber),
+    ContPov(VJDevice, VJDPovNumber),
+}
+
+impl OscTarget {
+    /// Returns the range of the values of the control at the default addresses.
+    pub fn default_range(self) -> (f64, f64) {
+        match self {
+            OscTarget::Axis(..) | OscTarget::Button(..) => (0.0, 1.0),
+            OscTarget::DiscPov(..) => (0.0, 4.0),
+            OscTarget::ContPov(..) => (0.0, 360.0),
+        }
+    }
+
+    /// Returns the update of the control for a value scaled to the range 0 to 1.
+    fn event(self, value: f64) -> VJDFeedEvent {
+        match self {
+            OscTarget::Axis(device, axis) => VJDFeedEvent::Axis(
+                device,
+                axis,
+                VJDAxisRaw::from(VJDAxisUnit::saturating(value as f32)),
+            ),
+            OscTarget::Button(device, button) => {
+                let state = if value >= 0.5 {
+                    VJDButtonState::Pressed
+                } else {
+                    VJDButtonState::Released
+                };
+                VJDFeedEvent::Button(device, button, state)
+            }
+            OscTarget::DiscPov(device, pov) => {
+                let direction = match value {
+                    value if value < 0.0 => VJDPovDisc::Neutral,
+                    value if value < 0.25 => VJDPovDisc::North,
+                    value if value < 0.5 => VJDPovDisc::East,
+                    value if value < 0.75 => VJDPovDisc::South,
+                    _ => VJDPovDisc::West,
+                };
+                VJDFeedEvent::DiscPov(device, pov, direction)
+            }
+            OscTarget::ContPov(device, pov) => {
+                let value = if value < 0.0 {
+                    VJDPovCont::NEUTRAL
+                } else {
+                    VJDPovCont::from_degrees((value * 360.0) as f32).unwrap_or(VJDPovCont::NEUTRAL)
+                };
+                VJDFeedEvent::ContPov(device, pov, value)
+            }
+        }
+    }
+}
+
+/**
+    Routes the messages sent to an address to a control, scaling their values from a range.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMapping {
+    pub address: String,
+    pub target: OscTarget,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl OscMapping {
+    /// Returns a mapping with the default range of the control.
+    pub fn new(address: &str, target: OscTarget) -> OscMapping {
+        let (min, max) = target.default_range();
+
+        OscMapping {
+            address: address.to_string(),
+            target,
+            min,
+            max,
+        }
+    }
+
+    /// Changes the range of the values. `min` may be greater than `max` to reverse the control.
+    pub fn range(mut self, min: f64, max: f64) -> OscMapping {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Returns the update of the control for the argument, if it has a value.
+    fn event(&self, arg: &OscArg) -> Option<VJDFeedEvent> {
+        let value = match *arg {
+            OscArg::Int(value) => value as f64,
+            OscArg::Float(value) => value as f64,
+            OscArg::Long(value) => value as f64,
+            OscArg::Double(value) => value,
+            OscArg::True => self.max,
+            OscArg::False => self.min,
+            _ => return None,
+        };
+
+        let scaled = (value - self.min) / (self.max - self.min);
+        if !scaled.is_finite() {
+            return None;
+        }
+
+        Some(self.target.event(scaled))
+    }
+}
+
+/**
+    Turns OSC messages into updates of device controls, from the default addresses and the
+    [`OscMapping`]s, see the module documentation.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscRouter {
+    mappings: Vec<OscMapping>,
+    default_addresses: bool,
+}
+
+impl Default for OscRouter {
+    fn default() -> Self {
+        OscRouter::new()
+    }
+}
+
+impl OscRouter {
+    /// Returns a router of the default addresses only.
+    pub fn new() -> OscRouter {
+        OscRouter {
+            mappings: Vec::new(),
+            default_addresses: true,
+        }
+    }
+
+    /// Adds a mapping.
+    pub fn map(mut self, mapping: OscMapping) -> OscRouter {
+        self.mappings.push(mapping);
+        self
+    }
+
+    /// Enables or disables the default addresses, enabled by default.
+    pub fn default_addresses(mut self, enabled: bool) -> OscRouter {
+        self.default_addresses = enabled;
+        self
+    }
+
+    pub fn mappings(&self) -> &[OscMapping] {
+        &self.mappings
+    }
+
+    /// Returns the updates of the controls addressed by a message.
+    pub fn route(&self, message: &OscMessage) -> Vec<VJDFeedEvent> {
+        let arg = match message.args.first() {
+            Some(arg) => arg,
+            None => return Vec::new(),
+        };
+
+        let mut mapped = self
+            .mappings
+            .iter()
+            .filter(|mapping| pattern_matches(&message.address, &mapping.address))
+            .peekable();
+
+        if mapped.peek().is_some() {
+            return mapped.filter_map(|mapping| mapping.event(arg)).collect();
+        }
+
+        if !self.default_addresses {
+            return Vec::new();
+        }
+
+        default_target(&message.address)
+            .and_then(|target| {
+                let (min, max) = target.default_range();
+                OscMapping {
+                    address: message.address.clone(),
+                    target,
+                    min,
+                    max,
+                }
+                .event(arg)
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Returns the control named by a default address.
+fn default_target(address: &str) -> Option<OscTarget> {
+    let parts: Vec<&str> = address.split('/').collect();
+    let (device, kind, control) = match parts[..] {
+        ["", "vjoy", device, kind, control] => (device, kind, control),
+        _ => return None,
+    };
+
+    let device = VJDevice::get_from(device.parse().ok()?)?;
+    let number = || control.parse::<u8>().ok();
+    let pov = || match number()? {
+        1 => Some(VJDPovNumber::Pov1),
+        2 => Some(VJDPovNumber::Pov2),
+        3 => Some(VJDPovNumber::Pov3),
+        4 => Some(VJDPovNumber::Pov4),
+        _ => None,
+    };
+
+    match kind {
+        "axis" => VJDAxis::ALL
+            .iter()
+            .find(|axis| format!("{:?}", axis).eq_ignore_ascii_case(control))
+            .map(|&axis| OscTarget::Axis(device, axis)),
+        "button" => VJDButton::get_from(number()?).map(|button| OscTarget::Button(device, button)),
+        "pov" => pov().map(|pov| OscTarget::DiscPov(device, pov)),
+        "cpov" => pov().map(|pov| OscTarget::ContPov(device, pov)),
+        _ => None,
+    }
+}
+
+/**
+    Receives OSC packets on a UDP socket and routes their messages with an [`OscRouter`].
+*/
+pub struct OscAdapter {
+    socket: UdpSocket,
+    router: OscRouter,
+    buffer: Vec<u8>,
+    invalid_packets: u64,
+    failed_sends: u64,
+}
+
+impl OscAdapter {
+    /// Longest time [`OscAdapter::run`] waits for a packet before checking whether to stop.
+    const TICK: Duration = Duration::from_millis(50);
+
+    pub fn bind(address: impl ToSocketAddrs, router: OscRouter) -> io::Result<OscAdapter> {
+        Ok(OscAdapter {
+            socket: UdpSocket::bind(address)?,
+            router,
+            buffer: vec![0; 65536],
+            invalid_packets: 0,
+            failed_sends: 0,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn router(&self) -> &OscRouter {
+        &self.router
+    }
+
+    /// Returns the number of datagrams ignored because they are not valid OSC packets.
+    pub fn invalid_packets(&self) -> u64 {
+        self.invalid_packets
+    }
+
+    /// Returns the number of updates [`OscAdapter::run`] failed to send.
+    pub fn failed_sends(&self) -> u64 {
+        self.failed_sends
+    }
+
+    /**
+        Waits up to `wait` for a packet, handles every packet received, and returns the updates
+        of the controls in the order of the messages.
+    */
+    pub fn receive(&mut self, wait: Duration) -> io::Result<Vec<VJDFeedEvent>> {
+        let mut events = Vec::new();
+
+        // A zero timeout is rejected by the socket.
+        self.socket.set_nonblocking(false)?;
+        self.socket
+            .set_read_timeout(Some(wait.max(Duration::from_micros(1))))?;
+
+        loop {
+            match self.socket.recv(&mut self.buffer) {
+                Ok(length) => match parse_packet(&self.buffer[..length]) {
+                    Ok(messages) => {
+                        for message in &messages {
+                            events.extend(self.router.route(message));
+                        }
+                    }
+                    Err(_) => self.invalid_packets += 1,
+                },
+                Err(error)
+                    if error.kind() == io::ErrorKind::WouldBlock
+                        || error.kind() == io::ErrorKind::TimedOut =>
+                {
+                    break
+                }
+                // Reported on some platforms when a previous datagram sent to a peer failed.
+                Err(error) if error.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(error) => return Err(error),
+            }
+
+            // Only wait for the first packet.
+            self.socket.set_nonblocking(true)?;
+        }
+
+        Ok(events)
+    }
+
+    /**
+        Receives packets and sends the updates of the controls with [`VJDFeedEvent::send`]
+        until `stop` is set. The devices must be owned by this application. Updates which
+        cannot be sent are counted in [`OscAdapter::failed_sends`].
+    */
+    pub fn run(&mut self, stop: &AtomicBool) -> io::Result<()> {
+        while !stop.load(Ordering::Relaxed) {
+            for event in self.receive(OscAdapter::TICK)? {
+                if !event.send() {
+                    self.failed_sends += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,552 @@
+//! Contains a probe to identify which SDL2 joystick corresponds to which vJoy device.
+//!
+//! Every vJoy device has the same name and GUID in SDL2, so they cannot be told apart from their
+//! description alone. The probe writes a signature, a short sequence of values, to one owned vJoy
+//! device at a time and looks for the single SDL2 joystick which reflects the whole sequence.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(axes: &[i16], buttons: &[bool]) -> Snapshot {
+        Snapshot {
+            axes: axes.to_vec(),
+            buttons: buttons.to_vec(),
+        }
+    }
+
+    fn sdl2(raw: i32) -> i16 {
+        SDL2AxisValue::from(VJDAxisRaw::saturating(raw)).get()
+    }
+
+    fn observe(snapshots: Vec<(u32, Snapshot)>) -> HashMap<u32, Snapshot> {
+        snapshots.into_iter().collect()
+    }
+
+    #[test]
+    fn axis_signals_are_distinct_per_device() {
+        let mut distinctive = Vec::new();
+
+        for n in 1..=VJGeneral::MAX_DEVICES {
+            let signals = signals(Signature::Axis(VJDAxis::X), VJDevice::get_from(n).unwrap());
+
+            assert_eq!(3, signals.len());
+            distinctive.push(signals[2]);
+        }
+
+        for (i, signal) in distinctive.iter().enumerate() {
+            assert!(!distinctive[i + 1..].contains(signal));
+        }
+    }
+
+    #[test]
+    fn first_signal_differs_from_reset_state() {
+        let reset = [
+            Signal::Axis(VJGeneral::MIN_AXIS_VALUE),
+            Signal::Axis(VJGeneral::NEUTRAL_AXIS_VALUE),
+            Signal::Button(false),
+        ];
+
+        for signature in [Signature::Axis(VJDAxis::Rx), Signature::Button].iter() {
+            assert!(!reset.contains(&signals(*signature, VJDevice::D1)[0]));
+        }
+    }
+
+    #[test]
+    fn single_joystick_reflects_axis_signature() {
+        let signals = signals(Signature::Axis(VJDAxis::X), VJDevice::D2);
+        let values: Vec<i32> = signals
+            .iter()
+            .map(|signal| match signal {
+                Signal::Axis(raw) => *raw,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let observations: Vec<HashMap<u32, Snapshot>> = values
+            .iter()
+            .map(|&raw| {
+                observe(vec![
+                    (7, snapshot(&[0, sdl2(raw)], &[])),
+                    (9, snapshot(&[0, 0], &[])),
+                ])
+            })
+            .collect();
+
+        assert_eq!(vec![7], reflecting(&signals, &observations));
+    }
+
+    #[test]
+    fn axis_tolerates_sdl2_rounding() {
+        let signals = [Signal::Axis(28671)];
+        let observations = [observe(vec![(1, snapshot(&[sdl2(28671) - 1], &[]))])];
+
+        assert_eq!(vec![1], reflecting(&signals, &observations));
+    }
+
+    #[test]
+    fn same_axis_must_reflect_every_signal() {
+        let signals = [Signal::Axis(0), Signal::Axis(32767)];
+
+        // Axis 0 reflects the first signal, axis 1 the second one: not a match.
+        let observations = [
+            observe(vec![(1, snapshot(&[sdl2(0), 0], &[]))]),
+            observe(vec![(1, snapshot(&[0, sdl2(32767)], &[]))]),
+        ];
+
+        assert!(reflecting(&signals, &observations).is_empty());
+    }
+
+    #[test]
+    fn several_joysticks_are_all_reported() {
+        let signals = [Signal::Button(true), Signal::Button(false)];
+        let observations = [
+            observe(vec![
+                (4, snapshot(&[], &[true])),
+                (2, snapshot(&[], &[true, false])),
+                (3, snapshot(&[], &[false])),
+            ]),
+            observe(vec![
+                (4, snapshot(&[], &[false])),
+                (2, snapshot(&[], &[false, false])),
+                (3, snapshot(&[], &[false])),
+            ]),
+        ];
+
+        assert_eq!(vec![2, 4], reflecting(&signals, &observations));
+    }
+
+    #[test]
+    fn resting_distractor_does_not_end_a_step() {
+        let signals = signals(Signature::Axis(VJDAxis::X), VJDevice::D2);
+        let axis = |signal: Signal| match signal {
+            Signal::Axis(raw) => sdl2(raw),
+            _ => unreachable!(),
+        };
+        let poll = |vjoy: i16| {
+            observe(vec![
+                (5, snapshot(&[sdl2(VJGeneral::MAX_AXIS_VALUE)], &[])),
+                (7, snapshot(&[vjoy], &[])),
+            ])
+        };
+
+        // The vJoy joystick shows each write one poll late, the distractor rests at the end.
+        let mut previous = poll(sdl2(VJGeneral::NEUTRAL_AXIS_VALUE));
+        let mut observations = Vec::new();
+
+        for (step, signal) in signals.iter().enumerate() {
+            let prefix = &signals[..=step];
+            observations.push(HashMap::new());
+
+            record(prefix, &mut observations, previous.clone());
+            assert!(!reflected_change(prefix, &observations, &previous));
+
+            record(prefix, &mut observations, poll(axis(*signal)));
+            assert!(reflected_change(prefix, &observations, &previous));
+
+            previous = observations[step].clone();
+        }
+
+        assert_eq!(vec![7], reflecting(&signals, &observations));
+    }
+
+    #[test]
+    fn joystick_missing_from_an_observation_is_discarded() {
+        let signals = [Signal::Button(true), Signal::Button(false)];
+        let observations = [observe(vec![(1, snapshot(&[], &[true]))]), observe(vec![])];
+
+        assert!(reflecting(&signals, &observations).is_empty());
+    }
+}
+
+use super::{SDL2VjoyError, SDL2Vjoys};
+use crate::vjoy_base::device::axis::{SDL2AxisValue, VJDAxisRaw};
+use crate::vjoy_base::device::feeding::VJDSeqFeed;
+use crate::vjoy_base::device::info::VJDInfo;
+use crate::vjoy_base::device::{VJDAxis, VJDButton, VJDButtonState, VJDStatus, VJDevice};
+use crate::vjoy_base::driver::VJGeneral;
+use sdl2::joystick::Joystick;
+use sdl2::{EventPump, JoystickSubsystem};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/**
+    Describes why a vJoy device could not be matched with a SDL2 joystick.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeFailure {
+    /// The device is not owned by this application, its status is provided. Devices must be
+    /// acquired before probing.
+    NotOwned(VJDStatus),
+
+    /// The device has neither an axis nor a button to write a signature to.
+    NoControl,
+
+    /// vJoy refused to update the device during the probe.
+    WriteFailed,
+
+    /// No SDL2 joystick reflected the signature in time.
+    NotSeen,
+
+    /// Several SDL2 joysticks reflected the signature. Their instance ids are provided.
+    Ambiguous(Vec<u32>),
+}
+
+/**
+    Holds the result of [`SDL2Probe::run`].
+*/
+pub struct SDL2VjoyProbe {
+    /// vJoy devices whose SDL2 joystick has been identified.
+    pub matched: SDL2Vjoys,
+
+    /// vJoy devices which could not be identified, in the order they were probed.
+    pub unmatched: Vec<(VJDevice, ProbeFailure)>,
+}
+
+impl SDL2VjoyProbe {
+    /// Returns `true` if every probed device has been identified.
+    pub fn is_complete(&self) -> bool {
+        self.unmatched.is_empty()
+    }
+}
+
+/// Describes which control of a vJoy device carries the signature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Signature {
+    Axis(VJDAxis),
+
+    /// Always button 1, which is button 0 for SDL2.
+    Button,
+}
+
+/// Describes a value written to the control carrying the signature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Signal {
+    Axis(i32),
+    Button(bool),
+}
+
+/// Holds the state of a SDL2 joystick at a given time.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Snapshot {
+    axes: Vec<i16>,
+    buttons: Vec<bool>,
+}
+
+impl Snapshot {
+    fn take(joystick: &Joystick) -> Snapshot {
+        Snapshot {
+            axes: (0..joystick.num_axes())
+                .map(|axis| joystick.axis(axis).unwrap_or(0))
+                .collect(),
+            buttons: (0..joystick.num_buttons())
+                .map(|button| joystick.button(button).unwrap_or(false))
+                .collect(),
+        }
+    }
+
+    /// Returns the indexes of the axes or buttons reflecting the signal.
+    fn reflected_by(&self, signal: Signal) -> HashSet<usize> {
+        match signal {
+            Signal::Axis(raw) => self
+                .axes
+                .iter()
+                .enumerate()
+                // SDL2 may round the scaled value down by one, so allow a difference of one
+                // vJoy unit.
+                .filter(|(_, &value)| {
+                    (VJDAxisRaw::from(SDL2AxisValue::new(value)).get() - raw).abs() <= 1
+                })
+                .map(|(index, _)| index)
+                .collect(),
+            Signal::Button(pressed) => match self.buttons.first() {
+                Some(&state) if state == pressed => std::iter::once(0).collect(),
+                _ => HashSet::new(),
+            },
+        }
+    }
+}
+
+/**
+    Returns the values to write for a signature. Both ends of the axis are written first, then a
+    value specific to the device so that two devices never share a whole signature.
+
+    The first value differs from the state of a reset device, neutral or minimum for an axis and
+    released for a button. A joystick can't reflect it before SDL2 has seen the write.
+*/
+fn signals(signature: Signature, device: VJDevice) -> Vec<Signal> {
+    match signature {
+        Signature::Axis(_) => vec![
+            Signal::Axis(VJGeneral::MAX_AXIS_VALUE),
+            Signal::Axis(VJGeneral::MIN_AXIS_VALUE),
+            Signal::Axis(4096 + 1024 * device as i32),
+        ],
+        Signature::Button => vec![
+            Signal::Button(true),
+            Signal::Button(false),
+            Signal::Button(true),
+        ],
+    }
+}
+
+/**
+    Returns the instance ids, in ascending order, of the joysticks whose observations reflect
+    every signal with the same control. `observations[i]` holds the snapshots taken after
+    writing `signals[i]`.
+*/
+fn reflecting(signals: &[Signal], observations: &[HashMap<u32, Snapshot>]) -> Vec<u32> {
+    let first = match observations.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+
+    let mut found: Vec<u32> = first
+        .keys()
+        .copied()
+        .filter(|&id| !reflecting_controls(id, signals, observations).is_empty())
+        .collect();
+
+    found.sort_unstable();
+    found
+}
+
+/// Returns the controls of a joystick whose observations reflect every signal.
+fn reflecting_controls(
+    id: u32,
+    signals: &[Signal],
+    observations: &[HashMap<u32, Snapshot>],
+) -> HashSet<usize> {
+    let mut controls: Option<HashSet<usize>> = None;
+
+    for (signal, observation) in signals.iter().zip(observations) {
+        let reflected = match observation.get(&id) {
+            Some(snapshot) => snapshot.reflected_by(*signal),
+            None => return HashSet::new(),
+        };
+
+        let remaining: HashSet<usize> = match controls {
+            Some(controls) => controls.intersection(&reflected).copied().collect(),
+            None => reflected,
+        };
+
+        if remaining.is_empty() {
+            return remaining;
+        }
+
+        controls = Some(remaining);
+    }
+
+    controls.unwrap_or_default()
+}
+
+/**
+    Adds the snapshots of a poll to the observation of the last signal. A joystick keeps the
+    first of its snapshots reflecting every signal so far, so a later poll cannot lose it.
+*/
+fn record(
+    signals: &[Signal],
+    observations: &mut [HashMap<u32, Snapshot>],
+    poll: HashMap<u32, Snapshot>,
+) {
+    let reflected: HashSet<u32> = reflecting(signals, observations).into_iter().collect();
+
+    if let Some(last) = observations.last_mut() {
+        for (id, snapshot) in poll {
+            if !reflected.contains(&id) {
+                last.insert(id, snapshot);
+            }
+        }
+    }
+}
+
+/**
+    Returns whether a joystick reflects every signal so far with a control moved by the last
+    signal, compared to `previous`, the snapshots before the last write. A control already
+    resting at the written value, such as a physical axis at its end, does not count: the
+    joystick of the device may not have reflected the write yet.
+*/
+fn reflected_change(
+    signals: &[Signal],
+    observations: &[HashMap<u32, Snapshot>],
+    previous: &HashMap<u32, Snapshot>,
+) -> bool {
+    let (signal, observation) = match (signals.last(), observations.last()) {
+        (Some(signal), Some(observation)) => (*signal, observation),
+        _ => return false,
+    };
+
+    observation.iter().any(|(&id, snapshot)| {
+        let before = match previous.get(&id) {
+            Some(before) => before,
+            None => return false,
+        };
+
+        reflecting_controls(id, signals, observations)
+            .iter()
+            .any(|&control| match signal {
+                Signal::Axis(_) => snapshot.axes.get(control) != before.axes.get(control),
+                Signal::Button(_) => snapshot.buttons.get(control) != before.buttons.get(control),
+            })
+    })
+}
+
+/**
+    Identifies the SDL2 joystick of vJoy devices by probing them one at a time.
+
+    Probing writes to the devices and resets them afterwards, see [`VJDSeqFeed::reset`]. Their
+    previous position is lost.
+*/
+pub struct SDL2Probe<'a> {
+    joystick_subsystem: &'a JoystickSubsystem,
+    step_timeout: Duration,
+}
+
+impl<'a> SDL2Probe<'a> {
+    /// Default time given to SDL2 to reflect each value of a signature.
+    pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_millis(250);
+
+    pub fn new(joystick_subsystem: &'a JoystickSubsystem) -> SDL2Probe<'a> {
+        SDL2Probe {
+            joystick_subsystem,
+            step_timeout: Self::DEFAULT_STEP_TIMEOUT,
+        }
+    }
+
+    /// Changes the time given to SDL2 to reflect each value of a signature.
+    pub fn step_timeout(mut self, timeout: Duration) -> SDL2Probe<'a> {
+        self.step_timeout = timeout;
+        self
+    }
+
+    /**
+        Probes the specified devices, which must be owned by this application.
+
+        Every SDL2 joystick is a candidate, whatever its name. A joystick matched with a device
+        is no longer a candidate for the following devices. [`SDL2VjoyError`] is only returned
+        if SDL2 itself fails; devices which cannot be identified are reported in
+        [`SDL2VjoyProbe::unmatched`].
+    */
+    pub fn run(
+        &self,
+        event_pump: &mut EventPump,
+        devices: &[VJDevice],
+    ) -> Result<SDL2VjoyProbe, SDL2VjoyError> {
+        let total = self
+            .joystick_subsystem
+            .num_joysticks()
+            .map_err(SDL2VjoyError::SdlError)?;
+
+        let mut candidates: HashMap<u32, Joystick> = HashMap::with_capacity(total as usize);
+
+        for index in 0..total {
+            let joystick = self.joystick_subsystem.open(index)?;
+            candidates.insert(joystick.instance_id(), joystick);
+        }
+
+        let mut probe = SDL2VjoyProbe {
+            matched: HashMap::new(),
+            unmatched: Vec::new(),
+        };
+
+        for &device in devices {
+            match self.probe_device(event_pump, device, &candidates) {
+                Ok(id) => {
+                    // The id comes from the candidates, it is always present.
+                    let joystick = candidates.remove(&id).unwrap();
+                    probe.matched.insert(device, joystick);
+                }
+                Err(failure) => probe.unmatched.push((device, failure)),
+            }
+        }
+
+        Ok(probe)
+    }
+
+    fn probe_device(
+        &self,
+        event_pump: &mut EventPump,
+        device: VJDevice,
+        candidates: &HashMap<u32, Joystick>,
+    ) -> Result<u32, ProbeFailure> {
+        let status = VJDInfo::get_status(device);
+        if status != VJDStatus::Own {
+            return Err(ProbeFailure::NotOwned(status));
+        }
+
+        let signature = match VJDAxis::ALL
+            .iter()
+            .find(|&&axis| VJDInfo::is_exist_axis(device, axis))
+        {
+            Some(&axis) => Signature::Axis(axis),
+            None => match VJDInfo::get_total_btns(device) {
+                Ok(total) if total > 0 => Signature::Button,
+                _ => return Err(ProbeFailure::NoControl),
+            },
+        };
+
+        let signals = signals(signature, device);
+        let mut observations: Vec<HashMap<u32, Snapshot>> = Vec::with_capacity(signals.len());
+        let take = |event_pump: &mut EventPump| -> HashMap<u32, Snapshot> {
+            event_pump.pump_events();
+            candidates
+                .iter()
+                .map(|(&id, joystick)| (id, Snapshot::take(joystick)))
+                .collect()
+        };
+        let mut previous = take(event_pump);
+
+        for signal in &signals {
+            let written = match (signature, *signal) {
+                (Signature::Axis(axis), Signal::Axis(raw)) => {
+                    VJDSeqFeed::set_axis(device, axis, raw)
+                }
+                (_, Signal::Button(pressed)) => VJDSeqFeed::set_btn(
+                    device,
+                    VJDButton::B1,
+                    if pressed {
+                        VJDButtonState::Pressed
+                    } else {
+                        VJDButtonState::Released
+                    },
+                ),
+                _ => false,
+            };
+
+            if !written {
+                VJDSeqFeed::reset(device);
+                return Err(ProbeFailure::WriteFailed);
+            }
+
+            // vJoy updates the device asynchronously: poll until a joystick reflects every
+            // value written so far by moving a control, or until the timeout. Each joystick
+            // keeps its first snapshot reflecting them.
+            let deadline = Instant::now() + self.step_timeout;
+            let step = observations.len();
+            let so_far = &signals[..=step];
+            observations.push(HashMap::new());
+
+            loop {
+                record(so_far, &mut observations, take(event_pump));
+
+                if reflected_change(so_far, &observations, &previous) || Instant::now() >= deadline
+                {
+                    break;
+                }
+
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            previous = observations[step].clone();
+        }
+
+        VJDSeqFeed::reset(device);
+
+        let found = reflecting(&signals, &observations);
+
+        match found.len() {
+            0 => Err(ProbeFailure::NotSeen),
+            1 => Ok(found[0]),
+            _ => Err(ProbeFailure::Ambiguous(found)),
+        }
+    }
+}
@@ -0,0 +1,968 @@
+//! Contains declarative mapping profiles, read from TOML files.
+//!
+//! A profile declares named inputs, fed by the application, and routes them to the controls of
+//! vJoy devices:
+//!
+//! ```toml
+//! [inputs]
+//! throttle = "axis"
+//! trigger = "button"
+//! hat = "pov"
+//!
+//! [[axes]]
+//! input = "throttle"
+//! device = "D1"
+//! axis = "Slider1"
+//! chain = [
+//!     { type = "deadzone", center = 300 },
+//!     { type = "curve", exponent = 1.5 },
+//!     { type = "ema", time_constant_ms = 20 },
+//! ]
+//!
+//! [[buttons]]
+//! input = "trigger"
+//! device = "D1"
+//! button = 1
+//! behaviour = "toggle"
+//!
+//! [[povs]]
+//! input = "hat"
+//! device = "D1"
+//! pov = "Pov1"
+//! discrete = true
+//! ```
+//!
+//! Parsing validates the whole profile and gives a [`Profile`], a graph of mappings whose inputs
+//! are resolved, or a [`ProfileError`] locating the first mistake. A
+//! [`ProfileEngine`](engine::ProfileEngine) then evaluates the profile each tick, and a
+//! [`ProfileReloader`](reload::ProfileReloader) swaps it when its file changes.
+//!
+//! Axis values are in vJoy units (see [`VJGeneral::MIN_AXIS_VALUE`] and
+//! [`VJGeneral::MAX_AXIS_VALUE`]) from the input to the target.
+
+pub mod engine;
+pub mod reload;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+[inputs]
+throttle = "axis"
+trigger = "button"
+hat = "pov"
+
+[[axes]]
+input = "throttle"
+device = "D2"
+axis = "Slider1"
+chain = [{ type = "invert" }, { type = "curve", exponent = 2.0 }]
+
+[[buttons]]
+input = "trigger"
+device = "D1"
+button = 3
+behaviour = "toggle"
+
+[[povs]]
+input = "hat"
+device = "D1"
+pov = "Pov1"
+"#;
+
+    fn error_of(text: &str) -> ProfileError {
+        Profile::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn profile_is_parsed() {
+        let profile = Profile::parse(PROFILE).unwrap();
+        let throttle = profile.input_id("throttle").unwrap();
+
+        assert_eq!(3, profile.inputs().len());
+        assert_eq!(InputKind::Axis, profile.input(throttle).kind);
+        assert_eq!(None, profile.input_id("brake"));
+        assert_eq!(&[VJDevice::D1, VJDevice::D2], profile.devices());
+
+        assert_eq!(
+            vec![AxisMapping {
+                input: throttle,
+                device: VJDevice::D2,
+                axis: VJDAxis::Slider1,
+                chain: vec![AxisStage::Invert, AxisStage::Curve { exponent: 2.0 }],
+            }],
+            profile.axes()
+        );
+        assert_eq!(VJDButton::B3, profile.buttons()[0].button);
+        assert_eq!(ButtonBehaviour::Toggle, profile.buttons()[0].behaviour);
+        assert!(!profile.povs()[0].discrete);
+    }
+
+    #[test]
+    fn syntax_errors_are_located() {
+        let error = error_of("[inputs]\nthrottle = \"axis\"\n[[axes]\n");
+
+        assert!(matches!(error.kind, ProfileErrorKind::Syntax(_)));
+        assert_eq!(Some(3), error.location.line);
+    }
+
+    #[test]
+    fn references_are_checked() {
+        let text = PROFILE.replace("input = \"trigger\"", "input = \"triger\"");
+        let error = error_of(&text);
+        assert!(matches!(
+            &error.kind,
+            ProfileErrorKind::UnknownInput(name) if name == "triger"
+        ));
+        assert_eq!("buttons[0].input", error.location.path);
+        assert_eq!(Some(14), error.location.line);
+
+        let text = PROFILE.replace("input = \"hat\"", "input = \"throttle\"");
+        assert!(matches!(
+            error_of(&text).kind,
+            ProfileErrorKind::WrongInputKind {
+                expected: InputKind::Pov,
+                found: InputKind::Axis,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn targets_are_checked() {
+        let text = PROFILE.replace("button = 3", "button = 129");
+        let error = error_of(&text);
+        assert!(matches!(error.kind, ProfileErrorKind::InvalidButton(129)));
+        assert_eq!("buttons[0].button", error.location.path);
+
+        let text = format!(
+            "{}\n[[axes]]\ninput = \"throttle\"\ndevice = \"D2\"\naxis = \"Slider1\"\n",
+            PROFILE
+        );
+        let error = error_of(&text);
+        assert!(matches!(
+            error.kind,
+            ProfileErrorKind::DuplicateTarget { .. }
+        ));
+        assert_eq!("axes[1]", error.location.path);
+        assert_eq!(
+            "axes[1] (line 25): D2 Slider1 is already the target of axes[0]",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn chains_are_checked() {
+        let text = PROFILE.replace("exponent = 2.0", "exponent = -1.0");
+        let error = error_of(&text);
+        assert!(matches!(
+            error.kind,
+            ProfileErrorKind::InvalidStage(FilterError::InvalidParameter("exponent"))
+        ));
+        assert_eq!("axes[0].chain[1]", error.location.path);
+        assert_eq!(Some(11), error.location.line);
+
+        let text = PROFILE.replace("{ type = \"invert\" }", "{ type = \"median\", size = 0 }");
+        assert!(matches!(
+            error_of(&text).kind,
+            ProfileErrorKind::InvalidStage(FilterError::EmptyWindow)
+        ));
+
+        let text = PROFILE.replace("{ type = \"invert\" }", "{ type = \"spline\" }");
+        assert!(matches!(error_of(&text).kind, ProfileErrorKind::Syntax(_)));
+    }
+
+    #[test]
+    fn stages_shape_values() {
+        let ms = Duration::from_millis;
+        let apply = |stage: AxisStage, value: i32| stage.build().unwrap().filter(value, ms(0));
+
+        assert_eq!(32767, apply(AxisStage::Invert, 0));
+        assert_eq!(16384, apply(AxisStage::Invert, 16383));
+
+        let deadzone = AxisStage::Deadzone {
+            center: 1000,
+            edges: 1000,
+        };
+        assert_eq!(16384, apply(deadzone.clone(), 17000));
+        assert_eq!(32767, apply(deadzone.clone(), 32000));
+        assert_eq!(0, apply(deadzone.clone(), 500));
+        assert_eq!(24576, apply(deadzone, 16384 + 1000 + 7192));
+
+        let curve = AxisStage::Curve { exponent: 2.0 };
+        assert_eq!(16384, apply(curve.clone(), 16384));
+        assert_eq!(32767, apply(curve.clone(), 32767));
+        assert_eq!(20480, apply(curve.clone(), 24576));
+        assert_eq!(12288, apply(curve, 8192));
+
+        let range = AxisStage::Range {
+            min: 1000,
+            max: 2000,
+        };
+        assert_eq!(0, apply(range.clone(), 500));
+        assert_eq!(16384, apply(range.clone(), 1500));
+        assert_eq!(32767, apply(range, 2500));
+
+        let combine = AxisStage::Combine {
+            operator: AxisCombine::Max,
+            operands: vec![8000],
+        };
+        assert_eq!(8000, apply(combine.clone(), 100));
+        assert_eq!(20000, apply(combine, 20000));
+
+        let half = AxisStage::Half {
+            half: AxisHalf::Upper,
+        };
+        assert_eq!(0, apply(half.clone(), 100));
+        assert_eq!(32767, apply(half, 32767));
+    }
+
+    #[test]
+    fn operators_are_stages() {
+        let text = PROFILE.replace(
+            "{ type = \"invert\" }",
+            "{ type = \"threshold\", threshold = 20000, direction = \"below\" }",
+        );
+        let profile = Profile::parse(&text).unwrap();
+        assert_eq!(
+            AxisStage::Threshold {
+                threshold: 20000,
+                hysteresis: 0,
+                direction: ThresholdDirection::Below,
+            },
+            profile.axes()[0].chain[0]
+        );
+
+        let text = PROFILE.replace("{ type = \"invert\" }", "{ type = \"step\", step = 0 }");
+        assert!(matches!(
+            error_of(&text).kind,
+            ProfileErrorKind::InvalidStage(FilterError::InvalidParameter("step"))
+        ));
+    }
+}
+
+use super::combine::{AxisCombine, AxisHalf, AxisThreshold, StepAxis, ThresholdDirection};
+use super::filter::{
+    AxisFilter, EmaFilter, FilterError, HysteresisFilter, MedianFilter, OneEuroFilter,
+    SlewRateLimiter,
+};
+use crate::vjoy_base::device::axis::{VJDAxisRaw, VJDAxisSigned};
+use crate::vjoy_base::device::{VJDAxis, VJDButton, VJDPovNumber, VJDevice};
+use crate::vjoy_base::driver::VJGeneral;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use toml::Spanned;
+
+/**
+    Describes where a [`ProfileError`] was found.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ProfileLocation {
+    /// Path of the faulty entry, such as `axes[2].chain[1]`. Empty when the profile cannot be
+    /// parsed at all.
+    pub path: String,
+
+    /// Line of the entry in the file, starting at 1, when known.
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for ProfileLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.path.is_empty(), self.line) {
+            (false, Some(line)) => write!(f, "{} (line {})", self.path, line),
+            (false, None) => write!(f, "{}", self.path),
+            (true, Some(line)) => write!(f, "line {}", line),
+            (true, None) => write!(f, "profile"),
+        }
+    }
+}
+
+/**
+    Describes the kind of a [`ProfileError`].
+*/
+#[derive(Debug)]
+pub enum ProfileErrorKind {
+    /// The profile file cannot be read. [`std::io::Error`] is provided for further
+    /// investigation.
+    Io(io::Error),
+
+    /// The text is not valid TOML or does not follow the profile format. The message of the
+    /// parser is provided.
+    Syntax(String),
+
+    /// A mapping refers to an input which is not declared. The name is provided.
+    UnknownInput(String),
+
+    /// A mapping refers to an input of another kind.
+    WrongInputKind {
+        input: String,
+        expected: InputKind,
+        found: InputKind,
+    },
+
+    /// The button number is not in the range [1, [`VJDButton::MAX_BUTTONS`]]. The number is
+    /// provided.
+    InvalidButton(u8),
+
+    /// The control is already the target of another mapping. The name of the control and the
+    /// path of the other mapping are provided.
+    DuplicateTarget { target: String, other: String },
+
+    /// A stage of an axis chain has an invalid parameter.
+    InvalidStage(FilterError),
+}
+
+/**
+    Describes an error state of [`Profile`] parsing, with its location in the profile.
+*/
+#[derive(Debug)]
+pub struct ProfileError {
+    pub location: ProfileLocation,
+    pub kind: ProfileErrorKind,
+}
+
+impl ProfileError {
+    fn new(path: String, line: Option<usize>, kind: ProfileErrorKind) -> ProfileError {
+        ProfileError {
+            location: ProfileLocation { path, line },
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.location)?;
+
+        match &self.kind {
+            ProfileErrorKind::Io(error) => write!(f, "{}", error),
+            ProfileErrorKind::Syntax(message) => write!(f, "{}", message),
+            ProfileErrorKind::UnknownInput(name) => write!(f, "unknown input `{}`", name),
+            ProfileErrorKind::WrongInputKind {
+                input,
+                expected,
+                found,
+            } => write!(
+                f,
+                "input `{}` is {:?}, expected {:?}",
+                input, found, expected
+            ),
+            ProfileErrorKind::InvalidButton(number) => {
+                write!(f, "button {} does not exist", number)
+            }
+            ProfileErrorKind::DuplicateTarget { target, other } => {
+                write!(f, "{} is already the target of {}", target, other)
+            }
+            ProfileErrorKind::InvalidStage(error) => write!(f, "invalid stage: {:?}", error),
+        }
+    }
+}
+
+/**
+    Describes the kind of an input of a profile, and so the value it is fed with.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputKind {
+    /// An axis, fed with vJoy units.
+    Axis,
+
+    /// A button, fed with its pressed state.
+    Button,
+
+    /// A POV hat, fed with a [`VJDPovCont`](crate::vjoy_base::device::pov::VJDPovCont).
+    Pov,
+}
+
+/**
+    Identifies an input of a [`Profile`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InputId(usize);
+
+impl InputId {
+    /// Returns the position of the input in [`Profile::inputs`].
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/**
+    Describes a declared input of a [`Profile`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProfileInput {
+    pub name: String,
+    pub kind: InputKind,
+}
+
+/**
+    Describes a stage of the processing chain of an axis. Stages are applied in order and the
+    final value is clamped to the vJoy axis range.
+*/
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AxisStage {
+    /// Reverses the direction of the axis.
+    Invert,
+
+    /// Snaps values within `center` vJoy units of the neutral point to neutral, and values
+    /// within `edges` units of an end to that end. Values in between are stretched over the
+    /// whole range.
+    Deadzone {
+        #[serde(default)]
+        center: i32,
+
+        #[serde(default)]
+        edges: i32,
+    },
+
+    /// Raises the distance to the neutral point, from 0.0 to 1.0, to the power of `exponent`.
+    /// Above 1.0 the axis gets more precise around the neutral point.
+    Curve { exponent: f64 },
+
+    /// Stretches the range from `min` to `max` over the whole vJoy range, to calibrate an axis
+    /// which does not reach its ends.
+    Range { min: i32, max: i32 },
+
+    /// See [`EmaFilter`].
+    Ema { time_constant_ms: u64 },
+
+    /// See [`OneEuroFilter`].
+    OneEuro {
+        min_cutoff: f64,
+
+        #[serde(default)]
+        beta: f64,
+
+        #[serde(default = "AxisStage::default_derivative_cutoff")]
+        derivative_cutoff: f64,
+    },
+
+    /// See [`MedianFilter`].
+    Median { size: usize },
+
+    /// See [`SlewRateLimiter`].
+    Slew { max_rate: f64 },
+
+    /// See [`HysteresisFilter`].
+    Hysteresis { threshold: i32 },
+
+    /// Combines the value with constant `operands`, see [`AxisCombine::with`].
+    Combine {
+        operator: AxisCombine,
+        operands: Vec<i32>,
+    },
+
+    /// Keeps one half of the axis, see [`AxisHalf`].
+    Half { half: AxisHalf },
+
+    /// Moves to the maximum past `threshold` and back to the minimum, see [`AxisThreshold`].
+    Threshold {
+        threshold: i32,
+
+        #[serde(default)]
+        hysteresis: i32,
+
+        #[serde(default = "AxisStage::default_direction")]
+        direction: ThresholdDirection,
+    },
+
+    /// Steps a trim from `initial` by `step` when the value is pushed to an end, see
+    /// [`StepAxis`].
+    Step {
+        #[serde(default = "AxisStage::default_initial")]
+        initial: i32,
+
+        step: i32,
+    },
+}
+
+impl AxisStage {
+    fn default_derivative_cutoff() -> f64 {
+        1.0
+    }
+
+    fn default_direction() -> ThresholdDirection {
+        ThresholdDirection::Above
+    }
+
+    fn default_initial() -> i32 {
+        VJGeneral::NEUTRAL_AXIS_VALUE
+    }
+
+    /**
+        Returns a new instance of the stage, or [`FilterError::InvalidParameter`] if a parameter
+        is out of its range.
+    */
+    pub fn build(&self) -> Result<Box<dyn AxisFilter>, FilterError> {
+        let half_span = VJGeneral::MAX_AXIS_VALUE - VJGeneral::NEUTRAL_AXIS_VALUE;
+
+        Ok(match *self {
+            AxisStage::Invert => Box::new(Invert),
+            AxisStage::Deadzone { center, edges } => {
+                if center < 0 || center >= half_span {
+                    return Err(FilterError::InvalidParameter("center"));
+                }
+                if edges < 0 || center + edges >= half_span {
+                    return Err(FilterError::InvalidParameter("edges"));
+                }
+
+                Box::new(Deadzone {
+                    center: center as f32 / half_span as f32,
+                    edges: edges as f32 / half_span as f32,
+                })
+            }
+            AxisStage::Curve { exponent } => {
+                if !exponent.is_finite() || exponent <= 0.0 {
+                    return Err(FilterError::InvalidParameter("exponent"));
+                }
+
+                Box::new(Curve { exponent })
+            }
+            AxisStage::Range { min, max } => {
+                if min < VJGeneral::MIN_AXIS_VALUE || min >= max {
+                    return Err(FilterError::InvalidParameter("min"));
+                }
+                if max > VJGeneral::MAX_AXIS_VALUE {
+                    return Err(FilterError::InvalidParameter("max"));
+                }
+
+                Box::new(Range { min, max })
+            }
+            AxisStage::Ema { time_constant_ms } => {
+                Box::new(EmaFilter::new(Duration::from_millis(time_constant_ms)))
+            }
+            AxisStage::OneEuro {
+                min_cutoff,
+                beta,
+                derivative_cutoff,
+            } => Box::new(OneEuroFilter::new(min_cutoff, beta, derivative_cutoff)?),
+            AxisStage::Median { size } => Box::new(MedianFilter::new(size)?),
+            AxisStage::Slew { max_rate } => Box::new(SlewRateLimiter::new(max_rate)?),
+            AxisStage::Hysteresis { threshold } => Box::new(HysteresisFilter::new(threshold)?),
+            AxisStage::Combine {
+                operator,
+                ref operands,
+            } => Box::new(operator.with(operands.clone())),
+            AxisStage::Half { half } => Box::new(half),
+            AxisStage::Threshold {
+                threshold,
+                hysteresis,
+                direction,
+            } => Box::new(AxisThreshold::new(threshold, hysteresis, direction)?),
+            AxisStage::Step { initial, step } => Box::new(StepAxis::new(initial, step)?),
+        })
+    }
+}
+
+/// Applies a function to the value as a [`VJDAxisSigned`].
+fn map_signed(value: i32, map: impl Fn(f32) -> f32) -> i32 {
+    let signed = VJDAxisSigned::from(VJDAxisRaw::saturating(value)).get();
+
+    VJDAxisRaw::from(VJDAxisSigned::saturating(map(signed))).get()
+}
+
+struct Invert;
+
+impl AxisFilter for Invert {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        VJGeneral::MAX_AXIS_VALUE - value + VJGeneral::MIN_AXIS_VALUE
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Widths of [`AxisStage::Deadzone`] as fractions of half the axis.
+struct Deadzone {
+    center: f32,
+    edges: f32,
+}
+
+impl AxisFilter for Deadzone {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        map_signed(value, |signed| {
+            let stretched = (signed.abs() - self.center) / (1.0 - self.center - self.edges);
+
+            stretched.clamp(0.0, 1.0).copysign(signed)
+        })
+    }
+
+    fn reset(&mut self) {}
+}
+
+struct Curve {
+    exponent: f64,
+}
+
+impl AxisFilter for Curve {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        map_signed(value, |signed| {
+            (signed.abs() as f64)
+                .powf(self.exponent)
+                .copysign(signed as f64) as f32
+        })
+    }
+
+    fn reset(&mut self) {}
+}
+
+struct Range {
+    min: i32,
+    max: i32,
+}
+
+impl AxisFilter for Range {
+    fn filter(&mut self, value: i32, _timestamp: Duration) -> i32 {
+        let span = (VJGeneral::MAX_AXIS_VALUE - VJGeneral::MIN_AXIS_VALUE) as f64;
+        let ratio = (value - self.min) as f64 / (self.max - self.min) as f64;
+
+        (ratio.clamp(0.0, 1.0) * span).round() as i32 + VJGeneral::MIN_AXIS_VALUE
+    }
+
+    fn reset(&mut self) {}
+}
+
+/**
+    Describes how the state of a button input is turned into the state of its target.
+*/
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ButtonBehaviour {
+    /// The target is pressed while the input is pressed.
+    #[default]
+    Momentary,
+
+    /// The target is pressed while the input is released.
+    Inverted,
+
+    /// Each press of the input flips the target.
+    Toggle,
+}
+
+/**
+    Routes an axis input, through a processing chain, to an axis of a device.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisMapping {
+    pub input: InputId,
+    pub device: VJDevice,
+    pub axis: VJDAxis,
+    pub chain: Vec<AxisStage>,
+}
+
+/**
+    Routes a button input to a button of a device.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ButtonMapping {
+    pub input: InputId,
+    pub device: VJDevice,
+    pub button: VJDButton,
+    pub behaviour: ButtonBehaviour,
+}
+
+/**
+    Routes a POV input to a POV of a device, discrete or continuous.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PovMapping {
+    pub input: InputId,
+    pub device: VJDevice,
+    pub pov: VJDPovNumber,
+    pub discrete: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProfile {
+    #[serde(default)]
+    inputs: BTreeMap<String, InputKind>,
+
+    #[serde(default)]
+    axes: Vec<RawAxis>,
+
+    #[serde(default)]
+    buttons: Vec<RawButton>,
+
+    #[serde(default)]
+    povs: Vec<RawPov>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawAxis {
+    input: Spanned<String>,
+    device: VJDevice,
+    axis: VJDAxis,
+
+    #[serde(default)]
+    chain: Vec<Spanned<AxisStage>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawButton {
+    input: Spanned<String>,
+    device: VJDevice,
+    button: u8,
+
+    #[serde(default)]
+    behaviour: ButtonBehaviour,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPov {
+    input: Spanned<String>,
+    device: VJDevice,
+    pov: VJDPovNumber,
+
+    #[serde(default)]
+    discrete: bool,
+}
+
+/// Describes a control of a device, to find controls targeted twice.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Target {
+    Axis(VJDevice, VJDAxis),
+    Button(VJDevice, VJDButton),
+    Pov(VJDevice, VJDPovNumber),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Axis(device, axis) => write!(f, "{:?} {:?}", device, axis),
+            Target::Button(device, button) => write!(f, "{:?} button {}", device, *button as u8),
+            Target::Pov(device, pov) => write!(f, "{:?} {:?}", device, pov),
+        }
+    }
+}
+
+/// Resolves the references of a parsed profile.
+struct Validator<'a> {
+    text: &'a str,
+    inputs: Vec<ProfileInput>,
+    targets: Vec<(Target, String)>,
+}
+
+impl<'a> Validator<'a> {
+    /// Returns the line of a byte offset of the text, starting at 1.
+    fn line(&self, offset: usize) -> usize {
+        self.text[..offset.min(self.text.len())]
+            .matches('\n')
+            .count()
+            + 1
+    }
+
+    fn input(
+        &self,
+        path: &str,
+        name: &Spanned<String>,
+        expected: InputKind,
+    ) -> Result<InputId, ProfileError> {
+        let line = Some(self.line(name.start()));
+        let error = |kind| Err(ProfileError::new(format!("{}.input", path), line, kind));
+
+        match self
+            .inputs
+            .iter()
+            .position(|input| input.name == *name.get_ref())
+        {
+            None => error(ProfileErrorKind::UnknownInput(name.get_ref().clone())),
+            Some(index) if self.inputs[index].kind != expected => {
+                error(ProfileErrorKind::WrongInputKind {
+                    input: name.get_ref().clone(),
+                    expected,
+                    found: self.inputs[index].kind,
+                })
+            }
+            Some(index) => Ok(InputId(index)),
+        }
+    }
+
+    fn target(&mut self, path: &str, line: usize, target: Target) -> Result<(), ProfileError> {
+        if let Some((_, other)) = self.targets.iter().find(|(other, _)| *other == target) {
+            return Err(ProfileError::new(
+                path.to_string(),
+                Some(line),
+                ProfileErrorKind::DuplicateTarget {
+                    target: target.to_string(),
+                    other: other.clone(),
+                },
+            ));
+        }
+
+        self.targets.push((target, path.to_string()));
+
+        Ok(())
+    }
+}
+
+/**
+    Holds a validated mapping profile: declared inputs and the mappings routing them to the
+    controls of vJoy devices. Each control is the target of at most one mapping.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    inputs: Vec<ProfileInput>,
+    axes: Vec<AxisMapping>,
+    buttons: Vec<ButtonMapping>,
+    povs: Vec<PovMapping>,
+
+    /// Devices targeted by the mappings, ordered by number.
+    devices: Vec<VJDevice>,
+}
+
+impl Profile {
+    /// Returns the profile read from a TOML text, or the first [`ProfileError`] found.
+    pub fn parse(text: &str) -> Result<Profile, ProfileError> {
+        let raw: RawProfile = toml::from_str(text).map_err(|error| {
+            let line = error.line_col().map(|(line, _)| line + 1);
+
+            ProfileError::new(
+                String::new(),
+                line,
+                ProfileErrorKind::Syntax(error.to_string()),
+            )
+        })?;
+
+        let mut validator = Validator {
+            text,
+            inputs: raw
+                .inputs
+                .into_iter()
+                .map(|(name, kind)| ProfileInput { name, kind })
+                .collect(),
+            targets: Vec::new(),
+        };
+        let mut profile = Profile {
+            inputs: Vec::new(),
+            axes: Vec::new(),
+            buttons: Vec::new(),
+            povs: Vec::new(),
+            devices: Vec::new(),
+        };
+
+        for (i, raw) in raw.axes.into_iter().enumerate() {
+            let path = format!("axes[{}]", i);
+            let line = validator.line(raw.input.start());
+            let input = validator.input(&path, &raw.input, InputKind::Axis)?;
+
+            for (j, stage) in raw.chain.iter().enumerate() {
+                stage.get_ref().build().map_err(|error| {
+                    ProfileError::new(
+                        format!("{}.chain[{}]", path, j),
+                        Some(validator.line(stage.start())),
+                        ProfileErrorKind::InvalidStage(error),
+                    )
+                })?;
+            }
+
+            validator.target(&path, line, Target::Axis(raw.device, raw.axis))?;
+            profile.axes.push(AxisMapping {
+                input,
+                device: raw.device,
+                axis: raw.axis,
+                chain: raw.chain.into_iter().map(Spanned::into_inner).collect(),
+            });
+        }
+
+        for (i, raw) in raw.buttons.into_iter().enumerate() {
+            let path = format!("buttons[{}]", i);
+            let line = validator.line(raw.input.start());
+            let input = validator.input(&path, &raw.input, InputKind::Button)?;
+            let button = VJDButton::get_from(raw.button).ok_or_else(|| {
+                ProfileError::new(
+                    format!("{}.button", path),
+                    Some(line),
+                    ProfileErrorKind::InvalidButton(raw.button),
+                )
+            })?;
+
+            validator.target(&path, line, Target::Button(raw.device, button))?;
+            profile.buttons.push(ButtonMapping {
+                input,
+                device: raw.device,
+                button,
+                behaviour: raw.behaviour,
+            });
+        }
+
+        for (i, raw) in raw.povs.into_iter().enumerate() {
+            let path = format!("povs[{}]", i);
+            let line = validator.line(raw.input.start());
+            let input = validator.input(&path, &raw.input, InputKind::Pov)?;
+
+            validator.target(&path, line, Target::Pov(raw.device, raw.pov))?;
+            profile.povs.push(PovMapping {
+                input,
+                device: raw.device,
+                pov: raw.pov,
+                discrete: raw.discrete,
+            });
+        }
+
+        profile.inputs = validator.inputs;
+        profile.devices = profile
+            .axes
+            .iter()
+            .map(|mapping| mapping.device)
+            .chain(profile.buttons.iter().map(|mapping| mapping.device))
+            .chain(profile.povs.iter().map(|mapping| mapping.device))
+            .collect();
+        profile.devices.sort_by_key(|&device| device as u32);
+        profile.devices.dedup();
+
+        Ok(profile)
+    }
+
+    /// Returns the profile read from a TOML file, or the first [`ProfileError`] found.
+    pub fn load(path: &Path) -> Result<Profile, ProfileError> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| ProfileError::new(String::new(), None, ProfileErrorKind::Io(error)))?;
+
+        Profile::parse(&text)
+    }
+
+    /// Returns the declared inputs, ordered by name.
+    pub fn inputs(&self) -> &[ProfileInput] {
+        &self.inputs
+    }
+
+    pub fn input(&self, id: InputId) -> &ProfileInput {
+        &self.inputs[id.0]
+    }
+
+    /// Returns the identifier of an input, or [`None`] if it is not declared.
+    pub fn input_id(&self, name: &str) -> Option<InputId> {
+        self.inputs
+            .iter()
+            .position(|input| input.name == name)
+            .map(InputId)
+    }
+
+    pub fn axes(&self) -> &[AxisMapping] {
+        &self.axes
+    }
+
+    pub fn buttons(&self) -> &[ButtonMapping] {
+        &self.buttons
+    }
+
+    pub fn povs(&self) -> &[PovMapping] {
+        &self.povs
+    }
+
+    /// Returns the devices targeted by the profile, ordered by number.
+    pub fn devices(&self) -> &[VJDevice] {
+        &self.devices
+    }
+}
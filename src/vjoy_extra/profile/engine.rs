@@ -0,0 +1,394 @@
+//! Evaluates a [`Profile`] to produce the positions of its devices.
+//!
+//! The application feeds the inputs of the profile with [`ProfileEngine::set_input`], then calls
+//! [`ProfileEngine::tick`] at its own rate and sends the resulting positions, for instance with
+//! [`VJDPosFeed`](crate::vjoy_base::device::feeding::VJDPosFeed). The timestamp of each tick is
+//! given by the caller, like the timestamps of [`filter`](crate::vjoy_extra::filter).
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::{VJDAxis, VJDButton, VJDPovNumber};
+
+    const PROFILE: &str = r#"
+[inputs]
+throttle = "axis"
+rudder = "axis"
+fire = "button"
+gear = "button"
+hat = "pov"
+
+[[axes]]
+input = "throttle"
+device = "D1"
+axis = "Slider1"
+chain = [{ type = "invert" }]
+
+[[axes]]
+input = "rudder"
+device = "D2"
+axis = "Rz"
+chain = [{ type = "slew", max_rate = 1000.0 }]
+
+[[buttons]]
+input = "fire"
+device = "D1"
+button = 2
+
+[[buttons]]
+input = "gear"
+device = "D1"
+button = 7
+behaviour = "toggle"
+
+[[povs]]
+input = "hat"
+device = "D2"
+pov = "Pov2"
+discrete = true
+"#;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn engine() -> ProfileEngine {
+        ProfileEngine::new(Profile::parse(PROFILE).unwrap())
+    }
+
+    #[test]
+    fn positions_follow_inputs() {
+        let mut engine = engine();
+
+        assert!(engine.set_input("throttle", InputValue::Axis(1000)));
+        assert!(engine.set_input("fire", InputValue::Button(true)));
+        assert!(engine.set_input("hat", InputValue::Pov(VJDPovCont::new(9000).unwrap())));
+
+        let positions = engine.tick(ms(0));
+        assert_eq!(2, positions.len());
+        assert_eq!(VJDevice::D1, positions[0].get_device());
+        assert_eq!(31767, positions[0].get_axis(VJDAxis::Slider1).get());
+        assert!(positions[0].get_buttons().contains(VJDButton::B2));
+        assert!(!positions[0].get_buttons().contains(VJDButton::B7));
+        assert_eq!(
+            VJDPovDisc::East,
+            positions[1].get_disc_pov(VJDPovNumber::Pov2)
+        );
+
+        engine.set_input("fire", InputValue::Button(false));
+        assert!(engine.tick(ms(10))[0].get_buttons().is_empty());
+        assert_eq!(Some(&engine.positions()[1]), engine.position(VJDevice::D2));
+        assert_eq!(None, engine.position(VJDevice::D3));
+    }
+
+    #[test]
+    fn invalid_inputs_are_rejected() {
+        let mut engine = engine();
+
+        assert!(!engine.set_input("brake", InputValue::Axis(0)));
+        assert!(!engine.set_input("fire", InputValue::Axis(0)));
+        assert!(!engine.set_input("throttle", InputValue::Axis(40000)));
+    }
+
+    #[test]
+    fn toggle_flips_on_press() {
+        let mut engine = engine();
+        let mut gear = |pressed, time| {
+            engine.set_input("gear", InputValue::Button(pressed));
+            engine.tick(ms(time))[0]
+                .get_buttons()
+                .contains(VJDButton::B7)
+        };
+
+        assert!(gear(true, 0));
+        assert!(gear(true, 10));
+        assert!(gear(false, 20));
+        assert!(!gear(true, 30));
+        assert!(!gear(false, 40));
+    }
+
+    #[test]
+    fn chains_keep_state_between_ticks() {
+        let mut engine = engine();
+
+        engine.set_input("rudder", InputValue::Axis(0));
+        engine.tick(ms(0));
+        engine.set_input("rudder", InputValue::Axis(32767));
+        assert_eq!(100, engine.tick(ms(100))[1].get_axis(VJDAxis::Rz).get());
+
+        engine.reset();
+        assert_eq!(
+            VJGeneral::NEUTRAL_AXIS_VALUE,
+            engine.tick(ms(200))[1].get_axis(VJDAxis::Rz).get()
+        );
+    }
+}
+
+use super::{ButtonBehaviour, InputId, InputKind, Profile};
+use crate::vjoy_base::device::pov::{VJDPovCont, VJDPovDirection};
+use crate::vjoy_base::device::{VJDPosition, VJDPovDisc, VJDevice};
+use crate::vjoy_base::driver::VJGeneral;
+use crate::vjoy_extra::filter::{AxisFilter, FilterChain};
+use std::time::Duration;
+
+/**
+    Describes the value of an input of a profile.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum InputValue {
+    /// Value of an axis, in vJoy units.
+    Axis(i32),
+
+    /// Pressed state of a button.
+    Button(bool),
+
+    /// Value of a POV hat.
+    Pov(VJDPovCont),
+}
+
+impl InputValue {
+    /// Returns the value of an input of a kind before it is first fed.
+    pub fn neutral(kind: InputKind) -> InputValue {
+        match kind {
+            InputKind::Axis => InputValue::Axis(VJGeneral::NEUTRAL_AXIS_VALUE),
+            InputKind::Button => InputValue::Button(false),
+            InputKind::Pov => InputValue::Pov(VJDPovCont::NEUTRAL),
+        }
+    }
+
+    pub fn kind(self) -> InputKind {
+        match self {
+            InputValue::Axis(_) => InputKind::Axis,
+            InputValue::Button(_) => InputKind::Button,
+            InputValue::Pov(_) => InputKind::Pov,
+        }
+    }
+}
+
+/// State of a button mapping between ticks.
+#[derive(Debug, Copy, Clone, Default)]
+struct ButtonState {
+    pressed: bool,
+    latched: bool,
+}
+
+/**
+    Evaluates a [`Profile`]: holds the current value of each input and the state of each
+    mapping, and produces one [`VJDPosition`] per targeted device on each tick.
+
+    Controls which are not the target of a mapping keep the value of [`VJDPosition::new`].
+*/
+pub struct ProfileEngine {
+    profile: Profile,
+    inputs: Vec<InputValue>,
+    chains: Vec<FilterChain>,
+    buttons: Vec<ButtonState>,
+    povs: Vec<VJDPovDisc>,
+    positions: Vec<VJDPosition>,
+}
+
+impl ProfileEngine {
+    pub fn new(profile: Profile) -> ProfileEngine {
+        let inputs = profile
+            .inputs()
+            .iter()
+            .map(|input| InputValue::neutral(input.kind))
+            .collect();
+        let chains = profile
+            .axes()
+            .iter()
+            .map(|mapping| {
+                // Stages are checked when the profile is parsed.
+                mapping
+                    .chain
+                    .iter()
+                    .fold(FilterChain::new(), |mut chain, stage| {
+                        chain.push_boxed(stage.build().expect("stage of a validated profile"));
+                        chain
+                    })
+            })
+            .collect();
+        let positions = profile
+            .devices()
+            .iter()
+            .map(|&device| VJDPosition::new(device))
+            .collect();
+
+        ProfileEngine {
+            inputs,
+            chains,
+            buttons: vec![ButtonState::default(); profile.buttons().len()],
+            povs: vec![VJDPovDisc::Neutral; profile.povs().len()],
+            positions,
+            profile,
+        }
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /**
+        Sets the value of an input, used from the next tick. Returns `false` if the input is not
+        declared, if the value is not of the kind of the input, or if an axis value is out of the
+        vJoy range.
+    */
+    pub fn set_input(&mut self, name: &str, value: InputValue) -> bool {
+        match self.profile.input_id(name) {
+            Some(id) => self.set_input_by_id(id, value),
+            None => false,
+        }
+    }
+
+    /// Same as [`ProfileEngine::set_input`] with the identifier of the input.
+    pub fn set_input_by_id(&mut self, id: InputId, value: InputValue) -> bool {
+        let valid = match value {
+            InputValue::Axis(value) => {
+                (VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE).contains(&value)
+            }
+            _ => true,
+        };
+
+        match self.inputs.get_mut(id.index()) {
+            Some(input) if valid && input.kind() == value.kind() => {
+                *input = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the current value of an input.
+    pub fn input(&self, id: InputId) -> InputValue {
+        self.inputs[id.index()]
+    }
+
+    /**
+        Evaluates every mapping with the current inputs and returns the new positions, one per
+        device of [`Profile::devices`] in the same order.
+    */
+    pub fn tick(&mut self, timestamp: Duration) -> &[VJDPosition] {
+        let devices = self.profile.devices();
+        let index = |device| {
+            devices
+                .binary_search_by_key(&(device as u32), |&other| other as u32)
+                .unwrap()
+        };
+
+        for position in &mut self.positions {
+            *position = VJDPosition::new(position.get_device());
+        }
+
+        for (mapping, chain) in self.profile.axes().iter().zip(&mut self.chains) {
+            if let InputValue::Axis(value) = self.inputs[mapping.input.index()] {
+                let value = chain.filter(value, timestamp);
+                self.positions[index(mapping.device)].set_axis(mapping.axis, value);
+            }
+        }
+
+        for (mapping, state) in self.profile.buttons().iter().zip(&mut self.buttons) {
+            if let InputValue::Button(pressed) = self.inputs[mapping.input.index()] {
+                if pressed && !state.pressed {
+                    state.latched = !state.latched;
+                }
+                state.pressed = pressed;
+
+                let output = match mapping.behaviour {
+                    ButtonBehaviour::Momentary => pressed,
+                    ButtonBehaviour::Inverted => !pressed,
+                    ButtonBehaviour::Toggle => state.latched,
+                };
+
+                if output {
+                    self.positions[index(mapping.device)].set_button_pressed(mapping.button as u32);
+                }
+            }
+        }
+
+        for (mapping, previous) in self.profile.povs().iter().zip(&mut self.povs) {
+            if let InputValue::Pov(value) = self.inputs[mapping.input.index()] {
+                let position = &mut self.positions[index(mapping.device)];
+
+                if mapping.discrete {
+                    *previous = VJDPovDirection::from(value).to_disc_from(*previous);
+                    position.set_disc_pov(mapping.pov, *previous);
+                } else {
+                    position.set_cont_pov(mapping.pov, value);
+                }
+            }
+        }
+
+        &self.positions
+    }
+
+    /// Returns the positions computed by the last tick.
+    pub fn positions(&self) -> &[VJDPosition] {
+        &self.positions
+    }
+
+    /// Returns the position of a device computed by the last tick, or [`None`] if the device is
+    /// not targeted by the profile.
+    pub fn position(&self, device: VJDevice) -> Option<&VJDPosition> {
+        self.positions
+            .iter()
+            .find(|position| position.get_device() == device)
+    }
+
+    /**
+        Takes over the state of the engine of a previous profile: the value of each input declared
+        in both profiles with the same kind, and the state of each button and POV mapping with the
+        same input and target. Processing chains start afresh.
+    */
+    pub fn inherit(&mut self, previous: &ProfileEngine) {
+        for (i, input) in self.profile.inputs().iter().enumerate() {
+            if let Some(id) = previous.profile.input_id(&input.name) {
+                if previous.profile.input(id).kind == input.kind {
+                    self.inputs[i] = previous.input(id);
+                }
+            }
+        }
+
+        let profile = &self.profile;
+        let same_input = |input: InputId, other: InputId| {
+            profile.input(input).name == previous.profile.input(other).name
+        };
+
+        for (mapping, state) in profile.buttons().iter().zip(&mut self.buttons) {
+            let found = previous.profile.buttons().iter().position(|other| {
+                (other.device, other.button) == (mapping.device, mapping.button)
+                    && same_input(mapping.input, other.input)
+            });
+
+            if let Some(index) = found {
+                *state = previous.buttons[index];
+            }
+        }
+
+        for (mapping, disc) in profile.povs().iter().zip(&mut self.povs) {
+            let found = previous.profile.povs().iter().position(|other| {
+                (other.device, other.pov) == (mapping.device, mapping.pov)
+                    && same_input(mapping.input, other.input)
+            });
+
+            if let Some(index) = found {
+                *disc = previous.povs[index];
+            }
+        }
+    }
+
+    /// Forgets the state of every mapping and sets every input back to neutral.
+    pub fn reset(&mut self) {
+        for (value, input) in self.inputs.iter_mut().zip(self.profile.inputs()) {
+            *value = InputValue::neutral(input.kind);
+        }
+        for chain in &mut self.chains {
+            chain.reset();
+        }
+        for state in &mut self.buttons {
+            *state = ButtonState::default();
+        }
+        for previous in &mut self.povs {
+            *previous = VJDPovDisc::Neutral;
+        }
+    }
+}
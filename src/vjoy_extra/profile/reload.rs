@@ -0,0 +1,436 @@
+//! Reloads a [`Profile`] when its file changes, without interrupting the devices it feeds.
+//!
+//! A [`ProfileReloader`] wraps a [`ProfileEngine`]. Each call to [`ProfileReloader::reload`]
+//! polls a [`ProfileSource`] and, when the text changed, validates the new profile and swaps the
+//! engine in one step: the next tick is entirely computed with either the previous or the new
+//! profile. An invalid profile is reported and the previous one is kept.
+//!
+//! The reloader never acquires nor relinquishes devices: ownership stays with the application,
+//! so games do not see the controller disappear. Input values and the state of unchanged button
+//! and POV mappings are carried over (see [`ProfileEngine::inherit`]), and each axis fed by both
+//! profiles moves from its previous value to its new one over a transition, instead of jumping.
+//! A device the new profile no longer targets is set back to neutral once.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::VJDButton;
+    use crate::vjoy_extra::profile::engine::InputValue;
+    use std::sync::{Arc, Mutex};
+
+    /// Source whose text is changed by the test through a shared handle.
+    #[derive(Clone, Default)]
+    struct SharedSource(Arc<Mutex<Option<String>>>);
+
+    impl SharedSource {
+        fn set(&self, text: &str) {
+            *self.0.lock().unwrap() = Some(text.to_string());
+        }
+    }
+
+    impl ProfileSource for SharedSource {
+        fn poll(&mut self) -> io::Result<Option<String>> {
+            Ok(self.0.lock().unwrap().take())
+        }
+    }
+
+    fn profile(exponent: f64) -> String {
+        format!(
+            "[inputs]\nstick = \"axis\"\ngear = \"button\"\n\n\
+             [[axes]]\ninput = \"stick\"\ndevice = \"D1\"\naxis = \"X\"\n\
+             chain = [{{ type = \"curve\", exponent = {:.1} }}]\n\n\
+             [[buttons]]\ninput = \"gear\"\ndevice = \"D1\"\nbutton = 1\nbehaviour = \"toggle\"\n",
+            exponent
+        )
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn x(reloader: &mut ProfileReloader<SharedSource>, time: u64) -> i32 {
+        reloader.tick(ms(time))[0].get_axis(VJDAxis::X).get()
+    }
+
+    fn reloader() -> (SharedSource, ProfileReloader<SharedSource>) {
+        let source = SharedSource::default();
+        source.set(&profile(1.0));
+
+        let reloader = ProfileReloader::new(source.clone(), ms(100)).unwrap();
+
+        (source, reloader)
+    }
+
+    #[test]
+    fn file_changes_are_polled() {
+        let path = std::env::temp_dir().join(format!("vjoy-profile-{}.toml", std::process::id()));
+        let mut source = FileProfileSource::new(&path);
+
+        assert!(source.poll().is_err());
+
+        fs::write(&path, "[inputs]\n").unwrap();
+        assert_eq!(Some("[inputs]\n".to_string()), source.poll().unwrap());
+        assert_eq!(None, source.poll().unwrap());
+
+        fs::write(&path, "[inputs]\nstick = \"axis\"\n").unwrap();
+        assert!(source.poll().unwrap().is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn initial_profile_is_required() {
+        let source = SharedSource::default();
+        assert!(matches!(
+            ProfileReloader::new(source.clone(), ms(100)),
+            Err(ProfileError {
+                kind: ProfileErrorKind::Io(_),
+                ..
+            })
+        ));
+
+        source.set("[inputs]\nstick = \"wheel\"\n");
+        assert!(ProfileReloader::new(source, ms(100)).is_err());
+    }
+
+    #[test]
+    fn axes_move_smoothly_to_the_new_profile() {
+        let (source, mut reloader) = reloader();
+
+        reloader
+            .engine_mut()
+            .set_input("stick", InputValue::Axis(24576));
+        assert_eq!(24576, x(&mut reloader, 0));
+        assert_eq!(ReloadStatus::Unchanged, reloader.reload());
+
+        // The curve maps 24576 to 20480: the value moves there over 100ms.
+        source.set(&profile(2.0));
+        assert_eq!(ReloadStatus::Reloaded, reloader.reload());
+        assert_eq!(24576, x(&mut reloader, 1000));
+        assert_eq!(22528, x(&mut reloader, 1050));
+        assert_eq!(20480, x(&mut reloader, 1100));
+        assert_eq!(20480, x(&mut reloader, 1200));
+    }
+
+    #[test]
+    fn invalid_profile_is_rolled_back() {
+        let (source, mut reloader) = reloader();
+
+        reloader
+            .engine_mut()
+            .set_input("stick", InputValue::Axis(24576));
+        x(&mut reloader, 0);
+
+        source.set(&profile(1.0).replace("button = 1", "button = 0"));
+        assert_eq!(ReloadStatus::RolledBack, reloader.reload());
+        assert!(matches!(
+            reloader.last_error(),
+            Some(ProfileError {
+                kind: ProfileErrorKind::InvalidButton(0),
+                ..
+            })
+        ));
+        assert_eq!(24576, x(&mut reloader, 10));
+
+        source.set(&profile(1.0));
+        assert_eq!(ReloadStatus::Reloaded, reloader.reload());
+        assert!(reloader.last_error().is_none());
+    }
+
+    #[test]
+    fn dropped_devices_are_set_to_neutral_once() {
+        let (source, mut reloader) = reloader();
+
+        reloader
+            .engine_mut()
+            .set_input("stick", InputValue::Axis(24576));
+        x(&mut reloader, 0);
+
+        source.set(&profile(1.0).replace("\"D1\"", "\"D2\""));
+        assert_eq!(ReloadStatus::Reloaded, reloader.reload());
+
+        let positions = reloader.tick(ms(10));
+        assert_eq!(
+            vec![VJDevice::D2, VJDevice::D1],
+            positions
+                .iter()
+                .map(VJDPosition::get_device)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(VJDPosition::new(VJDevice::D1), positions[1]);
+
+        assert_eq!(1, reloader.tick(ms(20)).len());
+    }
+
+    #[test]
+    fn state_is_carried_over() {
+        let (source, mut reloader) = reloader();
+
+        reloader
+            .engine_mut()
+            .set_input("gear", InputValue::Button(true));
+        assert!(reloader.tick(ms(0))[0]
+            .get_buttons()
+            .contains(VJDButton::B1));
+
+        source.set(&profile(3.0));
+        assert_eq!(ReloadStatus::Reloaded, reloader.reload());
+
+        // The toggle is still latched and the held input does not flip it again.
+        assert!(reloader.tick(ms(10))[0]
+            .get_buttons()
+            .contains(VJDButton::B1));
+        assert_eq!(
+            InputValue::Button(true),
+            reloader
+                .engine()
+                .input(reloader.engine().profile().input_id("gear").unwrap())
+        );
+    }
+}
+
+use super::engine::ProfileEngine;
+use super::{Profile, ProfileError, ProfileErrorKind};
+use crate::vjoy_base::device::{VJDAxis, VJDPosition, VJDevice};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/**
+    Describes where the text of a profile comes from.
+*/
+pub trait ProfileSource {
+    /**
+        Returns the text of the profile if it changed since the previous call, the first call
+        always returning it. Returns [`None`] if it did not change.
+    */
+    fn poll(&mut self) -> io::Result<Option<String>>;
+}
+
+/**
+    Reads a profile from a file. The file is read again when its modification time or its size
+    changes, and its text is only reported when it differs from the last one.
+*/
+#[derive(Debug, Clone)]
+pub struct FileProfileSource {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    text: Option<String>,
+}
+
+impl FileProfileSource {
+    pub fn new(path: impl Into<PathBuf>) -> FileProfileSource {
+        FileProfileSource {
+            path: path.into(),
+            stamp: None,
+            text: None,
+        }
+    }
+}
+
+impl ProfileSource for FileProfileSource {
+    fn poll(&mut self) -> io::Result<Option<String>> {
+        let metadata = fs::metadata(&self.path)?;
+        let stamp = Some((metadata.modified()?, metadata.len()));
+
+        if stamp == self.stamp {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(&self.path)?;
+        self.stamp = stamp;
+
+        if self.text.as_ref() == Some(&text) {
+            return Ok(None);
+        }
+
+        self.text = Some(text.clone());
+
+        Ok(Some(text))
+    }
+}
+
+/**
+    Describes the outcome of [`ProfileReloader::reload`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ReloadStatus {
+    /// The profile did not change.
+    Unchanged,
+
+    /// The new profile is in use.
+    Reloaded,
+
+    /// The new profile was rejected and the previous one is still in use.
+    RolledBack,
+}
+
+/// Transition of the axes after a swap, from their values before the swap.
+struct Transition {
+    start: Option<Duration>,
+    from: Vec<(VJDevice, VJDAxis, i32)>,
+}
+
+/**
+    Holds a [`ProfileEngine`] whose profile is reloaded from a [`ProfileSource`].
+*/
+pub struct ProfileReloader<S: ProfileSource> {
+    source: S,
+    engine: ProfileEngine,
+    duration: Duration,
+    transition: Option<Transition>,
+    positions: Vec<VJDPosition>,
+    dropped: Vec<VJDevice>,
+    last_error: Option<ProfileError>,
+}
+
+impl<S: ProfileSource> ProfileReloader<S> {
+    /**
+        Returns a reloader with the current profile of the source, or [`ProfileError`] if it
+        cannot be read or is invalid. After a swap, axes move to their new values over
+        `transition`.
+    */
+    pub fn new(mut source: S, transition: Duration) -> Result<ProfileReloader<S>, ProfileError> {
+        let text = source.poll().map_err(io_error)?.ok_or_else(|| {
+            io_error(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the source has no profile",
+            ))
+        })?;
+
+        Ok(ProfileReloader {
+            source,
+            engine: ProfileEngine::new(Profile::parse(&text)?),
+            duration: transition,
+            transition: None,
+            positions: Vec::new(),
+            dropped: Vec::new(),
+            last_error: None,
+        })
+    }
+
+    pub fn engine(&self) -> &ProfileEngine {
+        &self.engine
+    }
+
+    /// Gives access to the engine, to feed its inputs.
+    pub fn engine_mut(&mut self) -> &mut ProfileEngine {
+        &mut self.engine
+    }
+
+    /// Returns the error of the last reload, or [`None`] if it succeeded.
+    pub fn last_error(&self) -> Option<&ProfileError> {
+        self.last_error.as_ref()
+    }
+
+    /**
+        Polls the source and swaps to the new profile if it changed. When the new profile cannot
+        be read or is invalid, the current one is kept and the error is available from
+        [`ProfileReloader::last_error`].
+    */
+    pub fn reload(&mut self) -> ReloadStatus {
+        let result = match self.source.poll() {
+            Ok(None) => return ReloadStatus::Unchanged,
+            Ok(Some(text)) => Profile::parse(&text),
+            Err(error) => Err(io_error(error)),
+        };
+
+        match result {
+            Ok(profile) => {
+                self.swap(profile);
+                self.last_error = None;
+                ReloadStatus::Reloaded
+            }
+            Err(error) => {
+                self.last_error = Some(error);
+                ReloadStatus::RolledBack
+            }
+        }
+    }
+
+    /// Replaces the profile at once, keeping the state of the current one.
+    pub fn swap(&mut self, profile: Profile) {
+        let mut engine = ProfileEngine::new(profile);
+        engine.inherit(&self.engine);
+
+        let from = engine
+            .profile()
+            .axes()
+            .iter()
+            .filter_map(|mapping| {
+                let previous = self.positions.iter().find(|position| {
+                    position.get_device() == mapping.device
+                        && self.engine.profile().axes().iter().any(|other| {
+                            (other.device, other.axis) == (mapping.device, mapping.axis)
+                        })
+                })?;
+
+                Some((
+                    mapping.device,
+                    mapping.axis,
+                    previous.get_axis(mapping.axis).get(),
+                ))
+            })
+            .collect();
+
+        let targeted = engine.profile().devices();
+        self.dropped.retain(|device| !targeted.contains(device));
+
+        for &device in self.engine.profile().devices() {
+            if !targeted.contains(&device) && !self.dropped.contains(&device) {
+                self.dropped.push(device);
+            }
+        }
+
+        self.engine = engine;
+        self.transition = Some(Transition { start: None, from });
+    }
+
+    /**
+        Evaluates the current profile like [`ProfileEngine::tick`], blending the axes with their
+        values before the last swap while the transition lasts.
+
+        The first tick after a swap also returns, after the positions of the profile, the
+        position of [`VJDPosition::new`] for each device the new profile no longer targets.
+    */
+    pub fn tick(&mut self, timestamp: Duration) -> &[VJDPosition] {
+        self.positions = self.engine.tick(timestamp).to_vec();
+
+        if let Some(transition) = &mut self.transition {
+            let start = *transition.start.get_or_insert(timestamp);
+            let elapsed = timestamp.checked_sub(start).unwrap_or_default();
+
+            if elapsed >= self.duration {
+                self.transition = None;
+            } else {
+                let progress = elapsed.as_secs_f64() / self.duration.as_secs_f64();
+
+                for &(device, axis, from) in &transition.from {
+                    if let Some(position) = self
+                        .positions
+                        .iter_mut()
+                        .find(|position| position.get_device() == device)
+                    {
+                        let to = position.get_axis(axis).get();
+                        let value = from as f64 + (to - from) as f64 * progress;
+
+                        position.set_axis(axis, value.round() as i32);
+                    }
+                }
+            }
+        }
+
+        self.positions
+            .extend(self.dropped.drain(..).map(VJDPosition::new));
+
+        &self.positions
+    }
+}
+
+fn io_error(error: io::Error) -> ProfileError {
+    ProfileError {
+        location: Default::default(),
+        kind: ProfileErrorKind::Io(error),
+    }
+}
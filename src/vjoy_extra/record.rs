@@ -0,0 +1,898 @@
+//! Records the positions sent to vJoy devices, to reproduce a session exactly.
+//!
+//! A [`Recorder`] captures every successful update made through
+//! [`VJDPosFeed`](crate::vjoy_base::device::feeding::VJDPosFeed) and
+//! [`VJDSeqFeed`](crate::vjoy_base::device::feeding::VJDSeqFeed) once
+//! [`Recorder::capture_feeds`] is called, and optionally the raw values of the inputs the
+//! application reads. Sequential updates are applied to the last position of their device, so
+//! every frame holds a whole [`VJDPosition`]. Timestamps are monotonic, measured from the
+//! creation of the recorder.
+//!
+//! A [`Recording`] is saved in a compact binary format:
+//! - the magic bytes `VJDREC` and the version of the format, as a little-endian `u16`;
+//! - the header: the controls of each recorded device at capture time, and the names of the
+//!   recorded inputs;
+//! - the frames until the end of the file. Each frame starts with the time elapsed since the
+//!   previous one in microseconds, and a position only holds the fields which changed since the
+//!   previous position of the same device.
+//!
+//! With the `serde` feature, [`Recording::to_json`] exports a recording for inspection.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::axis::VJDAxisRaw;
+    use crate::vjoy_base::device::feeding::VJDFeedEvent;
+    use crate::vjoy_base::device::pov::VJDPovCont;
+    use crate::vjoy_base::device::{VJDButton, VJDButtonState, VJDPovDisc, VJDPovNumber};
+    use crate::vjoy_base::force_feedback::VJDFfbEffect;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn header() -> RecordingHeader {
+        RecordingHeader {
+            devices: vec![
+                RecordedDevice {
+                    device: VJDevice::D1,
+                    config: VJDConfig::new()
+                        .axes(&[VJDAxis::Slider1, VJDAxis::X])
+                        .buttons(32)
+                        .cont_povs(2)
+                        .ffb_effects(&[VJDFfbEffect::Spring, VJDFfbEffect::Constant]),
+                },
+                RecordedDevice {
+                    device: VJDevice::D16,
+                    config: VJDConfig::new().buttons(128).disc_povs(4),
+                },
+            ],
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Recorder whose clock is advanced by the test.
+    fn recorder() -> (Recorder, Arc<AtomicU64>) {
+        let clock = Arc::new(AtomicU64::new(0));
+        let time = clock.clone();
+        let recorder = Recorder::with_clock(header(), move || {
+            Duration::from_micros(time.load(Ordering::SeqCst))
+        });
+
+        (recorder, clock)
+    }
+
+    fn sample() -> Recording {
+        let (recorder, clock) = recorder();
+
+        let mut position = VJDPosition::new(VJDevice::D1);
+        position.set_axis(VJDAxis::X, 1000);
+        position.set_cont_pov(VJDPovNumber::Pov2, VJDPovCont::new(27000).unwrap());
+        recorder.record_position(&position);
+
+        clock.store(12_500, Ordering::SeqCst);
+        recorder.record_input("stick x", -42);
+        recorder.record_event(&VJDFeedEvent::Button(
+            VJDevice::D16,
+            VJDButton::B128,
+            VJDButtonState::Pressed,
+        ));
+        recorder.record_event(&VJDFeedEvent::DiscPov(
+            VJDevice::D16,
+            VJDPovNumber::Pov3,
+            VJDPovDisc::West,
+        ));
+
+        clock.store(1_000_000, Ordering::SeqCst);
+        recorder.record_input("throttle", 32767);
+        recorder.record_input("stick x", 7);
+        recorder.record_event(&VJDFeedEvent::Axis(
+            VJDevice::D1,
+            VJDAxis::Slider1,
+            VJDAxisRaw::saturating(5),
+        ));
+
+        recorder.finish()
+    }
+
+    #[test]
+    fn sequential_updates_make_whole_positions() {
+        let recording = sample();
+
+        assert_eq!(vec!["stick x", "throttle"], recording.header.inputs);
+        assert_eq!(7, recording.frames.len());
+        assert_eq!(ms(1000), recording.duration());
+
+        let timestamps: Vec<_> = recording
+            .frames
+            .iter()
+            .map(|frame| frame.timestamp)
+            .collect();
+        assert_eq!(
+            vec![
+                ms(0),
+                ms(12) + Duration::from_micros(500),
+                ms(12) + Duration::from_micros(500)
+            ],
+            timestamps[..3]
+        );
+
+        match &recording.frames[3].event {
+            FrameEvent::Position(position) => {
+                assert!(position.get_buttons().contains(VJDButton::B128));
+                assert_eq!(VJDPovDisc::West, position.get_disc_pov(VJDPovNumber::Pov3));
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+
+        match &recording.frames[6].event {
+            FrameEvent::Position(position) => {
+                assert_eq!(1000, position.get_axis(VJDAxis::X).get());
+                assert_eq!(5, position.get_axis(VJDAxis::Slider1).get());
+            }
+            event => panic!("unexpected {:?}", event),
+        }
+        assert_eq!(
+            FrameEvent::Input { input: 0, value: 7 },
+            recording.frames[5].event
+        );
+    }
+
+    #[test]
+    fn binary_format_round_trips() {
+        let recording = sample();
+        let bytes = recording.to_bytes().unwrap();
+
+        assert_eq!(b"VJDREC", &bytes[..6]);
+        assert_eq!(recording, Recording::from_bytes(&bytes).unwrap());
+
+        // An unchanged position only costs its timestamp, tag, device and mask.
+        let mut longer = recording.clone();
+        let last = longer.frames.last().unwrap().clone();
+        longer.frames.push(last);
+        assert_eq!(bytes.len() + 5, longer.to_bytes().unwrap().len());
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let bytes = sample().to_bytes().unwrap();
+
+        assert!(matches!(
+            Recording::from_bytes(b"VJDREX\x01\x00"),
+            Err(RecordingError::NotRecording)
+        ));
+        assert!(matches!(
+            Recording::from_bytes(b"VJDREC\x02\x00"),
+            Err(RecordingError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            Recording::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(RecordingError::Truncated)
+        ));
+
+        let mut unknown_device = bytes.clone();
+        unknown_device[9] = 17;
+        assert!(matches!(
+            Recording::from_bytes(&unknown_device),
+            Err(RecordingError::Corrupt("device"))
+        ));
+
+        let mut long_input = sample();
+        long_input.header.inputs.push("x".repeat(65536));
+        assert!(matches!(
+            long_input.to_bytes(),
+            Err(RecordingError::TooLarge("input"))
+        ));
+
+        let mut many_axes = sample();
+        many_axes.header.devices[0].config.axes = vec![VJDAxis::X; 256];
+        assert!(matches!(
+            many_axes.to_bytes(),
+            Err(RecordingError::TooLarge("axes"))
+        ));
+
+        let mut many_devices = sample();
+        let device = many_devices.header.devices[0].clone();
+        many_devices.header.devices = vec![device; 256];
+        assert!(matches!(
+            many_devices.to_bytes(),
+            Err(RecordingError::TooLarge("devices"))
+        ));
+
+        let mut unknown_input = sample();
+        unknown_input.header.inputs.clear();
+        assert!(matches!(
+            unknown_input.to_bytes(),
+            Err(RecordingError::Corrupt("input"))
+        ));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn feeds_are_captured() {
+        use crate::test_env::TEST_DEVICE_1;
+        use crate::vjoy_base::device::feeding::{VJDOwnership, VJDPosFeed, VJDSeqFeed};
+
+        VJDOwnership::acquire(TEST_DEVICE_1);
+
+        let recorder = Recorder::new(RecordingHeader::capture(&[TEST_DEVICE_1]));
+        recorder.capture_feeds();
+
+        let mut position = VJDPosition::new(TEST_DEVICE_1);
+        position.set_axis(VJDAxis::Ry, 100);
+        assert!(VJDPosFeed::send_position(&position));
+        assert!(VJDSeqFeed::set_axis(TEST_DEVICE_1, VJDAxis::X, 200));
+
+        let recording = recorder.finish();
+        VJDOwnership::relinquish(TEST_DEVICE_1);
+
+        assert_eq!(TEST_DEVICE_1, recording.header.devices[0].device);
+        assert!(recording.header.devices[0].config.has_axis(VJDAxis::Ry));
+        assert_eq!(2, recording.frames.len());
+        assert!(recording.frames[0].timestamp <= recording.frames[1].timestamp);
+
+        position.set_axis(VJDAxis::X, 200);
+        assert_eq!(FrameEvent::Position(position), recording.frames[1].event);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_export() {
+        let json: serde_json::Value = serde_json::from_str(&sample().to_json()).unwrap();
+
+        assert_eq!(1, json["version"]);
+        assert_eq!("D16", json["devices"][1]["device"]);
+        assert_eq!(32, json["devices"][0]["buttons"]);
+        assert_eq!(12500, json["frames"][1]["time_us"]);
+        assert_eq!("stick x", json["frames"][1]["input"]);
+        assert_eq!(-42, json["frames"][1]["value"]);
+        assert_eq!(1000, json["frames"][0]["axes"]["X"]);
+        assert_eq!(serde_json::json!([128]), json["frames"][3]["buttons"]);
+    }
+}
+
+use crate::vjoy_base::device::button::VJDButtonSet;
+use crate::vjoy_base::device::config::VJDConfig;
+use crate::vjoy_base::device::feeding::{VJDFeedEvent, VJDFeedObserver, VJDFeedObserverId};
+use crate::vjoy_base::device::{VJDAxis, VJDPosition, VJDevice};
+use crate::vjoy_base::force_feedback::VJDFfbEffect;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/**
+    Describes an error state when reading a [`Recording`].
+*/
+#[derive(Debug)]
+pub enum RecordingError {
+    /// The file cannot be read. The I/O error is provided.
+    Io(io::Error),
+
+    /// The data does not start with the magic bytes of a recording.
+    NotRecording,
+
+    /// The recording was written by a newer version of the format. Its version is provided.
+    UnsupportedVersion(u16),
+
+    /// The data ends in the middle of the header or of a frame.
+    Truncated,
+
+    /// A value is invalid. The name of the value is provided.
+    Corrupt(&'static str),
+
+    /// A value is too large to be encoded. The name of the value is provided.
+    TooLarge(&'static str),
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(error: io::Error) -> Self {
+        RecordingError::Io(error)
+    }
+}
+
+/**
+    Holds the controls of a recorded device at capture time.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecordedDevice {
+    pub device: VJDevice,
+    pub config: VJDConfig,
+}
+
+/**
+    Holds the description of a recording.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct RecordingHeader {
+    /// Recorded devices, with their controls at capture time.
+    pub devices: Vec<RecordedDevice>,
+
+    /// Names of the recorded inputs, in the order they were first recorded.
+    pub inputs: Vec<String>,
+}
+
+impl RecordingHeader {
+    /**
+        Returns a header with the current controls of the devices, read with
+        [`VJDConfig::read`]. Devices whose controls cannot be read are recorded without
+        controls.
+    */
+    pub fn capture(devices: &[VJDevice]) -> RecordingHeader {
+        let devices = devices
+            .iter()
+            .map(|&device| RecordedDevice {
+                device,
+                config: VJDConfig::read(device).unwrap_or_default(),
+            })
+            .collect();
+
+        RecordingHeader {
+            devices,
+            inputs: Vec::new(),
+        }
+    }
+}
+
+/**
+    Describes what happened in a [`Frame`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FrameEvent {
+    /// A device reached a new position.
+    Position(VJDPosition),
+
+    /// An input took a new value. The input is an index in [`RecordingHeader::inputs`].
+    Input { input: usize, value: i32 },
+}
+
+/**
+    Holds an event of a recording and its timestamp, measured from the start of the recording.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub timestamp: Duration,
+    pub event: FrameEvent,
+}
+
+/**
+    Holds a recorded session: its header and its frames, ordered by timestamp.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    /// Describes the version of the binary format written.
+    pub const VERSION: u16 = 1;
+
+    const MAGIC: &'static [u8; 6] = b"VJDREC";
+
+    const TAG_POSITION: u8 = 0;
+    const TAG_INPUT: u8 = 1;
+
+    /// Returns the timestamp of the last frame.
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map(|frame| frame.timestamp)
+            .unwrap_or_default()
+    }
+
+    /**
+        Encodes the recording in the binary format, or returns [`RecordingError::TooLarge`] if
+        it has more than 255 devices, axes or effects, more than 65535 inputs or an input name
+        longer than 65535 bytes. An input frame whose index is not in the header is returned as
+        [`RecordingError::Corrupt`], as it could not be decoded.
+    */
+    pub fn to_bytes(&self) -> Result<Vec<u8>, RecordingError> {
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.extend_from_slice(&Self::VERSION.to_le_bytes());
+
+        let count =
+            |len: usize, name| u8::try_from(len).map_err(|_| RecordingError::TooLarge(name));
+
+        bytes.push(count(self.header.devices.len(), "devices")?);
+        for recorded in &self.header.devices {
+            let config = &recorded.config;
+
+            bytes.push(recorded.device as u8);
+            bytes.push(count(config.axes.len(), "axes")?);
+            bytes.extend(config.axes.iter().map(|&axis| axis as u8));
+            bytes.extend_from_slice(&[config.buttons, config.disc_povs, config.cont_povs]);
+            bytes.push(count(config.ffb_effects.len(), "effects")?);
+            bytes.extend(config.ffb_effects.iter().map(|effect| {
+                VJDFfbEffect::ALL
+                    .iter()
+                    .position(|other| other == effect)
+                    .unwrap() as u8
+            }));
+        }
+
+        let too_large = |_| RecordingError::TooLarge("input");
+
+        bytes.extend_from_slice(
+            &u16::try_from(self.header.inputs.len())
+                .map_err(too_large)?
+                .to_le_bytes(),
+        );
+        for input in &self.header.inputs {
+            bytes.extend_from_slice(&u16::try_from(input.len()).map_err(too_large)?.to_le_bytes());
+            bytes.extend_from_slice(input.as_bytes());
+        }
+
+        let mut previous_time = Duration::ZERO;
+        let mut previous: Vec<VJDPosition> = Vec::new();
+
+        for frame in &self.frames {
+            let delta = frame
+                .timestamp
+                .checked_sub(previous_time)
+                .unwrap_or_default();
+            previous_time = previous_time.max(frame.timestamp);
+            write_varint(&mut bytes, delta.as_micros() as u64);
+
+            match &frame.event {
+                FrameEvent::Position(position) => {
+                    let last = last_position(&mut previous, position.get_device());
+                    bytes.push(Self::TAG_POSITION);
+                    bytes.push(position.get_device() as u8);
+                    write_position(&mut bytes, last, position);
+                    *last = *position;
+                }
+                FrameEvent::Input { input, value } => {
+                    if *input >= self.header.inputs.len() {
+                        return Err(RecordingError::Corrupt("input"));
+                    }
+
+                    bytes.push(Self::TAG_INPUT);
+                    write_varint(&mut bytes, *input as u64);
+                    write_varint(&mut bytes, ((value << 1) ^ (value >> 31)) as u32 as u64);
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decodes a recording in the binary format, or returns [`RecordingError`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Recording, RecordingError> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        if reader.take(Self::MAGIC.len())? != Self::MAGIC {
+            return Err(RecordingError::NotRecording);
+        }
+
+        let version = reader.u16()?;
+        if version != Self::VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+
+        let mut header = RecordingHeader::default();
+
+        for _ in 0..reader.u8()? {
+            let device = reader.device()?;
+            let mut config = VJDConfig::new();
+
+            for _ in 0..reader.u8()? {
+                let usage = reader.u8()?;
+                let axis = VJDAxis::ALL.iter().find(|&&axis| axis as u8 == usage);
+                config
+                    .axes
+                    .push(*axis.ok_or(RecordingError::Corrupt("axis"))?);
+            }
+
+            config.buttons = reader.u8()?;
+            config.disc_povs = reader.u8()?;
+            config.cont_povs = reader.u8()?;
+
+            for _ in 0..reader.u8()? {
+                let effect = VJDFfbEffect::ALL.get(reader.u8()? as usize);
+                config
+                    .ffb_effects
+                    .push(*effect.ok_or(RecordingError::Corrupt("effect"))?);
+            }
+
+            header.devices.push(RecordedDevice { device, config });
+        }
+
+        for _ in 0..reader.u16()? {
+            let length = reader.u16()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| RecordingError::Corrupt("input"))?;
+
+            header.inputs.push(name.to_string());
+        }
+
+        let mut frames = Vec::new();
+        let mut timestamp = Duration::ZERO;
+        let mut previous: Vec<VJDPosition> = Vec::new();
+
+        while !reader.is_empty() {
+            timestamp = timestamp
+                .checked_add(Duration::from_micros(reader.varint()?))
+                .ok_or(RecordingError::Corrupt("timestamp"))?;
+
+            let event = match reader.u8()? {
+                Self::TAG_POSITION => {
+                    let last = last_position(&mut previous, reader.device()?);
+                    reader.position(last)?;
+                    FrameEvent::Position(*last)
+                }
+                Self::TAG_INPUT => {
+                    let input = reader.varint()? as usize;
+                    if input >= header.inputs.len() {
+                        return Err(RecordingError::Corrupt("input"));
+                    }
+
+                    let value = u32::try_from(reader.varint()?)
+                        .map_err(|_| RecordingError::Corrupt("value"))?;
+                    let value = (value >> 1) as i32 ^ -((value & 1) as i32);
+
+                    FrameEvent::Input { input, value }
+                }
+                _ => return Err(RecordingError::Corrupt("frame")),
+            };
+
+            frames.push(Frame { timestamp, event });
+        }
+
+        Ok(Recording { header, frames })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RecordingError> {
+        Ok(fs::write(path, self.to_bytes()?)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Recording, RecordingError> {
+        Recording::from_bytes(&fs::read(path)?)
+    }
+
+    /**
+        Exports the recording as JSON. Each position lists every axis, the pressed buttons and
+        the raw values of the POV fields (see [`VJDPosition::get_povs_raw`]).
+    */
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        use serde_json::json;
+
+        let frames: Vec<_> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let time_us = frame.timestamp.as_micros() as u64;
+
+                match &frame.event {
+                    FrameEvent::Position(position) => {
+                        let axes: serde_json::Map<_, _> = VJDAxis::ALL
+                            .iter()
+                            .map(|&axis| {
+                                (format!("{:?}", axis), position.get_axis(axis).get().into())
+                            })
+                            .collect();
+                        let buttons: Vec<_> = position
+                            .get_buttons()
+                            .iter()
+                            .map(|button| button as u8)
+                            .collect();
+
+                        json!({
+                            "time_us": time_us,
+                            "device": position.get_device(),
+                            "axes": axes,
+                            "buttons": buttons,
+                            "povs": position.get_povs_raw(),
+                        })
+                    }
+                    FrameEvent::Input { input, value } => json!({
+                        "time_us": time_us,
+                        "input": self.header.inputs[*input],
+                        "value": value,
+                    }),
+                }
+            })
+            .collect();
+
+        let devices: Vec<_> = self
+            .header
+            .devices
+            .iter()
+            .map(|recorded| {
+                let mut device = serde_json::to_value(&recorded.config).unwrap();
+                device["device"] = json!(recorded.device);
+                device
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&json!({
+            "version": Self::VERSION,
+            "devices": devices,
+            "frames": frames,
+        }))
+        .unwrap()
+    }
+}
+
+/// Returns the last position of a device, starting from [`VJDPosition::new`].
+fn last_position(positions: &mut Vec<VJDPosition>, device: VJDevice) -> &mut VJDPosition {
+    let index = match positions
+        .iter()
+        .position(|position| position.get_device() == device)
+    {
+        Some(index) => index,
+        None => {
+            positions.push(VJDPosition::new(device));
+            positions.len() - 1
+        }
+    };
+
+    &mut positions[index]
+}
+
+/// Returns the fields of a position: 8 axes, 4 button words and 4 POV fields.
+fn fields(position: &VJDPosition) -> [u32; 16] {
+    let mut fields = [0; 16];
+
+    for (field, &axis) in fields.iter_mut().zip(&VJDAxis::ALL) {
+        *field = position.get_axis(axis).get() as u32;
+    }
+    fields[8..12].copy_from_slice(&position.get_buttons().to_words());
+    fields[12..].copy_from_slice(&position.get_povs_raw());
+
+    fields
+}
+
+/// Writes a mask of the fields which changed since `last`, then their values.
+fn write_position(bytes: &mut Vec<u8>, last: &VJDPosition, position: &VJDPosition) {
+    let (last, fields) = (self::fields(last), self::fields(position));
+    let mask = (0..16)
+        .filter(|&i| last[i] != fields[i])
+        .fold(0u16, |mask, i| mask | 1 << i);
+
+    bytes.extend_from_slice(&mask.to_le_bytes());
+
+    for (i, &field) in fields.iter().enumerate() {
+        if mask & 1 << i != 0 {
+            if i < 8 {
+                bytes.extend_from_slice(&(field as u16).to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&field.to_le_bytes());
+            }
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads the binary format, checking every read against the end of the data.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], RecordingError> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + length)
+            .ok_or(RecordingError::Truncated)?;
+
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, RecordingError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, RecordingError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, RecordingError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn varint(&mut self) -> Result<u64, RecordingError> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(RecordingError::Corrupt("varint"))
+    }
+
+    fn device(&mut self) -> Result<VJDevice, RecordingError> {
+        VJDevice::get_from(self.u8()?).ok_or(RecordingError::Corrupt("device"))
+    }
+
+    /// Reads the fields of a position which changed since `position`, and updates it.
+    fn position(&mut self, position: &mut VJDPosition) -> Result<(), RecordingError> {
+        let mut fields = fields(position);
+        let mask = self.u16()?;
+
+        for (i, field) in fields.iter_mut().enumerate() {
+            if mask & 1 << i != 0 {
+                *field = if i < 8 {
+                    self.u16()? as u32
+                } else {
+                    self.u32()?
+                };
+            }
+        }
+
+        for (&field, &axis) in fields.iter().zip(&VJDAxis::ALL) {
+            let value = crate::vjoy_base::device::axis::VJDAxisRaw::new(field as i32)
+                .ok_or(RecordingError::Corrupt("axis"))?;
+            position.set_axis(axis, value);
+        }
+
+        position.set_buttons(&VJDButtonSet::from_words([
+            fields[8], fields[9], fields[10], fields[11],
+        ]));
+        position.set_povs_raw([fields[12], fields[13], fields[14], fields[15]]);
+
+        Ok(())
+    }
+}
+
+/// State of a recorder, shared with the feed observer.
+struct RecorderState {
+    recording: Recording,
+    positions: Vec<VJDPosition>,
+    last_timestamp: Duration,
+}
+
+type Clock = Box<dyn Fn() -> Duration + Send + Sync>;
+
+/**
+    Records positions and inputs into a [`Recording`]. A recorder can be shared between threads.
+*/
+pub struct Recorder {
+    state: Arc<Mutex<RecorderState>>,
+    clock: Arc<Clock>,
+    observer: Mutex<Option<VJDFeedObserverId>>,
+}
+
+impl Recorder {
+    /// Returns a recorder timing frames with a monotonic clock started now.
+    pub fn new(header: RecordingHeader) -> Recorder {
+        let start = Instant::now();
+        Recorder::with_clock(header, move || start.elapsed())
+    }
+
+    /**
+        Returns a recorder timing frames with `clock`, which gives the time elapsed since the
+        start of the recording. A timestamp older than the previous one is recorded as equal to
+        it.
+    */
+    pub fn with_clock<F>(header: RecordingHeader, clock: F) -> Recorder
+    where
+        F: Fn() -> Duration + Send + Sync + 'static,
+    {
+        let state = RecorderState {
+            recording: Recording {
+                header,
+                frames: Vec::new(),
+            },
+            positions: Vec::new(),
+            last_timestamp: Duration::ZERO,
+        };
+
+        Recorder {
+            state: Arc::new(Mutex::new(state)),
+            clock: Arc::new(Box::new(clock)),
+            observer: Mutex::new(None),
+        }
+    }
+
+    fn lock(state: &Mutex<RecorderState>) -> MutexGuard<'_, RecorderState> {
+        state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn push(state: &mut RecorderState, clock: &Clock, event: FrameEvent) {
+        let timestamp = clock().max(state.last_timestamp);
+        state.last_timestamp = timestamp;
+        state.recording.frames.push(Frame { timestamp, event });
+    }
+
+    fn record(state: &Mutex<RecorderState>, clock: &Clock, event: &VJDFeedEvent) {
+        let mut state = Self::lock(state);
+        let position = last_position(&mut state.positions, event.get_device());
+
+        event.apply(position);
+        let position = *position;
+
+        Self::push(&mut state, clock, FrameEvent::Position(position));
+    }
+
+    /**
+        Starts capturing every successful update made through
+        [`VJDPosFeed`](crate::vjoy_base::device::feeding::VJDPosFeed) and
+        [`VJDSeqFeed`](crate::vjoy_base::device::feeding::VJDSeqFeed), until
+        [`Recorder::stop_capture`] or [`Recorder::finish`]. Other [`VJDFeedObserver`]s are
+        left in place.
+    */
+    pub fn capture_feeds(&self) {
+        let mut observer = self.observer.lock().unwrap();
+
+        if observer.is_none() {
+            let (state, clock) = (self.state.clone(), self.clock.clone());
+            *observer = Some(VJDFeedObserver::add(move |event| {
+                Self::record(&state, &clock, event)
+            }));
+        }
+    }
+
+    /// Stops capturing the updates of the devices.
+    pub fn stop_capture(&self) {
+        if let Some(observer) = self.observer.lock().unwrap().take() {
+            VJDFeedObserver::remove(observer);
+        }
+    }
+
+    /// Records an update of a device, applied to its last recorded position.
+    pub fn record_event(&self, event: &VJDFeedEvent) {
+        Self::record(&self.state, &self.clock, event);
+    }
+
+    /// Records a whole position of a device.
+    pub fn record_position(&self, position: &VJDPosition) {
+        self.record_event(&VJDFeedEvent::Position(*position));
+    }
+
+    /// Records the raw value of an input of the application.
+    pub fn record_input(&self, name: &str, value: i32) {
+        let mut state = Self::lock(&self.state);
+        let inputs = &mut state.recording.header.inputs;
+
+        let input = match inputs.iter().position(|input| input == name) {
+            Some(input) => input,
+            None => {
+                inputs.push(name.to_string());
+                inputs.len() - 1
+            }
+        };
+
+        Self::push(&mut state, &self.clock, FrameEvent::Input { input, value });
+    }
+
+    /// Stops capturing and returns the recording.
+    pub fn finish(self) -> Recording {
+        self.stop_capture();
+
+        let mut state = Self::lock(&self.state);
+        std::mem::take(&mut state.recording)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop_capture();
+    }
+}
@@ -0,0 +1,498 @@
+//! Reads the vJoy devices defined in the registry, through a [`VJDRegistrySource`], and writes
+//! them through a [`VJDRegistryWriter`].
+//!
+//! Each device is a `DeviceNN` subkey of [`VJGeneral::REG_DEVICES_PATH`] holding the HID report
+//! descriptor of the device, which is decoded by [`parse_descriptor`]. Devices are thus known
+//! without the driver being loaded. The driver reads the registry when it starts: written devices
+//! appear once it is restarted.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::config::VJDConfigError;
+    use crate::vjoy_base::device::VJDAxis;
+    use crate::vjoy_base::force_feedback::VJDFfbEffect;
+    use crate::vjoy_extra::descriptor::VJDDescriptorError;
+
+    /// Report descriptor with axis X, 2 buttons and the sine effect.
+    #[rustfmt::skip]
+    fn descriptor(report_id: u8) -> Vec<u8> {
+        vec![
+            0x05, 0x01, 0x09, 0x04, 0xA1, 0x01, 0x85, report_id,
+            0x26, 0xFF, 0x7F, 0x75, 0x20, 0x95, 0x01, 0x09, 0x30, 0x81, 0x02,
+            0x05, 0x09, 0x19, 0x01, 0x29, 0x02, 0x25, 0x01, 0x75, 0x01, 0x95, 0x02, 0x81, 0x02,
+            0x05, 0x0F, 0x09, 0x25, 0xA1, 0x02, 0x09, 0x31, 0x95, 0x01, 0x91, 0x00, 0xC0,
+            0xC0,
+        ]
+    }
+
+    fn registry_with(keys: &[&str]) -> MemoryRegistry {
+        let mut registry = MemoryRegistry::new();
+
+        for key in keys {
+            registry.insert_binary(key, DESCRIPTOR_VALUE, &descriptor(1));
+        }
+
+        registry
+    }
+
+    #[test]
+    fn registered_devices_are_validated() {
+        assert_eq!(
+            vec![VJDevice::D1, VJDevice::D2, VJDevice::D12],
+            registered_devices(&registry_with(&[
+                "Device01", "Device02", "Other", "Device12"
+            ]))
+            .unwrap()
+        );
+
+        assert!(matches!(
+            registered_devices(&registry_with(&["Device17"])),
+            Err(VJDRegistryError::InvalidDevice)
+        ));
+        assert!(matches!(
+            registered_devices(&registry_with(&["Device1", "Device01"])),
+            Err(VJDRegistryError::DuplicateEntry)
+        ));
+        // Without zero padding, alphabetical order is not the order of devices.
+        assert!(matches!(
+            registered_devices(&registry_with(&["Device9", "Device10"])),
+            Err(VJDRegistryError::InvalidOrder)
+        ));
+    }
+
+    #[test]
+    fn device_config_is_read() {
+        let mut registry = MemoryRegistry::new();
+        let mut bytes = descriptor(4);
+        let size = bytes.len() as u32;
+
+        // Bytes past the declared size are ignored.
+        bytes.extend_from_slice(&[0xC0, 0xC0]);
+        registry.insert_binary(&device_key(VJDevice::D4), DESCRIPTOR_VALUE, &bytes);
+        registry.insert_dword(&device_key(VJDevice::D4), DESCRIPTOR_SIZE_VALUE, size);
+
+        let expected = VJDConfig::new()
+            .axes(&[VJDAxis::X])
+            .buttons(2)
+            .ffb_effects(&[VJDFfbEffect::Sine]);
+
+        assert_eq!(
+            expected,
+            read_device_config(&registry, VJDevice::D4).unwrap()
+        );
+        assert_eq!(
+            vec![(VJDevice::D4, expected)],
+            read_device_configs(&registry).unwrap()
+        );
+    }
+
+    #[test]
+    fn device_config_is_written() {
+        let mut registry = registry_with(&["Device02"]);
+        let config = VJDConfig::new()
+            .axes(&[VJDAxis::X, VJDAxis::Rz])
+            .buttons(12)
+            .disc_povs(2);
+
+        write_device_config(&mut registry, VJDevice::D5, &config).unwrap();
+        assert_eq!(config, read_device_config(&registry, VJDevice::D5).unwrap());
+        assert!(matches!(
+            write_device_config(&mut registry, VJDevice::D6, &config.clone().cont_povs(1)),
+            Err(VJDRegistryError::InvalidConfig(
+                VJDevice::D6,
+                VJDConfigError::MixedPovs
+            ))
+        ));
+
+        remove_device_config(&mut registry, VJDevice::D2).unwrap();
+        remove_device_config(&mut registry, VJDevice::D7).unwrap();
+        assert_eq!(vec![VJDevice::D5], registered_devices(&registry).unwrap());
+    }
+
+    #[test]
+    fn broken_device_configs() {
+        let mut registry = MemoryRegistry::new();
+        registry.insert_dword("Device01", DESCRIPTOR_SIZE_VALUE, 3);
+        registry.insert_binary("Device02", DESCRIPTOR_VALUE, &[0x05, 0x01]);
+
+        assert!(matches!(
+            read_device_config(&registry, VJDevice::D1),
+            Err(VJDRegistryError::MissingDescriptor(VJDevice::D1))
+        ));
+        assert!(matches!(
+            read_device_config(&registry, VJDevice::D2),
+            Err(VJDRegistryError::InvalidDescriptor(
+                VJDevice::D2,
+                VJDDescriptorError::NotJoystick
+            ))
+        ));
+        assert!(matches!(
+            read_device_config(&registry, VJDevice::D3),
+            Err(VJDRegistryError::MissingDescriptor(VJDevice::D3))
+        ));
+    }
+}
+
+use super::descriptor::{build_descriptor, parse_descriptor};
+use super::VJDRegistryError;
+use crate::vjoy_base::device::config::VJDConfig;
+use crate::vjoy_base::device::VJDevice;
+use crate::vjoy_base::driver::VJGeneral;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io;
+
+/// Name of the binary value of a device key holding its HID report descriptor.
+pub const DESCRIPTOR_VALUE: &str = "HidReportDescriptor";
+
+/// Name of the DWORD value of a device key holding the size of its HID report descriptor.
+pub const DESCRIPTOR_SIZE_VALUE: &str = "HidReportDescriptorSize";
+
+/// Returns the name of the key of a device, such as `Device01`.
+pub fn device_key(device: VJDevice) -> String {
+    format!("{}{:02}", VJGeneral::REG_DEVICE_PREFIX, device as u8)
+}
+
+/**
+    Describes a source of the vJoy devices registry, the subkeys and values found under
+    [`VJGeneral::REG_DEVICES_PATH`].
+*/
+pub trait VJDRegistrySource {
+    /// Returns the names of the subkeys, in the order of the registry.
+    fn keys(&self) -> io::Result<Vec<String>>;
+
+    /// Returns a binary value of a subkey, or [`None`] if the subkey or the value is missing.
+    fn binary_value(&self, key: &str, name: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Returns a DWORD value of a subkey, or [`None`] if the subkey or the value is missing.
+    fn dword_value(&self, key: &str, name: &str) -> io::Result<Option<u32>>;
+}
+
+/**
+    Describes a registry source which can also be modified.
+*/
+pub trait VJDRegistryWriter: VJDRegistrySource {
+    /// Sets a binary value of a subkey, creating the subkey if missing.
+    fn set_binary_value(&mut self, key: &str, name: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Sets a DWORD value of a subkey, creating the subkey if missing.
+    fn set_dword_value(&mut self, key: &str, name: &str, value: u32) -> io::Result<()>;
+
+    /// Deletes a subkey and its values. Deleting a missing subkey is not an error.
+    fn delete_key(&mut self, key: &str) -> io::Result<()>;
+}
+
+/**
+    Describes a value of a [`MemoryRegistry`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VJDRegistryValue {
+    Binary(Vec<u8>),
+    Dword(u32),
+}
+
+/**
+    Holds a registry in memory: fixtures for tests, or a registry built without Windows.
+    Subkeys are listed in alphabetical order.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryRegistry {
+    keys: BTreeMap<String, BTreeMap<String, VJDRegistryValue>>,
+}
+
+impl MemoryRegistry {
+    pub fn new() -> MemoryRegistry {
+        MemoryRegistry::default()
+    }
+
+    /// Creates the subkey if missing.
+    pub fn insert_key(&mut self, key: &str) {
+        self.keys.entry(key.to_string()).or_default();
+    }
+
+    /// Sets a value, creating the subkey if missing.
+    pub fn insert_value(&mut self, key: &str, name: &str, value: VJDRegistryValue) {
+        self.keys
+            .entry(key.to_string())
+            .or_default()
+            .insert(name.to_string(), value);
+    }
+
+    pub fn insert_binary(&mut self, key: &str, name: &str, bytes: &[u8]) {
+        self.insert_value(key, name, VJDRegistryValue::Binary(bytes.to_vec()));
+    }
+
+    pub fn insert_dword(&mut self, key: &str, name: &str, value: u32) {
+        self.insert_value(key, name, VJDRegistryValue::Dword(value));
+    }
+
+    /// Removes a subkey and its values. Returns `false` if it did not exist.
+    pub fn remove_key(&mut self, key: &str) -> bool {
+        self.keys.remove(key).is_some()
+    }
+
+    /// Returns a value, or [`None`] if the subkey or the value is missing.
+    pub fn get_value(&self, key: &str, name: &str) -> Option<&VJDRegistryValue> {
+        self.keys.get(key)?.get(name)
+    }
+}
+
+impl VJDRegistrySource for MemoryRegistry {
+    fn keys(&self) -> io::Result<Vec<String>> {
+        Ok(self.keys.keys().cloned().collect())
+    }
+
+    fn binary_value(&self, key: &str, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.get_value(key, name) {
+            Some(VJDRegistryValue::Binary(bytes)) => Ok(Some(bytes.clone())),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a binary value",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn dword_value(&self, key: &str, name: &str) -> io::Result<Option<u32>> {
+        match self.get_value(key, name) {
+            Some(VJDRegistryValue::Dword(value)) => Ok(Some(*value)),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a DWORD value",
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl VJDRegistryWriter for MemoryRegistry {
+    fn set_binary_value(&mut self, key: &str, name: &str, bytes: &[u8]) -> io::Result<()> {
+        self.insert_binary(key, name, bytes);
+        Ok(())
+    }
+
+    fn set_dword_value(&mut self, key: &str, name: &str, value: u32) -> io::Result<()> {
+        self.insert_dword(key, name, value);
+        Ok(())
+    }
+
+    fn delete_key(&mut self, key: &str) -> io::Result<()> {
+        self.remove_key(key);
+        Ok(())
+    }
+}
+
+/**
+    Reads and writes the vJoy devices registry of Windows, under `HKEY_LOCAL_MACHINE`.
+*/
+#[cfg(feature = "registry")]
+pub struct WindowsRegistry {
+    devices_key: winreg::RegKey,
+}
+
+#[cfg(feature = "registry")]
+impl WindowsRegistry {
+    /// Opens [`VJGeneral::REG_DEVICES_PATH`] for reading.
+    pub fn open() -> io::Result<WindowsRegistry> {
+        let devices_key = winreg::RegKey::predef(winreg::enums::HKEY_LOCAL_MACHINE)
+            .open_subkey(VJGeneral::REG_DEVICES_PATH)?;
+
+        Ok(WindowsRegistry { devices_key })
+    }
+
+    /// Opens [`VJGeneral::REG_DEVICES_PATH`] for reading and writing. Administrator rights are
+    /// required.
+    pub fn open_writable() -> io::Result<WindowsRegistry> {
+        let devices_key = winreg::RegKey::predef(winreg::enums::HKEY_LOCAL_MACHINE)
+            .open_subkey_with_flags(VJGeneral::REG_DEVICES_PATH, winreg::enums::KEY_ALL_ACCESS)?;
+
+        Ok(WindowsRegistry { devices_key })
+    }
+
+    fn value(&self, key: &str, name: &str) -> io::Result<Option<winreg::RegValue>> {
+        let result = self
+            .devices_key
+            .open_subkey(key)
+            .and_then(|key| key.get_raw_value(name));
+
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(feature = "registry")]
+impl VJDRegistrySource for WindowsRegistry {
+    fn keys(&self) -> io::Result<Vec<String>> {
+        self.devices_key.enum_keys().collect()
+    }
+
+    fn binary_value(&self, key: &str, name: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.value(key, name)?.map(|value| value.bytes))
+    }
+
+    fn dword_value(&self, key: &str, name: &str) -> io::Result<Option<u32>> {
+        match self.value(key, name)? {
+            Some(value) if value.bytes.len() == 4 => Ok(Some(u32::from_le_bytes([
+                value.bytes[0],
+                value.bytes[1],
+                value.bytes[2],
+                value.bytes[3],
+            ]))),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a DWORD value",
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(feature = "registry")]
+impl VJDRegistryWriter for WindowsRegistry {
+    fn set_binary_value(&mut self, key: &str, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let (key, _) = self.devices_key.create_subkey(key)?;
+
+        key.set_raw_value(
+            name,
+            &winreg::RegValue {
+                bytes: bytes.to_vec(),
+                vtype: winreg::enums::REG_BINARY,
+            },
+        )
+    }
+
+    fn set_dword_value(&mut self, key: &str, name: &str, value: u32) -> io::Result<()> {
+        let (key, _) = self.devices_key.create_subkey(key)?;
+
+        key.set_value(name, &value)
+    }
+
+    fn delete_key(&mut self, key: &str) -> io::Result<()> {
+        match self.devices_key.delete_subkey_all(key) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/**
+    Returns the list of devices registered in a source, or [`VJDRegistryError`] if it fails. The
+    list is ordered.
+*/
+pub fn registered_devices<S: VJDRegistrySource + ?Sized>(
+    source: &S,
+) -> Result<Vec<VJDevice>, VJDRegistryError> {
+    let mut captured_device_numbers: Vec<VJDevice> = Vec::new();
+
+    let reg_device_prefix = VJGeneral::REG_DEVICE_PREFIX;
+
+    for i in source.keys().map_err(VJDRegistryError::PathError)? {
+        // First check reg key starts with 'Device' with a potential following number.
+        if i.starts_with(reg_device_prefix) && i.len() > reg_device_prefix.len() {
+            // Then check the potential number is really a valid number.
+            if let Ok(val) = i[reg_device_prefix.len()..].parse::<u8>() {
+                // A device number should be in the correct range in registry
+                if !(1..=VJGeneral::MAX_DEVICES).contains(&val) {
+                    return Err(VJDRegistryError::InvalidDevice);
+                } else {
+                    for i in &captured_device_numbers {
+                        match (*i as u8).cmp(&val) {
+                            // A device number should not appear twice in registry
+                            Ordering::Equal => return Err(VJDRegistryError::DuplicateEntry),
+
+                            // A device number should not appear in unordered fashion in registry
+                            Ordering::Greater => return Err(VJDRegistryError::InvalidOrder),
+                            _ => {}
+                        }
+                    }
+                }
+
+                let device = match VJDevice::get_from(val) {
+                    Some(vjd) => vjd,
+                    None => return Err(VJDRegistryError::InvalidDevice),
+                };
+
+                captured_device_numbers.push(device);
+            }
+        };
+    }
+
+    Ok(captured_device_numbers)
+}
+
+/**
+    Returns the configuration of a device decoded from its report descriptor, or
+    [`VJDRegistryError`] if the descriptor is missing or malformed.
+*/
+pub fn read_device_config<S: VJDRegistrySource + ?Sized>(
+    source: &S,
+    device: VJDevice,
+) -> Result<VJDConfig, VJDRegistryError> {
+    let key = device_key(device);
+
+    let mut bytes = source
+        .binary_value(&key, DESCRIPTOR_VALUE)
+        .map_err(VJDRegistryError::PathError)?
+        .ok_or(VJDRegistryError::MissingDescriptor(device))?;
+
+    // The size is authoritative when present.
+    if let Some(size) = source
+        .dword_value(&key, DESCRIPTOR_SIZE_VALUE)
+        .map_err(VJDRegistryError::PathError)?
+    {
+        bytes.truncate(size as usize);
+    }
+
+    parse_descriptor(&bytes)
+        .map(|descriptor| descriptor.config)
+        .map_err(|error| VJDRegistryError::InvalidDescriptor(device, error))
+}
+
+/**
+    Returns the configuration of every device registered in a source, ordered by device, or the
+    first [`VJDRegistryError`] met.
+*/
+pub fn read_device_configs<S: VJDRegistrySource + ?Sized>(
+    source: &S,
+) -> Result<Vec<(VJDevice, VJDConfig)>, VJDRegistryError> {
+    registered_devices(source)?
+        .into_iter()
+        .map(|device| Ok((device, read_device_config(source, device)?)))
+        .collect()
+}
+
+/**
+    Writes the report descriptor of a configuration as the key of a device, replacing the
+    previous one, or returns [`VJDRegistryError`] if the configuration is invalid or the write
+    fails.
+*/
+pub fn write_device_config<W: VJDRegistryWriter + ?Sized>(
+    writer: &mut W,
+    device: VJDevice,
+    config: &VJDConfig,
+) -> Result<(), VJDRegistryError> {
+    let key = device_key(device);
+    let bytes = build_descriptor(device, config)
+        .map_err(|error| VJDRegistryError::InvalidConfig(device, error))?;
+
+    writer
+        .set_binary_value(&key, DESCRIPTOR_VALUE, &bytes)
+        .and_then(|_| writer.set_dword_value(&key, DESCRIPTOR_SIZE_VALUE, bytes.len() as u32))
+        .map_err(VJDRegistryError::PathError)
+}
+
+/**
+    Removes the key of a device, or returns [`VJDRegistryError`] if it fails.
+*/
+pub fn remove_device_config<W: VJDRegistryWriter + ?Sized>(
+    writer: &mut W,
+    device: VJDevice,
+) -> Result<(), VJDRegistryError> {
+    writer
+        .delete_key(&device_key(device))
+        .map_err(VJDRegistryError::PathError)
+}
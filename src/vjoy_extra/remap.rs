@@ -0,0 +1,999 @@
+//! Contains a remapper to feed vJoy devices from physical joysticks and game controllers read
+//! with SDL2.
+//!
+//! A [`RemapTable`] routes the inputs (axes, buttons, hats) of physical sources to the controls
+//! of vJoy devices. [`SDL2Remapper`] opens the sources described in the table, follows their
+//! connection and disconnection, and sends the resulting positions with [`VJDPosFeed`].
+//!
+//! Games may get confused when they see both a physical device and the vJoy device it is
+//! remapped to. A [`SourceHider`] such as [`HidHide`] can hide the physical device from them.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stick() -> RemapSource {
+        RemapSource::name("Stick")
+    }
+
+    fn position_with<F: FnOnce(&mut VJDPosition)>(device: VJDevice, f: F) -> VJDPosition {
+        let mut position = VJDPosition::new(device);
+        f(&mut position);
+        position
+    }
+
+    #[test]
+    fn incompatible_rule_is_rejected() {
+        let mut table = RemapTable::new();
+
+        let result = table.add(RemapRule {
+            source: stick(),
+            input: SourceInput::Button(0),
+            target: RemapTarget::Axis {
+                device: VJDevice::D1,
+                axis: VJDAxis::X,
+                inverted: false,
+            },
+        });
+
+        assert!(matches!(result, Err(RemapError::IncompatibleTarget)));
+        assert!(table.rules().is_empty());
+    }
+
+    #[test]
+    fn axis_is_routed_and_inverted() {
+        let mut table = RemapTable::new();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Axis(0),
+                target: RemapTarget::Axis {
+                    device: VJDevice::D2,
+                    axis: VJDAxis::Rz,
+                    inverted: false,
+                },
+            })
+            .unwrap();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Axis(1),
+                target: RemapTarget::Axis {
+                    device: VJDevice::D2,
+                    axis: VJDAxis::Slider1,
+                    inverted: true,
+                },
+            })
+            .unwrap();
+
+        let changed = table.apply(&stick(), SourceInput::Axis(0), SourceValue::Axis(i16::MIN));
+        assert_eq!(vec![VJDevice::D2], changed);
+
+        table.apply(&stick(), SourceInput::Axis(1), SourceValue::Axis(i16::MIN));
+
+        assert_eq!(
+            position_with(VJDevice::D2, |p| {
+                p.set_axis_zr(VJGeneral::MIN_AXIS_VALUE);
+                p.set_slider1(VJGeneral::MAX_AXIS_VALUE);
+            }),
+            *table.position(VJDevice::D2).unwrap()
+        );
+    }
+
+    #[test]
+    fn unmapped_inputs_and_sources_are_ignored() {
+        let mut table = RemapTable::new();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Button(3),
+                target: RemapTarget::Button {
+                    device: VJDevice::D1,
+                    button: VJDButton::B1,
+                },
+            })
+            .unwrap();
+
+        assert!(table
+            .apply(&stick(), SourceInput::Button(4), SourceValue::Button(true))
+            .is_empty());
+        assert!(table
+            .apply(
+                &RemapSource::name("Throttle"),
+                SourceInput::Button(3),
+                SourceValue::Button(true)
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn button_and_hats_are_routed() {
+        let mut table = RemapTable::new();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Button(3),
+                target: RemapTarget::Button {
+                    device: VJDevice::D1,
+                    button: VJDButton::B7,
+                },
+            })
+            .unwrap();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Hat(0),
+                target: RemapTarget::DiscPov {
+                    device: VJDevice::D1,
+                    pov: VJDPovNumber::Pov2,
+                },
+            })
+            .unwrap();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Hat(1),
+                target: RemapTarget::ContPov {
+                    device: VJDevice::D3,
+                    pov: VJDPovNumber::Pov1,
+                },
+            })
+            .unwrap();
+
+        table.apply(&stick(), SourceInput::Button(3), SourceValue::Button(true));
+
+        // Rolling from East to North-East keeps East on a discrete POV.
+        table.apply(
+            &stick(),
+            SourceInput::Hat(0),
+            SourceValue::Hat(VJDPovDirection::East),
+        );
+        table.apply(
+            &stick(),
+            SourceInput::Hat(0),
+            SourceValue::Hat(VJDPovDirection::NorthEast),
+        );
+
+        table.apply(
+            &stick(),
+            SourceInput::Hat(1),
+            SourceValue::Hat(VJDPovDirection::SouthWest),
+        );
+
+        assert_eq!(
+            position_with(VJDevice::D1, |p| {
+                p.set_button_pressed(7);
+                p.set_disc_pov(VJDPovNumber::Pov2, VJDPovDisc::East);
+            }),
+            *table.position(VJDevice::D1).unwrap()
+        );
+        assert_eq!(
+            position_with(VJDevice::D3, |p| {
+                p.set_cont_pov(VJDPovNumber::Pov1, 22500);
+            }),
+            *table.position(VJDevice::D3).unwrap()
+        );
+    }
+
+    #[test]
+    fn controller_trigger_uses_full_range() {
+        let mut table = RemapTable::new();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::ControllerAxis(ControllerAxis::TriggerLeft),
+                target: RemapTarget::Axis {
+                    device: VJDevice::D1,
+                    axis: VJDAxis::Z,
+                    inverted: false,
+                },
+            })
+            .unwrap();
+
+        table.apply(
+            &stick(),
+            SourceInput::ControllerAxis(ControllerAxis::TriggerLeft),
+            SourceValue::Axis(0),
+        );
+
+        assert_eq!(
+            position_with(VJDevice::D1, |p| p.set_axis_z(VJGeneral::MIN_AXIS_VALUE)),
+            *table.position(VJDevice::D1).unwrap()
+        );
+    }
+
+    #[test]
+    fn release_source_neutralizes_targets() {
+        let mut table = RemapTable::new();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Axis(0),
+                target: RemapTarget::Axis {
+                    device: VJDevice::D1,
+                    axis: VJDAxis::X,
+                    inverted: false,
+                },
+            })
+            .unwrap();
+        table
+            .add(RemapRule {
+                source: stick(),
+                input: SourceInput::Button(0),
+                target: RemapTarget::Button {
+                    device: VJDevice::D1,
+                    button: VJDButton::B1,
+                },
+            })
+            .unwrap();
+
+        table.apply(&stick(), SourceInput::Axis(0), SourceValue::Axis(i16::MAX));
+        table.apply(&stick(), SourceInput::Button(0), SourceValue::Button(true));
+
+        assert_eq!(vec![VJDevice::D1], table.release_source(&stick()));
+        assert_eq!(
+            position_with(VJDevice::D1, |_| ()),
+            *table.position(VJDevice::D1).unwrap()
+        );
+    }
+
+    #[test]
+    fn sources_are_matched_by_occurrence() {
+        let connected = vec![
+            ("Pedals".to_string(), "guid-p".to_string()),
+            ("Stick".to_string(), "guid-s".to_string()),
+            ("Stick".to_string(), "guid-s".to_string()),
+        ];
+
+        let sources = vec![
+            RemapSource::name("Stick").occurrence(1),
+            RemapSource::guid("guid-p"),
+            RemapSource::name("Throttle"),
+        ];
+
+        let matched = match_sources(&connected, &sources);
+
+        assert_eq!(2, matched.len());
+        assert_eq!(Some(&&sources[0]), matched.get(&2));
+        assert_eq!(Some(&&sources[1]), matched.get(&0));
+    }
+}
+
+use super::SDL2VjoyError;
+use crate::vjoy_base::device::axis::{SDL2AxisValue, VJDAxisRaw};
+use crate::vjoy_base::device::feeding::{VJDOwnership, VJDPosFeed};
+use crate::vjoy_base::device::pov::{VJDPovCont, VJDPovDirection};
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDPosition, VJDPovDisc, VJDPovNumber, VJDevice,
+};
+use crate::vjoy_base::driver::VJGeneral;
+use sdl2::controller::{Axis as ControllerAxis, Button as ControllerButton, GameController};
+use sdl2::event::Event;
+use sdl2::joystick::{HatState, Joystick};
+use sdl2::{GameControllerSubsystem, IntegerOrSdlError, JoystickSubsystem};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/**
+    Describes how to recognize a physical source among the joysticks found by SDL2.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SourceMatcher {
+    /// The name of the joystick, as given by SDL2.
+    Name(String),
+
+    /// The GUID of the joystick, as given by SDL2 in its string form.
+    Guid(String),
+}
+
+/**
+    Describes a physical source of inputs.
+
+    Identical devices share the same name and GUID. They are told apart by their `occurrence`:
+    the 0-based rank of the device among the connected devices matching the same
+    [`SourceMatcher`], ordered by SDL2 device index.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemapSource {
+    pub matcher: SourceMatcher,
+    pub occurrence: usize,
+}
+
+impl RemapSource {
+    /// First connected joystick with the given name.
+    pub fn name(name: &str) -> RemapSource {
+        RemapSource {
+            matcher: SourceMatcher::Name(name.to_string()),
+            occurrence: 0,
+        }
+    }
+
+    /// First connected joystick with the given GUID.
+    pub fn guid(guid: &str) -> RemapSource {
+        RemapSource {
+            matcher: SourceMatcher::Guid(guid.to_string()),
+            occurrence: 0,
+        }
+    }
+
+    /// Changes the rank of the device among the devices matching the same [`SourceMatcher`].
+    pub fn occurrence(mut self, occurrence: usize) -> RemapSource {
+        self.occurrence = occurrence;
+        self
+    }
+
+    fn matches(&self, name: &str, guid: &str) -> bool {
+        match &self.matcher {
+            SourceMatcher::Name(n) => n == name,
+            SourceMatcher::Guid(g) => g.eq_ignore_ascii_case(guid),
+        }
+    }
+}
+
+/**
+    Describes an input of a physical source.
+
+    Joystick inputs are identified by their SDL2 index. Game controller inputs are available for
+    devices known by the SDL2 game controller database.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SourceInput {
+    Axis(u8),
+    Button(u8),
+    Hat(u8),
+    ControllerAxis(ControllerAxis),
+    ControllerButton(ControllerButton),
+}
+
+/**
+    Describes the value of a [`SourceInput`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SourceValue {
+    /// Axis value as given by SDL2.
+    Axis(i16),
+
+    /// `true` when pressed.
+    Button(bool),
+
+    Hat(VJDPovDirection),
+}
+
+/**
+    Describes a control of a vJoy device which receives a [`SourceInput`].
+
+    Axes feed axes, buttons feed buttons and hats feed POVs.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RemapTarget {
+    Axis {
+        device: VJDevice,
+        axis: VJDAxis,
+        inverted: bool,
+    },
+    Button {
+        device: VJDevice,
+        button: VJDButton,
+    },
+    DiscPov {
+        device: VJDevice,
+        pov: VJDPovNumber,
+    },
+    ContPov {
+        device: VJDevice,
+        pov: VJDPovNumber,
+    },
+}
+
+impl RemapTarget {
+    pub fn get_device(&self) -> VJDevice {
+        match *self {
+            RemapTarget::Axis { device, .. }
+            | RemapTarget::Button { device, .. }
+            | RemapTarget::DiscPov { device, .. }
+            | RemapTarget::ContPov { device, .. } => device,
+        }
+    }
+}
+
+/**
+    Routes one input of a source to one control of a vJoy device.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemapRule {
+    pub source: RemapSource,
+    pub input: SourceInput,
+    pub target: RemapTarget,
+}
+
+/**
+    Describes an error state of the remapper.
+*/
+#[derive(Debug)]
+pub enum RemapError {
+    /// The input and the target of a rule are not of the same kind.
+    IncompatibleTarget,
+
+    /// A vJoy device could not be acquired.
+    AcquireFailed(VJDevice),
+
+    /// A vJoy device refused a position.
+    SendFailed(VJDevice),
+
+    /// SDL2 error when opening or reading a source.
+    SDL2(SDL2VjoyError),
+
+    /// The source hider failed.
+    Hider(std::io::Error),
+}
+
+/// Returns the value an axis takes after a reset, see [`VJDPosition::new`].
+fn neutral_axis_value(axis: VJDAxis) -> i32 {
+    match axis {
+        VJDAxis::X | VJDAxis::Y | VJDAxis::Z => VJGeneral::NEUTRAL_AXIS_VALUE,
+        _ => VJGeneral::MIN_AXIS_VALUE,
+    }
+}
+
+/**
+    Holds the remapping rules and the positions of the targeted vJoy devices.
+
+    The table doesn't talk to SDL2 nor vJoy: it receives input values and updates positions,
+    which makes it usable with any source of inputs.
+*/
+#[derive(Default)]
+pub struct RemapTable {
+    rules: Vec<RemapRule>,
+    positions: HashMap<VJDevice, VJDPosition>,
+    disc_povs: HashMap<(VJDevice, VJDPovNumber), VJDPovDisc>,
+}
+
+impl RemapTable {
+    pub fn new() -> RemapTable {
+        RemapTable::default()
+    }
+
+    /**
+        Adds a rule, or returns [`RemapError::IncompatibleTarget`] if its input and its target
+        are not of the same kind.
+    */
+    pub fn add(&mut self, rule: RemapRule) -> Result<(), RemapError> {
+        let compatible = matches!(
+            (rule.input, rule.target),
+            (SourceInput::Axis(_), RemapTarget::Axis { .. })
+                | (SourceInput::ControllerAxis(_), RemapTarget::Axis { .. })
+                | (SourceInput::Button(_), RemapTarget::Button { .. })
+                | (SourceInput::ControllerButton(_), RemapTarget::Button { .. })
+                | (SourceInput::Hat(_), RemapTarget::DiscPov { .. })
+                | (SourceInput::Hat(_), RemapTarget::ContPov { .. })
+        );
+
+        if !compatible {
+            return Err(RemapError::IncompatibleTarget);
+        }
+
+        let device = rule.target.get_device();
+        self.positions
+            .entry(device)
+            .or_insert_with(|| VJDPosition::new(device));
+
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[RemapRule] {
+        &self.rules
+    }
+
+    /// Returns the vJoy devices targeted by at least one rule.
+    pub fn devices(&self) -> Vec<VJDevice> {
+        let mut devices: Vec<VJDevice> = self.positions.keys().copied().collect();
+        devices.sort_by_key(|&device| device as u32);
+        devices
+    }
+
+    /// Returns the current position of a targeted vJoy device.
+    pub fn position(&self, device: VJDevice) -> Option<&VJDPosition> {
+        self.positions.get(&device)
+    }
+
+    /**
+        Routes the value of an input to every matching target.
+
+        Returns the vJoy devices whose position changed, without duplicates.
+    */
+    pub fn apply(
+        &mut self,
+        source: &RemapSource,
+        input: SourceInput,
+        value: SourceValue,
+    ) -> Vec<VJDevice> {
+        let targets: Vec<RemapTarget> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.source == *source && rule.input == input)
+            .map(|rule| rule.target)
+            .collect();
+
+        let mut changed = Vec::new();
+
+        for target in targets {
+            if self.write(target, input, value) && !changed.contains(&target.get_device()) {
+                changed.push(target.get_device());
+            }
+        }
+
+        changed
+    }
+
+    /**
+        Sets every target of the source back to its neutral state, typically when the source is
+        disconnected.
+
+        Returns the vJoy devices whose position changed, without duplicates.
+    */
+    pub fn release_source(&mut self, source: &RemapSource) -> Vec<VJDevice> {
+        let targets: Vec<RemapTarget> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.source == *source)
+            .map(|rule| rule.target)
+            .collect();
+
+        let mut changed = Vec::new();
+
+        for target in targets {
+            let device = target.get_device();
+            let position = self.positions.get_mut(&device).unwrap();
+
+            match target {
+                RemapTarget::Axis { axis, .. } => position.set_axis(axis, neutral_axis_value(axis)),
+                RemapTarget::Button { button, .. } => position.set_button_released(button as u32),
+                RemapTarget::DiscPov { pov, .. } => {
+                    position.set_disc_pov(pov, VJDPovDisc::Neutral);
+                    self.disc_povs.insert((device, pov), VJDPovDisc::Neutral);
+                }
+                RemapTarget::ContPov { pov, .. } => position.set_cont_pov(pov, VJDPovCont::NEUTRAL),
+            }
+
+            if !changed.contains(&device) {
+                changed.push(device);
+            }
+        }
+
+        changed
+    }
+
+    fn write(&mut self, target: RemapTarget, input: SourceInput, value: SourceValue) -> bool {
+        let device = target.get_device();
+        let position = match self.positions.get_mut(&device) {
+            Some(position) => position,
+            None => return false,
+        };
+
+        match (target, value) {
+            (RemapTarget::Axis { axis, inverted, .. }, SourceValue::Axis(value)) => {
+                let raw = match input {
+                    // Triggers of game controllers only use the positive half of the SDL2
+                    // range, which already matches the vJoy range.
+                    SourceInput::ControllerAxis(ControllerAxis::TriggerLeft)
+                    | SourceInput::ControllerAxis(ControllerAxis::TriggerRight) => {
+                        VJDAxisRaw::saturating(value as i32)
+                    }
+                    _ => VJDAxisRaw::from(SDL2AxisValue::new(value)),
+                };
+
+                let raw = if inverted {
+                    VJDAxisRaw::saturating(
+                        VJGeneral::MAX_AXIS_VALUE - raw.get() + VJGeneral::MIN_AXIS_VALUE,
+                    )
+                } else {
+                    raw
+                };
+
+                position.set_axis(axis, raw);
+            }
+            (RemapTarget::Button { button, .. }, SourceValue::Button(pressed)) => {
+                if pressed {
+                    position.set_button_pressed(button as u32);
+                } else {
+                    position.set_button_released(button as u32);
+                }
+            }
+            (RemapTarget::DiscPov { pov, .. }, SourceValue::Hat(direction)) => {
+                let previous = self
+                    .disc_povs
+                    .get(&(device, pov))
+                    .copied()
+                    .unwrap_or(VJDPovDisc::Neutral);
+                let disc = direction.to_disc_from(previous);
+
+                position.set_disc_pov(pov, disc);
+                self.disc_povs.insert((device, pov), disc);
+            }
+            (RemapTarget::ContPov { pov, .. }, SourceValue::Hat(direction)) => {
+                position.set_cont_pov(pov, direction);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/**
+    Returns the index of each connected device matched by a source. `connected` holds the name
+    and GUID of the devices, ordered by SDL2 device index.
+*/
+fn match_sources<'a>(
+    connected: &[(String, String)],
+    sources: &'a [RemapSource],
+) -> HashMap<usize, &'a RemapSource> {
+    let mut matched = HashMap::new();
+
+    for source in sources {
+        let found = connected
+            .iter()
+            .enumerate()
+            .filter(|(_, (name, guid))| source.matches(name, guid))
+            .nth(source.occurrence);
+
+        if let Some((index, _)) = found {
+            matched.insert(index, source);
+        }
+    }
+
+    matched
+}
+
+fn hat_direction(state: HatState) -> VJDPovDirection {
+    match state {
+        HatState::Centered => VJDPovDirection::Neutral,
+        HatState::Up => VJDPovDirection::North,
+        HatState::RightUp => VJDPovDirection::NorthEast,
+        HatState::Right => VJDPovDirection::East,
+        HatState::RightDown => VJDPovDirection::SouthEast,
+        HatState::Down => VJDPovDirection::South,
+        HatState::LeftDown => VJDPovDirection::SouthWest,
+        HatState::Left => VJDPovDirection::West,
+        HatState::LeftUp => VJDPovDirection::NorthWest,
+    }
+}
+
+fn sdl2_error(error: IntegerOrSdlError) -> RemapError {
+    RemapError::SDL2(error.into())
+}
+
+/**
+    Describes a mean to hide physical devices from games.
+*/
+pub trait SourceHider {
+    /// Hides the device with the given instance path.
+    fn hide(&mut self, instance_path: &str) -> std::io::Result<()>;
+
+    /// Makes the device with the given instance path visible again.
+    fn unhide(&mut self, instance_path: &str) -> std::io::Result<()>;
+}
+
+/**
+    Hides devices with the command line interface of HidHide
+    (<https://github.com/ViGEm/HidHide>).
+
+    The instance path of a device is the one listed by HidHide, such as
+    `HID\VID_044F&PID_B10A\7&1A2B3C4D&0&0000`. The application reading the hidden devices must be
+    registered with [`HidHide::register_application`] to keep seeing them.
+*/
+pub struct HidHide {
+    cli: PathBuf,
+}
+
+impl HidHide {
+    /// Default location of the HidHide command line interface.
+    pub const DEFAULT_CLI: &'static str =
+        r"C:\Program Files\Nefarius Software Solutions\HidHide\x64\HidHideCLI.exe";
+
+    pub fn new(cli: impl Into<PathBuf>) -> HidHide {
+        HidHide { cli: cli.into() }
+    }
+
+    fn run(&self, args: &[&str]) -> std::io::Result<()> {
+        let status = Command::new(&self.cli).args(args).status()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!(
+                "HidHide failed with {}",
+                status
+            )))
+        }
+    }
+
+    /// Allows the given application to see hidden devices. Usually the current executable.
+    pub fn register_application(&self, application: &std::path::Path) -> std::io::Result<()> {
+        self.run(&["--app-reg", &application.to_string_lossy()])
+    }
+
+    /// Turns hiding on: hidden devices disappear for every unregistered application.
+    pub fn enable(&self) -> std::io::Result<()> {
+        self.run(&["--cloak-on"])
+    }
+}
+
+impl Default for HidHide {
+    fn default() -> Self {
+        HidHide::new(Self::DEFAULT_CLI)
+    }
+}
+
+impl SourceHider for HidHide {
+    fn hide(&mut self, instance_path: &str) -> std::io::Result<()> {
+        self.run(&["--dev-hide", instance_path])
+    }
+
+    fn unhide(&mut self, instance_path: &str) -> std::io::Result<()> {
+        self.run(&["--dev-unhide", instance_path])
+    }
+}
+
+/// A physical device opened by the remapper.
+struct OpenedSource {
+    source: RemapSource,
+    _joystick: Joystick,
+    _controller: Option<GameController>,
+}
+
+/**
+    Feeds vJoy devices from physical devices read with SDL2, following a [`RemapTable`].
+
+    Events from the SDL2 event pump must be given to [`SDL2Remapper::handle_event`]. Sources are
+    opened when they are connected and their targets are set back to neutral when they are
+    disconnected. Hidden sources are made visible again and acquired devices are relinquished when
+    the remapper is dropped.
+*/
+pub struct SDL2Remapper {
+    joystick_subsystem: JoystickSubsystem,
+    controller_subsystem: GameControllerSubsystem,
+    table: RemapTable,
+    opened: HashMap<u32, OpenedSource>,
+    hider: Option<Box<dyn SourceHider>>,
+    hidden: Vec<String>,
+    acquired: Vec<VJDevice>,
+}
+
+impl SDL2Remapper {
+    pub fn new(
+        joystick_subsystem: JoystickSubsystem,
+        controller_subsystem: GameControllerSubsystem,
+        table: RemapTable,
+    ) -> SDL2Remapper {
+        SDL2Remapper {
+            joystick_subsystem,
+            controller_subsystem,
+            table,
+            opened: HashMap::new(),
+            hider: None,
+            hidden: Vec::new(),
+            acquired: Vec::new(),
+        }
+    }
+
+    pub fn get_table(&self) -> &RemapTable {
+        &self.table
+    }
+
+    /// Sets the hider used by [`SDL2Remapper::hide`].
+    pub fn set_hider<H: SourceHider + 'static>(&mut self, hider: H) {
+        self.hider = Some(Box::new(hider));
+    }
+
+    /**
+        Hides the physical device with the given instance path from games, until the remapper
+        is dropped. Does nothing if no hider is set.
+    */
+    pub fn hide(&mut self, instance_path: &str) -> Result<(), RemapError> {
+        if let Some(hider) = self.hider.as_mut() {
+            hider.hide(instance_path).map_err(RemapError::Hider)?;
+            self.hidden.push(instance_path.to_string());
+        }
+
+        Ok(())
+    }
+
+    /**
+        Acquires every vJoy device targeted by the table and sends their initial position. If a
+        device can't be acquired, the devices acquired before it are relinquished. Acquired
+        devices are relinquished when the remapper is dropped.
+    */
+    pub fn acquire(&mut self) -> Result<(), RemapError> {
+        for device in self.table.devices() {
+            if self.acquired.contains(&device) {
+                continue;
+            }
+
+            if !VJDOwnership::acquire(device) {
+                self.relinquish();
+                return Err(RemapError::AcquireFailed(device));
+            }
+
+            self.acquired.push(device);
+        }
+
+        self.send(&self.table.devices())
+    }
+
+    fn relinquish(&mut self) {
+        for device in self.acquired.drain(..) {
+            VJDOwnership::relinquish(device);
+        }
+    }
+
+    /**
+        Opens every connected source of the table which is not opened yet. Called automatically
+        when a device is connected.
+    */
+    pub fn scan(&mut self) -> Result<(), RemapError> {
+        let total = self
+            .joystick_subsystem
+            .num_joysticks()
+            .map_err(|msg| RemapError::SDL2(SDL2VjoyError::SdlError(msg)))?;
+
+        let mut connected = Vec::with_capacity(total as usize);
+
+        for index in 0..total {
+            let name = self
+                .joystick_subsystem
+                .name_for_index(index)
+                .map_err(sdl2_error)?;
+            let guid = self
+                .joystick_subsystem
+                .device_guid(index)
+                .map_err(sdl2_error)?
+                .string();
+
+            connected.push((name, guid));
+        }
+
+        let mut sources: Vec<RemapSource> = Vec::new();
+        for rule in self.table.rules() {
+            if !sources.contains(&rule.source) {
+                sources.push(rule.source.clone());
+            }
+        }
+
+        for (index, source) in match_sources(&connected, &sources) {
+            if self.opened.values().any(|opened| opened.source == *source) {
+                continue;
+            }
+
+            let joystick = self
+                .joystick_subsystem
+                .open(index as u32)
+                .map_err(sdl2_error)?;
+
+            let controller = if self.controller_subsystem.is_game_controller(index as u32) {
+                Some(
+                    self.controller_subsystem
+                        .open(index as u32)
+                        .map_err(sdl2_error)?,
+                )
+            } else {
+                None
+            };
+
+            self.opened.insert(
+                joystick.instance_id(),
+                OpenedSource {
+                    source: source.clone(),
+                    _joystick: joystick,
+                    _controller: controller,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /**
+        Updates the vJoy devices according to an SDL2 event. Events unrelated to opened sources
+        are ignored.
+    */
+    pub fn handle_event(&mut self, event: &Event) -> Result<(), RemapError> {
+        let (which, input, value) = match *event {
+            Event::JoyDeviceAdded { .. } | Event::ControllerDeviceAdded { .. } => {
+                return self.scan();
+            }
+            Event::JoyDeviceRemoved { which, .. } => {
+                let opened = match self.opened.remove(&which) {
+                    Some(opened) => opened,
+                    None => return Ok(()),
+                };
+
+                let changed = self.table.release_source(&opened.source);
+                return self.send(&changed);
+            }
+            Event::JoyAxisMotion {
+                which,
+                axis_idx,
+                value,
+                ..
+            } => (which, SourceInput::Axis(axis_idx), SourceValue::Axis(value)),
+            Event::JoyButtonDown {
+                which, button_idx, ..
+            } => (
+                which,
+                SourceInput::Button(button_idx),
+                SourceValue::Button(true),
+            ),
+            Event::JoyButtonUp {
+                which, button_idx, ..
+            } => (
+                which,
+                SourceInput::Button(button_idx),
+                SourceValue::Button(false),
+            ),
+            Event::JoyHatMotion {
+                which,
+                hat_idx,
+                state,
+                ..
+            } => (
+                which,
+                SourceInput::Hat(hat_idx),
+                SourceValue::Hat(hat_direction(state)),
+            ),
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => (
+                which,
+                SourceInput::ControllerAxis(axis),
+                SourceValue::Axis(value),
+            ),
+            Event::ControllerButtonDown { which, button, .. } => (
+                which,
+                SourceInput::ControllerButton(button),
+                SourceValue::Button(true),
+            ),
+            Event::ControllerButtonUp { which, button, .. } => (
+                which,
+                SourceInput::ControllerButton(button),
+                SourceValue::Button(false),
+            ),
+            _ => return Ok(()),
+        };
+
+        let source = match self.opened.get(&which) {
+            Some(opened) => opened.source.clone(),
+            None => return Ok(()),
+        };
+
+        let changed = self.table.apply(&source, input, value);
+        self.send(&changed)
+    }
+
+    fn send(&self, devices: &[VJDevice]) -> Result<(), RemapError> {
+        for &device in devices {
+            if let Some(position) = self.table.position(device) {
+                if !VJDPosFeed::send_position(position) {
+                    return Err(RemapError::SendFailed(device));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SDL2Remapper {
+    fn drop(&mut self) {
+        self.relinquish();
+
+        if let Some(hider) = self.hider.as_mut() {
+            for instance_path in &self.hidden {
+                // Nothing more can be done if it fails while dropping.
+                let _ = hider.unhide(instance_path);
+            }
+        }
+    }
+}
@@ -0,0 +1,581 @@
+//! Replays a [`Recording`] onto vJoy devices with its original timing.
+//!
+//! A [`Player`] is driven like [`filter`](crate::vjoy_extra::filter): the caller gives the time
+//! elapsed since an origin of its choice to [`Player::poll`], which returns the positions due
+//! since the previous call. The same calls always produce the same positions, so a replay is
+//! deterministic. [`Player::run`] drives a player with the system clock until the end.
+//!
+//! Playback can be sped up or slowed down, restricted to a range of the recording, looped, and
+//! moved to any time with [`Player::seek`]: the devices are then set to their recorded positions
+//! at that time. Recorded devices can be replayed onto other devices, and
+//! [`Player::validate`] checks the target devices have the controls of the recorded ones before
+//! starting. Recorded inputs are not replayed.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vjoy_base::device::config::VJDConfig;
+    use crate::vjoy_base::device::VJDAxis;
+    use crate::vjoy_extra::record::{Frame, RecordedDevice, RecordingHeader};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn position(device: VJDevice, x: i32) -> VJDPosition {
+        let mut position = VJDPosition::new(device);
+        position.set_axis(VJDAxis::X, x);
+        position
+    }
+
+    /// D1 moves to X = t at each t in 0, 100, ..., 500ms, D2 to X = 1 at 250ms.
+    fn recording() -> Recording {
+        let mut frames: Vec<_> = (0..=5)
+            .map(|i| Frame {
+                timestamp: ms(i * 100),
+                event: FrameEvent::Position(position(VJDevice::D1, i as i32 * 100)),
+            })
+            .collect();
+
+        frames.insert(
+            3,
+            Frame {
+                timestamp: ms(250),
+                event: FrameEvent::Position(position(VJDevice::D2, 1)),
+            },
+        );
+        frames.insert(
+            1,
+            Frame {
+                timestamp: ms(50),
+                event: FrameEvent::Input { input: 0, value: 3 },
+            },
+        );
+
+        let config = VJDConfig::new().axes(&[VJDAxis::X]).buttons(8);
+
+        Recording {
+            header: RecordingHeader {
+                devices: vec![
+                    RecordedDevice {
+                        device: VJDevice::D1,
+                        config: config.clone(),
+                    },
+                    RecordedDevice {
+                        device: VJDevice::D2,
+                        config,
+                    },
+                ],
+                inputs: vec!["stick".to_string()],
+            },
+            frames,
+        }
+    }
+
+    fn xs(positions: &[VJDPosition]) -> Vec<(VJDevice, i32)> {
+        positions
+            .iter()
+            .map(|position| (position.get_device(), position.get_axis(VJDAxis::X).get()))
+            .collect()
+    }
+
+    #[test]
+    fn original_timing() {
+        let mut player = Player::new(recording());
+
+        // The first poll starts the playback, whatever the origin of the caller.
+        assert_eq!(vec![(VJDevice::D1, 0)], xs(&player.poll(ms(1000))));
+        assert_eq!(Some(ms(1100)), player.next_due());
+        assert!(player.poll(ms(1099)).is_empty());
+        assert_eq!(
+            vec![(VJDevice::D1, 100), (VJDevice::D1, 200), (VJDevice::D2, 1)],
+            xs(&player.poll(ms(1250)))
+        );
+        assert!(!player.is_finished());
+        assert_eq!(
+            vec![
+                (VJDevice::D1, 300),
+                (VJDevice::D1, 400),
+                (VJDevice::D1, 500)
+            ],
+            xs(&player.poll(ms(5000)))
+        );
+        assert!(player.is_finished());
+        assert_eq!(None, player.next_due());
+    }
+
+    #[test]
+    fn speed_and_remapping() {
+        let mut player = Player::new(recording());
+        player.set_speed(2.0).unwrap();
+        player.remap(VJDevice::D2, VJDevice::D7);
+
+        player.poll(ms(0));
+        assert_eq!(
+            vec![(VJDevice::D1, 100), (VJDevice::D1, 200), (VJDevice::D7, 1)],
+            xs(&player.poll(ms(125)))
+        );
+
+        // The new speed applies from the last poll.
+        player.set_speed(0.5).unwrap();
+        assert!(player.poll(ms(200)).is_empty());
+        assert_eq!(vec![(VJDevice::D1, 300)], xs(&player.poll(ms(225))));
+
+        assert_eq!(Err(ReplayError::InvalidSpeed), player.set_speed(0.0));
+        assert_eq!(Err(ReplayError::InvalidSpeed), player.set_speed(f64::NAN));
+    }
+
+    #[test]
+    fn seeking_restores_recorded_state() {
+        let mut player = Player::new(recording());
+        player.poll(ms(0));
+
+        player.seek(ms(320));
+        assert_eq!(ms(320), player.time());
+        assert_eq!(
+            vec![(VJDevice::D1, 300), (VJDevice::D2, 1)],
+            xs(&player.poll(ms(10)))
+        );
+        assert_eq!(vec![(VJDevice::D1, 400)], xs(&player.poll(ms(90))));
+
+        // Before any frame of a device, its position is the initial one.
+        player.seek(ms(0));
+        assert_eq!(
+            vec![(VJDevice::D1, 0), (VJDevice::D2, 16384)],
+            xs(&player.poll(ms(100)))
+        );
+    }
+
+    #[test]
+    fn range_and_looping() {
+        let mut player = Player::new(recording());
+        player.set_range(ms(150), ms(350)).unwrap();
+        player.set_looping(true);
+
+        assert_eq!(
+            vec![(VJDevice::D1, 100), (VJDevice::D2, 16384)],
+            xs(&player.poll(ms(0)))
+        );
+        assert_eq!(
+            vec![
+                (VJDevice::D1, 200),
+                (VJDevice::D2, 1),
+                (VJDevice::D1, 300),
+                // Back to the start of the range.
+                (VJDevice::D1, 100),
+                (VJDevice::D2, 16384),
+            ],
+            xs(&player.poll(ms(200)))
+        );
+        assert_eq!(
+            vec![(VJDevice::D1, 200), (VJDevice::D2, 1)],
+            xs(&player.poll(ms(300)))
+        );
+        assert!(!player.is_finished());
+
+        assert_eq!(
+            Err(ReplayError::InvalidRange),
+            player.set_range(ms(300), ms(300))
+        );
+    }
+
+    #[test]
+    fn empty_ranges_do_not_loop() {
+        let mut recording = recording();
+        recording.frames.truncate(1);
+
+        let mut player = Player::new(recording);
+        player.set_looping(true);
+
+        assert_eq!(vec![(VJDevice::D1, 0)], xs(&player.poll(ms(0))));
+        assert!(player.poll(ms(100)).is_empty());
+        assert!(player.is_finished());
+        assert_eq!(None, player.next_due());
+        assert_eq!(Ok(()), player.run(|_| true));
+    }
+
+    #[test]
+    fn targets_are_validated() {
+        let mut player = Player::new(recording());
+        let full = VJDConfig::new().axes(&[VJDAxis::X, VJDAxis::Y]).buttons(8);
+        let configs = |device| match device {
+            VJDevice::D1 => Some(full.clone()),
+            VJDevice::D3 => Some(VJDConfig::new().axes(&[VJDAxis::Y]).buttons(8)),
+            VJDevice::D4 => Some(VJDConfig::new().axes(&[VJDAxis::X]).buttons(2)),
+            VJDevice::D5 => Some(full.clone()),
+            _ => None,
+        };
+
+        assert_eq!(
+            Err(ReplayError::MissingDevice(VJDevice::D2)),
+            player.validate(configs)
+        );
+
+        player.remap(VJDevice::D2, VJDevice::D3);
+        assert_eq!(
+            Err(ReplayError::MissingAxis(VJDevice::D3, VJDAxis::X)),
+            player.validate(configs)
+        );
+
+        player.remap(VJDevice::D2, VJDevice::D4);
+        assert_eq!(
+            Err(ReplayError::MissingButtons(VJDevice::D4, 8)),
+            player.validate(configs)
+        );
+
+        player.remap(VJDevice::D2, VJDevice::D5);
+        assert_eq!(Ok(()), player.validate(configs));
+
+        let recorded = &mut player.recording.header.devices[1].config;
+        recorded.disc_povs = 2;
+        assert_eq!(
+            Err(ReplayError::MissingDiscPovs(VJDevice::D5, 2)),
+            player.validate(configs)
+        );
+
+        let recorded = &mut player.recording.header.devices[1].config;
+        recorded.disc_povs = 0;
+        recorded.cont_povs = 1;
+        assert_eq!(
+            Err(ReplayError::MissingContPovs(VJDevice::D5, 1)),
+            player.validate(configs)
+        );
+    }
+}
+
+use crate::vjoy_base::device::config::VJDConfig;
+use crate::vjoy_base::device::{VJDAxis, VJDPosition, VJDevice};
+use crate::vjoy_extra::record::{FrameEvent, Recording};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/**
+    Describes an error state of a [`Player`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReplayError {
+    /// The speed must be a positive number.
+    InvalidSpeed,
+
+    /// The end of a range must come after its start.
+    InvalidRange,
+
+    /// The target device does not exist.
+    MissingDevice(VJDevice),
+
+    /// The target device lacks an axis of the recorded device.
+    MissingAxis(VJDevice, VJDAxis),
+
+    /// The target device has fewer buttons than the recorded device. The required number of
+    /// buttons is provided.
+    MissingButtons(VJDevice, u8),
+
+    /// The target device has fewer discrete POVs than the recorded device. The required number
+    /// of discrete POVs is provided.
+    MissingDiscPovs(VJDevice, u8),
+
+    /// The target device has fewer continuous POVs than the recorded device. The required number
+    /// of continuous POVs is provided.
+    MissingContPovs(VJDevice, u8),
+
+    /// Sending a position to the target device failed.
+    SendFailed(VJDevice),
+}
+
+/**
+    Replays the positions of a [`Recording`].
+*/
+#[derive(Debug, Clone)]
+pub struct Player {
+    recording: Recording,
+    speed: f64,
+    looping: bool,
+    range: (Duration, Duration),
+    targets: HashMap<VJDevice, VJDevice>,
+    /// Index of the next frame to replay.
+    cursor: usize,
+    /// Current time in the recording.
+    time: Duration,
+    /// Time of the caller matching the current time in the recording, set by the first poll.
+    anchor: Option<Duration>,
+    /// Positions to send before the next frames, after a seek.
+    pending: Vec<VJDPosition>,
+}
+
+impl Player {
+    /// Returns a player replaying the whole recording once at its original speed.
+    pub fn new(recording: Recording) -> Player {
+        let range = (Duration::ZERO, recording.duration());
+
+        Player {
+            recording,
+            speed: 1.0,
+            looping: false,
+            range,
+            targets: HashMap::new(),
+            cursor: 0,
+            time: Duration::ZERO,
+            anchor: None,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /**
+        Sets how many times faster than recorded the playback goes, or returns
+        [`ReplayError::InvalidSpeed`] if `speed` is not a positive number.
+    */
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), ReplayError> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(ReplayError::InvalidSpeed);
+        }
+
+        self.speed = speed;
+        Ok(())
+    }
+
+    /**
+        Makes the playback start over from the beginning of its range when it reaches its end. A
+        recording whose frames are all at the same time has an empty range and never loops.
+    */
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /**
+        Restricts the playback to the frames from `start` to `end` included, and moves to
+        `start`. Returns [`ReplayError::InvalidRange`] if `end` is not after `start`.
+    */
+    pub fn set_range(&mut self, start: Duration, end: Duration) -> Result<(), ReplayError> {
+        if end <= start {
+            return Err(ReplayError::InvalidRange);
+        }
+
+        self.range = (start, end);
+        self.seek(start);
+        Ok(())
+    }
+
+    /// Replays the positions of a recorded device onto another device.
+    pub fn remap(&mut self, recorded: VJDevice, target: VJDevice) {
+        self.targets.insert(recorded, target);
+    }
+
+    /// Returns the device the positions of a recorded device are sent to.
+    pub fn target(&self, recorded: VJDevice) -> VJDevice {
+        self.targets.get(&recorded).copied().unwrap_or(recorded)
+    }
+
+    /**
+        Checks every target device has the controls of its recorded device, `configs` giving
+        the controls of a device or [`None`] if it does not exist. Returns the first missing
+        control as [`ReplayError`].
+    */
+    pub fn validate<F>(&self, configs: F) -> Result<(), ReplayError>
+    where
+        F: Fn(VJDevice) -> Option<VJDConfig>,
+    {
+        for recorded in &self.recording.header.devices {
+            let target = self.target(recorded.device);
+            let config = configs(target).ok_or(ReplayError::MissingDevice(target))?;
+            let required = &recorded.config;
+
+            if let Some(&axis) = required.axes.iter().find(|&&axis| !config.has_axis(axis)) {
+                return Err(ReplayError::MissingAxis(target, axis));
+            }
+            if config.buttons < required.buttons {
+                return Err(ReplayError::MissingButtons(target, required.buttons));
+            }
+            if config.disc_povs < required.disc_povs {
+                return Err(ReplayError::MissingDiscPovs(target, required.disc_povs));
+            }
+            if config.cont_povs < required.cont_povs {
+                return Err(ReplayError::MissingContPovs(target, required.cont_povs));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`Player::validate`] with the current controls of the devices.
+    pub fn validate_devices(&self) -> Result<(), ReplayError> {
+        self.validate(VJDConfig::read)
+    }
+
+    /// Returns the current time in the recording.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /**
+        Moves to a time of the recording, clamped to the range. The next poll first returns the
+        recorded position of each device at that time, then continues from there.
+    */
+    pub fn seek(&mut self, time: Duration) {
+        let time = time.clamp(self.range.0, self.range.1);
+        let frames = &self.recording.frames;
+        let cursor = frames.partition_point(|frame| frame.timestamp <= time);
+
+        // Every recorded device is set, to its initial position if it has no frame yet.
+        let mut state: Vec<VJDPosition> = self
+            .recording
+            .header
+            .devices
+            .iter()
+            .map(|recorded| VJDPosition::new(recorded.device))
+            .collect();
+
+        for frame in &frames[..cursor] {
+            if let FrameEvent::Position(position) = &frame.event {
+                match state
+                    .iter_mut()
+                    .find(|other| other.get_device() == position.get_device())
+                {
+                    Some(other) => *other = *position,
+                    None => state.push(*position),
+                }
+            }
+        }
+
+        self.pending = state;
+        self.cursor = cursor;
+        self.time = time;
+        self.anchor = None;
+    }
+
+    /// Returns whether the end of the range was reached without looping.
+    pub fn is_finished(&self) -> bool {
+        !self.loops() && self.pending.is_empty() && self.next_frame().is_none()
+    }
+
+    /// Returns whether the playback starts over at the end of its range, which must not be empty.
+    fn loops(&self) -> bool {
+        self.looping && self.range.1 > self.range.0
+    }
+
+    /// Returns the index of the next frame of a position within the range.
+    fn next_frame(&self) -> Option<usize> {
+        self.recording.frames[self.cursor..]
+            .iter()
+            .take_while(|frame| frame.timestamp <= self.range.1)
+            .position(|frame| matches!(frame.event, FrameEvent::Position(_)))
+            .map(|index| self.cursor + index)
+    }
+
+    /**
+        Returns the time of the caller at which the next position is due, or [`None`] if the
+        playback is finished or was not started by a poll.
+    */
+    pub fn next_due(&self) -> Option<Duration> {
+        let anchor = self.anchor?;
+
+        if !self.pending.is_empty() {
+            return Some(anchor);
+        }
+
+        let timestamp = match self.next_frame() {
+            Some(index) => self.recording.frames[index].timestamp,
+            None if self.loops() => self.range.1,
+            None => return None,
+        };
+
+        Some(anchor + (timestamp - self.time).div_f64(self.speed))
+    }
+
+    /**
+        Returns the positions due at `now`, the time elapsed since an origin chosen by the
+        caller, in the order they were recorded and sent to their target device. The first poll
+        after a creation or a seek starts the playback at `now`.
+    */
+    pub fn poll(&mut self, now: Duration) -> Vec<VJDPosition> {
+        let anchor = *self.anchor.get_or_insert(now);
+        let mut target = self.time
+            + now
+                .checked_sub(anchor)
+                .unwrap_or_default()
+                .mul_f64(self.speed);
+        let mut positions = std::mem::take(&mut self.pending);
+
+        loop {
+            let end = target.min(self.range.1);
+            let frames = &self.recording.frames;
+
+            while let Some(frame) = frames
+                .get(self.cursor)
+                .filter(|frame| frame.timestamp <= end)
+            {
+                if let FrameEvent::Position(position) = &frame.event {
+                    positions.push(*position);
+                }
+                self.cursor += 1;
+            }
+
+            if !(self.loops() && target >= self.range.1) {
+                break;
+            }
+
+            // Start over, keeping the time left over after the end of the range.
+            let overflow = target - self.range.1;
+            self.seek(self.range.0);
+            positions.append(&mut self.pending);
+            target = self.range.0 + overflow;
+        }
+
+        self.time = target.min(self.range.1);
+        self.anchor = Some(now);
+
+        positions
+            .iter()
+            .map(|position| retarget(position, self.target(position.get_device())))
+            .collect()
+    }
+
+    /**
+        Replays the recording in real time until its end, sending each position with `send`,
+        for instance [`VJDPosFeed::send_position`](crate::vjoy_base::device::feeding::VJDPosFeed::send_position).
+        Returns [`ReplayError::SendFailed`] as soon as sending fails. A looping playback never
+        ends.
+
+        The target devices are not checked: call [`Player::validate`] or
+        [`Player::validate_devices`] first, as positions sent to a device lacking a recorded
+        control lose it.
+    */
+    pub fn run<F>(&mut self, mut send: F) -> Result<(), ReplayError>
+    where
+        F: FnMut(&VJDPosition) -> bool,
+    {
+        let start = Instant::now();
+
+        loop {
+            for position in self.poll(start.elapsed()) {
+                if !send(&position) {
+                    return Err(ReplayError::SendFailed(position.get_device()));
+                }
+            }
+
+            match self.next_due() {
+                Some(due) => std::thread::sleep(due.saturating_sub(start.elapsed())),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Returns the same position for another device.
+fn retarget(position: &VJDPosition, device: VJDevice) -> VJDPosition {
+    if position.get_device() == device {
+        return *position;
+    }
+
+    let mut retargeted = VJDPosition::new(device);
+    for &axis in &VJDAxis::ALL {
+        retargeted.set_axis(axis, position.get_axis(axis));
+    }
+    retargeted.set_buttons(&position.get_buttons());
+    retargeted.set_povs_raw(position.get_povs_raw());
+
+    retargeted
+}
@@ -0,0 +1,762 @@
+//! Runs custom input logic written in [Rhai](https://rhai.rs), for mappings too complex for a
+//! declarative [profile](crate::vjoy_extra::profile).
+//!
+//! The top level of a script runs once when it is loaded, then its `tick` function runs on each
+//! call to [`ScriptEngine::tick`]:
+//!
+//! ```text
+//! set_state("detent", 0);
+//!
+//! fn tick() {
+//!     let throttle = axis_input("throttle");
+//!
+//!     // Gear-dependent detent: hold the throttle at idle while the gear is down.
+//!     if button_input("gear_down") && throttle < 8000 {
+//!         throttle = 0;
+//!     }
+//!
+//!     set_axis(1, "Slider1", throttle);
+//!     set_button(1, 1, button_input("fire"));
+//!
+//!     if button_input("hat_mode") {
+//!         start_timer("mode");
+//!     }
+//!     if timer("mode") > 500 {
+//!         set_state("detent", get_state("detent") + 1);
+//!         stop_timer("mode");
+//!     }
+//! }
+//! ```
+//!
+//! Script functions do not see the variables of the top level: persistent values are kept with
+//! `set_state` and `get_state`. The bindings are:
+//! - `axis_input(name)`, `button_input(name)`, `pov_input(name)`: values of the inputs fed by
+//!   the application. Axes are in vJoy units, POVs in hundredths of a degree or -1 when neutral.
+//!   An input never fed reads as neutral.
+//! - `set_axis(device, axis, value)`, `set_button(device, button, pressed)`,
+//!   `set_cont_pov(device, pov, value)`, `set_disc_pov(device, pov, direction)`: update the
+//!   position of a device, which is kept between ticks. Axes are named like [`VJDAxis`],
+//!   continuous POVs range from 0 to 35999 or -1 (neutral) and directions from -1 (neutral) to
+//!   3 (West) like [`VJDPovDisc`].
+//! - `now()`: timestamp of the tick in milliseconds. `start_timer(name)`, `timer(name)` and
+//!   `stop_timer(name)`: milliseconds elapsed since a timer started, or -1 when stopped.
+//! - `get_state(key)`, `set_state(key, value)`: persistent values, `()` when never set.
+//!
+//! Scripts are sandboxed: they cannot import modules nor use `eval`, `print` and `debug` go to
+//! [`ScriptEngine::take_log`], and each run is bounded by [`ScriptLimits`] so a bad script
+//! cannot hang the feeder nor fill its memory. A failing tick leaves the positions, the state
+//! and the timers as they were.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn load(script: &str) -> ScriptEngine {
+        ScriptEngine::new(script, ScriptLimits::default()).unwrap()
+    }
+
+    #[test]
+    fn bindings_update_positions() {
+        let mut engine = load(
+            r#"
+            fn tick() {
+                let throttle = axis_input("throttle");
+                if button_input("gear_down") && throttle < 8000 {
+                    throttle = 0;
+                }
+                set_axis(2, "Slider1", throttle);
+                set_button(2, 5, button_input("fire"));
+                set_cont_pov(1, 2, pov_input("hat"));
+                set_disc_pov(1, 1, 1);
+            }
+            "#,
+        );
+
+        engine.set_axis_input("throttle", 5000);
+        engine.set_button_input("fire", true);
+        engine.set_pov_input("hat", VJDPovCont::new(9000).unwrap());
+
+        let positions = engine.tick(ms(0)).unwrap();
+        assert_eq!(vec![VJDevice::D1, VJDevice::D2], devices(&positions));
+        assert_eq!(9000, positions[0].get_cont_pov(VJDPovNumber::Pov2).get());
+        assert_eq!(
+            VJDPovDisc::East,
+            positions[0].get_disc_pov(VJDPovNumber::Pov1)
+        );
+        assert_eq!(5000, positions[1].get_axis(VJDAxis::Slider1).get());
+        assert!(positions[1].get_buttons().contains(VJDButton::B5));
+
+        engine.set_button_input("gear_down", true);
+        engine.set_button_input("fire", false);
+        let positions = engine.tick(ms(10)).unwrap();
+        assert_eq!(0, positions[1].get_axis(VJDAxis::Slider1).get());
+        assert!(positions[1].get_buttons().is_empty());
+    }
+
+    fn devices(positions: &[VJDPosition]) -> Vec<VJDevice> {
+        positions.iter().map(VJDPosition::get_device).collect()
+    }
+
+    #[test]
+    fn state_and_timers_persist() {
+        let mut engine = load(
+            r#"
+            set_state("count", 10);
+
+            fn tick() {
+                let count = get_state("count");
+                if type_of(count) == "()" {
+                    count = 0;
+                }
+                set_state("count", count + 1);
+                if get_state("count") == 11 {
+                    start_timer("hold");
+                }
+                set_axis(1, "X", timer("hold"));
+                if timer("hold") >= 30 {
+                    stop_timer("hold");
+                }
+                print(`count ${get_state("count")} at ${now()}`);
+            }
+            "#,
+        );
+
+        let x = |engine: &mut ScriptEngine, time| {
+            engine.tick(ms(time)).unwrap()[0].get_axis(VJDAxis::X).get()
+        };
+
+        assert_eq!(0, x(&mut engine, 100));
+        assert_eq!(20, x(&mut engine, 120));
+        assert_eq!(30, x(&mut engine, 130));
+        // A stopped timer reads -1, clamped to the axis range.
+        assert_eq!(0, x(&mut engine, 140));
+        assert_eq!(
+            vec![
+                "count 11 at 100",
+                "count 12 at 120",
+                "count 13 at 130",
+                "count 14 at 140"
+            ],
+            engine.take_log()
+        );
+        assert!(engine.take_log().is_empty());
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let mut engine = load(
+            r#"
+            fn tick() {
+                set_axis(1, "X", 100);
+                if button_input("hang") {
+                    set_axis(1, "X", 200);
+                    loop {}
+                }
+            }
+            "#,
+        );
+
+        engine.tick(ms(0)).unwrap();
+        engine.set_button_input("hang", true);
+        assert!(matches!(
+            engine.tick(ms(10)),
+            Err(ScriptError::BudgetExceeded)
+        ));
+
+        // The failed tick did not change the position.
+        assert_eq!(100, engine.positions()[0].get_axis(VJDAxis::X).get());
+
+        engine.set_button_input("hang", false);
+        assert!(engine.tick(ms(20)).is_ok());
+
+        assert!(matches!(
+            ScriptEngine::new("loop {} fn tick() {}", ScriptLimits::default()),
+            Err(ScriptError::BudgetExceeded)
+        ));
+    }
+
+    #[test]
+    fn failed_ticks_are_rolled_back() {
+        let mut engine = load(
+            r#"
+            fn tick() {
+                let count = get_state("count");
+                if type_of(count) == "()" {
+                    count = 0;
+                }
+                set_state("count", count + 1);
+                start_timer("hold");
+                if button_input("fail") {
+                    stop_timer("hold");
+                    set_state("count", 100);
+                    throw "failed";
+                }
+            }
+            fn read() { get_state("count") }
+            "#,
+        );
+
+        engine.tick(ms(0)).unwrap();
+        engine.set_button_input("fail", true);
+        assert!(engine.tick(ms(10)).is_err());
+
+        let context = lock(&engine.context);
+        assert_eq!(Some(1), context.state["count"].as_int().ok());
+        assert_eq!(Some(&ms(0)), context.timers.get("hold"));
+    }
+
+    #[test]
+    fn state_and_log_are_bounded() {
+        let limits = ScriptLimits {
+            max_collection_size: 4,
+            max_log_lines: 4,
+            ..ScriptLimits::default()
+        };
+        let script = r#"
+            fn tick() {
+                for i in 0..3 { print(`line ${i}`); }
+                for i in 0..axis_input("keys") { set_state(`key ${i}`, i); }
+            }
+            "#;
+        let mut engine = ScriptEngine::new(script, limits).unwrap();
+
+        engine.set_axis_input("keys", 4);
+        engine.tick(ms(0)).unwrap();
+        engine.tick(ms(10)).unwrap();
+        assert_eq!(
+            vec!["line 2", "line 0", "line 1", "line 2"],
+            &engine.take_log()[..]
+        );
+
+        engine.set_axis_input("keys", 5);
+        assert!(matches!(engine.tick(ms(20)), Err(ScriptError::Runtime(_))));
+        assert_eq!(4, lock(&engine.context).state.len());
+    }
+
+    #[test]
+    fn scripts_are_sandboxed() {
+        for script in &[
+            r#"import "hack" as h; fn tick() {}"#,
+            r#"eval("1"); fn tick() {}"#,
+            r#"let s = "x"; loop { s += s; } fn tick() {}"#,
+        ] {
+            assert!(
+                ScriptEngine::new(script, ScriptLimits::default()).is_err(),
+                "{} was allowed",
+                script
+            );
+        }
+    }
+
+    #[test]
+    fn script_errors() {
+        assert!(matches!(
+            ScriptEngine::new("fn tick( {", ScriptLimits::default()),
+            Err(ScriptError::Compile(_))
+        ));
+        assert!(matches!(
+            ScriptEngine::new("let x = 1;", ScriptLimits::default()),
+            Err(ScriptError::MissingTick)
+        ));
+
+        let mut engine = load(r#"fn tick() { set_axis(17, "X", 0); }"#);
+        assert!(matches!(engine.tick(ms(0)), Err(ScriptError::Runtime(_))));
+
+        let mut engine = load(r#"fn tick() { set_button(1, 1, axis_input("x")); }"#);
+        assert!(matches!(engine.tick(ms(0)), Err(ScriptError::Runtime(_))));
+
+        for value in &[-2, 36000] {
+            let script = format!("fn tick() {{ set_cont_pov(1, 1, {}); }}", value);
+            let mut engine = load(&script);
+            assert!(matches!(engine.tick(ms(0)), Err(ScriptError::Runtime(_))));
+        }
+    }
+}
+
+use crate::vjoy_base::device::pov::VJDPovCont;
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDPosition, VJDPovDisc, VJDPovNumber, VJDevice,
+};
+use crate::vjoy_base::driver::VJGeneral;
+use rhai::{
+    CallFnOptions, Dynamic, Engine, EvalAltResult, ImmutableString, Map, Position, AST, INT,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/**
+    Describes an error state of a [`ScriptEngine`].
+*/
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script cannot be compiled. The error of the parser is provided.
+    Compile(rhai::ParseError),
+
+    /// The script has no `tick` function.
+    MissingTick,
+
+    /// The script used more than its [`ScriptLimits`] of operations or time.
+    BudgetExceeded,
+
+    /// The script failed while running. The error of the interpreter is provided.
+    Runtime(Box<EvalAltResult>),
+}
+
+/**
+    Bounds the resources a script can use. Each run of the top level or of `tick` starts with a
+    new budget.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptLimits {
+    /// Maximum number of operations of a run.
+    pub max_operations: u64,
+
+    /// Maximum duration of a run, checked every few operations. [`None`] to only count
+    /// operations, which keeps runs deterministic.
+    pub max_duration: Option<Duration>,
+
+    /// Maximum depth of function calls.
+    pub max_call_levels: usize,
+
+    /// Maximum length of a string, in bytes.
+    pub max_string_size: usize,
+
+    /// Maximum number of items of an array, of an object map or of the persistent state.
+    pub max_collection_size: usize,
+
+    /// Maximum number of lines kept until [`ScriptEngine::take_log`]: older lines are dropped.
+    pub max_log_lines: usize,
+}
+
+impl Default for ScriptLimits {
+    /// 100 000 operations within 2ms, 32 nested calls, collections of 4096 items and 256 lines
+    /// of log.
+    fn default() -> Self {
+        ScriptLimits {
+            max_operations: 100_000,
+            max_duration: Some(Duration::from_millis(2)),
+            max_call_levels: 32,
+            max_string_size: 4096,
+            max_collection_size: 4096,
+            max_log_lines: 256,
+        }
+    }
+}
+
+/// Number of operations between two checks of the duration of a run.
+const DURATION_CHECK_INTERVAL: u64 = 256;
+
+/// Message of the error raised when a run exceeds its budget.
+const BUDGET_EXCEEDED: &str = "budget exceeded";
+
+/// State shared by the script bindings.
+#[derive(Default)]
+struct ScriptContext {
+    axes: HashMap<String, i32>,
+    buttons: HashMap<String, bool>,
+    povs: HashMap<String, VJDPovCont>,
+    positions: Vec<VJDPosition>,
+    timestamp: Duration,
+    timers: HashMap<String, Duration>,
+    state: Map,
+    log: Vec<String>,
+    deadline: Option<Instant>,
+}
+
+impl ScriptContext {
+    /// Adds a line to the log, truncated to `max_size` bytes, keeping the last `max_lines`.
+    fn log(&mut self, text: &str, limits: &ScriptLimits) {
+        let mut end = text.len().min(limits.max_string_size);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        if limits.max_log_lines == 0 {
+            return;
+        }
+        if self.log.len() >= limits.max_log_lines {
+            self.log.remove(0);
+        }
+
+        self.log.push(text[..end].to_string());
+    }
+
+    fn position(&mut self, device: VJDevice) -> &mut VJDPosition {
+        let index = match self
+            .positions
+            .binary_search_by_key(&(device as u32), |position| position.get_device() as u32)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                self.positions.insert(index, VJDPosition::new(device));
+                index
+            }
+        };
+
+        &mut self.positions[index]
+    }
+}
+
+type Shared = Arc<Mutex<ScriptContext>>;
+
+fn lock(context: &Shared) -> MutexGuard<'_, ScriptContext> {
+    // A panicking binding cannot leave the context inconsistent: keep using it.
+    context
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn script_error(message: String) -> Box<EvalAltResult> {
+    EvalAltResult::ErrorRuntime(message.into(), Position::NONE).into()
+}
+
+fn device_of(device: INT) -> Result<VJDevice, Box<EvalAltResult>> {
+    u8::try_from(device)
+        .ok()
+        .and_then(VJDevice::get_from)
+        .ok_or_else(|| script_error(format!("device {} does not exist", device)))
+}
+
+fn axis_of(name: &str) -> Result<VJDAxis, Box<EvalAltResult>> {
+    VJDAxis::ALL
+        .iter()
+        .copied()
+        .find(|axis| format!("{:?}", axis) == name)
+        .ok_or_else(|| script_error(format!("axis {} does not exist", name)))
+}
+
+fn button_of(button: INT) -> Result<VJDButton, Box<EvalAltResult>> {
+    u8::try_from(button)
+        .ok()
+        .and_then(VJDButton::get_from)
+        .ok_or_else(|| script_error(format!("button {} does not exist", button)))
+}
+
+fn pov_of(pov: INT) -> Result<VJDPovNumber, Box<EvalAltResult>> {
+    match pov {
+        1 => Ok(VJDPovNumber::Pov1),
+        2 => Ok(VJDPovNumber::Pov2),
+        3 => Ok(VJDPovNumber::Pov3),
+        4 => Ok(VJDPovNumber::Pov4),
+        _ => Err(script_error(format!("POV {} does not exist", pov))),
+    }
+}
+
+fn direction_of(direction: INT) -> Result<VJDPovDisc, Box<EvalAltResult>> {
+    match direction {
+        -1 => Ok(VJDPovDisc::Neutral),
+        0 => Ok(VJDPovDisc::North),
+        1 => Ok(VJDPovDisc::East),
+        2 => Ok(VJDPovDisc::South),
+        3 => Ok(VJDPovDisc::West),
+        _ => Err(script_error(format!(
+            "direction {} does not exist",
+            direction
+        ))),
+    }
+}
+
+fn millis(duration: Duration) -> INT {
+    duration.as_millis() as INT
+}
+
+/// Registers the bindings of the module documentation.
+fn register_bindings(engine: &mut Engine, context: &Shared, limits: ScriptLimits) {
+    let shared = context.clone();
+    engine.register_fn("axis_input", move |name: &str| -> INT {
+        let value = lock(&shared).axes.get(name).copied();
+        value.unwrap_or(VJGeneral::NEUTRAL_AXIS_VALUE) as INT
+    });
+
+    let shared = context.clone();
+    engine.register_fn("button_input", move |name: &str| -> bool {
+        lock(&shared).buttons.get(name).copied().unwrap_or(false)
+    });
+
+    let shared = context.clone();
+    engine.register_fn("pov_input", move |name: &str| -> INT {
+        match lock(&shared).povs.get(name) {
+            Some(value) if !value.is_neutral() => value.get() as INT,
+            _ => -1,
+        }
+    });
+
+    let shared = context.clone();
+    engine.register_fn(
+        "set_axis",
+        move |device: INT, axis: &str, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let (device, axis) = (device_of(device)?, axis_of(axis)?);
+            let value = value.clamp(
+                VJGeneral::MIN_AXIS_VALUE as INT,
+                VJGeneral::MAX_AXIS_VALUE as INT,
+            );
+
+            lock(&shared).position(device).set_axis(axis, value as i32);
+            Ok(())
+        },
+    );
+
+    let shared = context.clone();
+    engine.register_fn(
+        "set_button",
+        move |device: INT, button: INT, pressed: bool| -> Result<(), Box<EvalAltResult>> {
+            let (device, button) = (device_of(device)?, button_of(button)?);
+            let mut context = lock(&shared);
+            let position = context.position(device);
+
+            if pressed {
+                position.set_button_pressed(button as u32);
+            } else {
+                position.set_button_released(button as u32);
+            }
+            Ok(())
+        },
+    );
+
+    let shared = context.clone();
+    engine.register_fn(
+        "set_cont_pov",
+        move |device: INT, pov: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let (device, pov) = (device_of(device)?, pov_of(pov)?);
+            let value = match value {
+                -1 => Some(VJDPovCont::NEUTRAL),
+                _ => u32::try_from(value).ok().and_then(VJDPovCont::new),
+            }
+            .ok_or_else(|| script_error(format!("POV value {} is out of range", value)))?;
+
+            lock(&shared).position(device).set_cont_pov(pov, value);
+            Ok(())
+        },
+    );
+
+    let shared = context.clone();
+    engine.register_fn(
+        "set_disc_pov",
+        move |device: INT, pov: INT, direction: INT| -> Result<(), Box<EvalAltResult>> {
+            let (device, pov) = (device_of(device)?, pov_of(pov)?);
+            let direction = direction_of(direction)?;
+
+            lock(&shared).position(device).set_disc_pov(pov, direction);
+            Ok(())
+        },
+    );
+
+    let shared = context.clone();
+    engine.register_fn("now", move || -> INT { millis(lock(&shared).timestamp) });
+
+    let shared = context.clone();
+    engine.register_fn("start_timer", move |name: &str| {
+        let mut context = lock(&shared);
+        let timestamp = context.timestamp;
+
+        context.timers.insert(name.to_string(), timestamp);
+    });
+
+    let shared = context.clone();
+    engine.register_fn("stop_timer", move |name: &str| {
+        lock(&shared).timers.remove(name);
+    });
+
+    let shared = context.clone();
+    engine.register_fn("timer", move |name: &str| -> INT {
+        let context = lock(&shared);
+
+        match context.timers.get(name) {
+            Some(&start) => millis(context.timestamp.checked_sub(start).unwrap_or_default()),
+            None => -1,
+        }
+    });
+
+    let shared = context.clone();
+    engine.register_fn("get_state", move |key: ImmutableString| -> Dynamic {
+        lock(&shared)
+            .state
+            .get(key.as_str())
+            .cloned()
+            .unwrap_or(Dynamic::UNIT)
+    });
+
+    let shared = context.clone();
+    engine.register_fn(
+        "set_state",
+        move |key: ImmutableString, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let mut context = lock(&shared);
+
+            if !context.state.contains_key(key.as_str())
+                && context.state.len() >= limits.max_collection_size
+            {
+                return Err(script_error(format!("state is full: {} is not added", key)));
+            }
+            if key.len() > limits.max_string_size
+                || value
+                    .read_lock::<ImmutableString>()
+                    .is_some_and(|text| text.len() > limits.max_string_size)
+            {
+                return Err(script_error(format!("state {} is too long", key)));
+            }
+
+            context.state.insert(key.as_str().into(), value);
+            Ok(())
+        },
+    );
+}
+
+/**
+    Holds a compiled script, its persistent state and the positions of the devices it sets.
+*/
+pub struct ScriptEngine {
+    engine: Engine,
+    ast: AST,
+    limits: ScriptLimits,
+    context: Shared,
+}
+
+impl ScriptEngine {
+    /**
+        Compiles a script and runs its top level, or returns [`ScriptError`] if it cannot be
+        compiled, fails, exceeds its limits or has no `tick` function.
+    */
+    pub fn new(script: &str, limits: ScriptLimits) -> Result<ScriptEngine, ScriptError> {
+        let context = Shared::default();
+        let mut engine = Engine::new();
+
+        // Sandbox: no access to files through modules, no code built at run time.
+        engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+        engine.disable_symbol("eval");
+
+        engine.set_max_operations(limits.max_operations);
+        engine.set_max_call_levels(limits.max_call_levels);
+        engine.set_max_string_size(limits.max_string_size);
+        engine.set_max_array_size(limits.max_collection_size);
+        engine.set_max_map_size(limits.max_collection_size);
+
+        let shared = context.clone();
+        engine.on_progress(move |operations| {
+            let deadline = lock(&shared).deadline?;
+
+            if operations % DURATION_CHECK_INTERVAL == 0 && Instant::now() > deadline {
+                Some(BUDGET_EXCEEDED.into())
+            } else {
+                None
+            }
+        });
+
+        let shared = context.clone();
+        engine.on_print(move |text| lock(&shared).log(text, &limits));
+        let shared = context.clone();
+        engine.on_debug(move |text, _, _| lock(&shared).log(text, &limits));
+
+        register_bindings(&mut engine, &context, limits);
+
+        let ast = engine.compile(script).map_err(ScriptError::Compile)?;
+
+        if !ast.iter_functions().any(|function| function.name == "tick") {
+            return Err(ScriptError::MissingTick);
+        }
+
+        let script_engine = ScriptEngine {
+            engine,
+            ast,
+            limits,
+            context,
+        };
+
+        script_engine.run(|engine| engine.engine.run_ast(&engine.ast))?;
+
+        Ok(script_engine)
+    }
+
+    /// Runs a part of the script within its budget, restoring the positions, the state and the
+    /// timers if it fails.
+    fn run(
+        &self,
+        run: impl FnOnce(&ScriptEngine) -> Result<(), Box<EvalAltResult>>,
+    ) -> Result<(), ScriptError> {
+        let saved = {
+            let mut context = lock(&self.context);
+            context.deadline = self
+                .limits
+                .max_duration
+                .map(|duration| Instant::now() + duration);
+            (
+                context.positions.clone(),
+                context.state.clone(),
+                context.timers.clone(),
+            )
+        };
+
+        let result = run(self);
+
+        let mut context = lock(&self.context);
+        context.deadline = None;
+
+        result.map_err(|error| {
+            let (positions, state, timers) = saved;
+            context.positions = positions;
+            context.state = state;
+            context.timers = timers;
+
+            match *error {
+                EvalAltResult::ErrorTooManyOperations(_) => ScriptError::BudgetExceeded,
+                EvalAltResult::ErrorTerminated(_, _) => ScriptError::BudgetExceeded,
+                _ => ScriptError::Runtime(error),
+            }
+        })
+    }
+
+    pub fn set_axis_input(&mut self, name: &str, value: i32) {
+        lock(&self.context).axes.insert(name.to_string(), value);
+    }
+
+    pub fn set_button_input(&mut self, name: &str, pressed: bool) {
+        lock(&self.context)
+            .buttons
+            .insert(name.to_string(), pressed);
+    }
+
+    pub fn set_pov_input(&mut self, name: &str, value: VJDPovCont) {
+        lock(&self.context).povs.insert(name.to_string(), value);
+    }
+
+    /**
+        Runs the `tick` function of the script at `timestamp` and returns the positions of the
+        devices set so far, ordered by device. On error, the positions, the state and the timers
+        are left as they were before the tick.
+    */
+    pub fn tick(&mut self, timestamp: Duration) -> Result<Vec<VJDPosition>, ScriptError> {
+        lock(&self.context).timestamp = timestamp;
+
+        self.run(|engine| {
+            let options = CallFnOptions::new().eval_ast(false).rewind_scope(true);
+
+            engine
+                .engine
+                .call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut rhai::Scope::new(),
+                    &engine.ast,
+                    "tick",
+                    (),
+                )
+                .map(drop)
+        })?;
+
+        Ok(self.positions())
+    }
+
+    /// Returns the positions of the devices set by the script, ordered by device.
+    pub fn positions(&self) -> Vec<VJDPosition> {
+        lock(&self.context).positions.clone()
+    }
+
+    /// Returns and forgets the lines written by `print` and `debug`.
+    pub fn take_log(&mut self) -> Vec<String> {
+        std::mem::take(&mut lock(&self.context).log)
+    }
+}
@@ -0,0 +1,900 @@
+//! Runs macros: timed sequences of control changes written in a small text format, for
+//! instance to test the bindings of a game.
+//!
+//! ```text
+//! # Fire three bursts while the stick sweeps right.
+//! track {
+//!     sweep X 0 32767 2s
+//!     axis X 16384
+//! }
+//!
+//! repeat 3 {
+//!     tap B1 50ms
+//!     wait 100ms
+//! }
+//! press B3
+//! wait 50ms
+//! pov POV1 East
+//! wait 500ms
+//! release all
+//! ```
+//!
+//! Each line holds one command, `#` starts a comment, and names are case-insensitive:
+//! - `press <button>`, `release <button>`: press or release a button named like [`VJDButton`].
+//! - `tap <button> <duration>`: press a button and release it after the duration.
+//! - `axis <axis> <value>`: set an axis named like [`VJDAxis`], in vJoy units.
+//! - `sweep <axis> <from> <to> <duration>`: move an axis linearly over the duration.
+//! - `pov <pov> <direction>`: set a discrete POV named like [`VJDPovNumber`] to a direction
+//!   named like [`VJDPovDisc`].
+//! - `cpov <pov> <value>`: set a continuous POV in hundredths of a degree, or to `Neutral`.
+//! - `release all`: release every button pressed and center every POV set by the macro.
+//! - `wait <duration>`: wait before the next command.
+//! - `repeat <count> { ... }`: run the enclosed commands several times.
+//! - `track { ... }`: run the enclosed commands in parallel with the rest of the macro, from
+//!   its start. Tracks are only allowed at the top level.
+//!
+//! Durations are written like `50ms` or `1.5s`. `tap`, `sweep` and `wait` take time: the next
+//! command of the same track starts when they end.
+//!
+//! A [`Macro`] is the compiled timeline of a text, run against a device by a [`MacroRunner`].
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn runner(text: &str) -> MacroRunner {
+        MacroRunner::new(Macro::parse(text).unwrap(), VJDevice::D1)
+    }
+
+    fn axis(value: i32) -> VJDFeedEvent {
+        VJDFeedEvent::Axis(VJDevice::D1, VJDAxis::X, VJDAxisRaw::saturating(value))
+    }
+
+    fn button(button: VJDButton, state: VJDButtonState) -> VJDFeedEvent {
+        VJDFeedEvent::Button(VJDevice::D1, button, state)
+    }
+
+    #[test]
+    fn compiles_to_a_timeline() {
+        let text = "
+            # Comment.
+            press B3
+            wait 50ms
+            sweep x 0 32767 2s   # Trailing comment.
+            POV pov1 EAST
+            cpov POV2 9000
+            tap B128 1.5s
+        ";
+        let timeline = Macro::parse(text).unwrap();
+
+        assert_eq!(ms(3550), timeline.duration());
+        assert_eq!(
+            vec![
+                (
+                    ms(0),
+                    MacroAction::Button(VJDButton::B3, VJDButtonState::Pressed)
+                ),
+                (
+                    ms(50),
+                    MacroAction::Sweep {
+                        axis: VJDAxis::X,
+                        from: 0,
+                        to: 32767,
+                        length: ms(2000)
+                    }
+                ),
+                (
+                    ms(2050),
+                    MacroAction::DiscPov(VJDPovNumber::Pov1, VJDPovDisc::East)
+                ),
+                (
+                    ms(2050),
+                    MacroAction::ContPov(VJDPovNumber::Pov2, VJDPovCont::new(9000).unwrap())
+                ),
+                (
+                    ms(2050),
+                    MacroAction::Button(VJDButton::B128, VJDButtonState::Pressed)
+                ),
+                (
+                    ms(3550),
+                    MacroAction::Button(VJDButton::B128, VJDButtonState::Released)
+                ),
+            ],
+            timeline
+                .steps()
+                .iter()
+                .map(|step| (step.at, step.action))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn errors_have_lines() {
+        let error = |text: &str| Macro::parse(text).unwrap_err();
+
+        assert_eq!(
+            MacroError::UnknownCommand(2, "jump".to_string()),
+            error("press B1\njump B2")
+        );
+        assert_eq!(
+            MacroError::InvalidArgument(1, "B129".to_string()),
+            error("press B129")
+        );
+        assert_eq!(
+            MacroError::InvalidArgument(1, "32768".to_string()),
+            error("axis X 32768")
+        );
+        assert_eq!(
+            MacroError::InvalidArgument(1, "-5ms".to_string()),
+            error("wait -5ms")
+        );
+        assert_eq!(MacroError::MissingArgument(1), error("sweep X 0 100"));
+        assert_eq!(
+            MacroError::UnbalancedBlock(1),
+            error("repeat 2 {\npress B1")
+        );
+        assert_eq!(MacroError::UnbalancedBlock(2), error("press B1\n}"));
+        assert_eq!(
+            MacroError::NestedTrack(2),
+            error("repeat 2 {\ntrack {\n}\n}")
+        );
+        assert_eq!(
+            MacroError::TooManySteps(1),
+            error("repeat 1000 {\nrepeat 1000 {\npress B1\n}\n}")
+        );
+        assert_eq!(
+            MacroError::TooManySteps(2),
+            error("press B1\nrepeat 4294967295 {\n}")
+        );
+        assert_eq!(
+            "line 2: unknown command `jump`",
+            error("press B1\njump B2").to_string()
+        );
+    }
+
+    #[test]
+    fn runs_tracks_and_repeats() {
+        let mut runner = runner(
+            "
+            track {
+                sweep X 0 1000 100ms
+            }
+            repeat 2 {
+                tap B1 30ms
+                wait 20ms
+            }
+            ",
+        );
+        let pressed = button(VJDButton::B1, VJDButtonState::Pressed);
+        let released = button(VJDButton::B1, VJDButtonState::Released);
+
+        assert_eq!(vec![pressed, axis(0)], runner.poll(ms(500)));
+        assert_eq!(vec![axis(200)], runner.poll(ms(520)));
+        assert_eq!(vec![released, axis(400)], runner.poll(ms(540)));
+        assert_eq!(Some(ms(550)), runner.next_due());
+        assert_eq!(vec![pressed, released, axis(900)], runner.poll(ms(590)));
+        assert!(!runner.is_finished());
+        assert_eq!(vec![axis(1000)], runner.poll(ms(700)));
+        assert!(runner.is_finished());
+        assert!(runner.poll(ms(800)).is_empty());
+    }
+
+    #[test]
+    fn later_commands_override_sweeps() {
+        let mut runner = runner("track {\nsweep X 0 1000 1s\n}\nwait 500ms\naxis X 7");
+
+        runner.poll(ms(0));
+        assert_eq!(vec![axis(7)], runner.poll(ms(600)));
+        assert!(runner.poll(ms(2000)).is_empty());
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn cancel_resets_touched_controls() {
+        let mut runner = runner(
+            "press B2\npress B5\nrelease B2\npov POV2 South\ncpov POV1 4500\naxis Rz 0\nwait 1s\npress B6",
+        );
+
+        assert_eq!(6, runner.poll(ms(0)).len());
+        assert_eq!(
+            vec![
+                button(VJDButton::B2, VJDButtonState::Released),
+                button(VJDButton::B5, VJDButtonState::Released),
+                VJDFeedEvent::DiscPov(VJDevice::D1, VJDPovNumber::Pov2, VJDPovDisc::Neutral),
+                VJDFeedEvent::ContPov(VJDevice::D1, VJDPovNumber::Pov1, VJDPovCont::NEUTRAL),
+                VJDFeedEvent::Axis(VJDevice::D1, VJDAxis::Rz, VJDAxisRaw::NEUTRAL),
+            ],
+            runner.cancel()
+        );
+        assert!(runner.is_finished());
+        assert!(runner.poll(ms(2000)).is_empty());
+    }
+}
+
+use crate::vjoy_base::device::axis::VJDAxisRaw;
+use crate::vjoy_base::device::feeding::VJDFeedEvent;
+use crate::vjoy_base::device::pov::VJDPovCont;
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDButtonState, VJDPovDisc, VJDPovNumber, VJDevice,
+};
+use crate::vjoy_base::driver::VJGeneral;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/**
+    Describes an error state of [`Macro`] parsing. Lines start at 1.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MacroError {
+    /// The line does not start with a known command. The line and the command are provided.
+    UnknownCommand(usize, String),
+
+    /// The command lacks an argument. The line is provided.
+    MissingArgument(usize),
+
+    /// An argument of the command is invalid or superfluous. The line and the argument are
+    /// provided.
+    InvalidArgument(usize, String),
+
+    /// A block is not closed, or a `}` closes no block. The line is provided.
+    UnbalancedBlock(usize),
+
+    /// A track is not at the top level of the macro. The line is provided.
+    NestedTrack(usize),
+
+    /**
+        Compiling the macro takes more than [`Macro::MAX_STEPS`] steps or statements, usually
+        because of nested repeats. The line of the outermost repeat is provided.
+    */
+    TooManySteps(usize),
+}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacroError::UnknownCommand(line, command) => {
+                write!(f, "line {}: unknown command `{}`", line, command)
+            }
+            MacroError::MissingArgument(line) => write!(f, "line {}: missing argument", line),
+            MacroError::InvalidArgument(line, argument) => {
+                write!(f, "line {}: invalid argument `{}`", line, argument)
+            }
+            MacroError::UnbalancedBlock(line) => write!(f, "line {}: unbalanced block", line),
+            MacroError::NestedTrack(line) => {
+                write!(f, "line {}: tracks are only allowed at the top level", line)
+            }
+            MacroError::TooManySteps(line) => {
+                write!(f, "line {}: the macro has too many steps", line)
+            }
+        }
+    }
+}
+
+/**
+    Describes a change of a control made by a [`Macro`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MacroAction {
+    Button(VJDButton, VJDButtonState),
+
+    Axis(VJDAxis, i32),
+
+    /// Moves an axis linearly from a value to another.
+    Sweep {
+        axis: VJDAxis,
+        from: i32,
+        to: i32,
+        length: Duration,
+    },
+
+    DiscPov(VJDPovNumber, VJDPovDisc),
+
+    ContPov(VJDPovNumber, VJDPovCont),
+
+    /// Releases every button pressed and centers every POV set by the macro.
+    ReleaseAll,
+}
+
+/**
+    Describes an action of a [`Macro`] and when it starts.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MacroStep {
+    /// Time since the start of the macro.
+    pub at: Duration,
+
+    pub action: MacroAction,
+}
+
+/**
+    Describes a parsed statement, before the timeline is compiled.
+*/
+enum Statement {
+    Action(MacroAction),
+    Wait(Duration),
+    Tap(VJDButton, Duration),
+    Repeat(usize, u32, Vec<Statement>),
+    Track(Vec<Statement>),
+}
+
+/**
+    Describes a compiled macro: its steps, sorted by start time.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Macro {
+    steps: Vec<MacroStep>,
+    duration: Duration,
+}
+
+impl Macro {
+    /// Maximum number of steps of a macro.
+    pub const MAX_STEPS: usize = 100_000;
+
+    /// Parses and compiles a macro written in the format of the module documentation.
+    pub fn parse(text: &str) -> Result<Macro, MacroError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or("").trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let statements = parse_block(&mut lines, None)?;
+        let mut timeline = Macro::default();
+        let mut budget = Macro::MAX_STEPS;
+        let end = timeline.compile(&statements, Duration::ZERO, None, &mut budget)?;
+        timeline.duration = timeline.duration.max(end);
+
+        // Stable, so simultaneous steps keep their order in the text.
+        timeline.steps.sort_by_key(|step| step.at);
+        Ok(timeline)
+    }
+
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    /// Returns the time at which the last step ends.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /**
+        Adds the steps of the statements starting at `start`, and returns when they end.
+        `repeat` is the line of the outermost repeat being compiled, if any. Each statement
+        compiled and each iteration of a repeat takes one unit of `budget`, so repeats of waits
+        or of empty blocks cannot hang the compilation.
+    */
+    fn compile(
+        &mut self,
+        statements: &[Statement],
+        start: Duration,
+        repeat: Option<usize>,
+        budget: &mut usize,
+    ) -> Result<Duration, MacroError> {
+        let mut time = start;
+
+        for statement in statements {
+            *budget = budget
+                .checked_sub(1)
+                .ok_or(MacroError::TooManySteps(repeat.unwrap_or_default()))?;
+
+            match statement {
+                Statement::Action(action) => self.push(time, *action, repeat)?,
+                Statement::Wait(duration) => time += *duration,
+                Statement::Tap(button, duration) => {
+                    self.push(
+                        time,
+                        MacroAction::Button(*button, VJDButtonState::Pressed),
+                        repeat,
+                    )?;
+                    time += *duration;
+                    self.push(
+                        time,
+                        MacroAction::Button(*button, VJDButtonState::Released),
+                        repeat,
+                    )?;
+                }
+                Statement::Repeat(line, count, body) => {
+                    let repeat = repeat.or(Some(*line));
+
+                    for _ in 0..*count {
+                        *budget = budget
+                            .checked_sub(1)
+                            .ok_or(MacroError::TooManySteps(repeat.unwrap_or_default()))?;
+                        time = self.compile(body, time, repeat, budget)?;
+                    }
+                }
+                Statement::Track(body) => {
+                    let end = self.compile(body, start, repeat, budget)?;
+                    self.duration = self.duration.max(end);
+                }
+            }
+
+            if let Statement::Action(MacroAction::Sweep { length, .. }) = statement {
+                time += *length;
+            }
+        }
+
+        Ok(time)
+    }
+
+    fn push(
+        &mut self,
+        at: Duration,
+        action: MacroAction,
+        repeat: Option<usize>,
+    ) -> Result<(), MacroError> {
+        if self.steps.len() == Macro::MAX_STEPS {
+            return Err(MacroError::TooManySteps(repeat.unwrap_or_default()));
+        }
+
+        self.steps.push(MacroStep { at, action });
+        Ok(())
+    }
+}
+
+/**
+    Parses the statements of a block until its `}`, or until the end of the text for the top
+    level. `opening` is the line of the `{` of the block.
+*/
+fn parse_block<'a, I>(lines: &mut I, opening: Option<usize>) -> Result<Vec<Statement>, MacroError>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let mut statements = Vec::new();
+
+    while let Some((line, text)) = lines.next() {
+        let mut words = text.split_whitespace();
+        let command = words.next().unwrap_or_default().to_lowercase();
+        let mut arg = || words.next().ok_or(MacroError::MissingArgument(line));
+
+        let statement = match command.as_str() {
+            "}" => {
+                return match opening {
+                    Some(_) => Ok(statements),
+                    None => Err(MacroError::UnbalancedBlock(line)),
+                };
+            }
+            "press" => Statement::Action(MacroAction::Button(
+                parse_button(line, arg()?)?,
+                VJDButtonState::Pressed,
+            )),
+            "release" => {
+                let target = arg()?;
+                if target.eq_ignore_ascii_case("all") {
+                    Statement::Action(MacroAction::ReleaseAll)
+                } else {
+                    Statement::Action(MacroAction::Button(
+                        parse_button(line, target)?,
+                        VJDButtonState::Released,
+                    ))
+                }
+            }
+            "tap" => Statement::Tap(parse_button(line, arg()?)?, parse_duration(line, arg()?)?),
+            "axis" => Statement::Action(MacroAction::Axis(
+                parse_axis(line, arg()?)?,
+                parse_value(line, arg()?)?,
+            )),
+            "sweep" => Statement::Action(MacroAction::Sweep {
+                axis: parse_axis(line, arg()?)?,
+                from: parse_value(line, arg()?)?,
+                to: parse_value(line, arg()?)?,
+                length: parse_duration(line, arg()?)?,
+            }),
+            "pov" => Statement::Action(MacroAction::DiscPov(
+                parse_pov(line, arg()?)?,
+                parse_direction(line, arg()?)?,
+            )),
+            "cpov" => Statement::Action(MacroAction::ContPov(
+                parse_pov(line, arg()?)?,
+                parse_cont(line, arg()?)?,
+            )),
+            "wait" => Statement::Wait(parse_duration(line, arg()?)?),
+            "repeat" => {
+                let count = arg()?;
+                let count = count
+                    .parse()
+                    .map_err(|_| MacroError::InvalidArgument(line, count.to_string()))?;
+                expect_opening(line, arg()?)?;
+                Statement::Repeat(line, count, parse_block(lines, Some(line))?)
+            }
+            "track" => {
+                if opening.is_some() {
+                    return Err(MacroError::NestedTrack(line));
+                }
+                expect_opening(line, arg()?)?;
+                Statement::Track(parse_block(lines, Some(line))?)
+            }
+            _ => return Err(MacroError::UnknownCommand(line, command)),
+        };
+
+        if let Some(extra) = words.next() {
+            return Err(MacroError::InvalidArgument(line, extra.to_string()));
+        }
+        statements.push(statement);
+    }
+
+    match opening {
+        Some(line) => Err(MacroError::UnbalancedBlock(line)),
+        None => Ok(statements),
+    }
+}
+
+fn expect_opening(line: usize, word: &str) -> Result<(), MacroError> {
+    match word {
+        "{" => Ok(()),
+        _ => Err(MacroError::InvalidArgument(line, word.to_string())),
+    }
+}
+
+fn invalid(line: usize, word: &str) -> MacroError {
+    MacroError::InvalidArgument(line, word.to_string())
+}
+
+fn parse_button(line: usize, word: &str) -> Result<VJDButton, MacroError> {
+    word.strip_prefix(|c| c == 'B' || c == 'b')
+        .and_then(|number| number.parse().ok())
+        .and_then(VJDButton::get_from)
+        .ok_or_else(|| invalid(line, word))
+}
+
+fn parse_axis(line: usize, word: &str) -> Result<VJDAxis, MacroError> {
+    VJDAxis::ALL
+        .iter()
+        .copied()
+        .find(|axis| format!("{:?}", axis).eq_ignore_ascii_case(word))
+        .ok_or_else(|| invalid(line, word))
+}
+
+fn parse_value(line: usize, word: &str) -> Result<i32, MacroError> {
+    word.parse()
+        .ok()
+        .filter(|value| (VJGeneral::MIN_AXIS_VALUE..=VJGeneral::MAX_AXIS_VALUE).contains(value))
+        .ok_or_else(|| invalid(line, word))
+}
+
+fn parse_pov(line: usize, word: &str) -> Result<VJDPovNumber, MacroError> {
+    match word.to_lowercase().as_str() {
+        "pov1" => Ok(VJDPovNumber::Pov1),
+        "pov2" => Ok(VJDPovNumber::Pov2),
+        "pov3" => Ok(VJDPovNumber::Pov3),
+        "pov4" => Ok(VJDPovNumber::Pov4),
+        _ => Err(invalid(line, word)),
+    }
+}
+
+fn parse_direction(line: usize, word: &str) -> Result<VJDPovDisc, MacroError> {
+    match word.to_lowercase().as_str() {
+        "neutral" => Ok(VJDPovDisc::Neutral),
+        "north" => Ok(VJDPovDisc::North),
+        "east" => Ok(VJDPovDisc::East),
+        "south" => Ok(VJDPovDisc::South),
+        "west" => Ok(VJDPovDisc::West),
+        _ => Err(invalid(line, word)),
+    }
+}
+
+fn parse_cont(line: usize, word: &str) -> Result<VJDPovCont, MacroError> {
+    if word.eq_ignore_ascii_case("neutral") {
+        return Ok(VJDPovCont::NEUTRAL);
+    }
+
+    word.parse()
+        .ok()
+        .and_then(VJDPovCont::new)
+        .filter(|value| !value.is_neutral())
+        .ok_or_else(|| invalid(line, word))
+}
+
+/// Longest duration of a command, a day.
+const MAX_SECONDS: f64 = 86_400.0;
+
+fn parse_duration(line: usize, word: &str) -> Result<Duration, MacroError> {
+    let (number, scale) = match word.strip_suffix("ms") {
+        Some(number) => (number, 0.001),
+        None => (word.strip_suffix('s').unwrap_or("-"), 1.0),
+    };
+
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|number| number * scale)
+        .filter(|seconds| (0.0..=MAX_SECONDS).contains(seconds))
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| invalid(line, word))
+}
+
+/**
+    Describes a sweep being run.
+*/
+#[derive(Debug, Clone)]
+struct ActiveSweep {
+    axis: VJDAxis,
+    from: i32,
+    to: i32,
+    start: Duration,
+    length: Duration,
+}
+
+impl ActiveSweep {
+    /// Returns the value of the axis at a time of the macro.
+    fn value_at(&self, time: Duration) -> i32 {
+        let elapsed = time.saturating_sub(self.start);
+        if elapsed >= self.length {
+            return self.to;
+        }
+
+        let progress = elapsed.as_secs_f64() / self.length.as_secs_f64();
+        self.from + ((self.to - self.from) as f64 * progress).round() as i32
+    }
+}
+
+/**
+    Runs a [`Macro`] against a device.
+
+    Like [`Player`](crate::vjoy_extra::replay::Player), a runner is driven by the caller, which
+    gives the time elapsed since an origin of its choice to [`MacroRunner::poll`], and gets the
+    updates to send to the device. [`MacroRunner::run`] drives it with the system clock.
+*/
+#[derive(Debug, Clone)]
+pub struct MacroRunner {
+    timeline: Macro,
+    device: VJDevice,
+    /// Index of the next step to run.
+    cursor: usize,
+    /// Time of the caller at which the macro started, set by the first poll.
+    start: Option<Duration>,
+    /// Time of the caller of the last poll.
+    last_poll: Duration,
+    sweeps: Vec<ActiveSweep>,
+    /// Last value sent to each axis, in the order they were first set.
+    axes: Vec<(VJDAxis, i32)>,
+    pressed: Vec<VJDButton>,
+    /// Every button the macro pressed or released.
+    buttons: Vec<VJDButton>,
+    disc_povs: Vec<VJDPovNumber>,
+    cont_povs: Vec<VJDPovNumber>,
+}
+
+impl MacroRunner {
+    /// Interval between two updates of an axis being swept, for [`MacroRunner::next_due`].
+    pub const SWEEP_PERIOD: Duration = Duration::from_millis(10);
+
+    pub fn new(timeline: Macro, device: VJDevice) -> MacroRunner {
+        MacroRunner {
+            timeline,
+            device,
+            cursor: 0,
+            start: None,
+            last_poll: Duration::ZERO,
+            sweeps: Vec::new(),
+            axes: Vec::new(),
+            pressed: Vec::new(),
+            buttons: Vec::new(),
+            disc_povs: Vec::new(),
+            cont_povs: Vec::new(),
+        }
+    }
+
+    pub fn get_device(&self) -> VJDevice {
+        self.device
+    }
+
+    /// Returns whether every step ran and every sweep ended, or the macro was cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.cursor == self.timeline.steps.len() && self.sweeps.is_empty()
+    }
+
+    /**
+        Returns the time of the caller at which the next update is due, or [`None`] if the macro
+        is finished or was not started by a poll. While an axis is swept, updates are due every
+        [`MacroRunner::SWEEP_PERIOD`].
+    */
+    pub fn next_due(&self) -> Option<Duration> {
+        let start = self.start?;
+        let step = self
+            .timeline
+            .steps
+            .get(self.cursor)
+            .map(|step| start + step.at);
+
+        let sweep = if self.sweeps.is_empty() {
+            None
+        } else {
+            Some(self.last_poll + MacroRunner::SWEEP_PERIOD)
+        };
+
+        match (step, sweep) {
+            (Some(step), Some(sweep)) => Some(step.min(sweep)),
+            (step, sweep) => step.or(sweep),
+        }
+    }
+
+    /**
+        Returns the updates due at `now`, the time elapsed since an origin chosen by the caller.
+        The first poll starts the macro at `now`.
+    */
+    pub fn poll(&mut self, now: Duration) -> Vec<VJDFeedEvent> {
+        let start = *self.start.get_or_insert(now);
+        let time = now.saturating_sub(start);
+        let mut events = Vec::new();
+        self.last_poll = now;
+
+        while let Some(step) = self.timeline.steps.get(self.cursor).copied() {
+            if step.at > time {
+                break;
+            }
+            self.cursor += 1;
+            self.apply(step, &mut events);
+        }
+
+        let mut updates = Vec::new();
+        self.sweeps.retain(|sweep| {
+            updates.push((sweep.axis, sweep.value_at(time)));
+            time < sweep.start + sweep.length
+        });
+        for (axis, value) in updates {
+            self.set_axis(axis, value, false, &mut events);
+        }
+
+        events
+    }
+
+    fn apply(&mut self, step: MacroStep, events: &mut Vec<VJDFeedEvent>) {
+        let device = self.device;
+
+        match step.action {
+            MacroAction::Button(button, state) => {
+                self.pressed.retain(|&pressed| pressed != button);
+                if state == VJDButtonState::Pressed {
+                    self.pressed.push(button);
+                }
+                touch(&mut self.buttons, button);
+                events.push(VJDFeedEvent::Button(device, button, state));
+            }
+            MacroAction::Axis(axis, value) => {
+                self.sweeps.retain(|sweep| sweep.axis != axis);
+                self.set_axis(axis, value, true, events);
+            }
+            MacroAction::Sweep {
+                axis,
+                from,
+                to,
+                length,
+            } => {
+                self.sweeps.retain(|sweep| sweep.axis != axis);
+                self.sweeps.push(ActiveSweep {
+                    axis,
+                    from,
+                    to,
+                    start: step.at,
+                    length,
+                });
+            }
+            MacroAction::DiscPov(pov, direction) => {
+                touch(&mut self.disc_povs, pov);
+                events.push(VJDFeedEvent::DiscPov(device, pov, direction));
+            }
+            MacroAction::ContPov(pov, value) => {
+                touch(&mut self.cont_povs, pov);
+                events.push(VJDFeedEvent::ContPov(device, pov, value));
+            }
+            MacroAction::ReleaseAll => {
+                for button in self.pressed.drain(..) {
+                    events.push(VJDFeedEvent::Button(
+                        device,
+                        button,
+                        VJDButtonState::Released,
+                    ));
+                }
+                events.extend(self.neutral_povs());
+            }
+        }
+    }
+
+    /// Sets an axis, only sending the value if it changed unless `always` is set.
+    fn set_axis(
+        &mut self,
+        axis: VJDAxis,
+        value: i32,
+        always: bool,
+        events: &mut Vec<VJDFeedEvent>,
+    ) {
+        match self.axes.iter_mut().find(|(other, _)| *other == axis) {
+            Some((_, last)) if *last == value && !always => return,
+            Some((_, last)) => *last = value,
+            None => self.axes.push((axis, value)),
+        }
+
+        events.push(VJDFeedEvent::Axis(
+            self.device,
+            axis,
+            VJDAxisRaw::saturating(value),
+        ));
+    }
+
+    fn neutral_povs(&self) -> Vec<VJDFeedEvent> {
+        let device = self.device;
+        let disc = self
+            .disc_povs
+            .iter()
+            .map(|&pov| VJDFeedEvent::DiscPov(device, pov, VJDPovDisc::Neutral));
+        let cont = self
+            .cont_povs
+            .iter()
+            .map(|&pov| VJDFeedEvent::ContPov(device, pov, VJDPovCont::NEUTRAL));
+
+        disc.chain(cont).collect()
+    }
+
+    /**
+        Stops the macro and returns the updates resetting every control it touched to neutral:
+        buttons are released, POVs centered and axes set to their neutral value.
+    */
+    pub fn cancel(&mut self) -> Vec<VJDFeedEvent> {
+        let device = self.device;
+        let mut events: Vec<_> = self
+            .buttons
+            .iter()
+            .map(|&button| VJDFeedEvent::Button(device, button, VJDButtonState::Released))
+            .collect();
+        events.extend(self.neutral_povs());
+        events.extend(
+            self.axes
+                .iter()
+                .map(|&(axis, _)| VJDFeedEvent::Axis(device, axis, VJDAxisRaw::NEUTRAL)),
+        );
+
+        self.cursor = self.timeline.steps.len();
+        self.sweeps.clear();
+        self.pressed.clear();
+        events
+    }
+
+    /**
+        Runs the macro in real time until its end, sending the updates with
+        [`VJDFeedEvent::send`]. When `cancel` is set, the macro is cancelled and the touched
+        controls reset.
+
+        Returns `false` as soon as sending an update fails, `true` otherwise. The resets of a
+        cancellation are all sent before a failure is reported.
+    */
+    pub fn run(&mut self, cancel: &AtomicBool) -> bool {
+        let origin = Instant::now();
+
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                // Every control is reset, even after a failed one.
+                let mut sent = true;
+                for event in self.cancel() {
+                    sent &= event.send();
+                }
+                return sent;
+            }
+            if !self.poll(origin.elapsed()).iter().all(VJDFeedEvent::send) {
+                return false;
+            }
+
+            match self.next_due() {
+                // Short sleeps, so that a cancellation is handled quickly.
+                Some(due) => std::thread::sleep(
+                    due.saturating_sub(origin.elapsed())
+                        .min(MacroRunner::SWEEP_PERIOD),
+                ),
+                None => return true,
+            }
+        }
+    }
+}
+
+/// Adds a control to the touched ones.
+fn touch<T: PartialEq>(touched: &mut Vec<T>, control: T) {
+    if !touched.contains(&control) {
+        touched.push(control);
+    }
+}
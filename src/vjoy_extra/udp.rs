@@ -0,0 +1,957 @@
+//! Feeds vJoy devices from remote machines with a compact binary protocol over UDP.
+//!
+//! A [`UdpClient`], for instance on the board of a controller, sends the changes of one device
+//! to a [`UdpServer`], which applies them through [`VJDPosFeed`].
+//!
+//! # Protocol
+//!
+//! Each datagram holds one [`UdpPacket`]: the changes of the controls of one device. Numbers
+//! are little-endian.
+//!
+//! | Size       | Field                                                                    |
+//! |------------|--------------------------------------------------------------------------|
+//! | 2          | Magic `VJ`.                                                              |
+//! | 1          | Version of the protocol, [`UdpPacket::VERSION`].                         |
+//! | 1          | Device, 1 to 16.                                                         |
+//! | 4          | Sequence number, incremented by one for each packet of the device.       |
+//! | 1          | Flags. Bit 0 is set on the first packet of a client, bit 1 on full-state packets, see below. |
+//! | 1          | Axis mask: bit `i` is set when the `i`-th axis of [`VJDAxis::ALL`] changed. |
+//! | 2 per axis | Value of each changed axis, in the order of the mask, from 0 to 32767.   |
+//! | 1          | Number of changed buttons, up to 128.                                    |
+//! | 1 per button | Bits 0-6: button number minus one. Bit 7: set when pressed.            |
+//! | 1          | POV mask: bits 0-3 for discrete POVs 1 to 4, bits 4-7 for continuous POVs 1 to 4. |
+//! | 1 per discrete POV | Direction, 0 (North) to 3 (West) or 255 when neutral.            |
+//! | 4 per continuous POV | Value in hundredths of a degree, 0 to 35999, or `0xFFFFFFFF` when neutral. |
+//!
+//! A packet with no change keeps the device alive. For example, a packet pressing button 3 and
+//! moving X to its maximum is `56 4A 01 01 05 00 00 00 00 01 FF 7F 01 82 00`.
+//!
+//! The server ignores invalid packets, and the packets of a device whose sequence number is not
+//! newer than the last one applied, so late and duplicated datagrams have no effect. Sequence
+//! numbers wrap around. The first packet of a client has the start flag set: it is applied
+//! from the neutral position whatever its sequence number, so a restarted client is not
+//! ignored, unless it is the last one applied or a few numbers behind it, which makes it a late
+//! or duplicated datagram of the current client. Clients start from a sequence number taken
+//! from the clock, so a restarted client is unlikely to be mistaken for a late one. When no
+//! packet of a device arrives for the timeout of the server, the device is reset to neutral.
+//!
+//! As only changes are sent, a lost datagram would leave its controls stale, and a device reset
+//! after a timeout would lose the controls which do not change afterwards. To recover, a client
+//! regularly sends its whole state in a full-state packet: every control it has set, with bit 1
+//! of the flags set. Like a start packet, it is applied from the neutral position.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    const WAIT: Duration = Duration::from_secs(2);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn server() -> UdpServer {
+        UdpServer::bind("127.0.0.1:0").unwrap().timeout(ms(500))
+    }
+
+    fn packet(sequence: u32, x: i32) -> UdpPacket {
+        UdpPacket {
+            device: VJDevice::D3,
+            sequence,
+            start: false,
+            axes: vec![(VJDAxis::X, x)],
+            ..UdpPacket::default()
+        }
+    }
+
+    #[test]
+    fn documented_layout() {
+        let packet = UdpPacket {
+            device: VJDevice::D1,
+            sequence: 5,
+            start: false,
+            axes: vec![(VJDAxis::X, 32767)],
+            buttons: vec![(VJDButton::B3, VJDButtonState::Pressed)],
+            ..UdpPacket::default()
+        };
+        let bytes = [
+            0x56, 0x4A, 0x01, 0x01, 0x05, 0x00, 0x00, 0x00, 0x00, 0x01, 0xFF, 0x7F, 0x01, 0x82,
+            0x00,
+        ];
+
+        assert_eq!(&bytes[..], &packet.encode()[..]);
+        assert_eq!(Ok(packet), UdpPacket::decode(&bytes));
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let packet = UdpPacket {
+            device: VJDevice::D16,
+            sequence: u32::MAX,
+            start: true,
+            full: true,
+            axes: vec![(VJDAxis::Y, 0), (VJDAxis::Slider2, 16384)],
+            buttons: vec![
+                (VJDButton::B1, VJDButtonState::Released),
+                (VJDButton::B128, VJDButtonState::Pressed),
+            ],
+            disc_povs: vec![
+                (VJDPovNumber::Pov2, VJDPovDisc::Neutral),
+                (VJDPovNumber::Pov4, VJDPovDisc::West),
+            ],
+            cont_povs: vec![
+                (VJDPovNumber::Pov1, VJDPovCont::NEUTRAL),
+                (VJDPovNumber::Pov3, VJDPovCont::new(35999).unwrap()),
+            ],
+        };
+
+        assert_eq!(Ok(packet.clone()), UdpPacket::decode(&packet.encode()));
+        assert!(packet.encode().len() <= UdpPacket::MAX_SIZE);
+    }
+
+    #[test]
+    fn invalid_packets() {
+        let valid = packet(1, 100).encode();
+        let with = |index: usize, byte: u8| {
+            let mut bytes = valid.clone();
+            bytes[index] = byte;
+            UdpPacket::decode(&bytes)
+        };
+
+        assert_eq!(Err(PacketError::Truncated), UdpPacket::decode(&valid[..11]));
+        assert_eq!(Err(PacketError::BadMagic), with(0, b'X'));
+        assert_eq!(Err(PacketError::UnsupportedVersion(2)), with(2, 2));
+        assert_eq!(Err(PacketError::InvalidDevice(17)), with(3, 17));
+        assert_eq!(Err(PacketError::InvalidValue("axis")), with(11, 0x80));
+
+        let mut trailing = valid.clone();
+        trailing.push(0);
+        assert_eq!(
+            Err(PacketError::TrailingBytes),
+            UdpPacket::decode(&trailing)
+        );
+
+        let mut bad_pov = packet(1, 100);
+        bad_pov.axes.clear();
+        let mut bytes = bad_pov.encode();
+        bytes.truncate(bytes.len() - 1);
+        bytes.extend_from_slice(&[0x01, 4]);
+        assert_eq!(
+            Err(PacketError::InvalidValue("discrete POV")),
+            UdpPacket::decode(&bytes)
+        );
+    }
+
+    #[test]
+    fn loopback_feeds_the_server() {
+        let mut server = server();
+        let mut client = UdpClient::connect(server.local_addr().unwrap(), VJDevice::D2).unwrap();
+
+        client.set_axis(VJDAxis::Rx, 1234);
+        client.set_button(VJDButton::B7, VJDButtonState::Pressed);
+        client.set_cont_pov(VJDPovNumber::Pov2, VJDPovCont::new(9000).unwrap());
+        client.flush().unwrap();
+
+        let positions = server.receive(WAIT, ms(0)).unwrap();
+        assert_eq!(1, positions.len());
+        let position = positions[0];
+        assert_eq!(VJDevice::D2, position.get_device());
+        assert_eq!(1234, position.get_axis(VJDAxis::Rx).get());
+        assert!(position.get_buttons().contains(VJDButton::B7));
+        assert_eq!(9000, position.get_cont_pov(VJDPovNumber::Pov2).get());
+
+        // Later changes apply on top of the previous ones.
+        client.set_button(VJDButton::B8, VJDButtonState::Pressed);
+        client.flush().unwrap();
+        let position = server.receive(WAIT, ms(10)).unwrap()[0];
+        assert_eq!(1234, position.get_axis(VJDAxis::Rx).get());
+        assert_eq!(2, position.get_buttons().len());
+        assert_eq!(2, server.stats().applied);
+    }
+
+    #[test]
+    fn full_state_repairs_lost_packets() {
+        let mut server = server();
+        let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+        relay.set_read_timeout(Some(WAIT)).unwrap();
+        let server_address = server.local_addr().unwrap();
+        let mut client = UdpClient::connect(relay.local_addr().unwrap(), VJDevice::D6)
+            .unwrap()
+            .full_state_period(3);
+
+        // Forwards the next datagram of the client to the server, or drops it.
+        let mut buffer = [0; UdpPacket::MAX_SIZE];
+        let mut relay_next = |forward: bool| {
+            let length = relay.recv(&mut buffer).unwrap();
+            if forward {
+                relay.send_to(&buffer[..length], server_address).unwrap();
+            }
+        };
+        let pressed = |position: &VJDPosition| position.get_buttons().len();
+
+        client.set_axis(VJDAxis::X, 100);
+        client.set_button(VJDButton::B1, VJDButtonState::Pressed);
+        client.flush().unwrap();
+        relay_next(true);
+        assert_eq!(1, pressed(&server.receive(WAIT, ms(0)).unwrap()[0]));
+
+        client.set_button(VJDButton::B2, VJDButtonState::Pressed);
+        client.flush().unwrap();
+        relay_next(false);
+
+        client.flush().unwrap();
+        relay_next(true);
+        assert_eq!(1, pressed(&server.receive(WAIT, ms(10)).unwrap()[0]));
+
+        // The third packet since the start one holds the whole state.
+        client.flush().unwrap();
+        relay_next(true);
+        let position = server.receive(WAIT, ms(20)).unwrap()[0];
+        assert_eq!(2, pressed(&position));
+        assert_eq!(100, position.get_axis(VJDAxis::X).get());
+
+        // After a reset, keep-alives only apply their changes until the next full state.
+        assert_eq!(1, server.receive(ms(1), ms(5000)).unwrap().len());
+        client.flush().unwrap();
+        relay_next(true);
+        assert_eq!(0, pressed(&server.receive(WAIT, ms(5010)).unwrap()[0]));
+
+        client.flush().unwrap();
+        relay_next(true);
+        assert_eq!(0, pressed(&server.receive(WAIT, ms(5020)).unwrap()[0]));
+
+        client.flush().unwrap();
+        relay_next(true);
+        assert_eq!(2, pressed(&server.receive(WAIT, ms(5030)).unwrap()[0]));
+    }
+
+    #[test]
+    fn late_packets_are_dropped() {
+        let mut server = server();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        let mut send = |packet: UdpPacket| {
+            socket.send(&packet.encode()).unwrap();
+            server.receive(WAIT, ms(0)).unwrap()
+        };
+
+        assert_eq!(1, send(packet(10, 1)).len());
+        assert!(send(packet(9, 2)).is_empty());
+        assert!(send(packet(10, 3)).is_empty());
+        assert_eq!(1, send(packet(11, 4)).len());
+
+        // A restarted client.
+        let mut start = packet(0, 5);
+        start.start = true;
+        let positions = send(start);
+        assert_eq!(5, positions[0].get_axis(VJDAxis::X).get());
+
+        // Sequence numbers wrap around.
+        let mut server = UdpServer::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        let mut send = |packet: UdpPacket| {
+            socket.send(&packet.encode()).unwrap();
+            server.receive(WAIT, ms(0)).unwrap()
+        };
+        assert_eq!(1, send(packet(u32::MAX, 1)).len());
+        assert_eq!(1, send(packet(0, 2)).len());
+        socket.send(b"garbage").unwrap();
+        assert!(server.receive(WAIT, ms(0)).unwrap().is_empty());
+
+        let stats = server.stats();
+        assert_eq!(
+            (3, 2, 1, 0),
+            (stats.received, stats.applied, stats.invalid, stats.late)
+        );
+    }
+
+    #[test]
+    fn late_start_packets_are_dropped() {
+        let mut server = server();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server.local_addr().unwrap()).unwrap();
+        let mut send = |sequence: u32, x: i32| {
+            let mut packet = packet(sequence, x);
+            packet.start = true;
+            socket.send(&packet.encode()).unwrap();
+            server.receive(WAIT, ms(0)).unwrap()
+        };
+
+        assert_eq!(1, send(20, 1).len());
+        assert!(send(20, 2).is_empty());
+        assert!(send(20 - UdpServer::START_WINDOW, 3).is_empty());
+        assert_eq!(1, send(21, 4).len());
+
+        // Far behind: a restarted client.
+        let positions = send(21 - UdpServer::START_WINDOW - 1, 5);
+        assert_eq!(5, positions[0].get_axis(VJDAxis::X).get());
+        assert_eq!(2, server.stats().late);
+    }
+
+    #[test]
+    fn silent_devices_are_reset() {
+        let mut server = server();
+        let mut client = UdpClient::connect(server.local_addr().unwrap(), VJDevice::D5).unwrap();
+
+        client.set_axis(VJDAxis::Z, 0);
+        client.flush().unwrap();
+        assert_eq!(1, server.receive(WAIT, ms(1000)).unwrap().len());
+
+        // A keep-alive.
+        client.flush().unwrap();
+        assert_eq!(1, server.receive(WAIT, ms(1400)).unwrap().len());
+
+        assert!(server.receive(ms(1), ms(1900)).unwrap().is_empty());
+        assert_eq!(
+            vec![VJDPosition::new(VJDevice::D5)],
+            server.receive(ms(1), ms(1901)).unwrap()
+        );
+        assert!(server.receive(ms(1), ms(5000)).unwrap().is_empty());
+        assert_eq!(1, server.stats().timeouts);
+    }
+}
+
+use crate::vjoy_base::device::feeding::VJDPosFeed;
+use crate::vjoy_base::device::pov::VJDPovCont;
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDButtonState, VJDPosition, VJDPovDisc, VJDPovNumber, VJDevice,
+};
+use crate::vjoy_base::driver::VJGeneral;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 2] = b"VJ";
+
+const POVS: [VJDPovNumber; 4] = [
+    VJDPovNumber::Pov1,
+    VJDPovNumber::Pov2,
+    VJDPovNumber::Pov3,
+    VJDPovNumber::Pov4,
+];
+
+/**
+    Describes why a datagram is not a valid [`UdpPacket`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PacketError {
+    /// The datagram ends in the middle of a field.
+    Truncated,
+
+    /// The datagram does not start with the magic `VJ`.
+    BadMagic,
+
+    /// The version of the protocol is not supported. The version is provided.
+    UnsupportedVersion(u8),
+
+    /// The device does not exist. Its number is provided.
+    InvalidDevice(u8),
+
+    /// A value is out of range. The kind of control is provided.
+    InvalidValue(&'static str),
+
+    /// Bytes follow the last field.
+    TrailingBytes,
+}
+
+/**
+    Describes the changes of the controls of a device sent in one datagram, see the module
+    documentation for its layout.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UdpPacket {
+    pub device: VJDevice,
+    pub sequence: u32,
+
+    /// Set on the first packet of a client.
+    pub start: bool,
+
+    /// Set when the packet holds every control set by the client, instead of its changes.
+    pub full: bool,
+
+    /// Changed axes, in vJoy units. Encoded in the order of [`VJDAxis::ALL`].
+    pub axes: Vec<(VJDAxis, i32)>,
+
+    pub buttons: Vec<(VJDButton, VJDButtonState)>,
+
+    /// Changed discrete POVs. Encoded in the order of their number.
+    pub disc_povs: Vec<(VJDPovNumber, VJDPovDisc)>,
+
+    /// Changed continuous POVs. Encoded in the order of their number.
+    pub cont_povs: Vec<(VJDPovNumber, VJDPovCont)>,
+}
+
+impl Default for UdpPacket {
+    fn default() -> Self {
+        UdpPacket {
+            device: VJDevice::D1,
+            sequence: 0,
+            start: false,
+            full: false,
+            axes: Vec::new(),
+            buttons: Vec::new(),
+            disc_povs: Vec::new(),
+            cont_povs: Vec::new(),
+        }
+    }
+}
+
+impl UdpPacket {
+    /// Version of the protocol.
+    pub const VERSION: u8 = 1;
+
+    /// Largest size of an encoded packet, when every control changes.
+    pub const MAX_SIZE: usize = 10 + 2 * 8 + 1 + 128 + 1 + 4 + 4 * 4;
+
+    /// Whether the packet has no change, only keeping the device alive.
+    pub fn is_empty(&self) -> bool {
+        self.axes.is_empty()
+            && self.buttons.is_empty()
+            && self.disc_povs.is_empty()
+            && self.cont_povs.is_empty()
+    }
+
+    /**
+        Encodes the packet. When a control appears several times, the last change is kept, and
+        axis values are clamped to the vJoy range.
+    */
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(UdpPacket::MAX_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(UdpPacket::VERSION);
+        bytes.push(self.device as u8);
+        bytes.extend_from_slice(&self.sequence.to_le_bytes());
+        bytes.push(self.start as u8 | (self.full as u8) << 1);
+
+        let axes: Vec<(usize, i32)> = VJDAxis::ALL
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &axis)| last_change(&self.axes, axis).map(|value| (index, value)))
+            .collect();
+        bytes.push(axes.iter().fold(0, |mask, (index, _)| mask | 1 << index));
+        for (_, value) in axes {
+            let value = value.clamp(VJGeneral::MIN_AXIS_VALUE, VJGeneral::MAX_AXIS_VALUE);
+            bytes.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+
+        let mut buttons: Vec<(VJDButton, VJDButtonState)> = Vec::new();
+        for &(button, state) in &self.buttons {
+            buttons.retain(|&(other, _)| other != button);
+            buttons.push((button, state));
+        }
+        bytes.push(buttons.len() as u8);
+        for (button, state) in buttons {
+            let pressed = if state == VJDButtonState::Pressed {
+                0x80
+            } else {
+                0
+            };
+            bytes.push((button as u8 - 1) | pressed);
+        }
+
+        let disc: Vec<_> = POVS
+            .iter()
+            .map(|&pov| last_change(&self.disc_povs, pov))
+            .collect();
+        let cont: Vec<_> = POVS
+            .iter()
+            .map(|&pov| last_change(&self.cont_povs, pov))
+            .collect();
+        let present = disc
+            .iter()
+            .map(Option::is_some)
+            .chain(cont.iter().map(Option::is_some));
+        let mask = present.enumerate().fold(
+            0,
+            |mask, (bit, present)| if present { mask | 1 << bit } else { mask },
+        );
+        bytes.push(mask);
+        for direction in disc.into_iter().flatten() {
+            bytes.push(direction as i8 as u8);
+        }
+        for value in cont.into_iter().flatten() {
+            bytes.extend_from_slice(&value.get().to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Decodes and validates a datagram.
+    pub fn decode(bytes: &[u8]) -> Result<UdpPacket, PacketError> {
+        let mut reader = Reader(bytes);
+
+        if reader.take(2)? != MAGIC {
+            return Err(PacketError::BadMagic);
+        }
+        let version = reader.byte()?;
+        if version != UdpPacket::VERSION {
+            return Err(PacketError::UnsupportedVersion(version));
+        }
+        let device = reader.byte()?;
+        let device = VJDevice::get_from(device).ok_or(PacketError::InvalidDevice(device))?;
+        let sequence = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        let flags = reader.byte()?;
+
+        let mut packet = UdpPacket {
+            device,
+            sequence,
+            start: flags & 1 != 0,
+            full: flags & 2 != 0,
+            ..UdpPacket::default()
+        };
+
+        let mask = reader.byte()?;
+        for (index, &axis) in VJDAxis::ALL.iter().enumerate() {
+            if mask & 1 << index != 0 {
+                let value = u16::from_le_bytes(reader.take(2)?.try_into().unwrap()) as i32;
+                if value > VJGeneral::MAX_AXIS_VALUE {
+                    return Err(PacketError::InvalidValue("axis"));
+                }
+                packet.axes.push((axis, value));
+            }
+        }
+
+        let count = reader.byte()?;
+        if count > VJDButton::MAX_BUTTONS {
+            return Err(PacketError::InvalidValue("button"));
+        }
+        for _ in 0..count {
+            let byte = reader.byte()?;
+            // Seven bits always make a valid button.
+            let button = VJDButton::get_from((byte & 0x7F) + 1).unwrap();
+            let state = if byte & 0x80 != 0 {
+                VJDButtonState::Pressed
+            } else {
+                VJDButtonState::Released
+            };
+            packet.buttons.push((button, state));
+        }
+
+        let mask = reader.byte()?;
+        for (bit, &pov) in POVS.iter().enumerate() {
+            if mask & 1 << bit != 0 {
+                let direction = match reader.byte()? {
+                    0 => VJDPovDisc::North,
+                    1 => VJDPovDisc::East,
+                    2 => VJDPovDisc::South,
+                    3 => VJDPovDisc::West,
+                    0xFF => VJDPovDisc::Neutral,
+                    _ => return Err(PacketError::InvalidValue("discrete POV")),
+                };
+                packet.disc_povs.push((pov, direction));
+            }
+        }
+        for (bit, &pov) in POVS.iter().enumerate() {
+            if mask & 1 << (bit + 4) != 0 {
+                let value = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+                let value =
+                    VJDPovCont::new(value).ok_or(PacketError::InvalidValue("continuous POV"))?;
+                packet.cont_povs.push((pov, value));
+            }
+        }
+
+        if !reader.0.is_empty() {
+            return Err(PacketError::TrailingBytes);
+        }
+
+        Ok(packet)
+    }
+
+    /// Applies the changes to a position of the device.
+    pub fn apply(&self, position: &mut VJDPosition) {
+        for &(axis, value) in &self.axes {
+            position.set_axis(axis, value);
+        }
+        for &(button, state) in &self.buttons {
+            position.set_button(button as u32, state);
+        }
+        for &(pov, direction) in &self.disc_povs {
+            position.set_disc_pov(pov, direction);
+        }
+        for &(pov, value) in &self.cont_povs {
+            position.set_cont_pov(pov, value);
+        }
+    }
+}
+
+/// Returns the last change of a control.
+fn last_change<C: PartialEq + Copy, V: Copy>(changes: &[(C, V)], control: C) -> Option<V> {
+    changes
+        .iter()
+        .rev()
+        .find(|(other, _)| *other == control)
+        .map(|&(_, value)| value)
+}
+
+/// Records changes into a state, keeping the last value of each control.
+fn merge<C: PartialEq + Copy, V: Copy>(state: &mut Vec<(C, V)>, changes: &[(C, V)]) {
+    for &(control, value) in changes {
+        state.retain(|&(other, _)| other != control);
+        state.push((control, value));
+    }
+}
+
+/// Reads the fields of a datagram.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], PacketError> {
+        if self.0.len() < length {
+            return Err(PacketError::Truncated);
+        }
+
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, PacketError> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+/**
+    Holds the counters of a [`UdpServer`].
+*/
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct UdpServerStats {
+    /// Datagrams received.
+    pub received: u64,
+
+    /// Packets applied to a device.
+    pub applied: u64,
+
+    /// Datagrams which are not valid packets.
+    pub invalid: u64,
+
+    /**
+        Packets dropped because their sequence number is not newer than the last applied, or
+        start packets within [`UdpServer::START_WINDOW`] of it.
+    */
+    pub late: u64,
+
+    /// Devices reset because no packet arrived in time.
+    pub timeouts: u64,
+
+    /// Positions [`VJDPosFeed`] failed to send, with [`UdpServer::run`].
+    pub failed_sends: u64,
+}
+
+/**
+    Holds what a [`UdpServer`] knows of a device fed by a client.
+*/
+struct RemoteDevice {
+    position: VJDPosition,
+    sequence: u32,
+    last_packet: Duration,
+}
+
+/**
+    Receives [`UdpPacket`]s and turns them into positions of vJoy devices.
+
+    Like [`Player`](crate::vjoy_extra::replay::Player), the server is given the time elapsed
+    since an origin of the caller's choice by [`UdpServer::receive`], and returns the positions
+    to send. [`UdpServer::run`] drives it with the system clock and sends them with
+    [`VJDPosFeed`].
+*/
+pub struct UdpServer {
+    socket: UdpSocket,
+    timeout: Duration,
+    devices: HashMap<VJDevice, RemoteDevice>,
+    stats: UdpServerStats,
+}
+
+impl UdpServer {
+    /// Default time without packets after which a device is reset.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Longest time [`UdpServer::run`] waits for a datagram before checking the timeouts.
+    const TICK: Duration = Duration::from_millis(20);
+
+    /// Sequence numbers behind the last applied one within which a start packet is late.
+    pub const START_WINDOW: u32 = 8;
+
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<UdpServer> {
+        Ok(UdpServer {
+            socket: UdpSocket::bind(address)?,
+            timeout: UdpServer::DEFAULT_TIMEOUT,
+            devices: HashMap::new(),
+            stats: UdpServerStats::default(),
+        })
+    }
+
+    /// Changes the time without packets after which a device is reset to neutral.
+    pub fn timeout(mut self, timeout: Duration) -> UdpServer {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn stats(&self) -> UdpServerStats {
+        self.stats
+    }
+
+    /**
+        Waits up to `wait` for a datagram, handles every datagram received, and returns the
+        positions to send: the updated devices, then the devices reset because they were
+        silent for longer than the timeout. `now` is the time elapsed since an origin chosen by
+        the caller.
+    */
+    pub fn receive(&mut self, wait: Duration, now: Duration) -> io::Result<Vec<VJDPosition>> {
+        let mut positions = Vec::new();
+        let mut buffer = [0; UdpPacket::MAX_SIZE + 1];
+
+        // A zero timeout is rejected by the socket.
+        self.socket.set_nonblocking(false)?;
+        self.socket
+            .set_read_timeout(Some(wait.max(Duration::from_micros(1))))?;
+
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(length) => {
+                    if let Some(position) = self.handle(&buffer[..length], now) {
+                        positions.retain(|other: &VJDPosition| {
+                            other.get_device() != position.get_device()
+                        });
+                        positions.push(position);
+                    }
+                }
+                Err(error)
+                    if error.kind() == io::ErrorKind::WouldBlock
+                        || error.kind() == io::ErrorKind::TimedOut =>
+                {
+                    break
+                }
+                // Reported on some platforms when a previous datagram sent to a client failed.
+                Err(error) if error.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(error) => return Err(error),
+            }
+
+            // Only wait for the first datagram.
+            self.socket.set_nonblocking(true)?;
+        }
+
+        positions.extend(self.expire(now));
+        Ok(positions)
+    }
+
+    /// Handles a datagram and returns the new position of its device if it is applied.
+    fn handle(&mut self, datagram: &[u8], now: Duration) -> Option<VJDPosition> {
+        self.stats.received += 1;
+
+        let packet = match UdpPacket::decode(datagram) {
+            Ok(packet) => packet,
+            Err(_) => {
+                self.stats.invalid += 1;
+                return None;
+            }
+        };
+
+        let device = packet.device;
+        if let Some(remote) = self.devices.get(&device) {
+            let late = if packet.start {
+                remote.sequence.wrapping_sub(packet.sequence) <= UdpServer::START_WINDOW
+            } else {
+                // Newer when ahead by less than half of the range, so that numbers wrap.
+                (packet.sequence.wrapping_sub(remote.sequence) as i32) <= 0
+            };
+
+            if late {
+                self.stats.late += 1;
+                return None;
+            }
+        }
+
+        let remote = self.devices.entry(device).or_insert(RemoteDevice {
+            position: VJDPosition::new(device),
+            sequence: packet.sequence,
+            last_packet: now,
+        });
+
+        if packet.start || packet.full {
+            remote.position = VJDPosition::new(device);
+        }
+        packet.apply(&mut remote.position);
+        remote.sequence = packet.sequence;
+        remote.last_packet = now;
+        self.stats.applied += 1;
+
+        Some(remote.position)
+    }
+
+    /// Forgets the silent devices and returns their neutral position.
+    fn expire(&mut self, now: Duration) -> Vec<VJDPosition> {
+        let timeout = self.timeout;
+        let mut expired: Vec<VJDevice> = self
+            .devices
+            .iter()
+            .filter(|(_, remote)| now.saturating_sub(remote.last_packet) > timeout)
+            .map(|(&device, _)| device)
+            .collect();
+        expired.sort_by_key(|&device| device as u32);
+
+        for device in &expired {
+            self.devices.remove(device);
+        }
+        self.stats.timeouts += expired.len() as u64;
+
+        expired.into_iter().map(VJDPosition::new).collect()
+    }
+
+    /**
+        Receives packets and feeds the devices with [`VJDPosFeed`] until `stop` is set. The
+        devices must be owned by this application. Positions which cannot be sent are counted
+        in [`UdpServerStats::failed_sends`].
+    */
+    pub fn run(&mut self, stop: &AtomicBool) -> io::Result<()> {
+        let origin = Instant::now();
+
+        while !stop.load(Ordering::Relaxed) {
+            for position in self.receive(UdpServer::TICK, origin.elapsed())? {
+                if !VJDPosFeed::send_position(&position) {
+                    self.stats.failed_sends += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/**
+    Sends the changes of the controls of one device to a [`UdpServer`].
+
+    Changes are gathered with the `set_` methods and sent in one packet by
+    [`UdpClient::flush`], which should also be called regularly when nothing changes to keep
+    the device alive. Every few packets, the whole state of the device is sent instead, so the
+    server recovers from lost datagrams.
+*/
+pub struct UdpClient {
+    socket: UdpSocket,
+    pending: UdpPacket,
+    /// Last value of every control set, sent in full-state packets.
+    state: UdpPacket,
+    full_state_period: u32,
+    /// Packets sent since the last one applied from the neutral position.
+    since_full: u32,
+}
+
+impl UdpClient {
+    /// Default number of packets from one full-state packet to the next.
+    pub const DEFAULT_FULL_STATE_PERIOD: u32 = 10;
+
+    /// Binds a local socket of the same family as the server and connects it to the server.
+    pub fn connect(server: impl ToSocketAddrs, device: VJDevice) -> io::Result<UdpClient> {
+        let server = server.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address for the server")
+        })?;
+        let local: SocketAddr = if server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(server)?;
+
+        Ok(UdpClient {
+            socket,
+            pending: UdpPacket {
+                device,
+                sequence: UdpClient::first_sequence(),
+                start: true,
+                ..UdpPacket::default()
+            },
+            state: UdpPacket {
+                device,
+                full: true,
+                ..UdpPacket::default()
+            },
+            full_state_period: UdpClient::DEFAULT_FULL_STATE_PERIOD,
+            since_full: 0,
+        })
+    }
+
+    /**
+        Changes how often the whole state is sent: one packet out of `packets` is a full-state
+        packet. A period of 0 only sends changes.
+    */
+    pub fn full_state_period(mut self, packets: u32) -> UdpClient {
+        self.full_state_period = packets;
+        self
+    }
+
+    /// Returns a sequence number from the clock, to start far from a previous client.
+    fn first_sequence() -> u32 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos() ^ time.as_secs() as u32)
+            .unwrap_or_default()
+    }
+
+    pub fn get_device(&self) -> VJDevice {
+        self.pending.device
+    }
+
+    pub fn set_axis(&mut self, axis: VJDAxis, value: i32) {
+        self.pending.axes.push((axis, value));
+    }
+
+    pub fn set_button(&mut self, button: VJDButton, state: VJDButtonState) {
+        self.pending.buttons.push((button, state));
+    }
+
+    pub fn set_disc_pov(&mut self, pov: VJDPovNumber, direction: VJDPovDisc) {
+        self.pending.disc_povs.push((pov, direction));
+    }
+
+    pub fn set_cont_pov(&mut self, pov: VJDPovNumber, value: VJDPovCont) {
+        self.pending.cont_povs.push((pov, value));
+    }
+
+    /**
+        Sends the pending changes in one packet, or a packet without change to keep the device
+        alive. Every [`UdpClient::full_state_period`] packets, the whole state is sent instead.
+    */
+    pub fn flush(&mut self) -> io::Result<()> {
+        let state = &mut self.state;
+        merge(&mut state.axes, &self.pending.axes);
+        merge(&mut state.buttons, &self.pending.buttons);
+        merge(&mut state.disc_povs, &self.pending.disc_povs);
+        merge(&mut state.cont_povs, &self.pending.cont_povs);
+
+        // A start packet is already applied from the neutral position.
+        let full = !self.pending.start
+            && self.full_state_period > 0
+            && self.since_full + 1 >= self.full_state_period;
+
+        if full {
+            state.sequence = self.pending.sequence;
+            self.socket.send(&state.encode())?;
+        } else {
+            self.socket.send(&self.pending.encode())?;
+        }
+
+        self.since_full = if full || self.pending.start {
+            0
+        } else {
+            self.since_full + 1
+        };
+
+        let packet = &mut self.pending;
+        packet.sequence = packet.sequence.wrapping_add(1);
+        packet.start = false;
+        packet.axes.clear();
+        packet.buttons.clear();
+        packet.disc_povs.clear();
+        packet.cont_povs.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,1221 @@
+//! Feeds vJoy devices from browsers and other WebSocket clients with JSON messages.
+//!
+//! A [`WsServer`] accepts WebSocket connections and hands their text messages to a [`Bridge`],
+//! which leases devices to clients, feeds them with [`VJDPosFeed`] and reports their state to
+//! the clients subscribed to them.
+//!
+//! # Messages
+//!
+//! Every message is a JSON object whose `type` names it. Devices, buttons and POVs are given by
+//! number, axes by name (`"X"`, `"Rx"`, `"Slider1"`...).
+//!
+//! | Request                                                     | Effect                                   |
+//! |-------------------------------------------------------------|------------------------------------------|
+//! | `{"type": "lease", "device": 1}`                            | Acquires the device for the client.      |
+//! | `{"type": "release", "device": 1}`                          | Resets the device and relinquishes it.   |
+//! | `{"type": "subscribe", "device": 1}`                        | Reports the controls and state of the device. |
+//! | `{"type": "unsubscribe", "device": 1}`                      | Stops reporting the state of the device. |
+//! | `{"type": "axis", "device": 1, "axis": "X", "value": 16384}` | Moves an axis, from 0 to 32767.         |
+//! | `{"type": "button", "device": 1, "button": 3, "pressed": true}` | Presses or releases a button.        |
+//! | `{"type": "pov", "device": 1, "pov": 1, "direction": "north"}` | Moves a discrete POV: `neutral`, `north`, `east`, `south` or `west`. |
+//! | `{"type": "cont_pov", "device": 1, "pov": 1, "value": 9000}` | Moves a continuous POV, in hundredths of a degree, or `null` for neutral. |
+//!
+//! A client must lease a device before feeding it, and a device is leased by one client at a
+//! time. Leasing a device acquires it with [`VJDOwnership`] and resets it to neutral; releasing
+//! it, or closing the connection, resets it and relinquishes it.
+//!
+//! The server replies `{"type": "leased", "device": 1}`, `{"type": "released", "device": 1}` and
+//! `{"type": "unsubscribed", "device": 1}`. A subscription is answered by the controls of the
+//! device, then its state:
+//!
+//! ```json
+//! {"type": "capabilities", "device": 1, "controls": {"axes": ["X", "Y"], "buttons": 8, "disc_povs": 1, "cont_povs": 0, "ffb_effects": []}}
+//! {"type": "state", "device": 1, "leased": true, "axes": {"X": 16384, "Y": 16384}, "buttons": [3], "disc_povs": ["north"], "cont_povs": []}
+//! ```
+//!
+//! The state is sent again to every subscriber after each change of the device. Feeding
+//! messages have no other reply. A failed request is answered by
+//! `{"type": "error", "request": "axis", "message": "..."}`, `request` being `null` when the
+//! message cannot be read.
+//!
+//! Each connection may send a limited number of messages per period, see [`RateLimit`].
+//! Messages over the limit are answered by an error and ignored.
+//!
+//! A client which does not read its messages is disconnected once [`Bridge::outbox_size`]
+//! messages wait for it, and sending to a client fails after a timeout. The server serves up to
+//! [`WsServer::max_connections`] connections at a time and closes the others when accepted.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::net::TcpStream;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use tungstenite::Message;
+
+    #[derive(Default)]
+    struct Fake {
+        owned: Vec<VJDevice>,
+        sent: Vec<VJDPosition>,
+    }
+
+    #[derive(Clone, Default)]
+    struct FakeDevices(Arc<Mutex<Fake>>);
+
+    impl FakeDevices {
+        fn owned(&self) -> Vec<VJDevice> {
+            self.0.lock().unwrap().owned.clone()
+        }
+
+        fn last_sent(&self) -> Option<VJDPosition> {
+            self.0.lock().unwrap().sent.last().copied()
+        }
+    }
+
+    impl BridgeDevices for FakeDevices {
+        fn acquire(&mut self, device: VJDevice) -> bool {
+            let owned = &mut self.0.lock().unwrap().owned;
+            // Device 2 is owned by another application.
+            if device == VJDevice::D2 || owned.contains(&device) {
+                return false;
+            }
+            owned.push(device);
+            true
+        }
+
+        fn relinquish(&mut self, device: VJDevice) {
+            self.0
+                .lock()
+                .unwrap()
+                .owned
+                .retain(|&owned| owned != device);
+        }
+
+        fn send(&mut self, position: &VJDPosition) -> bool {
+            self.0.lock().unwrap().sent.push(*position);
+            true
+        }
+
+        fn config(&mut self, device: VJDevice) -> Option<VJDConfig> {
+            match device {
+                VJDevice::D1 | VJDevice::D2 => Some(
+                    VJDConfig::new()
+                        .axes(&[VJDAxis::X, VJDAxis::Y])
+                        .buttons(8)
+                        .disc_povs(1),
+                ),
+                _ => None,
+            }
+        }
+    }
+
+    fn messages(outbox: &Receiver<String>) -> Vec<Value> {
+        outbox
+            .try_iter()
+            .map(|message| serde_json::from_str(&message).unwrap())
+            .collect()
+    }
+
+    fn error(outbox: &Receiver<String>) -> String {
+        let messages = messages(outbox);
+        assert_eq!(1, messages.len(), "{:?}", messages);
+        assert_eq!("error", messages[0]["type"]);
+        messages[0]["message"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn leases_guard_the_devices() {
+        let devices = FakeDevices::default();
+        let mut bridge = Bridge::new(devices.clone());
+        let (a, outbox_a) = bridge.connect();
+        let (b, outbox_b) = bridge.connect();
+        let now = Duration::from_secs(0);
+
+        bridge.handle(a, r#"{"type": "lease", "device": 1}"#, now);
+        assert_eq!("leased", messages(&outbox_a)[0]["type"]);
+        assert_eq!(vec![VJDevice::D1], devices.owned());
+        assert_eq!(Some(a), bridge.lessee(VJDevice::D1));
+
+        bridge.handle(b, r#"{"type": "lease", "device": 1}"#, now);
+        assert_eq!("device 1 is leased by another client", error(&outbox_b));
+        bridge.handle(
+            b,
+            r#"{"type": "axis", "device": 1, "axis": "X", "value": 0}"#,
+            now,
+        );
+        assert_eq!("device 1 is not leased by this client", error(&outbox_b));
+        bridge.handle(b, r#"{"type": "lease", "device": 2}"#, now);
+        assert_eq!("device 2 cannot be acquired", error(&outbox_b));
+        bridge.handle(b, r#"{"type": "lease", "device": 3}"#, now);
+        assert_eq!("device 3 does not exist", error(&outbox_b));
+
+        bridge.handle(
+            a,
+            r#"{"type": "axis", "device": 1, "axis": "X", "value": 0}"#,
+            now,
+        );
+        assert!(messages(&outbox_a).is_empty());
+        assert_eq!(0, devices.last_sent().unwrap().get_axis(VJDAxis::X).get());
+
+        // Closing the connection resets and frees the device.
+        bridge.disconnect(a);
+        assert_eq!(Some(VJDPosition::new(VJDevice::D1)), devices.last_sent());
+        assert!(devices.owned().is_empty());
+        bridge.handle(b, r#"{"type": "lease", "device": 1}"#, now);
+        assert_eq!("leased", messages(&outbox_b)[0]["type"]);
+    }
+
+    #[test]
+    fn subscribers_follow_the_state() {
+        let devices = FakeDevices::default();
+        let mut bridge = Bridge::new(devices.clone());
+        let (panel, outbox_panel) = bridge.connect();
+        let (viewer, outbox_viewer) = bridge.connect();
+        let now = Duration::from_secs(0);
+
+        bridge.handle(viewer, r#"{"type": "subscribe", "device": 1}"#, now);
+        let replies = messages(&outbox_viewer);
+        assert_eq!("capabilities", replies[0]["type"]);
+        assert_eq!(8, replies[0]["controls"]["buttons"]);
+        assert_eq!("state", replies[1]["type"]);
+        assert_eq!(false, replies[1]["leased"]);
+
+        bridge.handle(panel, r#"{"type": "lease", "device": 1}"#, now);
+        bridge.handle(
+            panel,
+            r#"{"type": "button", "device": 1, "button": 3, "pressed": true}"#,
+            now,
+        );
+        bridge.handle(
+            panel,
+            r#"{"type": "pov", "device": 1, "pov": 1, "direction": "east"}"#,
+            now,
+        );
+        let states = messages(&outbox_viewer);
+        assert_eq!(3, states.len());
+        assert_eq!(true, states[2]["leased"]);
+        assert_eq!(serde_json::json!([3]), states[2]["buttons"]);
+        assert_eq!(serde_json::json!(["east"]), states[2]["disc_povs"]);
+        assert_eq!(16384, states[2]["axes"]["X"]);
+        messages(&outbox_panel);
+
+        bridge.handle(
+            panel,
+            r#"{"type": "axis", "device": 1, "axis": "Z", "value": 0}"#,
+            now,
+        );
+        assert_eq!("device 1 has no axis Z", error(&outbox_panel));
+        bridge.handle(
+            panel,
+            r#"{"type": "button", "device": 1, "button": 9, "pressed": true}"#,
+            now,
+        );
+        assert_eq!("device 1 has no button 9", error(&outbox_panel));
+        bridge.handle(
+            panel,
+            r#"{"type": "axis", "device": 1, "axis": "X", "value": 40000}"#,
+            now,
+        );
+        assert_eq!("invalid axis value 40000", error(&outbox_panel));
+        bridge.handle(panel, r#"{"type": "jump"}"#, now);
+        let reply = &messages(&outbox_panel)[0];
+        assert_eq!(Value::Null, reply["request"]);
+
+        bridge.handle(viewer, r#"{"type": "unsubscribe", "device": 1}"#, now);
+        bridge.handle(panel, r#"{"type": "release", "device": 1}"#, now);
+        assert_eq!(1, messages(&outbox_viewer).len());
+        assert_eq!("released", messages(&outbox_panel)[0]["type"]);
+    }
+
+    #[test]
+    fn connections_are_rate_limited() {
+        let mut bridge = Bridge::new(FakeDevices::default()).rate_limit(RateLimit {
+            messages: 2,
+            period: Duration::from_secs(1),
+        });
+        let (client, outbox) = bridge.connect();
+        let subscribe = r#"{"type": "subscribe", "device": 1}"#;
+
+        bridge.handle(client, subscribe, Duration::from_millis(0));
+        bridge.handle(client, subscribe, Duration::from_millis(100));
+        assert_eq!(4, messages(&outbox).len());
+
+        bridge.handle(client, subscribe, Duration::from_millis(200));
+        assert_eq!("too many messages", error(&outbox));
+
+        // One message is allowed every half a second.
+        bridge.handle(client, subscribe, Duration::from_millis(600));
+        assert_eq!(2, messages(&outbox).len());
+        bridge.handle(client, subscribe, Duration::from_millis(700));
+        assert_eq!("too many messages", error(&outbox));
+    }
+
+    #[test]
+    fn slow_clients_are_disconnected() {
+        let devices = FakeDevices::default();
+        let mut bridge = Bridge::new(devices.clone()).outbox_size(2);
+        let (client, outbox) = bridge.connect();
+        let now = Duration::from_secs(0);
+
+        bridge.handle(client, r#"{"type": "lease", "device": 1}"#, now);
+        // The capabilities fit, not the state.
+        bridge.handle(client, r#"{"type": "subscribe", "device": 1}"#, now);
+
+        assert_eq!(None, bridge.lessee(VJDevice::D1));
+        assert!(devices.owned().is_empty());
+        assert_eq!(2, messages(&outbox).len());
+        assert!(outbox.recv().is_err());
+    }
+
+    #[test]
+    fn connections_are_limited() {
+        let server = WsServer::with_bridge("127.0.0.1:0", Bridge::new(FakeDevices::default()))
+            .unwrap()
+            .max_connections(1);
+        let address = server.local_addr().unwrap();
+        let url = format!("ws://{}/", address);
+        let server = Arc::new(server);
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = {
+            let (server, stop) = (Arc::clone(&server), Arc::clone(&stop));
+            thread::spawn(move || server.run(&stop))
+        };
+
+        let stream = TcpStream::connect(address).unwrap();
+        let (mut socket, _) = tungstenite::client(url.as_str(), stream).unwrap();
+
+        let stream = TcpStream::connect(address).unwrap();
+        assert!(tungstenite::client(url.as_str(), stream).is_err());
+        assert_eq!(1, server.rejected_connections());
+
+        socket.close(None).unwrap();
+        stop.store(true, Ordering::Relaxed);
+        running.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn loopback_connection() {
+        let devices = FakeDevices::default();
+        let server = WsServer::with_bridge("127.0.0.1:0", Bridge::new(devices.clone())).unwrap();
+        let url = format!("ws://{}/", server.local_addr().unwrap());
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || server.run(&stop))
+        };
+
+        let stream =
+            TcpStream::connect(url.trim_start_matches("ws://").trim_end_matches('/')).unwrap();
+        let (mut socket, _) = tungstenite::client(url.as_str(), stream).unwrap();
+        let mut request = |text: &str, replies: usize| {
+            socket.send(Message::Text(text.to_string())).unwrap();
+            (0..replies)
+                .map(|_| match socket.read().unwrap() {
+                    Message::Text(text) => serde_json::from_str::<Value>(&text).unwrap(),
+                    message => panic!("unexpected message {:?}", message),
+                })
+                .last()
+                .unwrap()
+        };
+
+        assert_eq!(
+            "leased",
+            request(r#"{"type": "lease", "device": 1}"#, 1)["type"]
+        );
+        assert_eq!(
+            true,
+            request(r#"{"type": "subscribe", "device": 1}"#, 2)["leased"]
+        );
+        assert_eq!(
+            serde_json::json!({"X": 0, "Y": 16384}),
+            request(
+                r#"{"type": "axis", "device": 1, "axis": "X", "value": 0}"#,
+                1
+            )["axes"]
+        );
+        socket.close(None).unwrap();
+
+        stop.store(true, Ordering::Relaxed);
+        running.join().unwrap().unwrap();
+        assert!(devices.owned().is_empty());
+    }
+
+    #[test]
+    fn slow_handshakes_are_accepted() {
+        let server = WsServer::with_bridge("127.0.0.1:0", Bridge::new(FakeDevices::default()));
+        let server = server.unwrap();
+        let address = server.local_addr().unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let running = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || server.run(&stop))
+        };
+
+        let stream = TcpStream::connect(address).unwrap();
+        thread::sleep(TICK * 5);
+        let url = format!("ws://{}/", address);
+        let (mut socket, _) = tungstenite::client(url.as_str(), stream).unwrap();
+        socket
+            .send(Message::Text(
+                r#"{"type": "lease", "device": 1}"#.to_string(),
+            ))
+            .unwrap();
+        assert!(matches!(socket.read().unwrap(), Message::Text(_)));
+        socket.close(None).unwrap();
+
+        stop.store(true, Ordering::Relaxed);
+        running.join().unwrap().unwrap();
+    }
+}
+
+use crate::vjoy_base::device::config::VJDConfig;
+use crate::vjoy_base::device::feeding::{VJDOwnership, VJDPosFeed};
+use crate::vjoy_base::device::pov::VJDPovCont;
+use crate::vjoy_base::device::{
+    VJDAxis, VJDButton, VJDButtonState, VJDPosition, VJDPovDisc, VJDPovNumber, VJDevice,
+};
+use crate::vjoy_base::driver::VJGeneral;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::{Error, Message, WebSocket};
+
+/**
+    Gives a [`Bridge`] access to the devices, [`VJoyDevices`] being the vJoy driver.
+*/
+pub trait BridgeDevices: Send {
+    /// Acquires the device, returning `true` if it succeeds.
+    fn acquire(&mut self, device: VJDevice) -> bool;
+
+    /// Relinquishes a device acquired with [`BridgeDevices::acquire`].
+    fn relinquish(&mut self, device: VJDevice);
+
+    /// Sends the position of a device, returning `true` if it succeeds.
+    fn send(&mut self, position: &VJDPosition) -> bool;
+
+    /// Returns the controls of the device, or [`None`] if it does not exist.
+    fn config(&mut self, device: VJDevice) -> Option<VJDConfig>;
+}
+
+/**
+    Gives access to the vJoy devices through [`VJDOwnership`], [`VJDPosFeed`] and
+    [`VJDConfig::read`].
+*/
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VJoyDevices;
+
+impl BridgeDevices for VJoyDevices {
+    fn acquire(&mut self, device: VJDevice) -> bool {
+        VJDOwnership::acquire(device)
+    }
+
+    fn relinquish(&mut self, device: VJDevice) {
+        VJDOwnership::relinquish(device);
+    }
+
+    fn send(&mut self, position: &VJDPosition) -> bool {
+        VJDPosFeed::send_position(position)
+    }
+
+    fn config(&mut self, device: VJDevice) -> Option<VJDConfig> {
+        VJDConfig::read(device)
+    }
+}
+
+/**
+    Describes why a message of a client is rejected by a [`Bridge`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RequestError {
+    /// The message is not a valid request. The message of the parser is provided.
+    Malformed(String),
+
+    /// The connection sent more messages than its [`RateLimit`] allows.
+    RateLimited,
+
+    /// The device does not exist. Its number is provided.
+    UnknownDevice(u8),
+
+    /// The device is leased by another client.
+    LeasedByOther(VJDevice),
+
+    /// The device cannot be acquired, for instance because another application owns it.
+    AcquireFailed(VJDevice),
+
+    /// The client feeds or releases a device it does not lease.
+    NotLeased(VJDevice),
+
+    /// The device has no such control. The control is provided, as in a message.
+    MissingControl(VJDevice, String),
+
+    /// A value is out of range. The kind of control and the value are provided.
+    InvalidValue(&'static str, i64),
+
+    /// The new position cannot be sent to the device.
+    SendFailed(VJDevice),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::Malformed(message) => write!(f, "invalid message: {}", message),
+            RequestError::RateLimited => write!(f, "too many messages"),
+            RequestError::UnknownDevice(number) => write!(f, "device {} does not exist", number),
+            RequestError::LeasedByOther(device) => {
+                write!(f, "device {} is leased by another client", *device as u32)
+            }
+            RequestError::AcquireFailed(device) => {
+                write!(f, "device {} cannot be acquired", *device as u32)
+            }
+            RequestError::NotLeased(device) => {
+                write!(f, "device {} is not leased by this client", *device as u32)
+            }
+            RequestError::MissingControl(device, control) => {
+                write!(f, "device {} has no {}", *device as u32, control)
+            }
+            RequestError::InvalidValue(kind, value) => {
+                write!(f, "invalid {} value {}", kind, value)
+            }
+            RequestError::SendFailed(device) => {
+                write!(f, "device {} cannot be fed", *device as u32)
+            }
+        }
+    }
+}
+
+/**
+    Describes how many messages a connection may send: `messages` per `period`, in bursts of
+    up to `messages`.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RateLimit {
+    pub messages: u32,
+    pub period: Duration,
+}
+
+/// Counts the messages a connection may still send, refilled continuously.
+struct Bucket {
+    tokens: f64,
+    last: Option<Duration>,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Bucket {
+        Bucket {
+            tokens: limit.messages as f64,
+            last: None,
+        }
+    }
+
+    fn take(&mut self, limit: RateLimit, now: Duration) -> bool {
+        let capacity = limit.messages as f64;
+
+        if let Some(last) = self.last {
+            let refill =
+                now.saturating_sub(last).as_secs_f64() * capacity / limit.period.as_secs_f64();
+            // NaN, for an empty period at the same time, fills the bucket.
+            self.tokens = (self.tokens + refill).min(capacity);
+        }
+        self.last = Some(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Neutral,
+    North,
+    East,
+    South,
+    West,
+}
+
+impl From<Direction> for VJDPovDisc {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Neutral => VJDPovDisc::Neutral,
+            Direction::North => VJDPovDisc::North,
+            Direction::East => VJDPovDisc::East,
+            Direction::South => VJDPovDisc::South,
+            Direction::West => VJDPovDisc::West,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Lease {
+        device: u8,
+    },
+    Release {
+        device: u8,
+    },
+    Subscribe {
+        device: u8,
+    },
+    Unsubscribe {
+        device: u8,
+    },
+    Axis {
+        device: u8,
+        axis: VJDAxis,
+        value: i64,
+    },
+    Button {
+        device: u8,
+        button: u8,
+        pressed: bool,
+    },
+    Pov {
+        device: u8,
+        pov: u8,
+        direction: Direction,
+    },
+    ContPov {
+        device: u8,
+        pov: u8,
+        value: Option<i64>,
+    },
+}
+
+impl Request {
+    fn name(&self) -> &'static str {
+        match self {
+            Request::Lease { .. } => "lease",
+            Request::Release { .. } => "release",
+            Request::Subscribe { .. } => "subscribe",
+            Request::Unsubscribe { .. } => "unsubscribe",
+            Request::Axis { .. } => "axis",
+            Request::Button { .. } => "button",
+            Request::Pov { .. } => "pov",
+            Request::ContPov { .. } => "cont_pov",
+        }
+    }
+}
+
+/**
+    Identifies a client connected to a [`Bridge`].
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+struct Client {
+    outbox: SyncSender<String>,
+    bucket: Bucket,
+    subscriptions: HashSet<VJDevice>,
+}
+
+/**
+    Handles the messages of the clients of a [`WsServer`], see the module documentation for
+    the messages.
+
+    Clients are registered with [`Bridge::connect`], which returns the channel receiving the
+    messages to send them. Like [`UdpServer`](crate::vjoy_extra::udp::UdpServer), the bridge
+    is given the time elapsed since an origin of the caller's choice, here to limit the rate of
+    the messages.
+*/
+pub struct Bridge<D: BridgeDevices> {
+    devices: D,
+    rate_limit: RateLimit,
+    outbox_size: usize,
+    clients: HashMap<ClientId, Client>,
+    next_client: u64,
+    leases: HashMap<VJDevice, ClientId>,
+
+    /// Positions of the leased devices, since they cannot be read back.
+    positions: HashMap<VJDevice, VJDPosition>,
+    configs: HashMap<VJDevice, VJDConfig>,
+}
+
+impl<D: BridgeDevices> Bridge<D> {
+    /// Default rate limit of the connections.
+    pub const DEFAULT_RATE_LIMIT: RateLimit = RateLimit {
+        messages: 120,
+        period: Duration::from_secs(1),
+    };
+
+    /// Default number of messages waiting for a client beyond which it is disconnected.
+    pub const DEFAULT_OUTBOX_SIZE: usize = 256;
+
+    pub fn new(devices: D) -> Bridge<D> {
+        Bridge {
+            devices,
+            rate_limit: Self::DEFAULT_RATE_LIMIT,
+            outbox_size: Self::DEFAULT_OUTBOX_SIZE,
+            clients: HashMap::new(),
+            next_client: 0,
+            leases: HashMap::new(),
+            positions: HashMap::new(),
+            configs: HashMap::new(),
+        }
+    }
+
+    /// Changes the number of messages each connection may send.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Bridge<D> {
+        self.rate_limit = limit;
+        self
+    }
+
+    /**
+        Changes the number of messages which may wait for a client. When a message does not
+        fit, the client is disconnected.
+    */
+    pub fn outbox_size(mut self, messages: usize) -> Bridge<D> {
+        self.outbox_size = messages;
+        self
+    }
+
+    /**
+        Registers a client and returns the channel receiving the messages to send it. The
+        channel is closed when the client is disconnected for not reading its messages.
+    */
+    pub fn connect(&mut self) -> (ClientId, Receiver<String>) {
+        let (outbox, receiver) = mpsc::sync_channel(self.outbox_size);
+        let id = ClientId(self.next_client);
+        self.next_client += 1;
+
+        self.clients.insert(
+            id,
+            Client {
+                outbox,
+                bucket: Bucket::new(self.rate_limit),
+                subscriptions: HashSet::new(),
+            },
+        );
+
+        (id, receiver)
+    }
+
+    /// Forgets a client, resetting and relinquishing the devices it leases.
+    pub fn disconnect(&mut self, client: ClientId) {
+        let mut leased: Vec<VJDevice> = self
+            .leases
+            .iter()
+            .filter(|(_, &lessee)| lessee == client)
+            .map(|(&device, _)| device)
+            .collect();
+        leased.sort_by_key(|&device| device as u32);
+
+        self.clients.remove(&client);
+        for device in leased {
+            self.end_lease(device);
+        }
+    }
+
+    /// Returns the client leasing the device.
+    pub fn lessee(&self, device: VJDevice) -> Option<ClientId> {
+        self.leases.get(&device).copied()
+    }
+
+    /**
+        Handles a text message of a client, `now` being the time elapsed since an origin
+        chosen by the caller. Replies and states are sent to the channels of the clients.
+    */
+    pub fn handle(&mut self, client: ClientId, text: &str, now: Duration) {
+        let limit = self.rate_limit;
+        let allowed = match self.clients.get_mut(&client) {
+            Some(state) => state.bucket.take(limit, now),
+            None => return,
+        };
+
+        let (name, result) = if !allowed {
+            (None, Err(RequestError::RateLimited))
+        } else {
+            match serde_json::from_str::<Request>(text) {
+                Ok(request) => (Some(request.name()), self.apply(client, request)),
+                Err(error) => (None, Err(RequestError::Malformed(error.to_string()))),
+            }
+        };
+
+        if let Err(error) = result {
+            let reply = json!({
+                "type": "error",
+                "request": name,
+                "message": error.to_string(),
+            });
+            self.reply(client, reply);
+        }
+    }
+
+    fn apply(&mut self, client: ClientId, request: Request) -> Result<(), RequestError> {
+        match request {
+            Request::Lease { device } => {
+                let device = self.device(device)?.0;
+                match self.lessee(device) {
+                    Some(lessee) if lessee == client => (),
+                    Some(_) => return Err(RequestError::LeasedByOther(device)),
+                    None => {
+                        if !self.devices.acquire(device) {
+                            return Err(RequestError::AcquireFailed(device));
+                        }
+                        self.leases.insert(device, client);
+                        self.positions.insert(device, VJDPosition::new(device));
+                        self.devices.send(&VJDPosition::new(device));
+                    }
+                }
+
+                self.reply(client, json!({ "type": "leased", "device": device as u32 }));
+                self.broadcast(device);
+            }
+            Request::Release { device } => {
+                let device = self.leased(client, device)?.0;
+                self.reply(
+                    client,
+                    json!({ "type": "released", "device": device as u32 }),
+                );
+                // Unless the reply disconnected a slow client, which ended its leases.
+                if self.lessee(device) == Some(client) {
+                    self.end_lease(device);
+                }
+            }
+            Request::Subscribe { device } => {
+                let (device, config) = self.device(device)?;
+                if let Some(state) = self.clients.get_mut(&client) {
+                    state.subscriptions.insert(device);
+                }
+
+                let reply = json!({
+                    "type": "capabilities",
+                    "device": device as u32,
+                    "controls": config,
+                });
+                self.reply(client, reply);
+                self.reply(client, self.state(device));
+            }
+            Request::Unsubscribe { device } => {
+                let device = self.device(device)?.0;
+                if let Some(state) = self.clients.get_mut(&client) {
+                    state.subscriptions.remove(&device);
+                }
+
+                self.reply(
+                    client,
+                    json!({ "type": "unsubscribed", "device": device as u32 }),
+                );
+            }
+            Request::Axis {
+                device,
+                axis,
+                value,
+            } => {
+                let (device, config) = self.leased(client, device)?;
+                if !config.has_axis(axis) {
+                    return Err(RequestError::MissingControl(
+                        device,
+                        format!("axis {:?}", axis),
+                    ));
+                }
+                let range = VJGeneral::MIN_AXIS_VALUE as i64..=VJGeneral::MAX_AXIS_VALUE as i64;
+                if !range.contains(&value) {
+                    return Err(RequestError::InvalidValue("axis", value));
+                }
+
+                self.feed(device, |position| position.set_axis(axis, value as i32))?;
+            }
+            Request::Button {
+                device,
+                button,
+                pressed,
+            } => {
+                let (device, config) = self.leased(client, device)?;
+                let button = VJDButton::get_from(button)
+                    .filter(|&number| number as u8 <= config.buttons)
+                    .ok_or_else(|| {
+                        RequestError::MissingControl(device, format!("button {}", button))
+                    })?;
+                let state = if pressed {
+                    VJDButtonState::Pressed
+                } else {
+                    VJDButtonState::Released
+                };
+
+                self.feed(device, |position| position.set_button(button as u32, state))?;
+            }
+            Request::Pov {
+                device,
+                pov,
+                direction,
+            } => {
+                let (device, config) = self.leased(client, device)?;
+                let pov = pov_number(pov, config.disc_povs).ok_or_else(|| {
+                    RequestError::MissingControl(device, format!("discrete POV {}", pov))
+                })?;
+
+                self.feed(device, |position| position.set_disc_pov(pov, direction))?;
+            }
+            Request::ContPov { device, pov, value } => {
+                let (device, config) = self.leased(client, device)?;
+                let pov = pov_number(pov, config.cont_povs).ok_or_else(|| {
+                    RequestError::MissingControl(device, format!("continuous POV {}", pov))
+                })?;
+                let value = match value {
+                    None => VJDPovCont::NEUTRAL,
+                    Some(value) => u32::try_from(value)
+                        .ok()
+                        .and_then(VJDPovCont::new)
+                        .ok_or(RequestError::InvalidValue("continuous POV", value))?,
+                };
+
+                self.feed(device, |position| position.set_cont_pov(pov, value))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns an existing device and its controls.
+    fn device(&mut self, number: u8) -> Result<(VJDevice, VJDConfig), RequestError> {
+        let device = VJDevice::get_from(number).ok_or(RequestError::UnknownDevice(number))?;
+
+        if !self.configs.contains_key(&device) {
+            let config = self
+                .devices
+                .config(device)
+                .ok_or(RequestError::UnknownDevice(number))?;
+            self.configs.insert(device, config);
+        }
+
+        Ok((device, self.configs[&device].clone()))
+    }
+
+    /// Returns a device leased by the client and its controls.
+    fn leased(
+        &mut self,
+        client: ClientId,
+        number: u8,
+    ) -> Result<(VJDevice, VJDConfig), RequestError> {
+        let (device, config) = self.device(number)?;
+
+        if self.lessee(device) != Some(client) {
+            return Err(RequestError::NotLeased(device));
+        }
+
+        Ok((device, config))
+    }
+
+    /// Changes the position of a leased device and sends it.
+    fn feed<F>(&mut self, device: VJDevice, change: F) -> Result<(), RequestError>
+    where
+        F: FnOnce(&mut VJDPosition),
+    {
+        let position = self
+            .positions
+            .entry(device)
+            .or_insert_with(|| VJDPosition::new(device));
+        change(position);
+
+        let sent = self.devices.send(position);
+        self.broadcast(device);
+
+        if sent {
+            Ok(())
+        } else {
+            Err(RequestError::SendFailed(device))
+        }
+    }
+
+    /// Resets a leased device to neutral and relinquishes it.
+    fn end_lease(&mut self, device: VJDevice) {
+        self.devices.send(&VJDPosition::new(device));
+        self.devices.relinquish(device);
+        self.leases.remove(&device);
+        self.positions.remove(&device);
+        self.broadcast(device);
+    }
+
+    /// Describes the state of a device, from its controls.
+    fn state(&self, device: VJDevice) -> Value {
+        let position = self
+            .positions
+            .get(&device)
+            .copied()
+            .unwrap_or_else(|| VJDPosition::new(device));
+        let config = &self.configs[&device];
+
+        let axes: Map<String, Value> = config
+            .axes
+            .iter()
+            .map(|&axis| (format!("{:?}", axis), position.get_axis(axis).get().into()))
+            .collect();
+        let buttons: Vec<u8> = position
+            .get_buttons()
+            .iter()
+            .map(|button| button as u8)
+            .filter(|&number| number <= config.buttons)
+            .collect();
+        let disc_povs: Vec<String> = (1..=config.disc_povs)
+            .filter_map(|number| pov_number(number, config.disc_povs))
+            .map(|pov| format!("{:?}", position.get_disc_pov(pov)).to_lowercase())
+            .collect();
+        let cont_povs: Vec<Option<u32>> = (1..=config.cont_povs)
+            .filter_map(|number| pov_number(number, config.cont_povs))
+            .map(|pov| position.get_cont_pov(pov))
+            .map(|value| Some(value.get()).filter(|_| value != VJDPovCont::NEUTRAL))
+            .collect();
+
+        json!({
+            "type": "state",
+            "device": device as u32,
+            "leased": self.leases.contains_key(&device),
+            "axes": axes,
+            "buttons": buttons,
+            "disc_povs": disc_povs,
+            "cont_povs": cont_povs,
+        })
+    }
+
+    fn reply(&mut self, client: ClientId, message: Value) {
+        let full = match self.clients.get(&client) {
+            Some(state) => is_full(state.outbox.try_send(message.to_string())),
+            None => false,
+        };
+
+        if full {
+            self.disconnect(client);
+        }
+    }
+
+    /// Sends the state of the device to its subscribers.
+    fn broadcast(&mut self, device: VJDevice) {
+        if !self.configs.contains_key(&device) {
+            return;
+        }
+
+        let state = self.state(device).to_string();
+        let mut slow: Vec<ClientId> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.subscriptions.contains(&device))
+            .filter(|(_, client)| is_full(client.outbox.try_send(state.clone())))
+            .map(|(&id, _)| id)
+            .collect();
+        slow.sort_by_key(|id| id.0);
+
+        for client in slow {
+            self.disconnect(client);
+        }
+    }
+}
+
+/// Returns whether a message was not sent because the outbox of the client is full.
+fn is_full(result: Result<(), TrySendError<String>>) -> bool {
+    // A disconnected outbox belongs to a closing connection.
+    matches!(result, Err(TrySendError::Full(_)))
+}
+
+/// Returns a POV of a device having `count` POVs of its type.
+fn pov_number(number: u8, count: u8) -> Option<VJDPovNumber> {
+    if number > count {
+        return None;
+    }
+
+    match number {
+        1 => Some(VJDPovNumber::Pov1),
+        2 => Some(VJDPovNumber::Pov2),
+        3 => Some(VJDPovNumber::Pov3),
+        4 => Some(VJDPovNumber::Pov4),
+        _ => None,
+    }
+}
+
+/// Longest time a connection waits for a message before sending the pending ones.
+const TICK: Duration = Duration::from_millis(20);
+
+/// Longest time a client may take to send a part of its opening handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest time a client may take to receive a part of a message.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn lock<D: BridgeDevices>(bridge: &Mutex<Bridge<D>>) -> MutexGuard<'_, Bridge<D>> {
+    bridge
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/**
+    Accepts WebSocket connections and hands their messages to a [`Bridge`], each connection
+    being served by its own thread.
+*/
+pub struct WsServer<D: BridgeDevices> {
+    listener: TcpListener,
+    bridge: Arc<Mutex<Bridge<D>>>,
+    max_connections: usize,
+    accept_errors: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl WsServer<VJoyDevices> {
+    /// Listens on `address`, feeding the vJoy devices.
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<WsServer<VJoyDevices>> {
+        WsServer::with_bridge(address, Bridge::new(VJoyDevices))
+    }
+}
+
+impl<D: BridgeDevices + 'static> WsServer<D> {
+    /// Default number of connections served at a time.
+    pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+    pub fn with_bridge(address: impl ToSocketAddrs, bridge: Bridge<D>) -> io::Result<WsServer<D>> {
+        Ok(WsServer {
+            listener: TcpListener::bind(address)?,
+            bridge: Arc::new(Mutex::new(bridge)),
+            max_connections: Self::DEFAULT_MAX_CONNECTIONS,
+            accept_errors: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        })
+    }
+
+    /// Changes the number of connections served at a time.
+    pub fn max_connections(mut self, connections: usize) -> WsServer<D> {
+        self.max_connections = connections;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns the number of connections which failed before being accepted.
+    pub fn accept_errors(&self) -> u64 {
+        self.accept_errors.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of connections closed because [`WsServer::max_connections`] were served.
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /**
+        Serves connections until `stop` is set, then closes them and waits for their threads,
+        which releases every lease.
+
+        Connections over [`WsServer::max_connections`] are closed and counted in
+        [`WsServer::rejected_connections`], and connections failing before being accepted in
+        [`WsServer::accept_errors`].
+        Any other error of the listener sets `stop`, so the connections are closed, and is
+        returned.
+    */
+    pub fn run(&self, stop: &AtomicBool) -> io::Result<()> {
+        let origin = Instant::now();
+        let active = AtomicUsize::new(0);
+        self.listener.set_nonblocking(true)?;
+
+        thread::scope(|scope| {
+            while !stop.load(Ordering::Relaxed) {
+                match self.listener.accept() {
+                    Ok(_) if active.load(Ordering::Relaxed) >= self.max_connections => {
+                        // Dropping the stream closes the connection.
+                        self.rejected.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Ok((stream, _)) => {
+                        active.fetch_add(1, Ordering::Relaxed);
+                        scope.spawn(|| {
+                            serve(stream, &self.bridge, stop, origin);
+                            active.fetch_sub(1, Ordering::Relaxed);
+                        });
+                    }
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => thread::sleep(TICK),
+                    Err(error) if is_transient(&error) => {
+                        self.accept_errors.fetch_add(1, Ordering::Relaxed);
+                        thread::sleep(TICK);
+                    }
+                    Err(error) => {
+                        stop.store(true, Ordering::Relaxed);
+                        return Err(error);
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Returns whether an error of [`TcpListener::accept`] only concerns the connection accepted.
+fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::OutOfMemory
+    )
+}
+
+/// Serves a connection until it is closed or `stop` is set.
+fn serve<D: BridgeDevices>(
+    stream: TcpStream,
+    bridge: &Mutex<Bridge<D>>,
+    stop: &AtomicBool,
+    origin: Instant,
+) {
+    // The handshake is read whole, then messages are read for one tick at most. Writes time
+    // out, so that a client which stops reading cannot block the connection.
+    let setup = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)))
+        .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+    if setup.is_err() {
+        return;
+    }
+
+    let mut socket: WebSocket<TcpStream> = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(_) => return,
+    };
+    if socket.get_ref().set_read_timeout(Some(TICK)).is_err() {
+        return;
+    }
+    let (client, outbox) = lock(bridge).connect();
+
+    while !stop.load(Ordering::Relaxed) {
+        match socket.read() {
+            Ok(Message::Text(text)) => lock(bridge).handle(client, &text, origin.elapsed()),
+            Ok(_) => (),
+            Err(Error::Io(error))
+                if error.kind() == io::ErrorKind::WouldBlock
+                    || error.kind() == io::ErrorKind::TimedOut => {}
+            Err(_) => break,
+        }
+
+        let flushed = loop {
+            match outbox.try_recv() {
+                Ok(message) => {
+                    if socket.send(Message::Text(message)).is_err() {
+                        break false;
+                    }
+                }
+                Err(TryRecvError::Empty) => break true,
+                // The bridge disconnected the client, which did not read its messages.
+                Err(TryRecvError::Disconnected) => break false,
+            }
+        };
+        if !flushed {
+            break;
+        }
+    }
+
+    lock(bridge).disconnect(client);
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}